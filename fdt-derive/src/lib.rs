@@ -0,0 +1,134 @@
+//! Derive macros for the fdt crate.
+//!
+//! Use through the 'derive' feature of fdt rather than depending on this
+//! crate directly.
+
+extern crate proc_macro;
+extern crate proc_macro2;
+extern crate quote;
+extern crate syn;
+
+use proc_macro::TokenStream;
+use proc_macro2::Span;
+use quote::quote;
+use syn::{parse_macro_input, Data, DeriveInput, Fields, GenericParam, Lifetime,
+	LifetimeParam, LitStr};
+
+/// Derives `TryFrom<Node>` for a struct with named fields.
+///
+/// Every field is parsed from the property with the field name, with
+/// underscores replaced by dashes, using the `PropertyValue` implementation
+/// of the field type. The following field attributes are supported:
+///
+/// * `#[fdt(name = "clock-frequency")]` parses the field from the named
+///   property instead.
+/// * `#[fdt(default)]` uses `Default::default()` if the property is absent.
+/// * `#[fdt(phandle)]` resolves the phandle stored in the property to a
+///   `Node` (or `Option<Node>`).
+///
+/// If the struct has a lifetime parameter the first one is used as the
+/// lifetime of the node, so fields may borrow from the device tree.
+///
+/// ```ignore
+/// #[derive(FromNode)]
+/// struct Uart {
+///     reg: [u32; 2],
+///     clock_frequency: u32,
+///     current_speed: Option<u32>,
+/// }
+/// ```
+#[proc_macro_derive(FromNode, attributes(fdt))]
+pub fn derive_from_node(input: TokenStream) -> TokenStream {
+	let input = parse_macro_input!(input as DeriveInput);
+	expand(input).unwrap_or_else(|err| err.to_compile_error()).into()
+}
+
+enum Kind {
+	Value,
+	Default,
+	Phandle,
+}
+
+struct FieldAttrs {
+	name: Option<LitStr>,
+	kind: Kind,
+}
+
+fn field_attrs(field: &syn::Field) -> syn::Result<FieldAttrs> {
+	let mut attrs = FieldAttrs { name: None, kind: Kind::Value };
+	for attr in field.attrs.iter().filter(|attr| attr.path().is_ident("fdt")) {
+		attr.parse_nested_meta(|meta| {
+			if meta.path.is_ident("name") {
+				attrs.name = Some(meta.value()?.parse()?);
+			} else if meta.path.is_ident("default") {
+				if let Kind::Phandle = attrs.kind {
+					return Err(meta.error("`default` can't be combined with `phandle`"));
+				}
+				attrs.kind = Kind::Default;
+			} else if meta.path.is_ident("phandle") {
+				if let Kind::Default = attrs.kind {
+					return Err(meta.error("`phandle` can't be combined with `default`"));
+				}
+				attrs.kind = Kind::Phandle;
+			} else {
+				return Err(meta.error("unsupported fdt attribute"));
+			}
+			Ok(())
+		})?;
+	}
+	Ok(attrs)
+}
+
+fn expand(input: DeriveInput) -> syn::Result<proc_macro2::TokenStream> {
+	let fields = match input.data {
+		Data::Struct(ref data) => match data.fields {
+			Fields::Named(ref fields) => &fields.named,
+			_ => return Err(syn::Error::new_spanned(&input.ident,
+				"FromNode can only be derived for structs with named fields")),
+		},
+		_ => return Err(syn::Error::new_spanned(&input.ident,
+			"FromNode can only be derived for structs")),
+	};
+
+	let mut inits = Vec::new();
+	for field in fields {
+		let ident = field.ident.as_ref().unwrap();
+		let attrs = field_attrs(field)?;
+		let name = attrs.name.unwrap_or_else(|| {
+			let name = ident.to_string();
+			LitStr::new(name.trim_start_matches("r#").replace('_', "-").as_str(), ident.span())
+		});
+		let parse = match attrs.kind {
+			Kind::Value => quote!(::fdt::binding::property(&node, #name)),
+			Kind::Default => quote!(::fdt::binding::property_or_default(&node, #name)),
+			Kind::Phandle => quote!(::fdt::binding::phandle(&node, #name)),
+		};
+		inits.push(quote!(#ident: #parse?));
+	}
+
+	let ident = &input.ident;
+	let (_, ty_generics, where_clause) = input.generics.split_for_impl();
+	let mut generics = input.generics.clone();
+	let lifetime = match generics.lifetimes().next() {
+		Some(param) => param.lifetime.clone(),
+		None => {
+			let lifetime = Lifetime::new("'fdt", Span::call_site());
+			generics.params.insert(0, GenericParam::Lifetime(LifetimeParam::new(lifetime.clone())));
+			lifetime
+		},
+	};
+	let (impl_generics, _, _) = generics.split_for_impl();
+
+	Ok(quote! {
+		impl #impl_generics ::fdt::binding::__private::TryFrom<::fdt::Node<#lifetime>> for #ident #ty_generics
+				#where_clause {
+			type Error = ::fdt::binding::PropertyError;
+
+			fn try_from(node: ::fdt::Node<#lifetime>) -> ::fdt::binding::__private::Result<Self, Self::Error> {
+				::fdt::binding::__private::Result::Ok(#ident {
+					#(#inits,)*
+				})
+			}
+		}
+	})
+}
@@ -0,0 +1,94 @@
+extern crate fdt;
+
+use std::convert::TryFrom;
+
+use fdt::{FDT, FromNode, Node, NodeIterator, Cells, StringList};
+use fdt::binding::PropertyError;
+use fdt::error::Error;
+
+const DTB: &[u8] = include_bytes!("../../tests/dt.dtb");
+
+fn init() -> FDT<'static> {
+	unsafe {
+		FDT::from_raw(DTB.as_ptr()).unwrap()
+	}
+}
+
+#[derive(FromNode, Debug, PartialEq)]
+struct I2c {
+	reg: [u32; 2],
+	clock_frequency: u32,
+	#[fdt(name = "#address-cells")]
+	address_cells: u32,
+	current_speed: Option<u32>,
+	#[fdt(default)]
+	bus_width: u32,
+	interrupt_controller: bool,
+}
+
+#[derive(FromNode)]
+struct Uart<'a> {
+	compatible: StringList<'a>,
+	clocks: Cells<'a>,
+	status: &'a str,
+}
+
+#[derive(FromNode)]
+struct Pinctrl<'a> {
+	#[fdt(phandle, name = "pinctrl-0")]
+	pins: Node<'a>,
+	#[fdt(phandle)]
+	interrupt_parent: Option<Node<'a>>,
+}
+
+#[derive(FromNode, Debug)]
+struct Missing {
+	#[allow(dead_code)]
+	clock_frequency: u32,
+}
+
+fn node(fdt: &'static FDT<'static>, name: &str) -> Node<'static> {
+	fdt.nodes().with_name(name).next().unwrap()
+}
+
+#[test]
+fn test_derive_values() {
+	let fdt = Box::leak(Box::new(init()));
+	let i2c = I2c::try_from(node(fdt, "i2c@7e205000")).unwrap();
+	assert_eq!(i2c, I2c {
+		reg: [0x7e205000, 0x1000],
+		clock_frequency: 100000,
+		address_cells: 1,
+		current_speed: None,
+		bus_width: 0,
+		interrupt_controller: false,
+	});
+}
+
+#[test]
+fn test_derive_borrowed() {
+	let fdt = Box::leak(Box::new(init()));
+	let uart = Uart::try_from(node(fdt, "serial@7e201000")).unwrap();
+	assert!(uart.compatible.contains("arm,pl011"));
+	assert_eq!(uart.clocks.len(), 4);
+	assert_eq!(uart.status, "okay\0");
+}
+
+#[test]
+fn test_derive_phandle() {
+	let fdt = Box::leak(Box::new(init()));
+	let i2c = Pinctrl::try_from(node(fdt, "i2c@7e205000")).unwrap();
+	assert_eq!(i2c.pins.name(), "i2c0");
+	assert!(i2c.interrupt_parent.is_none());
+}
+
+#[test]
+fn test_derive_errors() {
+	let fdt = Box::leak(Box::new(init()));
+	let err = Missing::try_from(node(fdt, "gpu")).unwrap_err();
+	assert_eq!(err, PropertyError { property: "clock-frequency", error: Error::NotFound });
+	assert_eq!(err.to_string(), "missing property 'clock-frequency'");
+
+	let err = I2c::try_from(node(fdt, "spidev@0")).unwrap_err();
+	assert_eq!(err, PropertyError { property: "reg", error: Error::Truncated });
+}
@@ -0,0 +1,13 @@
+#![no_main]
+#[macro_use] extern crate libfuzzer_sys;
+extern crate fdt;
+
+use fdt::FDT;
+
+fuzz_target!(|data: &[u8]| {
+	if let Ok(fdt) = FDT::from_bytes(data) {
+		assert!(fdt.total_size() as usize <= data.len());
+		let _ = fdt.boot_cpuid_phys();
+		let _ = fdt.nodes().count();
+	}
+});
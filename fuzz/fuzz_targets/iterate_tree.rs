@@ -0,0 +1,31 @@
+#![no_main]
+#[macro_use] extern crate libfuzzer_sys;
+extern crate fdt;
+
+use fdt::{FDT, NodeIterator};
+use fdt::owned::OwnedTree;
+
+fuzz_target!(|data: &[u8]| {
+	let fdt = match FDT::from_bytes(data) {
+		Ok(fdt) => fdt,
+		Err(_) => return,
+	};
+	for node in fdt.nodes() {
+		let _ = node.subnodes().count();
+		let _ = node.subnodes().with_name("cpus").next();
+		let _ = format!("{}", node.path());
+	}
+	let _ = fdt.tags().count();
+	let _ = format!("{}", fdt::dts::dts(&fdt));
+	fdt::checks::check(&fdt, |_| ());
+
+	// well formed blobs must survive a round trip through the writer, others
+	// may hold what it can't write such as a named root node
+	if fdt.check_full().is_err() {
+		return;
+	}
+	let tree = OwnedTree::from_fdt(&fdt);
+	let dtb = tree.to_dtb();
+	let copy = FDT::from_bytes(&dtb).unwrap();
+	assert!(OwnedTree::from_fdt(&copy) == tree);
+});
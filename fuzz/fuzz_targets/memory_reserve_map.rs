@@ -0,0 +1,13 @@
+#![no_main]
+#[macro_use] extern crate libfuzzer_sys;
+extern crate fdt;
+
+use fdt::FDT;
+
+fuzz_target!(|data: &[u8]| {
+	if let Ok(fdt) = FDT::from_bytes(data) {
+		let mut map = fdt.memory_reserve_map();
+		let _ = map.by_ref().count();
+		assert!(map.next().is_none());
+	}
+});
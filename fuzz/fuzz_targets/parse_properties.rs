@@ -0,0 +1,28 @@
+#![no_main]
+#[macro_use] extern crate libfuzzer_sys;
+extern crate fdt;
+
+use fdt::{FDT, PropertyIterator};
+
+fuzz_target!(|data: &[u8]| {
+	let fdt = match FDT::from_bytes(data) {
+		Ok(fdt) => fdt,
+		Err(_) => return,
+	};
+	for node in fdt.nodes() {
+		let _ = node.address_cells();
+		let _ = node.size_cells();
+		let _ = node.phandle();
+		let _ = node.is_compatible_with("simple-bus");
+		let _ = node.property_value::<Option<u64>>("reg");
+		let _ = node.properties().with_name("status").next();
+		for prop in node.properties() {
+			let _ = prop.as_str();
+			let _ = prop.as_stringlist().strings().count();
+			let _ = prop.parse::<u32>();
+			let _ = prop.parse::<u64>();
+			let _ = prop.parse::<&str>();
+			let _ = format!("{}", prop);
+		}
+	}
+});
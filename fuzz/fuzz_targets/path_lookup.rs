@@ -0,0 +1,28 @@
+#![no_main]
+#[macro_use] extern crate libfuzzer_sys;
+extern crate fdt;
+
+use fdt::FDT;
+use fdt::index::FdtIndex;
+
+// The input is a path, a NUL byte and a device tree blob
+fuzz_target!(|data: &[u8]| {
+	let split = data.iter().position(|&b| b == 0).unwrap_or(data.len());
+	let path = match std::str::from_utf8(&data[..split]) {
+		Ok(path) => path,
+		Err(_) => return,
+	};
+	let fdt = match FDT::from_bytes(data.get(split + 1..).unwrap_or(&[])) {
+		Ok(fdt) => fdt,
+		Err(_) => return,
+	};
+	let _ = fdt.nodes().with_path(path).count();
+	let _ = fdt.alias(path);
+	let index = FdtIndex::new(&fdt);
+	if let Some(node) = index.path(path) {
+		let _ = index.parent(&node);
+		let _ = index.first_child(&node);
+		let _ = index.next_sibling(&node);
+		let _ = index.descendant_count(&node);
+	}
+});
@@ -0,0 +1,26 @@
+#![no_main]
+#[macro_use] extern crate libfuzzer_sys;
+extern crate fdt;
+
+use fdt::FDT;
+
+fuzz_target!(|data: &[u8]| {
+	let fdt = match FDT::from_bytes(data) {
+		Ok(fdt) => fdt,
+		Err(_) => return,
+	};
+	let mut moved = vec![0; data.len()];
+	let _ = fdt.move_to(&mut moved).unwrap();
+	let mut opened = vec![0; data.len() * 2 + 64];
+	let opened = fdt.open_into(&mut opened);
+	let mut packed = vec![0; data.len() * 2 + 64];
+	let packed = fdt.pack(&mut packed);
+	let mut compacted = vec![0; data.len() * 2 + 64];
+	let compacted = fdt.compact_strings(&mut compacted);
+	// blobs which pass the full check are copied, and the copies pass it too
+	if fdt.check_full().is_ok() {
+		assert_eq!(opened.unwrap().check_full(), Ok(()));
+		assert_eq!(packed.unwrap().check_full(), Ok(()));
+		assert_eq!(compacted.unwrap().check_full(), Ok(()));
+	}
+});
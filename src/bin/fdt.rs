@@ -0,0 +1,524 @@
+//! The `fdt` command-line tool.
+//!
+//! Covers what dtc's fdtdump, fdtget, fdtput and fdtoverlay are used for on
+//! test rigs, plus the library's checks and structural diff, in a single
+//! binary. Built with the `cli` feature.
+
+extern crate fdt;
+
+use std::env;
+use std::fmt::Write as _;
+use std::fs;
+use std::io::{self, Write};
+use std::process;
+use std::str;
+
+use fdt::{FDT, Node};
+use fdt::checks::{self, Severity};
+use fdt::owned::{OwnedNode, OwnedTree};
+
+const USAGE: &str = "\
+usage: fdt <command> [options] <dtb> [args...]
+
+commands:
+  dump <dtb>                          print the tree as device tree source
+  get [-t type] <dtb> <node> <prop>...
+                                      print property values
+  put [-t type] [-p] [-o out] <dtb> <node> <prop> [value...]
+                                      set a property, creating missing nodes with -p
+  put -c [-p] [-o out] <dtb> <node>...
+                                      create nodes, and missing parents with -p
+  put -d [-o out] <dtb> <node> <prop>...
+                                      delete properties
+  put -r [-o out] <dtb> <node>...     remove nodes and their descendants
+  ls [-l] <dtb> [node]                list properties and subnodes, with values with -l
+  find [--compatible str] [--name name] [--property prop] <dtb>
+                                      print the paths of matching nodes
+  check <dtb>                         run dtc-style semantic checks
+  diff <a> <b>                        print the changes turning tree a into tree b
+  overlay -i <base> -o <out> <overlay>...
+                                      apply overlays to a base tree
+
+Nodes are given by path or alias. Types are [hh|h|l](s|i|u|x) as for fdtget:
+a string, signed, unsigned or hex integer of one, two or four bytes.
+";
+
+/// The reasons the tool gives up.
+enum Failure {
+	/// Bad command line, reported together with the usage text.
+	Usage(String),
+	/// Anything else, reported on its own.
+	Error(String),
+	/// Standard output was closed by the reader, as by `fdt dump | head`.
+	Closed,
+}
+
+impl From<io::Error> for Failure {
+	fn from(err: io::Error) -> Self {
+		match err.kind() {
+			io::ErrorKind::BrokenPipe => Failure::Closed,
+			_ => Failure::Error(format!("failed to write output: {}", err)),
+		}
+	}
+}
+
+type Result<T> = std::result::Result<T, Failure>;
+
+fn error<T, S: Into<String>>(msg: S) -> Result<T> {
+	Err(Failure::Error(msg.into()))
+}
+
+fn usage<T, S: Into<String>>(msg: S) -> Result<T> {
+	Err(Failure::Usage(msg.into()))
+}
+
+fn main() {
+	let args: Vec<String> = env::args().skip(1).collect();
+	let stdout = io::stdout();
+	let mut out = io::BufWriter::new(stdout.lock());
+	let result = run(&args, &mut out).and_then(|status| {
+		out.flush()?;
+		Ok(status)
+	});
+	match result {
+		Ok(status) => process::exit(status),
+		Err(Failure::Closed) => process::exit(0),
+		Err(Failure::Usage(msg)) => {
+			eprintln!("fdt: {}\n\n{}", msg, USAGE);
+			process::exit(2);
+		},
+		Err(Failure::Error(msg)) => {
+			eprintln!("fdt: {}", msg);
+			process::exit(1);
+		},
+	}
+}
+
+/// Runs the command of [args], writing its output to [out] and returning
+/// the exit status.
+fn run(args: &[String], out: &mut dyn Write) -> Result<i32> {
+	let (command, args) = match args.split_first() {
+		Some((command, args)) => (command.as_str(), args),
+		None => return usage("missing command"),
+	};
+	match command {
+		"dump" => dump(args, out),
+		"get" => get(args, out),
+		"put" => put(args),
+		"ls" => ls(args, out),
+		"find" => find(args, out),
+		"check" => check(args, out),
+		"diff" => diff(args, out),
+		"overlay" => overlay(args),
+		"help" | "-h" | "--help" => {
+			write!(out, "{}", USAGE)?;
+			Ok(0)
+		},
+		other => usage(format!("unknown command '{}'", other)),
+	}
+}
+
+fn dump(args: &[String], out: &mut dyn Write) -> Result<i32> {
+	let opts = Options::parse(args, &[])?;
+	let [file] = opts.free::<1>()?;
+	let dtb = load(file)?;
+	write!(out, "{}", fdt::dts::dts(&parse(file, &dtb)?))?;
+	Ok(0)
+}
+
+fn get(args: &[String], out: &mut dyn Write) -> Result<i32> {
+	let opts = Options::parse(args, &[("-t", true)])?;
+	let ty = opts.value("-t").map(Type::parse).transpose()?;
+	if opts.free.len() < 3 {
+		return usage("get needs a dtb, a node and at least one property");
+	}
+	let dtb = load(&opts.free[0])?;
+	let fdt = parse(&opts.free[0], &dtb)?;
+	let node = find_node(&fdt, &opts.free[1])?;
+	for name in &opts.free[2..] {
+		match node.property(name) {
+			Some(prop) => writeln!(out, "{}", format_value(prop.raw(), ty)?)?,
+			None => return error(format!("{}: no property '{}'", opts.free[1], name)),
+		}
+	}
+	Ok(0)
+}
+
+fn put(args: &[String]) -> Result<i32> {
+	let opts = Options::parse(args, &[
+		("-t", true), ("-o", true), ("-p", false), ("-c", false), ("-d", false), ("-r", false),
+	])?;
+	let file = match opts.free.first() {
+		Some(file) => file,
+		None => return usage("put needs a dtb"),
+	};
+	let dtb = load(file)?;
+	let mut tree = OwnedTree::from_fdt(&parse(file, &dtb)?);
+	let parents = opts.flag("-p");
+	let args = &opts.free[1..];
+
+	match (opts.flag("-c"), opts.flag("-d"), opts.flag("-r")) {
+		(true, false, false) => {
+			for path in args {
+				if tree.node(path).is_some() {
+					return error(format!("{}: node exists", path));
+				}
+				create(&mut tree, path, parents)?;
+			}
+		},
+		(false, true, false) => {
+			let (path, props) = match args.split_first() {
+				Some(split) => split,
+				None => return usage("put -d needs a node"),
+			};
+			let node = node_mut(&mut tree, path)?;
+			for name in props {
+				if node.remove_property(name).is_none() {
+					return error(format!("{}: no property '{}'", path, name));
+				}
+			}
+		},
+		(false, false, true) => {
+			for path in args {
+				let trimmed = path.trim_end_matches('/');
+				let (parent, name) = match trimmed.rfind('/') {
+					Some(0) => ("/", &trimmed[1..]),
+					Some(pos) => (&trimmed[..pos], &trimmed[pos + 1..]),
+					None => return error(format!("{}: not a node path", path)),
+				};
+				if name.is_empty() || node_mut(&mut tree, parent)?.remove_child(name).is_none() {
+					return error(format!("{}: no such node", path));
+				}
+			}
+		},
+		(false, false, false) => {
+			if args.len() < 2 {
+				return usage("put needs a node and a property");
+			}
+			let ty = opts.value("-t").map(Type::parse).transpose()?;
+			let value = encode_value(&args[2..], ty.unwrap_or(Type { kind: 's', size: None }))?;
+			let node = if parents { create(&mut tree, &args[0], true)? } else { node_mut(&mut tree, &args[0])? };
+			node.set_property(&args[1], &value);
+		},
+		_ => return usage("-c, -d and -r can't be combined"),
+	}
+
+	let out = opts.value("-o").unwrap_or(file);
+	fs::write(out, tree.to_dtb()).or_else(|err| error(format!("{}: {}", out, err)))?;
+	Ok(0)
+}
+
+fn ls(args: &[String], out: &mut dyn Write) -> Result<i32> {
+	let opts = Options::parse(args, &[("-l", false)])?;
+	let (file, path) = match opts.free.as_slice() {
+		[file] => (file, "/"),
+		[file, path] => (file, path.as_str()),
+		_ => return usage("ls needs a dtb and at most one node"),
+	};
+	let dtb = load(file)?;
+	let fdt = parse(file, &dtb)?;
+	let node = find_node(&fdt, path)?;
+	for prop in node.properties() {
+		if opts.flag("-l") && !prop.raw().is_empty() {
+			writeln!(out, "{} = {}", prop.name(), fdt::dts::value(prop.raw()))?;
+		} else {
+			writeln!(out, "{}", prop.name())?;
+		}
+	}
+	for child in node.children() {
+		writeln!(out, "{}/", child.name())?;
+	}
+	Ok(0)
+}
+
+fn find(args: &[String], out: &mut dyn Write) -> Result<i32> {
+	let opts = Options::parse(args, &[("--compatible", true), ("--name", true), ("--property", true)])?;
+	let [file] = opts.free::<1>()?;
+	let dtb = load(file)?;
+	let fdt = parse(file, &dtb)?;
+	let mut found = 0;
+	for node in fdt.nodes() {
+		let matches = opts.value("--compatible").is_none_or(|comp| node.is_compatible_with(comp))
+			&& opts.value("--name").is_none_or(|name| node.has_name(name))
+			&& opts.value("--property").is_none_or(|prop| node.property(prop).is_some());
+		if matches {
+			writeln!(out, "{}", node.path())?;
+			found += 1;
+		}
+	}
+	Ok(if found > 0 { 0 } else { 1 })
+}
+
+fn check(args: &[String], out: &mut dyn Write) -> Result<i32> {
+	let opts = Options::parse(args, &[])?;
+	let [file] = opts.free::<1>()?;
+	let dtb = load(file)?;
+	let mut errors = 0;
+	let mut written = Ok(());
+	checks::check(&parse(file, &dtb)?, |diagnostic| {
+		if written.is_ok() {
+			written = writeln!(out, "{}", diagnostic);
+		}
+		if diagnostic.problem.check().severity() == Severity::Error {
+			errors += 1;
+		}
+	});
+	written?;
+	Ok(if errors > 0 { 1 } else { 0 })
+}
+
+fn diff(args: &[String], out: &mut dyn Write) -> Result<i32> {
+	let opts = Options::parse(args, &[])?;
+	let [a, b] = opts.free::<2>()?;
+	let (dtb_a, dtb_b) = (load(a)?, load(b)?);
+	let mut written = Ok(());
+	let changes = fdt::diff(&parse(a, &dtb_a)?, &parse(b, &dtb_b)?, |change| {
+		if written.is_ok() {
+			written = writeln!(out, "{}", change);
+		}
+	});
+	written?;
+	Ok(if changes > 0 { 1 } else { 0 })
+}
+
+fn overlay(args: &[String]) -> Result<i32> {
+	let opts = Options::parse(args, &[("-i", true), ("-o", true)])?;
+	let (base, out) = match (opts.value("-i"), opts.value("-o")) {
+		(Some(base), Some(out)) => (base, out),
+		_ => return usage("overlay needs -i and -o"),
+	};
+	let dtb = load(base)?;
+	let mut tree = OwnedTree::from_fdt(&parse(base, &dtb)?);
+	for file in &opts.free {
+		let dtbo = load(file)?;
+		let overlay = OwnedTree::from_fdt(&parse(file, &dtbo)?);
+		fdt::overlay::apply(&mut tree, &overlay)
+			.or_else(|err| error(format!("{}: failed to apply: {}", file, err)))?;
+	}
+	fs::write(out, tree.to_dtb()).or_else(|err| error(format!("{}: {}", out, err)))?;
+	Ok(0)
+}
+
+/// Command line options of a command, split from its other arguments.
+struct Options<'a> {
+	values: Vec<(&'static str, Option<&'a str>)>,
+	free: Vec<String>,
+}
+
+impl<'a> Options<'a> {
+	/// Splits [args] according to [spec], which lists the options of the
+	/// command and whether they take a value.
+	///
+	/// Values of short options may also be attached, as in `-tx`.
+	fn parse(args: &'a [String], spec: &[(&'static str, bool)]) -> Result<Self> {
+		let mut opts = Options { values: Vec::new(), free: Vec::new() };
+		let mut args = args.iter();
+		while let Some(arg) = args.next() {
+			if arg == "--" {
+				opts.free.extend(args.cloned());
+				break;
+			}
+			// negative numbers are values, not options
+			if !arg.starts_with('-') || arg == "-" || arg[1..].starts_with(|c: char| c.is_ascii_digit()) {
+				opts.free.push(arg.clone());
+				continue;
+			}
+			let attached = |name: &str| name.len() == 2 && arg.len() > 2 && arg.starts_with(name);
+			match spec.iter().find(|&&(name, takes)| arg == name || (takes && attached(name))) {
+				Some(&(name, true)) if arg.len() > name.len() => {
+					opts.values.push((name, Some(&arg[name.len()..])));
+				},
+				Some(&(name, true)) => match args.next() {
+					Some(value) => opts.values.push((name, Some(value.as_str()))),
+					None => return usage(format!("{} needs a value", name)),
+				},
+				Some(&(name, false)) => opts.values.push((name, None)),
+				None => return usage(format!("unknown option '{}'", arg)),
+			}
+		}
+		Ok(opts)
+	}
+
+	fn flag(&self, name: &str) -> bool {
+		self.values.iter().any(|entry| entry.0 == name)
+	}
+
+	fn value(&self, name: &str) -> Option<&'a str> {
+		self.values.iter().rev().find(|entry| entry.0 == name).and_then(|entry| entry.1)
+	}
+
+	/// Returns exactly [N] free arguments.
+	fn free<const N: usize>(&self) -> Result<[&str; N]> {
+		if self.free.len() != N {
+			return usage(format!("expected {} argument(s), got {}", N, self.free.len()));
+		}
+		Ok(std::array::from_fn(|i| self.free[i].as_str()))
+	}
+}
+
+/// A value type as understood by fdtget and fdtput.
+#[derive(Clone, Copy)]
+struct Type {
+	kind: char,
+	size: Option<usize>,
+}
+
+impl Type {
+	fn parse(spec: &str) -> Result<Type> {
+		let (size, kind) = if let Some(kind) = spec.strip_prefix("hh") {
+			(Some(1), kind)
+		} else if let Some(kind) = spec.strip_prefix('h') {
+			(Some(2), kind)
+		} else if let Some(kind) = spec.strip_prefix('l') {
+			(Some(4), kind)
+		} else {
+			(None, spec)
+		};
+		match (kind, size) {
+			("s", None) => Ok(Type { kind: 's', size }),
+			("i", _) | ("u", _) | ("x", _) => Ok(Type { kind: kind.chars().next().unwrap_or('x'), size }),
+			_ => usage(format!("bad type '{}'", spec)),
+		}
+	}
+}
+
+/// Formats [raw] as [ty], or guesses the type like fdtget when there is none.
+fn format_value(raw: &[u8], ty: Option<Type>) -> Result<String> {
+	let ty = match ty {
+		Some(ty) => ty,
+		None if raw == [0] || is_printable(raw) => Type { kind: 's', size: None },
+		None if raw.len().is_multiple_of(4) => Type { kind: 'u', size: Some(4) },
+		None => Type { kind: 'x', size: Some(1) },
+	};
+	let mut out = String::new();
+	if ty.kind == 's' {
+		let strings = match raw.split_last() {
+			Some((&0, strings)) => str::from_utf8(strings).ok(),
+			_ => None,
+		};
+		match strings {
+			Some(strings) => out.push_str(&strings.replace('\0', " ")),
+			None => return error("value is not a string"),
+		}
+		return Ok(out);
+	}
+	let size = ty.size.unwrap_or(4);
+	if !raw.len().is_multiple_of(size) {
+		return error(format!("value length {} is not a multiple of {}", raw.len(), size));
+	}
+	for (i, chunk) in raw.chunks(size).enumerate() {
+		let val = chunk.iter().fold(0u32, |val, &byte| val << 8 | u32::from(byte));
+		if i > 0 {
+			out.push(' ');
+		}
+		let _ = match (ty.kind, size) {
+			('i', 1) => write!(out, "{}", val as u8 as i8),
+			('i', 2) => write!(out, "{}", val as u16 as i16),
+			('i', _) => write!(out, "{}", val as i32),
+			('u', _) => write!(out, "{}", val),
+			(_, 1) => write!(out, "{:02x}", val),
+			_ => write!(out, "{:x}", val),
+		};
+	}
+	Ok(out)
+}
+
+/// Tests if [raw] is a list of non-empty printable strings, which is how
+/// fdtget tells strings apart from other values.
+fn is_printable(raw: &[u8]) -> bool {
+	match raw.split_last() {
+		Some((&0, strings)) => strings.split(|&b| b == 0)
+			.all(|s| !s.is_empty() && s.iter().all(|&b| (0x20..0x7f).contains(&b))),
+		_ => false,
+	}
+}
+
+/// Encodes [args] as a property value of type [ty], like fdtput.
+fn encode_value(args: &[String], ty: Type) -> Result<Vec<u8>> {
+	let mut value = Vec::new();
+	for arg in args {
+		if ty.kind == 's' {
+			value.extend_from_slice(arg.as_bytes());
+			value.push(0);
+			continue;
+		}
+		let bits = ty.size.unwrap_or(4) as u32 * 8;
+		let parsed = match ty.kind {
+			'x' => i64::from_str_radix(arg.trim_start_matches("0x"), 16).ok(),
+			_ => parse_int(arg),
+		};
+		let min = if ty.kind == 'i' { -(1 << (bits - 1)) } else { 0 };
+		let val = match parsed {
+			Some(val) if val >= min && val < 1 << bits => val,
+			_ => return error(format!("bad value '{}'", arg)),
+		};
+		value.extend_from_slice(&val.to_be_bytes()[8 - bits as usize / 8..]);
+	}
+	Ok(value)
+}
+
+/// Parses a decimal, or hexadecimal with a 0x prefix, integer.
+fn parse_int(arg: &str) -> Option<i64> {
+	let (negative, abs) = match arg.strip_prefix('-') {
+		Some(abs) => (true, abs),
+		None => (false, arg),
+	};
+	let abs = match abs.strip_prefix("0x") {
+		Some(hex) => i64::from_str_radix(hex, 16).ok()?,
+		None => abs.parse::<i64>().ok()?,
+	};
+	Some(if negative { -abs } else { abs })
+}
+
+/// Reads the blob in [path].
+fn load(path: &str) -> Result<Vec<u8>> {
+	fs::read(path).or_else(|err| error(format!("{}: {}", path, err)))
+}
+
+/// Parses [dtb], read from [path]. Every version from 1 on is accepted,
+/// what is written back is converted to the latest as by
+/// [FDT::convert_to_latest].
+fn parse<'a>(path: &str, dtb: &'a [u8]) -> Result<FDT<'a>> {
+	FDT::from_bytes(dtb).or_else(|err| error(format!("{}: {}", path, err)))
+}
+
+/// Returns the node at [path], which may also be an alias.
+fn find_node<'buf>(fdt: &FDT<'buf>, path: &str) -> Result<Node<'buf>> {
+	let resolved = if path.starts_with('/') { Some(path) } else { fdt.alias(path) };
+	let node = match resolved.map(|path| path.trim_end_matches('/')) {
+		Some("") => fdt.nodes().next(),
+		Some(path) => fdt.nodes().with_path(path).next(),
+		None => None,
+	};
+	node.map_or_else(|| error(format!("{}: no such node", path)), Ok)
+}
+
+fn node_mut<'t>(tree: &'t mut OwnedTree, path: &str) -> Result<&'t mut OwnedNode> {
+	match tree.node_mut(path) {
+		Some(node) => Ok(node),
+		None => error(format!("{}: no such node", path)),
+	}
+}
+
+/// Returns the node at [path], creating it if it doesn't exist. Missing
+/// parents are created as well if [parents] is set.
+fn create<'t>(tree: &'t mut OwnedTree, path: &str, parents: bool) -> Result<&'t mut OwnedNode> {
+	if !path.starts_with('/') {
+		return error(format!("{}: not a node path", path));
+	}
+	let names: Vec<&str> = path.split('/').filter(|name| !name.is_empty()).collect();
+	let mut node = &mut tree.root;
+	for (i, name) in names.iter().enumerate() {
+		if node.child(name).is_none() {
+			if !parents && i + 1 < names.len() {
+				return error(format!("/{}: no such node", names[..=i].join("/")));
+			}
+			node.children.push(OwnedNode::new(name));
+		}
+		node = match node.child_mut(name) {
+			Some(child) => child,
+			None => return error(format!("{}: no such node", path)),
+		};
+	}
+	Ok(node)
+}
@@ -0,0 +1,101 @@
+//! Support for typed binding structs built from device tree nodes.
+//!
+//! The functions of this module are used by the code generated by
+//! `#[derive(FromNode)]` (available with the 'derive' feature) but can also
+//! be used to implement `TryFrom<Node>` by hand.
+
+use core::fmt;
+use core::error::Error as CoreError;
+
+use error::Error;
+use node::Node;
+use property::PropertyValue;
+
+/// An error naming the property of a node which couldn't be parsed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PropertyError {
+	/// The name of the missing or malformed property
+	pub property: &'static str,
+	/// The reason the property couldn't be parsed, [Error::NotFound] for
+	/// missing properties.
+	pub error: Error,
+}
+
+impl PropertyError {
+	pub fn new(property: &'static str, error: Error) -> Self {
+		PropertyError { property, error }
+	}
+	
+	/// Returns true if the error was caused by the property being absent.
+	pub fn is_missing(&self) -> bool {
+		self.error == Error::NotFound
+	}
+}
+
+impl fmt::Display for PropertyError {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		if self.is_missing() {
+			write!(f, "missing property '{}'", self.property)
+		} else {
+			write!(f, "malformed property '{}': {}", self.property, self.error)
+		}
+	}
+}
+
+impl CoreError for PropertyError {}
+
+/// Types which can be resolved from a property holding a phandle.
+pub trait FromPhandle<'buf>: Sized {
+	/// Resolves the phandle stored in property [name] of [node].
+	fn from_phandle(node: &Node<'buf>, name: &'static str) -> Result<Self, PropertyError>;
+}
+
+impl<'buf> FromPhandle<'buf> for Node<'buf> {
+	fn from_phandle(node: &Node<'buf>, name: &'static str) -> Result<Self, PropertyError> {
+		let phandle = property::<u32>(node, name)?;
+		node.lookup_phandle(phandle).ok_or(PropertyError::new(name, Error::BadPhandle))
+	}
+}
+
+impl<'buf> FromPhandle<'buf> for Option<Node<'buf>> {
+	fn from_phandle(node: &Node<'buf>, name: &'static str) -> Result<Self, PropertyError> {
+		match property::<Option<u32>>(node, name)? {
+			Some(phandle) => node.lookup_phandle(phandle)
+				.map(Some)
+				.ok_or(PropertyError::new(name, Error::BadPhandle)),
+			None => Ok(None),
+		}
+	}
+}
+
+/// Parses property [name] of [node].
+///
+/// Absent properties are handled by [PropertyValue::from_missing].
+pub fn property<'buf, T: PropertyValue<'buf>>(node: &Node<'buf>, name: &'static str)
+		-> Result<T, PropertyError> {
+	node.property_value(name).map_err(|error| PropertyError::new(name, error))
+}
+
+/// Parses property [name] of [node], or returns the default value of [T] if
+/// the node doesn't have the property.
+pub fn property_or_default<'buf, T: PropertyValue<'buf> + Default>(node: &Node<'buf>,
+		name: &'static str) -> Result<T, PropertyError> {
+	match node.property(name) {
+		Some(prop) => prop.parse().map_err(|error| PropertyError::new(name, error)),
+		None => Ok(T::default()),
+	}
+}
+
+/// Resolves the phandle in property [name] of [node].
+pub fn phandle<'buf, T: FromPhandle<'buf>>(node: &Node<'buf>, name: &'static str)
+		-> Result<T, PropertyError> {
+	T::from_phandle(node, name)
+}
+
+// Paths used by the generated code, which can't rely on `::core` being in
+// scope for 2015 edition crates.
+#[doc(hidden)]
+pub mod __private {
+	pub use core::convert::TryFrom;
+	pub use core::result::Result;
+}
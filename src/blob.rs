@@ -4,21 +4,19 @@
 //pub fn fdt_offset_ptr(fdt: FDT, offset: usize, u32 checklen) -> const* usize {}
 	// Todo: Not needed
 
-// returns the next tag and the a pointer to it in nextoffset
-// Could be usefull in rust code
-//pub fn fdt_next_tag(fdt: FDT, offset: usize, nextoffset: const* usize) -> &usize {}
-
 use header::Header;
-use header::HEADER_V1_SIZE;
+use header::{HEADER_V1_SIZE, MAGIC};
+use error::Error;
+use tag::TagCursor;
 
 pub const MIN_COMPAT_VERSION: u32 = 1;
 pub const MAX_COMPAT_VERSION: u32 = 17;
 
-const FDT_BEGIN_NODE: u32	= 0x00000001;
-const FDT_END_NODE: u32	 	= 0x00000002;
-const FDT_PROP: u32			= 0x00000003;
-const FDT_NOP: u32			= 0x00000004;
-const FDT_END: u32			= 0x00000009;
+pub const FDT_BEGIN_NODE: u32	= 0x00000001;
+pub const FDT_END_NODE: u32	 	= 0x00000002;
+pub const FDT_PROP: u32			= 0x00000003;
+pub const FDT_NOP: u32			= 0x00000004;
+pub const FDT_END: u32			= 0x00000009;
 
 use core::slice;
 
@@ -35,35 +33,106 @@ pub struct Blob<'buf> {
 
 impl<'buf> Blob<'buf> {
 	pub unsafe fn from_raw(ptr: *const u8) -> Result<Self, ()> { // FDTError> {
-		let h = slice::from_raw_parts(ptr, HEADER_V1_SIZE as usize);
+		let h = slice::from_raw_parts(ptr, HEADER_V1_SIZE);
 		let th = Header::new(h).validate()?;
 		let s = slice::from_raw_parts(ptr, th.totalsize() as usize);
 		Ok(Blob {raw: s})
 	}
 
-	pub fn header(&self) -> Header {
+	/// Checks the header of [raw] and returns the blob it starts with.
+	///
+	/// The checks and their errors follow libfdt's `fdt_check_header`, except
+	/// that version 1 blobs are accepted.
+	pub fn from_bytes(raw: &'buf [u8]) -> Result<Self, Error> {
+		if raw.len() < HEADER_V1_SIZE {
+			return Err(Error::Truncated);
+		}
+		let h = Header::new(raw);
+		if h.magic().number() != MAGIC {
+			return Err(Error::BadMagic(h.magic().number()));
+		}
+		let (version, last_comp) = (h.version().number(), h.last_comp_version());
+		if version < MIN_COMPAT_VERSION || version < last_comp {
+			return Err(Error::BadVersion(version));
+		}
+		if last_comp > MAX_COMPAT_VERSION {
+			return Err(Error::BadVersion(last_comp));
+		}
+		let size = h.totalsize() as usize;
+		let header_size = h.size();
+		if raw.len() < header_size || size > raw.len() || size < header_size {
+			return Err(Error::Truncated);
+		}
+		let block = |offset: u32, len: Option<u32>| {
+			let start = offset as usize;
+			let end = start.checked_add(len.unwrap_or(0) as usize);
+			start >= header_size && end.is_some_and(|end| end <= size)
+		};
+		if !block(h.off_mem_rsvmap(), None) || !block(h.off_dt_struct(), h.size_dt_struct())
+				|| !block(h.off_dt_strings(), h.size_dt_strings()) {
+			return Err(Error::Truncated);
+		}
+		Ok(Blob { raw: &raw[..size] })
+	}
+
+	/// Returns the bytes of the blob, up to its totalsize.
+	pub fn raw(&self) -> &'buf [u8] {
+		self.raw
+	}
+
+	pub fn header(&self) -> Header<'buf> {
 		Header::new(&self.raw[0..])
 	}
 	
 	pub fn nodes(&self) -> StructReader<'buf> {
-		let o = self.header().off_dt_struct() as usize;
-		let s = self.header().off_dt_strings() as usize;
 		StructReader { 
-			d: &self.raw[o..],
-			s: &self.raw[s..],
+			d: self.structure(),
+			s: self.strings(),
 			o: 0,
+			legacy: self.header().legacy(),
 		}
 	}
 	
+	/// Returns a cursor over the tags of the structure block.
+	///
+	/// The blocks are bounded by their sizes in the header, or by the end of
+	/// the blob for versions without sizes.
+	pub fn tags(&self) -> TagCursor<'buf> {
+		TagCursor::new(self.structure(), self.strings(), self.header().legacy())
+	}
+
+	/// Returns the structure block, bounded like [tags()](Blob::tags).
+	pub fn structure(&self) -> &'buf [u8] {
+		let h = self.header();
+		self.block(h.off_dt_struct(), h.size_dt_struct())
+	}
+
+	/// Returns the strings block, bounded like [tags()](Blob::tags).
+	pub fn strings(&self) -> &'buf [u8] {
+		let h = self.header();
+		self.block(h.off_dt_strings(), h.size_dt_strings())
+	}
+
+	fn block(&self, offset: u32, size: Option<u32>) -> &'buf [u8] {
+		let start = offset as usize;
+		let end = size.map_or(self.raw.len(), |size| start.saturating_add(size as usize));
+		self.raw.get(start..end.min(self.raw.len())).unwrap_or(&[])
+	}
+	
 	// max_phandle has been omitted for the time beeing, as it's use isn't 
 	// considered for the public api (we don't care for overlays).
 	// Todo: required for rw operations
 	
+	/// Returns the number of bytes from the memory reserve map to the end of
+	/// the blob.
+	pub fn rsvmap_len(&self) -> usize {
+		self.raw.len().saturating_sub(self.header().off_mem_rsvmap() as usize)
+	}
+
 	/// Retrieve the offset to the memory reserve map
-	
 	pub fn rsvmap(&self) -> RsvMapReader<'buf> {
 		let o = self.header().off_mem_rsvmap() as usize;
-		RsvMapReader { d: &self.raw[o..], o: 0 }
+		RsvMapReader { d: self.raw.get(o..).unwrap_or(&[]), o: 0 }
 	}
 }
 
@@ -78,12 +147,12 @@ pub enum Token {
 
 impl fmt::Display for Token {
 	fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
-		match self {
-			&Token::BeginNode => write!(f, "token FDT_BEGIN_NODE"),
-			&Token::EndNode => write!(f, "token FDT_END_NODE"),
-			&Token::Prop => write!(f, "token FDT_PROP"),
-			&Token::End => write!(f, "token FDT_END"),
-			&Token::Error(val) => write!(f, "{:x}", val),
+		match *self {
+			Token::BeginNode => write!(f, "token FDT_BEGIN_NODE"),
+			Token::EndNode => write!(f, "token FDT_END_NODE"),
+			Token::Prop => write!(f, "token FDT_PROP"),
+			Token::End => write!(f, "token FDT_END"),
+			Token::Error(val) => write!(f, "{:x}", val),
 		}
 	}
 }
@@ -93,6 +162,8 @@ pub struct StructReader<'blob> {
 	d: &'blob [u8],
 	s: &'blob [u8],
 	o: usize,
+	// versions before 16, see Header::legacy
+	legacy: bool,
 }
 
 impl<'blob> fmt::Debug for StructReader<'blob> {
@@ -105,46 +176,48 @@ impl<'blob> StructReader<'blob> {
 	pub fn offs(&self) -> usize {
 		self.o
 	}
+	
+	/// Returns a reader positioned at the start of the structure block
+	pub fn rewind(&self) -> Self {
+		StructReader { o: 0, ..self.clone() }
+	}
 
+	/// Reads the next tag, skipping FDT_NOP tags.
+	///
+	/// Reading past the end of the structure block, which only happens for
+	/// corrupt blobs, returns [Token::Error] like an unknown tag does.
 	pub fn token(&mut self) -> Token {
 		loop {
-			match BE::read_u32(&self.d[self.o..]) {
-				FDT_NOP => {
-					self.o += 4;
-				},
-				FDT_BEGIN_NODE => {
-					self.o += 4;
-					return Token::BeginNode;
-				},
-				FDT_END_NODE => {
-					self.o += 4;
-					return Token::EndNode;
-				},
-				FDT_PROP => {
-					self.o += 4;
-					return Token::Prop;
-				}
-				FDT_END => {
-					self.o += 4;
-					return Token::End;
-				},
-				other => {
-					self.o += 4;
-					return Token::Error(other);
-				},
+			let tag = self.read_u32();
+			match tag {
+				Some(FDT_NOP) => (),
+				Some(FDT_BEGIN_NODE) => return Token::BeginNode,
+				Some(FDT_END_NODE) => return Token::EndNode,
+				Some(FDT_PROP) => return Token::Prop,
+				Some(FDT_END) => return Token::End,
+				other => return Token::Error(other.unwrap_or(0)),
 			}
 		}
 	}
 	
+	/// Moves the reader [bytes] forward, at most to the end of the
+	/// structure block.
 	pub fn skip(&mut self, bytes: usize) -> &mut Self {
-		self.o += bytes;
+		self.o = self.o.saturating_add(bytes).min(self.d.len());
 		self
 	}
 	
-	pub fn read_u32(&mut self) -> u32 {
+	pub fn back(&mut self, bytes: usize) -> &mut Self {
+		self.o = self.o.saturating_sub(bytes);
+		self
+	}
+	
+	/// Reads a cell, None if the structure block ends before it. The
+	/// reader moves past the cell either way.
+	pub fn read_u32(&mut self) -> Option<u32> {
 		let o = self.o;
 		self.o += 4;
-		BE::read_u32(&self.d[o..])
+		self.d.get(o..o + 4).map(BE::read_u32)
 	}
 	
 	pub fn align(&mut self, align: usize) -> &mut Self {
@@ -152,39 +225,90 @@ impl<'blob> StructReader<'blob> {
 		self
 	}
 	
-	pub fn string(&mut self) -> &'blob str {
-		let d = &self.d[self.o..];
-		let len = memchr(b'\0', d).unwrap_or(d.len());
+	/// Reads a NUL terminated string, None if it isn't terminated within
+	/// the structure block or isn't valid utf8.
+	pub fn string(&mut self) -> Option<&'blob str> {
+		let d = self.d.get(self.o..)?;
+		let len = memchr(b'\0', d)?;
 		self.o += len + 1;
-		str::from_utf8(&d[..len]).unwrap()
+		str::from_utf8(&d[..len]).ok()
+	}
+	
+	/// Reads the name of a node. Legacy blobs store the full path of the
+	/// node, of which only the last component is returned.
+	pub fn node_name(&mut self) -> Option<&'blob str> {
+		let name = self.string()?;
+		Some(match self.legacy {
+			true => name.rsplit('/').next().unwrap_or(name),
+			false => name,
+		})
+	}
+	
+	/// Moves past the padding legacy blobs put before property values of 8
+	/// bytes or more to align them to 8 bytes, like libfdt.
+	fn value_align(&mut self, len: usize) -> &mut Self {
+		if self.legacy && len >= 8 && !self.o.is_multiple_of(8) {
+			self.skip(4);
+		}
+		self
+	}
+	
+	/// Reads a property value of [len] bytes, following the name offset.
+	pub fn value(&mut self, len: usize) -> Option<&'blob [u8]> {
+		self.value_align(len).slice(len)
+	}
+	
+	/// Skips the length, name offset and value of a property.
+	fn skip_prop(&mut self) {
+		let len = self.read_u32().unwrap_or(0) as usize;
+		self.skip(4).value_align(len).skip(len);
+		self.align(4);
 	}
 	
 	pub fn skip_props(&mut self) -> &mut Self {
+		while let Token::Prop = self.token() {
+			self.skip_prop();
+		}
+		self.back(4)
+	}
+	
+	/// Skips the rest of the node whose properties have been read, up to
+	/// and including its FDT_END_NODE tag.
+	///
+	/// Only tags are read, names and properties of the descendants are
+	/// skipped without being parsed. A reader at a bad tag is left at it.
+	pub fn skip_subtree(&mut self) -> &mut Self {
+		let mut depth = 0;
 		loop {
 			match self.token() {
-				Token::Prop => {
-					let len = self.read_u32() as usize;
-					self.skip(4 + len);
+				Token::BeginNode => {
+					let d = self.d.get(self.o..).unwrap_or(&[]);
+					self.skip(memchr(b'\0', d).unwrap_or(d.len()) + 1);
 					self.align(4);
+					depth += 1;
 				},
-				_ => break,
+				Token::Prop => self.skip_prop(),
+				Token::EndNode if depth == 0 => return self,
+				Token::EndNode => depth -= 1,
+				Token::End | Token::Error(_) => return self.back(4),
 			}
 		}
-		self.o -= 4;
-		self
 	}
 	
-	pub fn slice(&mut self, len: usize) -> &'blob [u8] {
-		let d = &self.d[self.o..];
+	/// Reads [len] bytes, None if the structure block ends before them.
+	pub fn slice(&mut self, len: usize) -> Option<&'blob [u8]> {
+		let d = self.d.get(self.o..)?.get(..len)?;
 		self.o += len;
-		&d[.. len]
+		Some(d)
 	}
 	
-	pub fn string_ref(&mut self) -> &'blob str {
-		let o = self.read_u32() as usize ;
-		let d = &self.s[o..];
-		let len = memchr(b'\0', d).unwrap_or(d.len());
-		str::from_utf8(&d[0..len]).unwrap()
+	/// Reads a string offset and returns the string it refers to in the
+	/// strings block.
+	pub fn string_ref(&mut self) -> Option<&'blob str> {
+		let o = self.read_u32()? as usize;
+		let d = self.s.get(o..)?;
+		let len = memchr(b'\0', d)?;
+		str::from_utf8(&d[0..len]).ok()
 	}
 }
 
@@ -195,9 +319,10 @@ pub struct RsvMapReader<'blob> {
 }
 
 impl<'blob> RsvMapReader<'blob> {
-	pub fn read_u64(&mut self) -> u64 {
+	/// Reads a 64 bit value, None past the end of the blob.
+	pub fn read_u64(&mut self) -> Option<u64> {
 		let o = self.o;
 		self.o += 8;
-		BE::read_u64(&self.d[o..])
+		self.d.get(o..o + 8).map(BE::read_u64)
 	}
 }
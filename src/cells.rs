@@ -0,0 +1,90 @@
+use core::fmt;
+
+use byteorder::{ByteOrder, BE};
+
+use error::Error;
+
+/// An iterator over the big endian 32 bit cells of a property value.
+///
+/// The cells are read directly from the device tree blob as they are iterated.
+#[derive(Clone)]
+pub struct Cells<'a> {
+	raw: &'a [u8],
+}
+
+impl<'a> Cells<'a> {
+	/// Creates a cell iterator over [raw].
+	///
+	/// # Errors
+	///
+	/// Returns [Error::BadLength] if the length of [raw] isn't a multiple of
+	/// the cell size.
+	pub fn new(raw: &'a [u8]) -> Result<Self, Error> {
+		if !raw.len().is_multiple_of(4) {
+			Err(Error::BadLength(raw.len()))
+		} else {
+			Ok(Self { raw })
+		}
+	}
+	
+	/// Returns the remaining cells as raw big endian bytes.
+	pub fn raw(&self) -> &'a [u8] {
+		self.raw
+	}
+}
+
+impl<'a> Iterator for Cells<'a> {
+	type Item = u32;
+	
+	fn next(&mut self) -> Option<Self::Item> {
+		if self.raw.is_empty() {
+			None
+		} else {
+			let (cell, rest) = self.raw.split_at(4);
+			self.raw = rest;
+			Some(BE::read_u32(cell))
+		}
+	}
+	
+	fn size_hint(&self) -> (usize, Option<usize>) {
+		(self.len(), Some(self.len()))
+	}
+}
+
+impl<'a> ExactSizeIterator for Cells<'a> {
+	fn len(&self) -> usize {
+		self.raw.len() / 4
+	}
+}
+
+impl<'a> DoubleEndedIterator for Cells<'a> {
+	fn next_back(&mut self) -> Option<Self::Item> {
+		if self.raw.is_empty() {
+			None
+		} else {
+			let (rest, cell) = self.raw.split_at(self.raw.len() - 4);
+			self.raw = rest;
+			Some(BE::read_u32(cell))
+		}
+	}
+}
+
+impl<'a> fmt::Debug for Cells<'a> {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		f.debug_list().entries(self.clone()).finish()
+	}
+}
+
+impl<'a> fmt::Display for Cells<'a> {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		write!(f, "<")?;
+		let mut cells = self.clone();
+		if let Some(cell) = cells.next() {
+			write!(f, "{:#x}", cell)?;
+		}
+		for cell in cells {
+			write!(f, " {:#x}", cell)?;
+		}
+		write!(f, ">")
+	}
+}
@@ -0,0 +1,337 @@
+//! Semantic checks of device trees, like the ones run by dtc.
+//!
+//! The checks find trees which are structurally valid but likely to be
+//! misinterpreted by the kernel: unit addresses not matching `reg`, cell
+//! counts left to their defaults, references to missing phandles and the
+//! like. Every problem found is reported with the [Check] which found it so
+//! diagnostics can be filtered or printed the way dtc does.
+//!
+//! # Examples
+//!
+//! ```
+//! use fdt::FDT;
+//! use fdt::checks::{self, Severity};
+//! let dtb = include_bytes!("../tests/dt.dtb").as_ptr();
+//!
+//! let fdt;
+//! unsafe { fdt = FDT::from_raw(dtb).unwrap(); }
+//!
+//! let mut errors = 0;
+//! checks::check(&fdt, |diagnostic| {
+//!     println!("{}", diagnostic);
+//!     if diagnostic.problem.check().severity() == Severity::Error {
+//!         errors += 1;
+//!     }
+//! });
+//! assert_eq!(errors, 0);
+//! ```
+
+use core::fmt;
+
+use byteorder::{ByteOrder, BE};
+
+use entries::{self, Context, Entries};
+use node::Node;
+use FDT;
+
+/// The checks run over a tree, named like their dtc counterparts.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Check {
+	UnitAddressVsReg,
+	RegFormat,
+	DuplicateNodeNames,
+	DuplicatePhandles,
+	PhandleReferences,
+	InterruptsProperty,
+	NodeNameChars,
+	AvoidDefaultAddrSize,
+	BusAddressCells,
+}
+
+/// The severity of a [Check].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+	Warning,
+	Error,
+}
+
+/// A problem found in a node.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Problem<'buf> {
+	/// The node has a `reg` or `ranges` property but no unit address.
+	MissingUnitAddress,
+	/// The node has a unit address but no `reg` or `ranges` property.
+	MissingReg,
+	/// The unit address isn't the first address of `reg`.
+	UnitAddressMismatch { unit: &'buf str, reg: u64 },
+	/// The length of `reg` isn't a multiple of the parent's cell counts.
+	RegLength { len: usize, address_cells: u32, size_cells: u32 },
+	/// Two children of the node have the same name.
+	DuplicateNodeName(&'buf str),
+	/// The node's phandle is used by an earlier node as well.
+	DuplicatePhandle(u32),
+	/// A property refers to a phandle which doesn't exist.
+	DanglingPhandle { property: &'buf str, phandle: u32 },
+	/// The node has interrupts but no interrupt parent.
+	MissingInterruptParent,
+	/// The interrupt parent doesn't have the `#interrupt-cells` property.
+	MissingInterruptCells,
+	/// The node name contains an invalid character or more than one '@'.
+	BadNodeName,
+	/// The node has `reg` or `ranges` but its parent relies on the default
+	/// value of the named property.
+	DefaultAddrSize(&'static str),
+	/// A bus node with children doesn't have the named property.
+	MissingBusCells(&'static str),
+}
+
+/// A problem found by a [Check] in [node].
+pub struct Diagnostic<'a, 'buf: 'a> {
+	pub node: &'a Node<'buf>,
+	pub problem: Problem<'buf>,
+}
+
+impl Check {
+	/// The name of the check, as used by dtc.
+	pub fn name(&self) -> &'static str {
+		match *self {
+			Check::UnitAddressVsReg => "unit_address_vs_reg",
+			Check::RegFormat => "reg_format",
+			Check::DuplicateNodeNames => "duplicate_node_names",
+			Check::DuplicatePhandles => "explicit_phandles",
+			Check::PhandleReferences => "phandle_references",
+			Check::InterruptsProperty => "interrupts_property",
+			Check::NodeNameChars => "node_name_chars",
+			Check::AvoidDefaultAddrSize => "avoid_default_addr_size",
+			Check::BusAddressCells => "bus_address_cells",
+		}
+	}
+
+	/// The severity of the problems found by the check.
+	pub fn severity(&self) -> Severity {
+		match *self {
+			Check::DuplicateNodeNames | Check::DuplicatePhandles
+				| Check::NodeNameChars => Severity::Error,
+			_ => Severity::Warning,
+		}
+	}
+}
+
+impl<'buf> Problem<'buf> {
+	/// The check which finds the problem.
+	pub fn check(&self) -> Check {
+		match *self {
+			Problem::MissingUnitAddress | Problem::MissingReg
+				| Problem::UnitAddressMismatch { .. } => Check::UnitAddressVsReg,
+			Problem::RegLength { .. } => Check::RegFormat,
+			Problem::DuplicateNodeName(_) => Check::DuplicateNodeNames,
+			Problem::DuplicatePhandle(_) => Check::DuplicatePhandles,
+			Problem::DanglingPhandle { .. } => Check::PhandleReferences,
+			Problem::MissingInterruptParent
+				| Problem::MissingInterruptCells => Check::InterruptsProperty,
+			Problem::BadNodeName => Check::NodeNameChars,
+			Problem::DefaultAddrSize(_) => Check::AvoidDefaultAddrSize,
+			Problem::MissingBusCells(_) => Check::BusAddressCells,
+		}
+	}
+}
+
+/// Runs every check over the nodes of [fdt], calling [report] for every
+/// problem found.
+///
+/// Returns the number of problems.
+pub fn check<'buf, F>(fdt: &FDT<'buf>, mut report: F) -> usize
+		where F: FnMut(Diagnostic<'_, 'buf>) {
+	match fdt.nodes().next() {
+		Some(root) => walk(&root, Context::root(), &mut report),
+		None => 0,
+	}
+}
+
+/// Runs every check over [node] and its descendants, calling [report] for
+/// every problem found.
+///
+/// Returns the number of problems.
+pub fn check_node<'buf, F>(node: &Node<'buf>, mut report: F) -> usize
+		where F: FnMut(Diagnostic<'_, 'buf>) {
+	walk(node, Context::of(node), &mut report)
+}
+
+fn walk<'buf, F>(node: &Node<'buf>, ctx: Context, report: &mut F) -> usize
+		where F: FnMut(Diagnostic<'_, 'buf>) {
+	let mut count = 0;
+	check_one(node, ctx, &mut |problem| {
+		count += 1;
+		report(Diagnostic { node, problem });
+	});
+	let child_ctx = ctx.child(node);
+	for child in node.children() {
+		count += walk(&child, child_ctx, report);
+	}
+	count
+}
+
+fn check_one<'buf>(node: &Node<'buf>, ctx: Context, emit: &mut dyn FnMut(Problem<'buf>)) {
+	let root = node.depth() == 0;
+	let name = node.name();
+	let unit = name.split_once('@').map(|(_, unit)| unit);
+	let reg = node.property("reg");
+
+	// node_name_chars
+	let valid = |c: char| c.is_ascii_alphanumeric() || ",._+-".contains(c);
+	if !root && (!name.split('@').all(|part| part.chars().all(valid))
+			|| name.matches('@').count() > 1) {
+		emit(Problem::BadNodeName);
+	}
+
+	// unit_address_vs_reg
+	if !root {
+		let has_reg = reg.is_some() || node.property("ranges").is_some();
+		match unit {
+			None if has_reg => emit(Problem::MissingUnitAddress),
+			Some(_) if !has_reg => emit(Problem::MissingReg),
+			Some(unit) => {
+				let address = reg.as_ref()
+					.filter(|_| (1..=2).contains(&ctx.address_cells))
+					.filter(|reg| reg.raw().len() >= ctx.address_cells as usize * 4)
+					.map(|reg| read_cells(reg.raw(), ctx.address_cells));
+				if let (Some(address), Ok(parsed)) = (address, u64::from_str_radix(unit, 16)) {
+					if address != parsed {
+						emit(Problem::UnitAddressMismatch { unit, reg: address });
+					}
+				}
+			},
+			None => {},
+		}
+	}
+
+	// reg_format
+	if let Some(ref reg) = reg {
+		let entry = (ctx.address_cells + ctx.size_cells) as usize * 4;
+		let len = reg.raw().len();
+		if len == 0 || entry == 0 || !len.is_multiple_of(entry) {
+			emit(Problem::RegLength {
+				len,
+				address_cells: ctx.address_cells,
+				size_cells: ctx.size_cells,
+			});
+		}
+	}
+
+	// avoid_default_addr_size
+	if !root && (reg.is_some() || node.property("ranges").is_some()) {
+		for &(cells, set) in &[("#address-cells", ctx.has_address_cells), ("#size-cells", ctx.has_size_cells)] {
+			if !set {
+				emit(Problem::DefaultAddrSize(cells));
+			}
+		}
+	}
+
+	// bus_address_cells
+	let bus = node.is_compatible_with("simple-bus")
+		|| node.has_name("spi") || node.has_name("i2c");
+	if bus && node.children().next().is_some() {
+		for &cells in &["#address-cells", "#size-cells"] {
+			if node.property(cells).is_none() {
+				emit(Problem::MissingBusCells(cells));
+			}
+		}
+	}
+
+	// duplicate_node_names
+	for (i, child) in node.children().enumerate() {
+		if node.children().take(i).any(|other| other.name() == child.name()) {
+			emit(Problem::DuplicateNodeName(child.name()));
+		}
+	}
+
+	// explicit_phandles
+	if let Some(phandle) = node.phandle() {
+		let duplicate = node.lookup_phandle(phandle)
+			.is_some_and(|first| first.offset() != node.offset());
+		if duplicate {
+			emit(Problem::DuplicatePhandle(phandle));
+		}
+	}
+
+	// phandle_references
+	for prop in node.properties() {
+		let name = prop.name();
+		let is_phandle = name == "interrupt-parent"
+			|| (name.starts_with("pinctrl-") && name != "pinctrl-names")
+			|| entries::phandle_array_cells(name).is_some();
+		if !is_phandle || !prop.raw().len().is_multiple_of(4) {
+			continue;
+		}
+		for entry in Entries::new(&prop, node, ctx) {
+			let phandle = BE::read_u32(entry);
+			// a zero phandle is a placeholder for unused entries
+			if phandle != 0 && node.lookup_phandle(phandle).is_none() {
+				emit(Problem::DanglingPhandle { property: name, phandle });
+				break;
+			}
+		}
+	}
+
+	// interrupts_property
+	if node.property("interrupts").is_some() {
+		let parent = node.property("interrupt-parent")
+			.and_then(|prop| prop.as_u32().ok())
+			.or(ctx.interrupt_parent);
+		match parent.map(|phandle| node.lookup_phandle(phandle)) {
+			None => emit(Problem::MissingInterruptParent),
+			Some(Some(intc)) if intc.property("#interrupt-cells").is_none() =>
+				emit(Problem::MissingInterruptCells),
+			// dangling interrupt parents are reported by phandle_references
+			Some(_) => {},
+		}
+	}
+}
+
+fn read_cells(raw: &[u8], cells: u32) -> u64 {
+	raw.chunks(4).take(cells as usize).fold(0, |value, cell| value << 32 | u64::from(BE::read_u32(cell)))
+}
+
+impl fmt::Display for Severity {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		f.write_str(match *self {
+			Severity::Warning => "Warning",
+			Severity::Error => "ERROR",
+		})
+	}
+}
+
+impl<'buf> fmt::Display for Problem<'buf> {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match *self {
+			Problem::MissingUnitAddress =>
+				f.write_str("node has a reg or ranges property, but no unit name"),
+			Problem::MissingReg =>
+				f.write_str("node has a unit name, but no reg or ranges property"),
+			Problem::UnitAddressMismatch { unit, reg } =>
+				write!(f, "unit address '{}' doesn't match reg address {:#x}", unit, reg),
+			Problem::RegLength { len, address_cells, size_cells } =>
+				write!(f, "\"reg\" property has invalid length ({} bytes) \
+					(#address-cells == {}, #size-cells == {})", len, address_cells, size_cells),
+			Problem::DuplicateNodeName(name) => write!(f, "Duplicate node name '{}'", name),
+			Problem::DuplicatePhandle(phandle) => write!(f, "duplicated phandle {:#x}", phandle),
+			Problem::DanglingPhandle { property, phandle } =>
+				write!(f, "'{}' refers to missing phandle {:#x}", property, phandle),
+			Problem::MissingInterruptParent => f.write_str("Missing interrupt-parent"),
+			Problem::MissingInterruptCells =>
+				f.write_str("Missing #interrupt-cells in interrupt-parent"),
+			Problem::BadNodeName => f.write_str("Bad character in node name"),
+			Problem::DefaultAddrSize(cells) => write!(f, "Relying on default {} value", cells),
+			Problem::MissingBusCells(cells) => write!(f, "Missing {} on bus node", cells),
+		}
+	}
+}
+
+impl<'a, 'buf> fmt::Display for Diagnostic<'a, 'buf> {
+	/// Formats the diagnostic like dtc does, e.g.
+	/// `Warning (reg_format): /soc/foo: "reg" property has invalid length`.
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		let check = self.problem.check();
+		write!(f, "{} ({}): {}: {}", check.severity(), check.name(), self.node.path(), self.problem)
+	}
+}
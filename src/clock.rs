@@ -0,0 +1,404 @@
+//! Typed access to the clock bindings.
+//!
+//! Clock providers are the nodes with a `#clock-cells` property. Consumers
+//! refer to their input clocks with the phandle list in `clocks`, each entry
+//! being the phandle of a provider followed by `#clock-cells` cells selecting
+//! one of its outputs, and may name them with `clock-names`. The clocks to be
+//! configured before the consumer is used are listed in `assigned-clocks`,
+//! with their parents in `assigned-clock-parents` and their rates in
+//! `assigned-clock-rates`.
+//!
+//! The rates of the `fixed-clock` and `fixed-factor-clock` providers are
+//! described completely by the tree, see [Clock::rate()].
+//!
+//! # Examples
+//!
+//! ```
+//! use fdt::{FDT, NodeIterator};
+//! use fdt::clock;
+//! let dtb = include_bytes!("../tests/dt.dtb");
+//!
+//! let fdt = FDT::from_bytes(dtb).unwrap();
+//! let usb = fdt.nodes().with_name("usb@7e980000").next().unwrap();
+//! let otg = clock::clock(&usb, "otg").unwrap();
+//! assert_eq!(otg.name(), Some("otg"));
+//! assert_eq!(otg.rate(), Ok(Some(480_000_000)));
+//! ```
+
+use byteorder::{ByteOrder, BE};
+
+use cells::Cells;
+use entries::{cells, PhandleArgs};
+use error::Error;
+use node::{Node, Subnodes};
+use stringlist::Strings;
+
+/// The maximum number of providers followed by [Clock::rate()] before it
+/// gives up on a chain of clocks, which is probably a loop.
+const MAX_DEPTH: usize = 32;
+
+/// An output clock of a provider: the provider and the specifier cells
+/// selecting the output.
+#[derive(Clone, Debug)]
+pub struct Clock<'buf> {
+	provider: Node<'buf>,
+	args: Cells<'buf>,
+}
+
+impl<'buf> Clock<'buf> {
+	pub fn provider(&self) -> &Node<'buf> {
+		&self.provider
+	}
+
+	/// Returns the `#clock-cells` cells of the specifier.
+	pub fn args(&self) -> Cells<'buf> {
+		self.args.clone()
+	}
+
+	/// Returns the index of the output, the single cell of the specifier
+	/// for providers with one `#clock-cells` and 0 for providers with none.
+	pub fn index(&self) -> Option<u32> {
+		match self.args.len() {
+			0 => Some(0),
+			1 => self.args().next(),
+			_ => None,
+		}
+	}
+
+	/// Returns the name of the output from `clock-output-names` of the
+	/// provider.
+	pub fn name(&self) -> Option<&'buf str> {
+		ClockProvider { node: self.provider.clone() }.output_name(self.index()?)
+	}
+
+	/// Returns the rate of the clock in Hz, or None if it depends on a
+	/// provider which isn't a fixed clock or a fixed factor clock.
+	///
+	/// Fixed factor clocks are followed through their parents up to a fixed
+	/// clock, each one scaling the rate of its parent rounded down like the
+	/// clock itself does.
+	///
+	/// # Errors
+	///
+	/// Returns [Error::BadValue] for a chain of more than 32 fixed factor
+	/// clocks, a divider of 0 or a rate which overflows, and the errors of
+	/// [ClockProvider::kind()] and [clocks()] for malformed providers.
+	pub fn rate(&self) -> Result<Option<u64>, Error> {
+		provider_rate(&self.provider, MAX_DEPTH)
+	}
+}
+
+/// Returns the rate of [provider], following at most [depth] providers.
+fn provider_rate(provider: &Node, depth: usize) -> Result<Option<u64>, Error> {
+	if depth == 0 {
+		return Err(Error::BadValue);
+	}
+	let provider = ClockProvider { node: provider.clone() };
+	match provider.kind()? {
+		ClockKind::Fixed { frequency, .. } => Ok(Some(frequency)),
+		ClockKind::FixedFactor { mult, div } => {
+			if div == 0 {
+				return Err(Error::BadValue);
+			}
+			let parent = clocks(&provider.node)?.next().ok_or(Error::NotFound)??;
+			match provider_rate(&parent.provider, depth - 1)? {
+				Some(rate) => rate.checked_mul(mult as u64)
+					.map(|rate| Some(rate / div as u64))
+					.ok_or(Error::BadValue),
+				None => Ok(None),
+			}
+		},
+		ClockKind::Other => Ok(None),
+	}
+}
+
+/// Returns the input clocks of [node] from its `clocks` property.
+///
+/// # Errors
+///
+/// Returns [Error::NotFound] if [node] doesn't have the property and
+/// [Error::BadLength] if it isn't a list of cells.
+pub fn clocks<'buf>(node: &Node<'buf>) -> Result<Clocks<'buf>, Error> {
+	let list = phandle_list(node, "clocks")?.ok_or(Error::NotFound)?;
+	Ok(Clocks { list })
+}
+
+/// Returns the input clock of [node] named [name] in `clock-names`.
+///
+/// # Errors
+///
+/// Returns [Error::NotFound] if [node] doesn't have a clock of that name and
+/// the errors of [clocks()] otherwise.
+///
+/// # Examples
+///
+/// ```
+/// use fdt::{FDT, NodeIterator};
+/// use fdt::clock;
+/// let dtb = include_bytes!("../tests/dt.dtb");
+///
+/// let fdt = FDT::from_bytes(dtb).unwrap();
+/// let uart = fdt.nodes().with_name("serial@7e201000").next().unwrap();
+/// let clock = clock::clock(&uart, "apb_pclk").unwrap();
+/// assert_eq!(clock.provider().name(), "cprman@7e101000");
+/// assert_eq!(clock.index(), Some(20));
+/// ```
+pub fn clock<'buf>(node: &Node<'buf>, name: &str) -> Result<Clock<'buf>, Error> {
+	let index = clock_names(node).position(|clock| clock == name).ok_or(Error::NotFound)?;
+	clocks(node)?.nth(index).ok_or(Error::NotFound)?
+}
+
+/// Returns the names of the input clocks of [node] from `clock-names`.
+pub fn clock_names<'buf>(node: &Node<'buf>) -> Strings<'buf> {
+	strings(node, "clock-names")
+}
+
+fn strings<'buf>(node: &Node<'buf>, name: &str) -> Strings<'buf> {
+	node.property(name)
+		.map(|prop| prop.as_stringlist())
+		.unwrap_or_default()
+		.strings()
+}
+
+/// Returns the entries of the clock phandle list [name] of [node], or None
+/// if [node] doesn't have the property.
+fn phandle_list<'buf>(node: &Node<'buf>, name: &str) -> Result<Option<PhandleArgs<'buf>>, Error> {
+	match node.property(name) {
+		Some(prop) => Ok(Some(PhandleArgs::new(node, Cells::new(prop.raw())?.raw(), "#clock-cells"))),
+		None => Ok(None),
+	}
+}
+
+/// Returns the clock of an entry of a clock phandle list.
+fn clock_of<'buf>((provider, args): (Node<'buf>, Cells<'buf>)) -> Clock<'buf> {
+	Clock { provider, args }
+}
+
+/// An iterator over the input clocks of a node, see [clocks()].
+///
+/// Iteration stops after the first error.
+#[derive(Clone, Debug)]
+pub struct Clocks<'buf> {
+	list: PhandleArgs<'buf>,
+}
+
+impl<'buf> Iterator for Clocks<'buf> {
+	type Item = Result<Clock<'buf>, Error>;
+
+	fn next(&mut self) -> Option<Self::Item> {
+		match self.list.next()? {
+			Ok(Some(entry)) => Some(Ok(clock_of(entry))),
+			Ok(None) => {
+				self.list.stop();
+				Some(Err(Error::BadPhandle))
+			},
+			Err(err) => Some(Err(err)),
+		}
+	}
+}
+
+/// The kind of a clock provider, from its `compatible` property.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ClockKind {
+	/// A `fixed-clock` running at [frequency] Hz, with an accuracy of
+	/// [accuracy] ppb if known.
+	Fixed { frequency: u64, accuracy: Option<u32> },
+	/// A `fixed-factor-clock` running at the rate of its parent times
+	/// [mult] divided by [div].
+	FixedFactor { mult: u32, div: u32 },
+	/// Any other provider, whose rates aren't described by the tree.
+	Other,
+}
+
+/// A clock provider, a node with the `#clock-cells` property.
+#[derive(Clone, Debug)]
+pub struct ClockProvider<'buf> {
+	node: Node<'buf>,
+}
+
+impl<'buf> ClockProvider<'buf> {
+	/// Returns the provider of [node], or None if it isn't a clock provider.
+	pub fn new(node: Node<'buf>) -> Option<Self> {
+		node.property("#clock-cells").map(|_| ClockProvider { node })
+	}
+
+	pub fn node(&self) -> &Node<'buf> {
+		&self.node
+	}
+
+	/// Returns the number of cells of the specifiers of the provider.
+	///
+	/// # Errors
+	///
+	/// Returns [Error::BadNCells] if `#clock-cells` is malformed.
+	pub fn clock_cells(&self) -> Result<u32, Error> {
+		cells(&self.node, "#clock-cells").ok_or(Error::BadNCells)
+	}
+
+	/// Returns the names of the outputs from `clock-output-names`.
+	pub fn output_names(&self) -> Strings<'buf> {
+		strings(&self.node, "clock-output-names")
+	}
+
+	/// Returns the output indices from `clock-indices`, or None if the
+	/// outputs are numbered from 0 in the order of `clock-output-names`.
+	///
+	/// # Errors
+	///
+	/// Returns [Error::BadLength] if `clock-indices` isn't a list of cells.
+	pub fn indices(&self) -> Result<Option<Cells<'buf>>, Error> {
+		self.node.property("clock-indices").map(|prop| Cells::new(prop.raw())).transpose()
+	}
+
+	/// Returns the name of output [index], looked up in `clock-indices` if
+	/// the provider has it.
+	pub fn output_name(&self, index: u32) -> Option<&'buf str> {
+		let position = match self.indices().ok()? {
+			Some(mut indices) => indices.position(|i| i == index)?,
+			None => index as usize,
+		};
+		self.output_names().nth(position)
+	}
+
+	/// Returns the kind of the provider.
+	///
+	/// # Errors
+	///
+	/// Returns [Error::NotFound] if a fixed clock lacks `clock-frequency` or
+	/// a fixed factor clock `clock-mult` or `clock-div`, and
+	/// [Error::BadLength] if these aren't cells.
+	pub fn kind(&self) -> Result<ClockKind, Error> {
+		let u32_of = |name| self.node.property(name).ok_or(Error::NotFound)?.as_u32();
+		if self.node.is_compatible_with("fixed-clock") {
+			let prop = self.node.property("clock-frequency").ok_or(Error::NotFound)?;
+			let frequency = match prop.raw().len() {
+				4 => BE::read_u32(prop.raw()) as u64,
+				8 => BE::read_u64(prop.raw()),
+				len => return Err(Error::BadLength(len)),
+			};
+			let accuracy = match u32_of("clock-accuracy") {
+				Err(Error::NotFound) => None,
+				accuracy => Some(accuracy?),
+			};
+			Ok(ClockKind::Fixed { frequency, accuracy })
+		} else if self.node.is_compatible_with("fixed-factor-clock") {
+			Ok(ClockKind::FixedFactor { mult: u32_of("clock-mult")?, div: u32_of("clock-div")? })
+		} else {
+			Ok(ClockKind::Other)
+		}
+	}
+}
+
+/// Returns the clock providers of [fdt] in tree order.
+pub fn providers<'buf>(fdt: &::FDT<'buf>) -> Providers<'buf> {
+	Providers { nodes: fdt.nodes() }
+}
+
+/// An iterator over the clock providers of a tree, see [providers()].
+#[derive(Clone, Debug)]
+pub struct Providers<'buf> {
+	nodes: Subnodes<'buf>,
+}
+
+impl<'buf> Iterator for Providers<'buf> {
+	type Item = ClockProvider<'buf>;
+
+	fn next(&mut self) -> Option<Self::Item> {
+		self.nodes.by_ref().find_map(ClockProvider::new)
+	}
+}
+
+/// The configuration of one clock of `assigned-clocks`.
+///
+/// [parent] and [rate] are None if they are to be left as they are.
+#[derive(Clone, Debug)]
+pub struct AssignedClock<'buf> {
+	pub clock: Clock<'buf>,
+	pub parent: Option<Clock<'buf>>,
+	pub rate: Option<u64>,
+}
+
+/// Returns the clock configuration of [node] from `assigned-clocks`,
+/// `assigned-clock-parents` and `assigned-clock-rates` or
+/// `assigned-clock-rates-u64`.
+///
+/// # Errors
+///
+/// Returns [Error::NotFound] if [node] doesn't have `assigned-clocks` and
+/// [Error::BadLength] if any of the properties isn't a list of cells.
+///
+/// # Examples
+///
+/// ```
+/// use fdt::{FDT, NodeIterator};
+/// use fdt::clock;
+/// let dtb = include_bytes!("../tests/dt.dtb");
+///
+/// let fdt = FDT::from_bytes(dtb).unwrap();
+/// let smi = fdt.nodes().with_name("smi@7e600000").next().unwrap();
+/// let assigned = clock::assigned_clocks(&smi).unwrap().next().unwrap().unwrap();
+/// assert_eq!(assigned.clock.index(), Some(42));
+/// assert!(assigned.parent.is_none());
+/// assert_eq!(assigned.rate, Some(125_000_000));
+/// ```
+pub fn assigned_clocks<'buf>(node: &Node<'buf>) -> Result<AssignedClocks<'buf>, Error> {
+	let clocks = phandle_list(node, "assigned-clocks")?.ok_or(Error::NotFound)?;
+	let parents = phandle_list(node, "assigned-clock-parents")?;
+	let (rates, rate_len) = match node.property("assigned-clock-rates-u64") {
+		Some(prop) if !prop.raw().len().is_multiple_of(8) => return Err(Error::BadLength(prop.raw().len())),
+		Some(prop) => (prop.raw(), 8),
+		None => match node.property("assigned-clock-rates") {
+			Some(prop) => (Cells::new(prop.raw())?.raw(), 4),
+			None => (&[][..], 4),
+		},
+	};
+	Ok(AssignedClocks { clocks, parents, rates, rate_len })
+}
+
+/// An iterator over the clock configuration of a node, see
+/// [assigned_clocks()].
+///
+/// Holes in `assigned-clocks` are skipped. Iteration stops after the first
+/// error.
+#[derive(Clone, Debug)]
+pub struct AssignedClocks<'buf> {
+	clocks: PhandleArgs<'buf>,
+	parents: Option<PhandleArgs<'buf>>,
+	rates: &'buf [u8],
+	rate_len: usize,
+}
+
+impl<'buf> AssignedClocks<'buf> {
+	fn fail(&mut self, err: Error) -> Option<Result<AssignedClock<'buf>, Error>> {
+		self.clocks.stop();
+		Some(Err(err))
+	}
+}
+
+impl<'buf> Iterator for AssignedClocks<'buf> {
+	type Item = Result<AssignedClock<'buf>, Error>;
+
+	fn next(&mut self) -> Option<Self::Item> {
+		loop {
+			let clock = self.clocks.next()?;
+			let parent = match self.parents.as_mut().and_then(Iterator::next) {
+				Some(Err(err)) => return self.fail(err),
+				Some(Ok(parent)) => parent.map(clock_of),
+				None => None,
+			};
+			let rate = match self.rates.len() >= self.rate_len {
+				true => {
+					let (rate, rest) = self.rates.split_at(self.rate_len);
+					self.rates = rest;
+					Some(BE::read_uint(rate, self.rate_len)).filter(|&rate| rate != 0)
+				},
+				false => None,
+			};
+			match clock {
+				Ok(Some(entry)) => return Some(Ok(AssignedClock { clock: clock_of(entry), parent, rate })),
+				Ok(None) => (),
+				Err(err) => return self.fail(err),
+			}
+		}
+	}
+}
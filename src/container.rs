@@ -0,0 +1,239 @@
+//! Containers holding several device tree blobs.
+//!
+//! Two layouts are supported:
+//!
+//! - Blobs concatenated back to back, as appended to kernel images. Each
+//!   blob may be followed by zero padding up to an 8 byte boundary.
+//! - Android DTB/DTBO images, a `dt_table_header` (magic 0xd7b7ab1e)
+//!   followed by entries carrying an `id`, a `rev` and four custom words
+//!   for each blob.
+//!
+//! [entries()] detects the layout and iterates the blobs, [Entries::select]
+//! picks the blob best matching the running [Board].
+//!
+//! # Examples
+//!
+//! ```
+//! use fdt::container::{self, Board};
+//! let dtb = include_bytes!("../tests/dt.dtb");
+//!
+//! let board = Board { compatible: &["raspberrypi,2-model-b", "brcm,bcm2836"], ..Board::default() };
+//! let entry = container::entries(dtb).unwrap().select(&board).unwrap();
+//! assert_eq!(entry.index, 0);
+//! ```
+
+use byteorder::{ByteOrder, BE};
+
+use error::Error;
+use header::MAGIC;
+use FDT;
+
+/// The magic number of Android DTB/DTBO images.
+pub const DT_TABLE_MAGIC: u32 = 0xd7b7ab1e;
+
+const DT_TABLE_HEADER_SIZE: usize = 32;
+const DT_TABLE_ENTRY_SIZE: usize = 32;
+
+/// A blob of a container.
+pub struct Entry<'buf> {
+	/// The position of the blob in the container.
+	pub index: usize,
+	pub fdt: FDT<'buf>,
+	/// The table entry of the blob, for Android images.
+	pub table: Option<TableEntry>,
+}
+
+/// The fields of an Android `dt_table_entry` describing a blob.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TableEntry {
+	pub id: u32,
+	pub rev: u32,
+	/// Vendor specific words. Version 1 images keep the compression flags
+	/// of the entry in the low bits of the first word, see [compression()].
+	///
+	/// [compression()]: TableEntry::compression
+	pub custom: [u32; 4],
+	version: u32,
+}
+
+impl TableEntry {
+	/// Returns the compression type of the blob, 0 for uncompressed blobs.
+	pub fn compression(&self) -> u32 {
+		if self.version >= 1 { self.custom[0] & 0xf } else { 0 }
+	}
+}
+
+/// What is known about the running board, used to [select](Entries::select)
+/// a blob.
+///
+/// Criteria left at their defaults are ignored.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Board<'a> {
+	/// The compatible strings of the board, most specific first.
+	pub compatible: &'a [&'a str],
+	/// The exact 'model' of the root node.
+	pub model: Option<&'a str>,
+	/// The table id of the blob, only blobs of Android images have ids.
+	pub id: Option<u32>,
+	/// The table revision of the blob.
+	pub rev: Option<u32>,
+}
+
+/// Returns an iterator over the blobs of [buf], which is either an Android
+/// DTB/DTBO image or blobs concatenated back to back.
+///
+/// # Errors
+///
+/// Returns [Error::BadMagic] if [buf] starts with neither layout and
+/// [Error::Truncated] if an image header doesn't fit in [buf].
+pub fn entries(buf: &[u8]) -> Result<Entries<'_>, Error> {
+	if buf.len() < 4 {
+		return Err(Error::Truncated);
+	}
+	match BE::read_u32(buf) {
+		MAGIC => Ok(Entries { buf, layout: Layout::Concatenated { offset: 0 }, index: 0 }),
+		DT_TABLE_MAGIC => {
+			let header = buf.get(..DT_TABLE_HEADER_SIZE).ok_or(Error::Truncated)?;
+			let field = |i: usize| BE::read_u32(&header[i * 4..]) as usize;
+			let (total_size, entry_size, count, offset) = (field(1), field(3), field(4), field(5));
+			if total_size > buf.len() || entry_size < DT_TABLE_ENTRY_SIZE {
+				return Err(Error::Truncated);
+			}
+			let table = Table { entry_size, count, offset, version: field(7) as u32 };
+			Ok(Entries { buf: &buf[..total_size], layout: Layout::Table(table), index: 0 })
+		},
+		other => Err(Error::BadMagic(other)),
+	}
+}
+
+#[derive(Clone, Copy)]
+struct Table {
+	entry_size: usize,
+	count: usize,
+	offset: usize,
+	version: u32,
+}
+
+#[derive(Clone, Copy)]
+enum Layout {
+	Concatenated { offset: usize },
+	Table(Table),
+	Done,
+}
+
+/// An iterator over the blobs of a container, see [entries()].
+///
+/// Blobs which fail to parse are returned as errors. Concatenated blobs end
+/// at the first error, since the position of the next blob is unknown.
+pub struct Entries<'buf> {
+	buf: &'buf [u8],
+	layout: Layout,
+	index: usize,
+}
+
+impl<'buf> Entries<'buf> {
+	/// Returns the blob best matching [board], skipping blobs which fail to
+	/// parse.
+	///
+	/// Blobs must match every id, rev and model criterion of [board]. Among
+	/// those, the blob whose root node is compatible with the earliest, most
+	/// specific, of the board's compatible strings wins; ties go to the blob
+	/// listing that string earliest and then to the first blob. If [board]
+	/// lists compatible strings, blobs compatible with none of them don't
+	/// match.
+	pub fn select(self, board: &Board) -> Option<Entry<'buf>> {
+		self.filter_map(Result::ok)
+			.filter_map(|entry| rank(&entry, board).map(|rank| (rank, entry)))
+			.fold(None, |best: Option<((usize, usize), Entry<'buf>)>, (rank, entry)| match best {
+				Some((best_rank, _)) if best_rank <= rank => best,
+				_ => Some((rank, entry)),
+			})
+			.map(|(_, entry)| entry)
+	}
+
+	fn table_entry(&self, table: Table) -> Result<(FDT<'buf>, TableEntry), Error> {
+		let start = self.index.checked_mul(table.entry_size)
+			.and_then(|start| start.checked_add(table.offset))
+			.ok_or(Error::Truncated)?;
+		let end = start.checked_add(DT_TABLE_ENTRY_SIZE).ok_or(Error::Truncated)?;
+		let raw = self.buf.get(start..end).ok_or(Error::Truncated)?;
+		let field = |i: usize| BE::read_u32(&raw[i * 4..]);
+		let (size, offset) = (field(0) as usize, field(1) as usize);
+		let entry = TableEntry {
+			id: field(2),
+			rev: field(3),
+			custom: [field(4), field(5), field(6), field(7)],
+			version: table.version,
+		};
+		if entry.compression() != 0 {
+			return Err(Error::BadValue);
+		}
+		let end = offset.checked_add(size).ok_or(Error::Truncated)?;
+		let blob = self.buf.get(offset..end).ok_or(Error::Truncated)?;
+		Ok((FDT::from_bytes(blob)?, entry))
+	}
+}
+
+impl<'buf> Iterator for Entries<'buf> {
+	type Item = Result<Entry<'buf>, Error>;
+
+	fn next(&mut self) -> Option<Self::Item> {
+		let index = self.index;
+		let item = match self.layout {
+			Layout::Concatenated { offset } => {
+				let rest = &self.buf[offset..];
+				if rest.is_empty() {
+					self.layout = Layout::Done;
+					return None;
+				}
+				match FDT::from_bytes(rest) {
+					Ok(fdt) => {
+						let end = offset + fdt.total_size() as usize;
+						let padded = (end + 7) & !7;
+						let next = match self.buf.get(end..padded.min(self.buf.len())) {
+							Some(padding) if padding.iter().all(|&b| b == 0) => padded.min(self.buf.len()),
+							_ => end,
+						};
+						self.layout = Layout::Concatenated { offset: next };
+						Ok(Entry { index, fdt, table: None })
+					},
+					Err(err) => {
+						self.layout = Layout::Done;
+						Err(err)
+					},
+				}
+			},
+			Layout::Table(table) if index < table.count => {
+				self.table_entry(table).map(|(fdt, entry)| Entry { index, fdt, table: Some(entry) })
+			},
+			Layout::Table(_) | Layout::Done => return None,
+		};
+		self.index += 1;
+		Some(item)
+	}
+}
+
+/// Ranks [entry] for [board], lower is better, or returns None if it
+/// doesn't match.
+fn rank(entry: &Entry, board: &Board) -> Option<(usize, usize)> {
+	let table = entry.table;
+	if board.id.is_some_and(|id| table.is_none_or(|table| table.id != id))
+			|| board.rev.is_some_and(|rev| table.is_none_or(|table| table.rev != rev)) {
+		return None;
+	}
+	let root = entry.fdt.nodes().next()?;
+	if let Some(model) = board.model {
+		if !root.property("model").is_some_and(|prop| prop.is_equal(model)) {
+			return None;
+		}
+	}
+	if board.compatible.is_empty() {
+		return Some((0, 0));
+	}
+	let compatible = root.property("compatible")?;
+	board.compatible.iter().enumerate()
+		.filter_map(|(i, board)| {
+			compatible.as_stringlist().strings().position(|comp| comp == *board).map(|j| (i, j))
+		})
+		.next()
+}
@@ -0,0 +1,563 @@
+//! Deserialization of device tree nodes using serde.
+//!
+//! Nodes deserialize as maps where properties and child nodes are keyed by
+//! their names, child nodes being nested maps. Property values adapt to the
+//! type they are deserialized into: empty properties are `true` booleans,
+//! strings have their NUL terminator stripped, integers are read from big
+//! endian cells and sequences consume the value element by element, so a
+//! `reg` property can be read as a `Vec<(u32, u32)>` or a `compatible`
+//! property as a `Vec<&str>`.
+//!
+//! When the target type doesn't decide the format, such as for
+//! `serde_json::Value`, the value is guessed: empty values are booleans,
+//! NUL-terminated printable values are strings (or sequences of strings),
+//! values with a length which is a multiple of the cell width are numbers
+//! (or sequences of numbers) and anything else is a sequence of bytes.
+
+use alloc::string::{String, ToString};
+
+use core::convert::TryFrom;
+use core::fmt;
+use core::str;
+
+use byteorder::{ByteOrder, BE};
+use serde::de::{self, Deserialize, DeserializeSeed, MapAccess, SeqAccess, Visitor};
+use serde::forward_to_deserialize_any;
+
+use error::Error as FdtError;
+use node::{Children, Node};
+use property::{Properties, Property};
+use stringlist::is_printable;
+
+/// Errors raised while deserializing a node.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Error {
+	/// A property value couldn't be parsed as the requested type
+	Value(FdtError),
+	/// A property value had bytes left after being deserialized
+	TrailingBytes(usize),
+	/// An error raised by the type being deserialized
+	Message(String),
+}
+
+impl fmt::Display for Error {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match *self {
+			Error::Value(ref err) => write!(f, "{}", err),
+			Error::TrailingBytes(len) => write!(f, "{} trailing bytes in property value", len),
+			Error::Message(ref msg) => write!(f, "{}", msg),
+		}
+	}
+}
+
+impl de::StdError for Error {}
+
+impl de::Error for Error {
+	fn custom<T: fmt::Display>(msg: T) -> Self {
+		Error::Message(msg.to_string())
+	}
+}
+
+impl From<FdtError> for Error {
+	fn from(err: FdtError) -> Self {
+		Error::Value(err)
+	}
+}
+
+/// The width of the elements of numeric property values whose type isn't
+/// decided by the type being deserialized.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CellWidth {
+	U8,
+	U16,
+	U32,
+	U64,
+}
+
+impl CellWidth {
+	fn bytes(self) -> usize {
+		match self {
+			CellWidth::U8 => 1,
+			CellWidth::U16 => 2,
+			CellWidth::U32 => 4,
+			CellWidth::U64 => 8,
+		}
+	}
+}
+
+/// Configuration of how property values are interpreted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Config {
+	/// The width of numbers in values not described by the target type,
+	/// defaults to 32 bit cells.
+	pub cell_width: CellWidth,
+}
+
+impl Default for Config {
+	fn default() -> Self {
+		Config { cell_width: CellWidth::U32 }
+	}
+}
+
+/// Deserializes [T] from [node] and its descendants.
+///
+/// # Examples
+///
+/// ```
+/// extern crate fdt;
+/// #[macro_use]
+/// extern crate serde_derive;
+///
+/// use fdt::{FDT, NodeIterator};
+///
+/// #[derive(Deserialize)]
+/// struct Cpu<'a> {
+///     compatible: &'a str,
+///     reg: u32,
+///     #[serde(rename = "clock-frequency")]
+///     clock_frequency: u32,
+/// }
+///
+/// # fn main() {
+/// let dtb = include_bytes!("../tests/dt.dtb").as_ptr();
+///
+/// let fdt;
+/// unsafe { fdt = FDT::from_raw(dtb).unwrap(); }
+///
+/// let node = fdt.nodes().with_name("cpu@1").next().unwrap();
+/// let cpu: Cpu = fdt::de::from_node(node).unwrap();
+/// assert_eq!(cpu.compatible, "arm,cortex-a7");
+/// assert_eq!(cpu.reg, 0xf01);
+/// # }
+/// ```
+pub fn from_node<'de, T: Deserialize<'de>>(node: Node<'de>) -> Result<T, Error> {
+	from_node_with_config(node, Config::default())
+}
+
+/// Deserializes [T] from [node] and its descendants using [config].
+pub fn from_node_with_config<'de, T: Deserialize<'de>>(node: Node<'de>, config: Config)
+		-> Result<T, Error> {
+	T::deserialize(NodeDeserializer::new(node, config))
+}
+
+/// Deserializes [T] from a single property value using [config].
+pub fn from_property<'de, T: Deserialize<'de>>(prop: &Property<'de>, config: Config)
+		-> Result<T, Error> {
+	T::deserialize(ValueDeserializer { raw: prop.raw(), config })
+}
+
+/// A serde Deserializer over a node and its descendants.
+pub struct NodeDeserializer<'de> {
+	node: Node<'de>,
+	config: Config,
+}
+
+impl<'de> NodeDeserializer<'de> {
+	pub fn new(node: Node<'de>, config: Config) -> Self {
+		NodeDeserializer { node, config }
+	}
+}
+
+impl<'de> de::Deserializer<'de> for NodeDeserializer<'de> {
+	type Error = Error;
+
+	fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+		visitor.visit_map(NodeMap {
+			properties: self.node.properties(),
+			children: self.node.children(),
+			value: None,
+			config: self.config,
+		})
+	}
+
+	fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+		visitor.visit_some(self)
+	}
+
+	fn deserialize_newtype_struct<V: Visitor<'de>>(self, _name: &'static str, visitor: V)
+			-> Result<V::Value, Error> {
+		visitor.visit_newtype_struct(self)
+	}
+
+	fn deserialize_ignored_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+		visitor.visit_unit()
+	}
+
+	forward_to_deserialize_any! {
+		bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+		bytes byte_buf unit unit_struct seq tuple tuple_struct map struct enum
+		identifier
+	}
+}
+
+enum Entry<'de> {
+	Property(&'de [u8]),
+	Node(Node<'de>),
+}
+
+struct NodeMap<'de> {
+	properties: Properties<'de>,
+	children: Children<'de>,
+	value: Option<Entry<'de>>,
+	config: Config,
+}
+
+impl<'de> MapAccess<'de> for NodeMap<'de> {
+	type Error = Error;
+
+	fn next_key_seed<K: DeserializeSeed<'de>>(&mut self, seed: K) -> Result<Option<K::Value>, Error> {
+		let key = if let Some(prop) = self.properties.next() {
+			self.value = Some(Entry::Property(prop.raw()));
+			prop.name()
+		} else if let Some(child) = self.children.next() {
+			let name = child.name();
+			self.value = Some(Entry::Node(child));
+			name
+		} else {
+			return Ok(None);
+		};
+		seed.deserialize(de::value::BorrowedStrDeserializer::new(key)).map(Some)
+	}
+
+	fn next_value_seed<V: DeserializeSeed<'de>>(&mut self, seed: V) -> Result<V::Value, Error> {
+		match self.value.take() {
+			Some(Entry::Property(raw)) => seed.deserialize(ValueDeserializer { raw, config: self.config }),
+			Some(Entry::Node(node)) => seed.deserialize(NodeDeserializer::new(node, self.config)),
+			None => Err(de::Error::custom("value requested before key")),
+		}
+	}
+}
+
+/// Deserializes a whole property value.
+struct ValueDeserializer<'de> {
+	raw: &'de [u8],
+	config: Config,
+}
+
+impl<'de> ValueDeserializer<'de> {
+	fn cursor<F, T>(self, f: F) -> Result<T, Error>
+			where F: FnOnce(Cursor<'_, 'de>) -> Result<T, Error> {
+		let any = Any::Number(self.config.cell_width);
+		self.cursor_with(any, f)
+	}
+
+	fn cursor_with<F, T>(self, any: Any, f: F) -> Result<T, Error>
+			where F: FnOnce(Cursor<'_, 'de>) -> Result<T, Error> {
+		let mut raw = self.raw;
+		let val = f(Cursor { raw: &mut raw, any })?;
+		match raw.len() {
+			0 => Ok(val),
+			len => Err(Error::TrailingBytes(len)),
+		}
+	}
+}
+
+macro_rules! forward_to_cursor {
+	($($method:ident)*) => {
+		$(
+			fn $method<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+				self.cursor(|cursor| de::Deserializer::$method(cursor, visitor))
+			}
+		)*
+	}
+}
+
+impl<'de> de::Deserializer<'de> for ValueDeserializer<'de> {
+	type Error = Error;
+
+	fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+		let width = self.config.cell_width;
+		let any = if is_printable(self.raw) {
+			Any::String
+		} else if self.raw.len().is_multiple_of(width.bytes()) {
+			Any::Number(width)
+		} else {
+			Any::Number(CellWidth::U8)
+		};
+		let single = match any {
+			Any::String => self.raw.iter().filter(|&&b| b == 0).count() == 1,
+			Any::Number(width) => self.raw.len() == width.bytes(),
+		};
+		if self.raw.is_empty() {
+			visitor.visit_bool(true)
+		} else if single {
+			self.cursor_with(any, |cursor| cursor.deserialize_any(visitor))
+		} else {
+			self.cursor_with(any, |cursor| visitor.visit_seq(Elements { cursor, len: None }))
+		}
+	}
+
+	fn deserialize_bool<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+		if self.raw.is_empty() {
+			visitor.visit_bool(true)
+		} else {
+			self.cursor(|cursor| cursor.deserialize_bool(visitor))
+		}
+	}
+
+	fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+		visitor.visit_some(self)
+	}
+
+	fn deserialize_unit<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+		visitor.visit_unit()
+	}
+
+	fn deserialize_unit_struct<V: Visitor<'de>>(self, _name: &'static str, visitor: V)
+			-> Result<V::Value, Error> {
+		visitor.visit_unit()
+	}
+
+	fn deserialize_newtype_struct<V: Visitor<'de>>(self, _name: &'static str, visitor: V)
+			-> Result<V::Value, Error> {
+		visitor.visit_newtype_struct(self)
+	}
+
+	fn deserialize_bytes<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+		visitor.visit_borrowed_bytes(self.raw)
+	}
+
+	fn deserialize_byte_buf<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+		visitor.visit_borrowed_bytes(self.raw)
+	}
+
+	fn deserialize_seq<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+		self.cursor(|cursor| visitor.visit_seq(Elements { cursor, len: None }))
+	}
+
+	fn deserialize_ignored_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+		visitor.visit_unit()
+	}
+
+	fn deserialize_tuple<V: Visitor<'de>>(self, len: usize, visitor: V) -> Result<V::Value, Error> {
+		self.cursor(|cursor| cursor.deserialize_tuple(len, visitor))
+	}
+
+	fn deserialize_tuple_struct<V: Visitor<'de>>(self, name: &'static str, len: usize, visitor: V)
+			-> Result<V::Value, Error> {
+		self.cursor(|cursor| cursor.deserialize_tuple_struct(name, len, visitor))
+	}
+
+	fn deserialize_enum<V: Visitor<'de>>(self, name: &'static str, variants: &'static [&'static str],
+			visitor: V) -> Result<V::Value, Error> {
+		self.cursor(|cursor| cursor.deserialize_enum(name, variants, visitor))
+	}
+
+	forward_to_cursor! {
+		deserialize_i8 deserialize_i16 deserialize_i32 deserialize_i64 deserialize_u8
+		deserialize_u16 deserialize_u32 deserialize_u64 deserialize_f32 deserialize_f64
+		deserialize_char deserialize_str deserialize_string deserialize_identifier
+	}
+
+	forward_to_deserialize_any! {
+		i128 u128 map struct
+	}
+}
+
+/// The format of values which the deserialized type leaves undecided.
+#[derive(Clone, Copy)]
+enum Any {
+	String,
+	Number(CellWidth),
+}
+
+/// Deserializes values from the front of a property value, consuming the
+/// bytes it reads.
+struct Cursor<'a, 'de: 'a> {
+	raw: &'a mut &'de [u8],
+	any: Any,
+}
+
+impl<'a, 'de> Cursor<'a, 'de> {
+	fn take(&mut self, len: usize) -> Result<&'de [u8], Error> {
+		if self.raw.len() < len {
+			return Err(Error::Value(FdtError::Truncated));
+		}
+		let (val, rest) = self.raw.split_at(len);
+		*self.raw = rest;
+		Ok(val)
+	}
+
+	fn cell(&mut self) -> Result<u32, Error> {
+		self.take(4).map(BE::read_u32)
+	}
+
+	/// Reads a cell as a two's complement number.
+	fn signed_cell(&mut self) -> Result<i32, Error> {
+		self.take(4).map(BE::read_i32)
+	}
+
+	fn string(&mut self) -> Result<&'de str, Error> {
+		let len = self.raw.iter().position(|&b| b == 0).ok_or(Error::Value(FdtError::BadValue))?;
+		let string = self.take(len + 1)?;
+		str::from_utf8(&string[..len]).map_err(|e| Error::Value(FdtError::BadUtf8(e)))
+	}
+
+	fn reborrow(&mut self) -> Cursor<'_, 'de> {
+		Cursor { raw: self.raw, any: self.any }
+	}
+}
+
+/// Deserializes a narrower integer from a cell, which must hold a value of
+/// the type, sign extended for signed types.
+macro_rules! deserialize_cell {
+	($($method:ident => $visit:ident: $ty:ty = $read:ident as $unexpected:ident,)*) => {
+		$(
+			fn $method<V: Visitor<'de>>(mut self, visitor: V) -> Result<V::Value, Error> {
+				let cell = self.$read()?;
+				match <$ty>::try_from(cell) {
+					Ok(val) => visitor.$visit(val),
+					Err(_) => Err(de::Error::invalid_value(
+						de::Unexpected::$unexpected(cell.into()), &visitor)),
+				}
+			}
+		)*
+	}
+}
+
+impl<'a, 'de> de::Deserializer<'de> for Cursor<'a, 'de> {
+	type Error = Error;
+
+	fn deserialize_any<V: Visitor<'de>>(mut self, visitor: V) -> Result<V::Value, Error> {
+		match self.any {
+			Any::String => visitor.visit_borrowed_str(self.string()?),
+			Any::Number(CellWidth::U8) => visitor.visit_u8(self.take(1)?[0]),
+			Any::Number(CellWidth::U16) => visitor.visit_u16(BE::read_u16(self.take(2)?)),
+			Any::Number(CellWidth::U32) => visitor.visit_u32(self.cell()?),
+			Any::Number(CellWidth::U64) => visitor.visit_u64(BE::read_u64(self.take(8)?)),
+		}
+	}
+
+	fn deserialize_bool<V: Visitor<'de>>(mut self, visitor: V) -> Result<V::Value, Error> {
+		visitor.visit_bool(self.cell()? != 0)
+	}
+
+	deserialize_cell! {
+		deserialize_i8 => visit_i8: i8 = signed_cell as Signed,
+		deserialize_i16 => visit_i16: i16 = signed_cell as Signed,
+		deserialize_u16 => visit_u16: u16 = cell as Unsigned,
+	}
+
+	fn deserialize_i32<V: Visitor<'de>>(mut self, visitor: V) -> Result<V::Value, Error> {
+		visitor.visit_i32(self.signed_cell()?)
+	}
+
+	fn deserialize_u8<V: Visitor<'de>>(mut self, visitor: V) -> Result<V::Value, Error> {
+		visitor.visit_u8(self.take(1)?[0])
+	}
+
+	fn deserialize_u32<V: Visitor<'de>>(mut self, visitor: V) -> Result<V::Value, Error> {
+		visitor.visit_u32(self.cell()?)
+	}
+
+	fn deserialize_i64<V: Visitor<'de>>(mut self, visitor: V) -> Result<V::Value, Error> {
+		visitor.visit_i64(BE::read_i64(self.take(8)?))
+	}
+
+	fn deserialize_u64<V: Visitor<'de>>(mut self, visitor: V) -> Result<V::Value, Error> {
+		visitor.visit_u64(BE::read_u64(self.take(8)?))
+	}
+
+	fn deserialize_f32<V: Visitor<'de>>(mut self, visitor: V) -> Result<V::Value, Error> {
+		visitor.visit_f32(BE::read_f32(self.take(4)?))
+	}
+
+	fn deserialize_f64<V: Visitor<'de>>(mut self, visitor: V) -> Result<V::Value, Error> {
+		visitor.visit_f64(BE::read_f64(self.take(8)?))
+	}
+
+	fn deserialize_char<V: Visitor<'de>>(mut self, visitor: V) -> Result<V::Value, Error> {
+		visitor.visit_char(char::from(self.take(1)?[0]))
+	}
+
+	fn deserialize_str<V: Visitor<'de>>(mut self, visitor: V) -> Result<V::Value, Error> {
+		visitor.visit_borrowed_str(self.string()?)
+	}
+
+	fn deserialize_string<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+		self.deserialize_str(visitor)
+	}
+
+	fn deserialize_identifier<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+		self.deserialize_str(visitor)
+	}
+
+	fn deserialize_bytes<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+		let raw = core::mem::take(self.raw);
+		visitor.visit_borrowed_bytes(raw)
+	}
+
+	fn deserialize_byte_buf<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+		self.deserialize_bytes(visitor)
+	}
+
+	fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+		visitor.visit_some(self)
+	}
+
+	fn deserialize_unit<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+		visitor.visit_unit()
+	}
+
+	fn deserialize_unit_struct<V: Visitor<'de>>(self, _name: &'static str, visitor: V)
+			-> Result<V::Value, Error> {
+		visitor.visit_unit()
+	}
+
+	fn deserialize_newtype_struct<V: Visitor<'de>>(self, _name: &'static str, visitor: V)
+			-> Result<V::Value, Error> {
+		visitor.visit_newtype_struct(self)
+	}
+
+	fn deserialize_seq<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+		visitor.visit_seq(Elements { cursor: self, len: None })
+	}
+
+	fn deserialize_tuple<V: Visitor<'de>>(self, len: usize, visitor: V) -> Result<V::Value, Error> {
+		visitor.visit_seq(Elements { cursor: self, len: Some(len) })
+	}
+
+	fn deserialize_tuple_struct<V: Visitor<'de>>(self, _name: &'static str, len: usize, visitor: V)
+			-> Result<V::Value, Error> {
+		self.deserialize_tuple(len, visitor)
+	}
+
+	fn deserialize_enum<V: Visitor<'de>>(mut self, _name: &'static str,
+			_variants: &'static [&'static str], visitor: V) -> Result<V::Value, Error> {
+		let variant = de::value::BorrowedStrDeserializer::<Error>::new(self.string()?);
+		visitor.visit_enum(variant)
+	}
+
+	fn deserialize_ignored_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+		*self.raw = &[];
+		visitor.visit_unit()
+	}
+
+	forward_to_deserialize_any! {
+		i128 u128 map struct
+	}
+}
+
+/// The elements of a sequence or tuple within a property value.
+struct Elements<'a, 'de: 'a> {
+	cursor: Cursor<'a, 'de>,
+	len: Option<usize>,
+}
+
+impl<'a, 'de> SeqAccess<'de> for Elements<'a, 'de> {
+	type Error = Error;
+
+	fn next_element_seed<T: DeserializeSeed<'de>>(&mut self, seed: T) -> Result<Option<T::Value>, Error> {
+		match self.len {
+			Some(0) => return Ok(None),
+			Some(ref mut len) => *len -= 1,
+			None if self.cursor.raw.is_empty() => return Ok(None),
+			None => {},
+		}
+		seed.deserialize(self.cursor.reborrow()).map(Some)
+	}
+
+	fn size_hint(&self) -> Option<usize> {
+		self.len
+	}
+}
@@ -0,0 +1,207 @@
+//! Structural comparison of two device trees.
+//!
+//! Nodes are matched by name under matching parents, so the order of nodes
+//! and properties doesn't matter. Properties referring to other nodes by
+//! phandle compare equal if the referenced nodes have the same path in both
+//! trees, so renumbered phandles aren't reported as changes.
+//!
+//! # Examples
+//!
+//! ```
+//! use fdt::FDT;
+//! let dtb = include_bytes!("../tests/dt.dtb").as_ptr();
+//!
+//! let a;
+//! let b;
+//! unsafe {
+//!     a = FDT::from_raw(dtb).unwrap();
+//!     b = FDT::from_raw(dtb).unwrap();
+//! }
+//!
+//! let changes = fdt::diff(&a, &b, |change| println!("{}", change));
+//! assert_eq!(changes, 0);
+//! ```
+
+use core::fmt;
+
+use byteorder::{ByteOrder, BE};
+
+use entries::{self, Context, Entries};
+use memory_reserve_map::MemoryReserveMapEntry;
+use node::Node;
+use property::Property;
+use FDT;
+
+/// A difference between tree [a] and tree [b] of [diff()].
+pub enum Change<'r, 'a: 'r, 'b: 'r> {
+	/// A header field differs.
+	Header { field: &'static str, old: u32, new: u32 },
+	/// A memory reserve map entry only exists in tree b.
+	ReserveAdded(MemoryReserveMapEntry),
+	/// A memory reserve map entry only exists in tree a.
+	ReserveRemoved(MemoryReserveMapEntry),
+	/// A node, and all of its descendants, only exists in tree b.
+	NodeAdded(&'r Node<'b>),
+	/// A node, and all of its descendants, only exists in tree a.
+	NodeRemoved(&'r Node<'a>),
+	/// A property of [node] only exists in tree b.
+	PropertyAdded { node: &'r Node<'b>, property: &'r Property<'b> },
+	/// A property of [node] only exists in tree a.
+	PropertyRemoved { node: &'r Node<'a>, property: &'r Property<'a> },
+	/// A property of [node] has different values in the trees.
+	PropertyChanged { node: &'r Node<'b>, old: &'r Property<'a>, new: &'r Property<'b> },
+}
+
+/// Compares tree [a] with tree [b], calling [report] for every change
+/// needed to turn [a] into [b].
+///
+/// Returns the number of changes.
+pub fn diff<'a, 'b, F>(a: &FDT<'a>, b: &FDT<'b>, mut report: F) -> usize
+		where F: FnMut(Change<'_, 'a, 'b>) {
+	let mut count = 0;
+	let mut emit = |change: Change<'_, 'a, 'b>| {
+		count += 1;
+		report(change);
+	};
+
+	let (ha, hb) = (a.blob.header(), b.blob.header());
+	let fields = [
+		("version", ha.version().number(), hb.version().number()),
+		("last_comp_version", ha.last_comp_version(), hb.last_comp_version()),
+		("boot_cpuid_phys", a.boot_cpuid_phys().unwrap_or(0), b.boot_cpuid_phys().unwrap_or(0)),
+	];
+	for &(field, old, new) in &fields {
+		if old != new {
+			emit(Change::Header { field, old, new });
+		}
+	}
+
+	for entry in a.memory_reserve_map() {
+		if !b.memory_reserve_map().any(|other| other == entry) {
+			emit(Change::ReserveRemoved(entry));
+		}
+	}
+	for entry in b.memory_reserve_map() {
+		if !a.memory_reserve_map().any(|other| other == entry) {
+			emit(Change::ReserveAdded(entry));
+		}
+	}
+
+	if let (Some(ra), Some(rb)) = (a.nodes().next(), b.nodes().next()) {
+		diff_nodes(&ra, &rb, Context::root(), Context::root(), &mut emit);
+	}
+	count
+}
+
+fn diff_nodes<'a, 'b>(a: &Node<'a>, b: &Node<'b>, ctx_a: Context, ctx_b: Context,
+		emit: &mut dyn FnMut(Change<'_, 'a, 'b>)) {
+	for old in a.properties() {
+		match b.properties().find(|new| new.name() == old.name()) {
+			None => emit(Change::PropertyRemoved { node: a, property: &old }),
+			Some(new) => if !same_value(&old, a, ctx_a, &new, b, ctx_b) {
+				emit(Change::PropertyChanged { node: b, old: &old, new: &new });
+			},
+		}
+	}
+	for new in b.properties() {
+		if a.property(new.name()).is_none() {
+			emit(Change::PropertyAdded { node: b, property: &new });
+		}
+	}
+
+	let (child_a, child_b) = (ctx_a.child(a), ctx_b.child(b));
+	for (i, old) in a.children().enumerate() {
+		match counterpart(b, old.name(), same_name_before(a, i, old.name())) {
+			Some(new) => diff_nodes(&old, &new, child_a, child_b, emit),
+			None => emit(Change::NodeRemoved(&old)),
+		}
+	}
+	for (i, new) in b.children().enumerate() {
+		if counterpart(a, new.name(), same_name_before(b, i, new.name())).is_none() {
+			emit(Change::NodeAdded(&new));
+		}
+	}
+}
+
+/// Returns the number of children of [parent] before child [index] named
+/// [name].
+fn same_name_before(parent: &Node, index: usize, name: &str) -> usize {
+	parent.children().take(index).filter(|child| child.name() == name).count()
+}
+
+/// Returns the [nth] child of [parent] named [name].
+fn counterpart<'x>(parent: &Node<'x>, name: &str, nth: usize) -> Option<Node<'x>> {
+	parent.children().filter(|child| child.name() == name).nth(nth)
+}
+
+/// Compares the values of two properties, comparing phandles by the paths
+/// of the nodes they refer to.
+///
+/// Entries which can't be split, such as those referring to a missing node,
+/// are compared as a whole.
+fn same_value(a: &Property, node_a: &Node, ctx_a: Context,
+		b: &Property, node_b: &Node, ctx_b: Context) -> bool {
+	let name = a.name();
+	if name == "phandle" || name == "linux,phandle" {
+		// the nodes are matched by path already
+		return a.raw().len() == 4 && b.raw().len() == 4;
+	}
+	let is_reference = name == "interrupt-parent"
+		|| (name.starts_with("pinctrl-") && name != "pinctrl-names")
+		|| entries::phandle_array_cells(name).is_some();
+	if !is_reference || a.raw().len() != b.raw().len() {
+		return a.raw() == b.raw();
+	}
+	// equal values may still refer to different nodes
+	let mut entries_a = Entries::new(a, node_a, ctx_a);
+	let mut entries_b = Entries::new(b, node_b, ctx_b);
+	loop {
+		match (entries_a.next(), entries_b.next()) {
+			(None, None) => return true,
+			(Some(ea), Some(eb)) if ea.len() == eb.len() => {
+				let (pa, pb) = (BE::read_u32(ea), BE::read_u32(eb));
+				let same_target = match (node_a.lookup_phandle(pa), node_b.lookup_phandle(pb)) {
+					(Some(ta), Some(tb)) => same_path(&ta, &tb),
+					(None, None) => pa == pb,
+					_ => false,
+				};
+				if !same_target || ea[4..] != eb[4..] {
+					return false;
+				}
+			},
+			_ => return false,
+		}
+	}
+}
+
+fn same_path(a: &Node, b: &Node) -> bool {
+	a.depth() == b.depth() && a.name() == b.name() && (1..a.depth()).all(|depth| {
+		match (a.supernode_at_depth(depth), b.supernode_at_depth(depth)) {
+			(Some(sa), Some(sb)) => sa.name() == sb.name(),
+			_ => false,
+		}
+	})
+}
+
+impl<'r, 'a, 'b> fmt::Display for Change<'r, 'a, 'b> {
+	/// Formats the change as a line starting with '+' for additions, '-' for
+	/// removals and '~' for changes.
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match *self {
+			Change::Header { field, old, new } => write!(f, "~ header {}: {} -> {}", field, old, new),
+			Change::ReserveAdded(entry) =>
+				write!(f, "+ /memreserve/ {:#x} {:#x}", entry.address, entry.size),
+			Change::ReserveRemoved(entry) =>
+				write!(f, "- /memreserve/ {:#x} {:#x}", entry.address, entry.size),
+			Change::NodeAdded(node) => write!(f, "+ {}", node.path()),
+			Change::NodeRemoved(node) => write!(f, "- {}", node.path()),
+			Change::PropertyAdded { node, property } =>
+				write!(f, "+ {}:{} = {}", node.path(), property.name(), property.display_value()),
+			Change::PropertyRemoved { node, property } =>
+				write!(f, "- {}:{} = {}", node.path(), property.name(), property.display_value()),
+			Change::PropertyChanged { node, old, new } =>
+				write!(f, "~ {}:{} = {} -> {}", node.path(), new.name(),
+					old.display_value(), new.display_value()),
+		}
+	}
+}
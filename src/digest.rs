@@ -0,0 +1,380 @@
+//! Message digests used to verify images, with software implementations.
+//!
+//! Digests are computed through a [Backend] so that hardware accelerators
+//! can stand in for the [Software] implementations. A digest covers a list
+//! of regions, as signed FIT configurations hash several parts of the blob.
+//!
+//! # Examples
+//!
+//! ```
+//! use fdt::digest::{Algorithm, Backend, Software};
+//!
+//! let digest = Software.digest(Algorithm::Crc32, &mut [&b"1234"[..], b"56789"].iter().copied()).unwrap();
+//! assert_eq!(digest.as_ref(), 0xcbf43926u32.to_be_bytes());
+//! ```
+
+use core::fmt;
+
+use byteorder::{ByteOrder, BE};
+
+/// A digest algorithm, named as in FIT 'algo' properties.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Algorithm {
+	/// The CRC-32 of zlib, stored big endian.
+	Crc32,
+	Sha1,
+	Sha256,
+	Sha384,
+	Sha512,
+}
+
+impl Algorithm {
+	/// Returns the algorithm named [name], such as "sha256".
+	pub fn from_name(name: &str) -> Option<Self> {
+		match name {
+			"crc32" => Some(Algorithm::Crc32),
+			"sha1" => Some(Algorithm::Sha1),
+			"sha256" => Some(Algorithm::Sha256),
+			"sha384" => Some(Algorithm::Sha384),
+			"sha512" => Some(Algorithm::Sha512),
+			_ => None,
+		}
+	}
+
+	pub fn name(&self) -> &'static str {
+		match self {
+			Algorithm::Crc32 => "crc32",
+			Algorithm::Sha1 => "sha1",
+			Algorithm::Sha256 => "sha256",
+			Algorithm::Sha384 => "sha384",
+			Algorithm::Sha512 => "sha512",
+		}
+	}
+
+	/// Returns the length in bytes of the digests of the algorithm.
+	pub fn size(&self) -> usize {
+		match self {
+			Algorithm::Crc32 => 4,
+			Algorithm::Sha1 => 20,
+			Algorithm::Sha256 => 32,
+			Algorithm::Sha384 => 48,
+			Algorithm::Sha512 => 64,
+		}
+	}
+}
+
+impl fmt::Display for Algorithm {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		f.write_str(self.name())
+	}
+}
+
+/// A computed digest of up to 64 bytes.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct Digest {
+	bytes: [u8; 64],
+	len: usize,
+}
+
+impl Digest {
+	/// Returns a digest holding [bytes].
+	///
+	/// # Panics
+	///
+	/// Panics if [bytes] is longer than 64 bytes.
+	pub fn new(bytes: &[u8]) -> Self {
+		let mut digest = Digest { bytes: [0; 64], len: bytes.len() };
+		digest.bytes[..bytes.len()].copy_from_slice(bytes);
+		digest
+	}
+}
+
+impl AsRef<[u8]> for Digest {
+	fn as_ref(&self) -> &[u8] {
+		&self.bytes[..self.len]
+	}
+}
+
+impl fmt::Debug for Digest {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		self.as_ref().iter().try_for_each(|byte| write!(f, "{:02x}", byte))
+	}
+}
+
+/// Computes digests, implemented by [Software] and by users for hardware
+/// accelerators.
+pub trait Backend {
+	/// Returns the [algorithm] digest of the concatenation of [regions], or
+	/// None if the backend doesn't support [algorithm].
+	fn digest(&mut self, algorithm: Algorithm, regions: &mut dyn Iterator<Item=&[u8]>) -> Option<Digest>;
+}
+
+/// The software implementations of every [Algorithm].
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Software;
+
+impl Backend for Software {
+	fn digest(&mut self, algorithm: Algorithm, regions: &mut dyn Iterator<Item=&[u8]>) -> Option<Digest> {
+		Some(match algorithm {
+			Algorithm::Crc32 => {
+				let crc = regions.fold(!0, crc32_update);
+				Digest::new(&(!crc).to_be_bytes())
+			},
+			Algorithm::Sha1 => hash::<Sha1>(regions),
+			Algorithm::Sha256 => hash::<Sha256>(regions),
+			Algorithm::Sha384 => hash::<Sha384>(regions),
+			Algorithm::Sha512 => hash::<Sha512>(regions),
+		})
+	}
+}
+
+const CRC32_TABLE: [u32; 256] = {
+	let mut table = [0; 256];
+	let mut i = 0;
+	while i < 256 {
+		let mut crc = i as u32;
+		let mut bit = 0;
+		while bit < 8 {
+			crc = if crc & 1 != 0 { 0xedb88320 ^ (crc >> 1) } else { crc >> 1 };
+			bit += 1;
+		}
+		table[i] = crc;
+		i += 1;
+	}
+	table
+};
+
+fn crc32_update(crc: u32, data: &[u8]) -> u32 {
+	data.iter().fold(crc, |crc, &byte| CRC32_TABLE[((crc ^ byte as u32) & 0xff) as usize] ^ (crc >> 8))
+}
+
+/// The block function and padding of a Merkle–Damgård hash.
+trait Compress: Default {
+	const BLOCK: usize;
+	/// The size of the message length appended by the padding.
+	const LENGTH: usize;
+	const OUTPUT: usize;
+
+	fn compress(&mut self, block: &[u8]);
+	fn output(&self, out: &mut [u8]);
+}
+
+fn hash<H: Compress>(regions: &mut dyn Iterator<Item=&[u8]>) -> Digest {
+	let mut state = H::default();
+	let mut buffer = [0u8; 128];
+	let (mut filled, mut total) = (0, 0u128);
+	for mut data in regions {
+		total += data.len() as u128;
+		if filled > 0 {
+			let take = data.len().min(H::BLOCK - filled);
+			buffer[filled..filled + take].copy_from_slice(&data[..take]);
+			filled += take;
+			data = &data[take..];
+			if filled < H::BLOCK {
+				continue;
+			}
+			state.compress(&buffer[..H::BLOCK]);
+		}
+		let mut blocks = data.chunks_exact(H::BLOCK);
+		blocks.by_ref().for_each(|block| state.compress(block));
+		let rest = blocks.remainder();
+		buffer[..rest.len()].copy_from_slice(rest);
+		filled = rest.len();
+	}
+
+	buffer[filled] = 0x80;
+	buffer[filled + 1..H::BLOCK].fill(0);
+	if filled + 1 > H::BLOCK - H::LENGTH {
+		state.compress(&buffer[..H::BLOCK]);
+		buffer[..H::BLOCK].fill(0);
+	}
+	let bits = (total * 8).to_be_bytes();
+	buffer[H::BLOCK - H::LENGTH..H::BLOCK].copy_from_slice(&bits[16 - H::LENGTH..]);
+	state.compress(&buffer[..H::BLOCK]);
+
+	let mut out = [0; 64];
+	state.output(&mut out);
+	Digest::new(&out[..H::OUTPUT])
+}
+
+struct Sha1([u32; 5]);
+
+impl Default for Sha1 {
+	fn default() -> Self {
+		Sha1([0x67452301, 0xefcdab89, 0x98badcfe, 0x10325476, 0xc3d2e1f0])
+	}
+}
+
+impl Compress for Sha1 {
+	const BLOCK: usize = 64;
+	const LENGTH: usize = 8;
+	const OUTPUT: usize = 20;
+
+	fn compress(&mut self, block: &[u8]) {
+		let mut w = [0u32; 80];
+		BE::read_u32_into(block, &mut w[..16]);
+		for i in 16..80 {
+			w[i] = (w[i - 3] ^ w[i - 8] ^ w[i - 14] ^ w[i - 16]).rotate_left(1);
+		}
+		let [mut a, mut b, mut c, mut d, mut e] = self.0;
+		for (i, &w) in w.iter().enumerate() {
+			let (f, k) = match i {
+				0..=19 => ((b & c) | (!b & d), 0x5a827999),
+				20..=39 => (b ^ c ^ d, 0x6ed9eba1),
+				40..=59 => ((b & c) | (b & d) | (c & d), 0x8f1bbcdc),
+				_ => (b ^ c ^ d, 0xca62c1d6),
+			};
+			let t = a.rotate_left(5).wrapping_add(f).wrapping_add(e).wrapping_add(k).wrapping_add(w);
+			(e, d, c, b, a) = (d, c, b.rotate_left(30), a, t);
+		}
+		for (h, v) in self.0.iter_mut().zip([a, b, c, d, e]) {
+			*h = h.wrapping_add(v);
+		}
+	}
+
+	fn output(&self, out: &mut [u8]) {
+		BE::write_u32_into(&self.0, &mut out[..20]);
+	}
+}
+
+const SHA256_K: [u32; 64] = [
+	0x428a2f98, 0x71374491, 0xb5c0fbcf, 0xe9b5dba5, 0x3956c25b, 0x59f111f1, 0x923f82a4, 0xab1c5ed5,
+	0xd807aa98, 0x12835b01, 0x243185be, 0x550c7dc3, 0x72be5d74, 0x80deb1fe, 0x9bdc06a7, 0xc19bf174,
+	0xe49b69c1, 0xefbe4786, 0x0fc19dc6, 0x240ca1cc, 0x2de92c6f, 0x4a7484aa, 0x5cb0a9dc, 0x76f988da,
+	0x983e5152, 0xa831c66d, 0xb00327c8, 0xbf597fc7, 0xc6e00bf3, 0xd5a79147, 0x06ca6351, 0x14292967,
+	0x27b70a85, 0x2e1b2138, 0x4d2c6dfc, 0x53380d13, 0x650a7354, 0x766a0abb, 0x81c2c92e, 0x92722c85,
+	0xa2bfe8a1, 0xa81a664b, 0xc24b8b70, 0xc76c51a3, 0xd192e819, 0xd6990624, 0xf40e3585, 0x106aa070,
+	0x19a4c116, 0x1e376c08, 0x2748774c, 0x34b0bcb5, 0x391c0cb3, 0x4ed8aa4a, 0x5b9cca4f, 0x682e6ff3,
+	0x748f82ee, 0x78a5636f, 0x84c87814, 0x8cc70208, 0x90befffa, 0xa4506ceb, 0xbef9a3f7, 0xc67178f2,
+];
+
+struct Sha256([u32; 8]);
+
+impl Default for Sha256 {
+	fn default() -> Self {
+		Sha256([0x6a09e667, 0xbb67ae85, 0x3c6ef372, 0xa54ff53a, 0x510e527f, 0x9b05688c, 0x1f83d9ab, 0x5be0cd19])
+	}
+}
+
+impl Compress for Sha256 {
+	const BLOCK: usize = 64;
+	const LENGTH: usize = 8;
+	const OUTPUT: usize = 32;
+
+	fn compress(&mut self, block: &[u8]) {
+		let mut w = [0u32; 64];
+		BE::read_u32_into(block, &mut w[..16]);
+		for i in 16..64 {
+			let s0 = w[i - 15].rotate_right(7) ^ w[i - 15].rotate_right(18) ^ (w[i - 15] >> 3);
+			let s1 = w[i - 2].rotate_right(17) ^ w[i - 2].rotate_right(19) ^ (w[i - 2] >> 10);
+			w[i] = w[i - 16].wrapping_add(s0).wrapping_add(w[i - 7]).wrapping_add(s1);
+		}
+		let [mut a, mut b, mut c, mut d, mut e, mut f, mut g, mut h] = self.0;
+		for (&k, &w) in SHA256_K.iter().zip(&w) {
+			let s1 = e.rotate_right(6) ^ e.rotate_right(11) ^ e.rotate_right(25);
+			let ch = (e & f) ^ (!e & g);
+			let t1 = h.wrapping_add(s1).wrapping_add(ch).wrapping_add(k).wrapping_add(w);
+			let s0 = a.rotate_right(2) ^ a.rotate_right(13) ^ a.rotate_right(22);
+			let maj = (a & b) ^ (a & c) ^ (b & c);
+			let t2 = s0.wrapping_add(maj);
+			(h, g, f, e, d, c, b, a) = (g, f, e, d.wrapping_add(t1), c, b, a, t1.wrapping_add(t2));
+		}
+		for (s, v) in self.0.iter_mut().zip([a, b, c, d, e, f, g, h]) {
+			*s = s.wrapping_add(v);
+		}
+	}
+
+	fn output(&self, out: &mut [u8]) {
+		BE::write_u32_into(&self.0, &mut out[..32]);
+	}
+}
+
+const SHA512_K: [u64; 80] = [
+	0x428a2f98d728ae22, 0x7137449123ef65cd, 0xb5c0fbcfec4d3b2f, 0xe9b5dba58189dbbc, 0x3956c25bf348b538,
+	0x59f111f1b605d019, 0x923f82a4af194f9b, 0xab1c5ed5da6d8118, 0xd807aa98a3030242, 0x12835b0145706fbe,
+	0x243185be4ee4b28c, 0x550c7dc3d5ffb4e2, 0x72be5d74f27b896f, 0x80deb1fe3b1696b1, 0x9bdc06a725c71235,
+	0xc19bf174cf692694, 0xe49b69c19ef14ad2, 0xefbe4786384f25e3, 0x0fc19dc68b8cd5b5, 0x240ca1cc77ac9c65,
+	0x2de92c6f592b0275, 0x4a7484aa6ea6e483, 0x5cb0a9dcbd41fbd4, 0x76f988da831153b5, 0x983e5152ee66dfab,
+	0xa831c66d2db43210, 0xb00327c898fb213f, 0xbf597fc7beef0ee4, 0xc6e00bf33da88fc2, 0xd5a79147930aa725,
+	0x06ca6351e003826f, 0x142929670a0e6e70, 0x27b70a8546d22ffc, 0x2e1b21385c26c926, 0x4d2c6dfc5ac42aed,
+	0x53380d139d95b3df, 0x650a73548baf63de, 0x766a0abb3c77b2a8, 0x81c2c92e47edaee6, 0x92722c851482353b,
+	0xa2bfe8a14cf10364, 0xa81a664bbc423001, 0xc24b8b70d0f89791, 0xc76c51a30654be30, 0xd192e819d6ef5218,
+	0xd69906245565a910, 0xf40e35855771202a, 0x106aa07032bbd1b8, 0x19a4c116b8d2d0c8, 0x1e376c085141ab53,
+	0x2748774cdf8eeb99, 0x34b0bcb5e19b48a8, 0x391c0cb3c5c95a63, 0x4ed8aa4ae3418acb, 0x5b9cca4f7763e373,
+	0x682e6ff3d6b2b8a3, 0x748f82ee5defb2fc, 0x78a5636f43172f60, 0x84c87814a1f0ab72, 0x8cc702081a6439ec,
+	0x90befffa23631e28, 0xa4506cebde82bde9, 0xbef9a3f7b2c67915, 0xc67178f2e372532b, 0xca273eceea26619c,
+	0xd186b8c721c0c207, 0xeada7dd6cde0eb1e, 0xf57d4f7fee6ed178, 0x06f067aa72176fba, 0x0a637dc5a2c898a6,
+	0x113f9804bef90dae, 0x1b710b35131c471b, 0x28db77f523047d84, 0x32caab7b40c72493, 0x3c9ebe0a15c9bebc,
+	0x431d67c49c100d4c, 0x4cc5d4becb3e42b6, 0x597f299cfc657e2a, 0x5fcb6fab3ad6faec, 0x6c44198c4a475817,
+];
+
+#[derive(Clone, Copy)]
+struct Sha512([u64; 8]);
+
+impl Default for Sha512 {
+	fn default() -> Self {
+		Sha512([
+			0x6a09e667f3bcc908, 0xbb67ae8584caa73b, 0x3c6ef372fe94f82b, 0xa54ff53a5f1d36f1,
+			0x510e527fade682d1, 0x9b05688c2b3e6c1f, 0x1f83d9abfb41bd6b, 0x5be0cd19137e2179,
+		])
+	}
+}
+
+impl Compress for Sha512 {
+	const BLOCK: usize = 128;
+	const LENGTH: usize = 16;
+	const OUTPUT: usize = 64;
+
+	fn compress(&mut self, block: &[u8]) {
+		let mut w = [0u64; 80];
+		BE::read_u64_into(block, &mut w[..16]);
+		for i in 16..80 {
+			let s0 = w[i - 15].rotate_right(1) ^ w[i - 15].rotate_right(8) ^ (w[i - 15] >> 7);
+			let s1 = w[i - 2].rotate_right(19) ^ w[i - 2].rotate_right(61) ^ (w[i - 2] >> 6);
+			w[i] = w[i - 16].wrapping_add(s0).wrapping_add(w[i - 7]).wrapping_add(s1);
+		}
+		let [mut a, mut b, mut c, mut d, mut e, mut f, mut g, mut h] = self.0;
+		for (&k, &w) in SHA512_K.iter().zip(&w) {
+			let s1 = e.rotate_right(14) ^ e.rotate_right(18) ^ e.rotate_right(41);
+			let ch = (e & f) ^ (!e & g);
+			let t1 = h.wrapping_add(s1).wrapping_add(ch).wrapping_add(k).wrapping_add(w);
+			let s0 = a.rotate_right(28) ^ a.rotate_right(34) ^ a.rotate_right(39);
+			let maj = (a & b) ^ (a & c) ^ (b & c);
+			let t2 = s0.wrapping_add(maj);
+			(h, g, f, e, d, c, b, a) = (g, f, e, d.wrapping_add(t1), c, b, a, t1.wrapping_add(t2));
+		}
+		for (s, v) in self.0.iter_mut().zip([a, b, c, d, e, f, g, h]) {
+			*s = s.wrapping_add(v);
+		}
+	}
+
+	fn output(&self, out: &mut [u8]) {
+		BE::write_u64_into(&self.0, &mut out[..64]);
+	}
+}
+
+/// SHA-384, SHA-512 with other initial values and a truncated output.
+struct Sha384(Sha512);
+
+impl Default for Sha384 {
+	fn default() -> Self {
+		Sha384(Sha512([
+			0xcbbb9d5dc1059ed8, 0x629a292a367cd507, 0x9159015a3070dd17, 0x152fecd8f70e5939,
+			0x67332667ffc00b31, 0x8eb44a8768581511, 0xdb0c2e0d64f98fa7, 0x47b5481dbefa4fa4,
+		]))
+	}
+}
+
+impl Compress for Sha384 {
+	const BLOCK: usize = 128;
+	const LENGTH: usize = 16;
+	const OUTPUT: usize = 48;
+
+	fn compress(&mut self, block: &[u8]) {
+		self.0.compress(block)
+	}
+
+	fn output(&self, out: &mut [u8]) {
+		self.0.output(out)
+	}
+}
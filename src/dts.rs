@@ -0,0 +1,128 @@
+//! Device tree source output, like the output of dtc when decompiling.
+//!
+//! Values are written in the form dtc guesses for them: lists of printable
+//! strings as strings, values with a length divisible by four as cells and
+//! everything else as bytes. Phandle references are written as numbers since
+//! a blob carries no labels.
+//!
+//! # Examples
+//!
+//! ```
+//! use fdt::FDT;
+//! let dtb = include_bytes!("../tests/dt.dtb").as_ptr();
+//!
+//! let fdt;
+//! unsafe { fdt = FDT::from_raw(dtb).unwrap(); }
+//!
+//! let dts = format!("{}", fdt::dts::dts(&fdt));
+//! assert!(dts.starts_with("/dts-v1/;\n"));
+//! ```
+
+use core::fmt::{self, Write};
+
+use byteorder::{ByteOrder, BE};
+
+use node::Node;
+use stringlist::is_printable;
+use FDT;
+
+/// Formats the whole tree of [fdt], including its memory reservations, as
+/// device tree source.
+pub fn dts<'a, 'buf>(fdt: &'a FDT<'buf>) -> Dts<'a, 'buf> {
+	Dts { fdt }
+}
+
+/// Formats [value] as the right hand side of a property assignment, or as
+/// nothing for empty values.
+pub fn value(value: &[u8]) -> Value<'_> {
+	Value(value)
+}
+
+/// A tree formatted as device tree source, see [dts()].
+pub struct Dts<'a, 'buf: 'a> {
+	fdt: &'a FDT<'buf>,
+}
+
+/// A property value formatted as device tree source, see [value()].
+pub struct Value<'a>(&'a [u8]);
+
+impl<'a, 'buf> fmt::Display for Dts<'a, 'buf> {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		f.write_str("/dts-v1/;\n\n")?;
+		let mut reserved = false;
+		for entry in self.fdt.memory_reserve_map() {
+			writeln!(f, "/memreserve/ {:#x} {:#x};", entry.address, entry.size)?;
+			reserved = true;
+		}
+		if reserved {
+			f.write_str("\n")?;
+		}
+		match self.fdt.nodes().next() {
+			Some(root) => node(f, &root),
+			None => Ok(()),
+		}
+	}
+}
+
+fn node(f: &mut fmt::Formatter, node: &Node) -> fmt::Result {
+	let depth = node.depth();
+	let name = if depth == 0 { "/" } else { node.name() };
+	writeln!(f, "{:\t<i$}{} {{", "", name, i=depth)?;
+	for prop in node.properties() {
+		write!(f, "{:\t<i$}{}", "", prop.name(), i=depth + 1)?;
+		if !prop.raw().is_empty() {
+			write!(f, " = {}", Value(prop.raw()))?;
+		}
+		f.write_str(";\n")?;
+	}
+	for child in node.children() {
+		f.write_str("\n")?;
+		self::node(f, &child)?;
+	}
+	writeln!(f, "{:\t<i$}}};", "", i=depth)
+}
+
+impl<'a> fmt::Display for Value<'a> {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		let raw = self.0;
+		if raw.is_empty() {
+			Ok(())
+		} else if raw == [0] {
+			f.write_str("\"\"")
+		} else if is_printable(raw) {
+			for (i, string) in raw[..raw.len() - 1].split(|&b| b == 0).enumerate() {
+				if i > 0 {
+					f.write_str(", ")?;
+				}
+				f.write_char('"')?;
+				for &b in string {
+					match b {
+						b'"' => f.write_str("\\\"")?,
+						b'\\' => f.write_str("\\\\")?,
+						b => f.write_char(b as char)?,
+					}
+				}
+				f.write_char('"')?;
+			}
+			Ok(())
+		} else if raw.len().is_multiple_of(4) {
+			f.write_char('<')?;
+			for (i, cell) in raw.chunks(4).enumerate() {
+				if i > 0 {
+					f.write_char(' ')?;
+				}
+				write!(f, "{:#x}", BE::read_u32(cell))?;
+			}
+			f.write_char('>')
+		} else {
+			f.write_char('[')?;
+			for (i, byte) in raw.iter().enumerate() {
+				if i > 0 {
+					f.write_char(' ')?;
+				}
+				write!(f, "{:02x}", byte)?;
+			}
+			f.write_char(']')
+		}
+	}
+}
@@ -0,0 +1,224 @@
+// Splitting of property values into entries according to the #*-cells
+// properties which apply to them, shared by the exporter and validators.
+
+use byteorder::{ByteOrder, BE};
+
+use cells::Cells;
+use error::Error;
+use node::{Node, DEFAULT_ADDRESS_CELLS, DEFAULT_SIZE_CELLS};
+use property::Property;
+
+/// Phandle array properties and the provider property holding their cell count
+pub const PHANDLE_ARRAYS: &[(&str, &str)] = &[
+	("clocks", "#clock-cells"),
+	("assigned-clocks", "#clock-cells"),
+	("assigned-clock-parents", "#clock-cells"),
+	("dmas", "#dma-cells"),
+	("resets", "#reset-cells"),
+	("pwms", "#pwm-cells"),
+	("power-domains", "#power-domain-cells"),
+	("phys", "#phy-cells"),
+	("mboxes", "#mbox-cells"),
+	("iommus", "#iommu-cells"),
+	("io-channels", "#io-channel-cells"),
+	("interrupts-extended", "#interrupt-cells"),
+	("sound-dai", "#sound-dai-cells"),
+	("thermal-sensors", "#thermal-sensor-cells"),
+	("hwlocks", "#hwlock-cells"),
+	("gpios", "#gpio-cells"),
+];
+
+/// Returns the provider cell count property of phandle array [name].
+pub fn phandle_array_cells(name: &str) -> Option<&'static str> {
+	if name.ends_with("-gpios") || name.ends_with("-gpio") {
+		Some("#gpio-cells")
+	} else {
+		PHANDLE_ARRAYS.iter().find(|entry| entry.0 == name).map(|entry| entry.1)
+	}
+}
+
+/// The cell counts inherited from the ancestors of a node.
+#[derive(Clone, Copy, Debug)]
+pub struct Context {
+	pub address_cells: u32,
+	pub size_cells: u32,
+	/// Whether the parent sets #address-cells and #size-cells rather than
+	/// leaving the defaults.
+	pub has_address_cells: bool,
+	pub has_size_cells: bool,
+	pub interrupt_parent: Option<u32>,
+}
+
+impl Context {
+	pub fn root() -> Self {
+		Context {
+			address_cells: DEFAULT_ADDRESS_CELLS,
+			size_cells: DEFAULT_SIZE_CELLS,
+			has_address_cells: false,
+			has_size_cells: false,
+			interrupt_parent: None,
+		}
+	}
+
+	/// The context of the children of [node] which has context [self].
+	pub fn child(&self, node: &Node) -> Self {
+		Context {
+			address_cells: node.address_cells(),
+			size_cells: node.size_cells(),
+			has_address_cells: node.property("#address-cells").is_some(),
+			has_size_cells: node.property("#size-cells").is_some(),
+			interrupt_parent: node.property("interrupt-parent")
+				.and_then(|prop| prop.as_u32().ok())
+				.or(self.interrupt_parent),
+		}
+	}
+
+	/// Builds the context of [node] from its ancestors.
+	pub fn of(node: &Node) -> Self {
+		(0..node.depth())
+			.filter_map(|depth| node.supernode_at_depth(depth))
+			.fold(Context::root(), |ctx, ancestor| ctx.child(&ancestor))
+	}
+}
+
+pub fn cells(node: &Node, name: &str) -> Option<u32> {
+	node.property(name).and_then(|prop| prop.as_u32().ok())
+}
+
+/// An iterator over a phandle list with arguments, each entry being the
+/// phandle of a provider followed by as many cells as the [cells_name]
+/// property of the provider gives. A phandle of 0 leaves a hole, returned
+/// as None.
+///
+/// Iteration stops after the first error.
+#[derive(Clone, Debug)]
+pub struct PhandleArgs<'buf> {
+	node: Node<'buf>,
+	cells_name: &'static str,
+	raw: &'buf [u8],
+}
+
+impl<'buf> PhandleArgs<'buf> {
+	/// Returns the entries of [raw], a list of cells of [node].
+	pub fn new(node: &Node<'buf>, raw: &'buf [u8], cells_name: &'static str) -> Self {
+		PhandleArgs { node: node.clone(), cells_name, raw }
+	}
+
+	/// Ends the iteration.
+	pub fn stop(&mut self) {
+		self.raw = &[];
+	}
+
+	/// Returns the entry at the start of the list and its length in bytes.
+	///
+	/// # Errors
+	///
+	/// Returns [Error::BadPhandle] if the provider doesn't exist,
+	/// [Error::BadNCells] if it lacks [cells_name] or it is malformed and
+	/// [Error::Truncated] if the entry doesn't fit in the list.
+	fn entry(&self) -> Result<(Option<(Node<'buf>, Cells<'buf>)>, usize), Error> {
+		let phandle = BE::read_u32(self.raw);
+		if phandle == 0 {
+			return Ok((None, 4));
+		}
+		let provider = self.node.lookup_phandle(phandle).ok_or(Error::BadPhandle)?;
+		let count = cells(&provider, self.cells_name).ok_or(Error::BadNCells)? as usize;
+		let len = count.checked_add(1).and_then(|len| len.checked_mul(4))
+			.filter(|&len| len <= self.raw.len())
+			.ok_or(Error::Truncated)?;
+		let args = Cells::new(&self.raw[4..len])?;
+		Ok((Some((provider, args)), len))
+	}
+}
+
+impl<'buf> Iterator for PhandleArgs<'buf> {
+	type Item = Result<Option<(Node<'buf>, Cells<'buf>)>, Error>;
+
+	fn next(&mut self) -> Option<Self::Item> {
+		if self.raw.len() < 4 {
+			return None;
+		}
+		match self.entry() {
+			Ok((entry, len)) => {
+				self.raw = &self.raw[len..];
+				Some(Ok(entry))
+			},
+			Err(err) => {
+				self.stop();
+				Some(Err(err))
+			},
+		}
+	}
+}
+
+/// Returns the number of cells in the entry of property [name] starting at
+/// [raw], or None if the property isn't split into entries or the entry
+/// length can't be determined.
+pub fn entry_len(name: &str, raw: &[u8], node: &Node, ctx: &Context) -> Option<usize> {
+	let provider_cells = |cells_name| {
+		PhandleArgs::new(&node.clone(), raw, cells_name).entry().ok().map(|(_, len)| len / 4)
+	};
+	match name {
+		"reg" => Some((ctx.address_cells + ctx.size_cells) as usize),
+		"ranges" | "dma-ranges" => {
+			let child = node.address_cells();
+			let size = node.size_cells();
+			Some((child + ctx.address_cells + size) as usize)
+		},
+		"interrupts" => {
+			let parent = cells(node, "interrupt-parent").or(ctx.interrupt_parent)?;
+			node.lookup_phandle(parent)
+				.and_then(|intc| cells(&intc, "#interrupt-cells"))
+				.map(|cells| cells as usize)
+		},
+		_ if name.starts_with("pinctrl-") => Some(1),
+		_ => phandle_array_cells(name).and_then(provider_cells),
+	}
+}
+
+/// An iterator over the entries of a cell based property value.
+///
+/// If an entry length can't be determined, or doesn't fit in the remainder
+/// of the value, the remainder is returned as the final entry and the
+/// iterator is marked as malformed.
+pub struct Entries<'a, 'n, 'buf: 'n> {
+	name: &'a str,
+	raw: &'a [u8],
+	node: &'n Node<'buf>,
+	ctx: Context,
+	malformed: bool,
+}
+
+impl<'a, 'n, 'buf> Entries<'a, 'n, 'buf> {
+	pub fn new(prop: &Property<'a>, node: &'n Node<'buf>, ctx: Context) -> Self {
+		let raw = prop.raw();
+		Entries { name: prop.name(), raw, node, ctx, malformed: !raw.len().is_multiple_of(4) }
+	}
+
+	/// Returns true if the entries iterated so far didn't match the cell
+	/// counts of the property.
+	pub fn is_malformed(&self) -> bool {
+		self.malformed
+	}
+}
+
+impl<'a, 'n, 'buf> Iterator for Entries<'a, 'n, 'buf> {
+	type Item = &'a [u8];
+
+	fn next(&mut self) -> Option<Self::Item> {
+		if self.raw.len() < 4 {
+			return None;
+		}
+		let cells = self.raw.len() / 4;
+		let len = match entry_len(self.name, self.raw, self.node, &self.ctx) {
+			Some(len) if len > 0 && len <= cells => len,
+			_ => {
+				self.malformed = true;
+				cells
+			},
+		};
+		let (entry, rest) = self.raw.split_at(len * 4);
+		self.raw = rest;
+		Some(entry)
+	}
+}
@@ -1,5 +1,8 @@
 use core::error::Error as CoreError;
+use core::fmt;
+use core::str::Utf8Error;
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Error {
 	NotFound, // The requested node or property does not exist : toremove use option
 	BadOffset, // The passed node or property is not part of the fdt : toremove use option
@@ -14,4 +17,32 @@ pub enum Error {
 	Internal, // Bug in the library causes an internal assertion to fail
 	BadNCells, // Device tree has a #address-cells, #size-cells or similar property with a bad format or value
 	BadValue, // Device tree has a property with an unexpected value. For example: a property expected to contain a string list is not NUL-terminated within the length of its value.
+	BadLength(usize), // Property value has a length (contained) which can't be parsed as the requested type
+	BadUtf8(Utf8Error), // Property value expected to contain a string isn't valid utf8
+	NoSpace, // A caller provided buffer is too small
 }
+
+impl fmt::Display for Error {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match *self {
+			Error::NotFound => write!(f, "node or property not found"),
+			Error::BadOffset => write!(f, "offset is not part of the device tree"),
+			Error::BadPath => write!(f, "badly formatted path"),
+			Error::BadPhandle => write!(f, "invalid phandle"),
+			Error::BadState => write!(f, "device tree is incomplete"),
+			Error::Truncated => write!(f, "value or structure is truncated"),
+			Error::BadMagic(magic) => write!(f, "bad magic number {:#x}", magic),
+			Error::BadVersion(version) => write!(f, "unsupported version {}", version),
+			Error::BadStructure => write!(f, "corrupt structure block"),
+			Error::BadLayout => write!(f, "sub-blocks in bad order"),
+			Error::Internal => write!(f, "internal error"),
+			Error::BadNCells => write!(f, "bad #address-cells, #size-cells or similar property"),
+			Error::BadValue => write!(f, "unexpected property value"),
+			Error::BadLength(len) => write!(f, "property value has unexpected length {}", len),
+			Error::BadUtf8(err) => write!(f, "property value is not valid utf8: {}", err),
+			Error::NoSpace => write!(f, "buffer too small"),
+		}
+	}
+}
+
+impl CoreError for Error {}
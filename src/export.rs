@@ -0,0 +1,231 @@
+//! Structured, machine readable export of device trees.
+//!
+//! The output follows the shape used by dt-schema for decoded blobs: each
+//! node is a map carrying its name in a `$nodename` entry followed by its
+//! properties and child nodes. String properties are arrays of strings, empty
+//! properties are `true` and every other property is a matrix of cells, where
+//! each row is one entry of the property. Rows are split using the cell
+//! counts which apply to the property: `#address-cells` and `#size-cells` of
+//! the parent for `reg`, `#interrupt-cells` of the interrupt parent for
+//! `interrupts` and the `#*-cells` of each referenced provider for phandle
+//! arrays such as `clocks` or `gpios`.
+//!
+//! Both formats are written through [core::fmt] so no allocations are made.
+//!
+//! # Examples
+//!
+//! ```
+//! use fdt::FDT;
+//! let dtb = include_bytes!("../tests/dt.dtb").as_ptr();
+//!
+//! let fdt;
+//! unsafe { fdt = FDT::from_raw(dtb).unwrap(); }
+//!
+//! let yaml = format!("{}", fdt::export::yaml(&fdt));
+//! assert!(yaml.starts_with("- $nodename: [\"/\"]\n"));
+//! ```
+
+use core::fmt::{self, Write};
+
+use byteorder::{ByteOrder, BE};
+
+use entries::{Context, Entries};
+use node::Node;
+use property::Property;
+use stringlist::is_printable;
+use FDT;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Style {
+	Json,
+	Yaml,
+}
+
+/// Formats the whole tree of [fdt] as YAML.
+pub fn yaml<'a, 'buf>(fdt: &'a FDT<'buf>) -> Export<'a, 'buf> {
+	Export { fdt: Some(fdt), node: None, style: Style::Yaml }
+}
+
+/// Formats the whole tree of [fdt] as JSON.
+pub fn json<'a, 'buf>(fdt: &'a FDT<'buf>) -> Export<'a, 'buf> {
+	Export { fdt: Some(fdt), node: None, style: Style::Json }
+}
+
+/// Formats [node] and its descendants as YAML.
+pub fn node_yaml<'a, 'buf>(node: &'a Node<'buf>) -> Export<'a, 'buf> {
+	Export { fdt: None, node: Some(node), style: Style::Yaml }
+}
+
+/// Formats [node] and its descendants as JSON.
+pub fn node_json<'a, 'buf>(node: &'a Node<'buf>) -> Export<'a, 'buf> {
+	Export { fdt: None, node: Some(node), style: Style::Json }
+}
+
+/// A tree or subtree formatted in dt-schema's shape.
+///
+/// Whole trees are written as a list holding the root node, like the output
+/// of dt-schema's tools, while single nodes are written as a map.
+pub struct Export<'a, 'buf: 'a> {
+	fdt: Option<&'a FDT<'buf>>,
+	node: Option<&'a Node<'buf>>,
+	style: Style,
+}
+
+impl<'a, 'buf> fmt::Display for Export<'a, 'buf> {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		let mut w = Writer { f, style: self.style };
+		if let Some(fdt) = self.fdt {
+			match fdt.nodes().next() {
+				Some(root) => {
+					w.f.write_str(if w.style == Style::Json { "[" } else { "- " })?;
+					w.node(&root, Context::root(), 1)?;
+					w.f.write_str(if w.style == Style::Json { "]\n" } else { "" })
+				},
+				None => w.f.write_str("[]\n"),
+			}
+		} else if let Some(node) = self.node {
+			w.node(node, Context::of(node), 0)?;
+			if w.style == Style::Json {
+				w.f.write_str("\n")?;
+			}
+			Ok(())
+		} else {
+			Ok(())
+		}
+	}
+}
+
+struct Writer<'f, 'g: 'f> {
+	f: &'f mut fmt::Formatter<'g>,
+	style: Style,
+}
+
+impl<'f, 'g> Writer<'f, 'g> {
+	fn indent(&mut self, level: usize) -> fmt::Result {
+		write!(self.f, "{:i$}", "", i=level * 2)
+	}
+
+	fn string(&mut self, string: &str) -> fmt::Result {
+		self.f.write_char('"')?;
+		for c in string.chars() {
+			match c {
+				'"' => self.f.write_str("\\\"")?,
+				'\\' => self.f.write_str("\\\\")?,
+				'\n' => self.f.write_str("\\n")?,
+				c if (c as u32) < 0x20 => write!(self.f, "\\u{:04x}", c as u32)?,
+				c => self.f.write_char(c)?,
+			}
+		}
+		self.f.write_char('"')
+	}
+
+	fn key(&mut self, key: &str, first: bool, level: usize) -> fmt::Result {
+		match self.style {
+			Style::Json => {
+				self.f.write_str(if first { "{" } else { ", " })?;
+				self.string(key)?;
+			},
+			Style::Yaml => {
+				if !first {
+					self.indent(level)?;
+				}
+				let plain = key.starts_with(|c: char| c.is_ascii_alphanumeric() || c == '$')
+					&& key.chars().all(|c| c.is_ascii_alphanumeric() || ",._+-@$".contains(c));
+				if plain {
+					self.f.write_str(key)?;
+				} else {
+					self.string(key)?;
+				}
+			},
+		}
+		self.f.write_str(":")
+	}
+
+	fn number(&mut self, val: u32) -> fmt::Result {
+		match self.style {
+			Style::Json => write!(self.f, "{}", val),
+			Style::Yaml => write!(self.f, "{:#x}", val),
+		}
+	}
+
+	fn node(&mut self, node: &Node, ctx: Context, level: usize) -> fmt::Result {
+		let name = if node.depth() == 0 { "/" } else { node.name() };
+		self.key("$nodename", true, level)?;
+		self.f.write_str(" [")?;
+		self.string(name)?;
+		self.f.write_str("]")?;
+		if self.style == Style::Yaml {
+			self.f.write_str("\n")?;
+		}
+		for prop in node.properties() {
+			self.key(prop.name(), false, level)?;
+			self.f.write_str(" ")?;
+			self.value(&prop, node, &ctx)?;
+			if self.style == Style::Yaml {
+				self.f.write_str("\n")?;
+			}
+		}
+		let child_ctx = ctx.child(node);
+		for child in node.children() {
+			self.key(child.name(), false, level)?;
+			match self.style {
+				Style::Json => self.f.write_str(" ")?,
+				Style::Yaml => {
+					self.f.write_str("\n")?;
+					self.indent(level + 1)?;
+				},
+			}
+			self.node(&child, child_ctx, level + 1)?;
+		}
+		match self.style {
+			Style::Json => self.f.write_str("}"),
+			Style::Yaml => Ok(()),
+		}
+	}
+
+	fn value(&mut self, prop: &Property, node: &Node, ctx: &Context) -> fmt::Result {
+		let raw = prop.raw();
+		if raw.is_empty() {
+			return self.f.write_str("true");
+		}
+		if raw == [0] {
+			// an empty string
+			return self.f.write_str("[\"\"]");
+		}
+		if is_printable(raw) {
+			self.f.write_str("[")?;
+			for (i, string) in raw[..raw.len() - 1].split(|&b| b == 0).enumerate() {
+				if i > 0 {
+					self.f.write_str(", ")?;
+				}
+				self.string(core::str::from_utf8(string).unwrap_or(""))?;
+			}
+			return self.f.write_str("]");
+		}
+		if !raw.len().is_multiple_of(4) {
+			self.f.write_str("[[")?;
+			for (i, byte) in raw.iter().enumerate() {
+				if i > 0 {
+					self.f.write_str(", ")?;
+				}
+				self.number(u32::from(*byte))?;
+			}
+			return self.f.write_str("]]");
+		}
+		self.f.write_str("[")?;
+		for (row, entry) in Entries::new(prop, node, *ctx).enumerate() {
+			if row > 0 {
+				self.f.write_str(", ")?;
+			}
+			self.f.write_str("[")?;
+			for (i, cell) in entry.chunks(4).enumerate() {
+				if i > 0 {
+					self.f.write_str(", ")?;
+				}
+				self.number(BE::read_u32(cell))?;
+			}
+			self.f.write_str("]")?;
+		}
+		self.f.write_str("]")
+	}
+}
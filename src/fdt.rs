@@ -35,8 +35,6 @@ impl Blob<'buf> {
 		Ok(())
 	}
 		
-	// todo: fdt_move intentionally left out until explicitly requested or required
-
 	/// Retrieve a string from the strings block of a device tree
 	///
 	/// Retrieves the string starting at byte offset 'string_offset'
@@ -0,0 +1,943 @@
+//! Typed access to U-Boot FIT (Flattened Image Tree) images.
+//!
+//! A FIT is a device tree blob with the images to boot below `/images` and
+//! the ways to combine them below `/configurations`. Image data is either
+//! embedded in the `data` property or stored after the blob, located by
+//! `data-offset` (relative to the 4 byte aligned end of the blob) or
+//! `data-position` (relative to the start of the blob) and `data-size`.
+//!
+//! The `hash` subnodes of images are checked with [Image::verify()], using a
+//! [digest backend](::digest::Backend). The `signature` subnodes of
+//! configurations and images are checked against the keys of a control
+//! device tree, see [Configuration::verify_required_signatures()].
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use fdt::error::Error;
+//! use fdt::fit::{Fit, ImageType};
+//!
+//! fn kernel(image: &[u8]) -> Result<(&[u8], Option<u64>), Error> {
+//!     let fit = Fit::from_bytes(image)?;
+//!     let config = fit.select_configuration(None)?;
+//!     let kernel = config.kernel()?.ok_or(Error::NotFound)?;
+//!     assert_eq!(kernel.kind()?, ImageType::Kernel);
+//!     Ok((kernel.data()?, kernel.entry()?))
+//! }
+//! ```
+
+use core::fmt;
+
+use byteorder::{ByteOrder, BE};
+
+use digest::{Algorithm, Backend, Digest};
+use error::Error;
+use node::{Children, Node};
+use property::PropertyValue;
+use signature::{self, Crypto, Key, Keys, SignatureError};
+use stringlist::{StringList, Strings};
+use tag::{Tag, TagCursor};
+use FDT;
+
+/// The type of an image, from its 'type' property.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ImageType<'buf> {
+	Kernel,
+	/// A kernel which can run from anywhere, its 'load' address is ignored.
+	KernelNoload,
+	/// A device tree blob, "flat_dt".
+	FlatDt,
+	Ramdisk,
+	Firmware,
+	Standalone,
+	Script,
+	Fpga,
+	/// A type without a variant of its own, such as "filesystem".
+	Other(&'buf str),
+}
+
+impl<'buf> ImageType<'buf> {
+	fn new(name: &'buf str) -> Self {
+		match name {
+			"kernel" => ImageType::Kernel,
+			"kernel_noload" => ImageType::KernelNoload,
+			"flat_dt" => ImageType::FlatDt,
+			"ramdisk" => ImageType::Ramdisk,
+			"firmware" => ImageType::Firmware,
+			"standalone" => ImageType::Standalone,
+			"script" => ImageType::Script,
+			"fpga" => ImageType::Fpga,
+			other => ImageType::Other(other),
+		}
+	}
+}
+
+/// The compression of image data, from its 'compression' property.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Compression<'buf> {
+	None,
+	Gzip,
+	Bzip2,
+	Lzma,
+	Lzo,
+	Lz4,
+	Zstd,
+	Other(&'buf str),
+}
+
+impl<'buf> Compression<'buf> {
+	fn new(name: &'buf str) -> Self {
+		match name {
+			"none" => Compression::None,
+			"gzip" => Compression::Gzip,
+			"bzip2" => Compression::Bzip2,
+			"lzma" => Compression::Lzma,
+			"lzo" => Compression::Lzo,
+			"lz4" => Compression::Lz4,
+			"zstd" => Compression::Zstd,
+			other => Compression::Other(other),
+		}
+	}
+}
+
+/// Where the data of an image is stored.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DataLocation {
+	/// In the 'data' property of the image node.
+	Embedded,
+	/// [size] bytes at [position] from the start of the FIT, after the blob.
+	External { position: usize, size: usize },
+}
+
+/// A FIT image, see the [module documentation](self).
+pub struct Fit<'buf> {
+	buf: &'buf [u8],
+	fdt: FDT<'buf>,
+}
+
+impl<'buf> Fit<'buf> {
+	/// Parses the FIT at the start of [buf], which extends to the end of
+	/// any external image data.
+	///
+	/// # Errors
+	///
+	/// Returns the errors of [FDT::from_bytes] and [Error::NotFound] if the
+	/// blob has no `/images` node.
+	pub fn from_bytes(buf: &'buf [u8]) -> Result<Self, Error> {
+		let fit = Fit { buf, fdt: FDT::from_bytes(buf)? };
+		fit.node("images").ok_or(Error::NotFound)?;
+		Ok(fit)
+	}
+
+	/// Returns the underlying device tree.
+	pub fn fdt(&self) -> &FDT<'buf> {
+		&self.fdt
+	}
+
+	/// Returns the 'description' of the FIT.
+	pub fn description(&self) -> Option<&'buf str> {
+		self.fdt.nodes().next().and_then(|root| string(&root, "description").ok()?)
+	}
+
+	/// Returns the 'timestamp' of the FIT, in seconds since the epoch.
+	pub fn timestamp(&self) -> Option<u32> {
+		self.fdt.nodes().next()?.property_value("timestamp").ok()
+	}
+
+	/// Returns an iterator over the images.
+	pub fn images(&self) -> Images<'buf> {
+		Images { children: self.node("images").map(|images| images.children()), fit: self.source() }
+	}
+
+	/// Returns the image named [name].
+	pub fn image(&self, name: &str) -> Option<Image<'buf>> {
+		self.images().find(|image| image.name() == name)
+	}
+
+	/// Returns an iterator over the configurations.
+	pub fn configurations(&self) -> Configurations<'buf> {
+		Configurations {
+			children: self.node("configurations").map(|configs| configs.children()),
+			images: self.node("images"),
+			fit: self.source(),
+		}
+	}
+
+	/// Verifies the hashes of every image, see [Image::verify()].
+	///
+	/// # Examples
+	///
+	/// ```no_run
+	/// use fdt::digest::Software;
+	/// use fdt::fit::Fit;
+	///
+	/// fn check(image: &[u8]) -> bool {
+	///     let fit = Fit::from_bytes(image).unwrap();
+	///     fit.verify_images(&mut Software).all(|(image, verification)| {
+	///         println!("{}: {}", image.name(), verification);
+	///         verification.is_verified()
+	///     })
+	/// }
+	/// ```
+	pub fn verify_images<'a, B: Backend + ?Sized>(&self, backend: &'a mut B)
+			-> impl Iterator<Item=(Image<'buf>, Verification<'buf>)> + 'a where 'buf: 'a {
+		self.images().map(move |image| {
+			let verification = image.verify(backend);
+			(image, verification)
+		})
+	}
+
+	/// Returns the configuration named [name].
+	pub fn configuration(&self, name: &str) -> Option<Configuration<'buf>> {
+		self.configurations().find(|config| config.name() == name)
+	}
+
+	/// Returns the configuration named by the 'default' property of
+	/// `/configurations`.
+	///
+	/// # Errors
+	///
+	/// Returns [Error::NotFound] if there is no default or it names a missing
+	/// configuration.
+	pub fn default_configuration(&self) -> Result<Configuration<'buf>, Error> {
+		let configs = self.node("configurations").ok_or(Error::NotFound)?;
+		let name = string(&configs, "default")?.ok_or(Error::NotFound)?;
+		self.configuration(name).ok_or(Error::NotFound)
+	}
+
+	/// Returns the configuration named [name] or the default configuration
+	/// if [name] is None, like U-Boot's `bootm <addr>#<name>`.
+	pub fn select_configuration(&self, name: Option<&str>) -> Result<Configuration<'buf>, Error> {
+		match name {
+			Some(name) => self.configuration(name).ok_or(Error::NotFound),
+			None => self.default_configuration(),
+		}
+	}
+
+	/// Returns the child of the root named exactly [name], so that a decoy
+	/// like `/images@0` can't stand in for `/images`.
+	fn node(&self, name: &str) -> Option<Node<'buf>> {
+		self.fdt.nodes().next()?.children().find(|node| node.name() == name)
+	}
+
+	fn source(&self) -> Source<'buf> {
+		let end = self.fdt.total_size() as usize;
+		Source { buf: self.buf, external: (end + 3) & !3 }
+	}
+}
+
+/// The buffer holding the FIT and the start of data-offset based data.
+#[derive(Clone, Copy, Debug)]
+struct Source<'buf> {
+	buf: &'buf [u8],
+	external: usize,
+}
+
+/// An image of a FIT, a subnode of `/images`.
+#[derive(Clone, Debug)]
+pub struct Image<'buf> {
+	node: Node<'buf>,
+	fit: Source<'buf>,
+}
+
+impl<'buf> Image<'buf> {
+	/// Returns the name of the image node, by which configurations refer to
+	/// it.
+	pub fn name(&self) -> &'buf str {
+		self.node.name()
+	}
+
+	/// Returns the image node.
+	pub fn node(&self) -> &Node<'buf> {
+		&self.node
+	}
+
+	pub fn description(&self) -> Option<&'buf str> {
+		string(&self.node, "description").ok()?
+	}
+
+	/// Returns the 'type' of the image.
+	///
+	/// # Errors
+	///
+	/// Returns [Error::NotFound] if the image has no type.
+	pub fn kind(&self) -> Result<ImageType<'buf>, Error> {
+		string(&self.node, "type")?.map(ImageType::new).ok_or(Error::NotFound)
+	}
+
+	/// Returns the 'arch' of the image, such as "arm64".
+	pub fn arch(&self) -> Option<&'buf str> {
+		string(&self.node, "arch").ok()?
+	}
+
+	/// Returns the 'os' of the image, such as "linux".
+	pub fn os(&self) -> Option<&'buf str> {
+		string(&self.node, "os").ok()?
+	}
+
+	/// Returns the 'compression' of the image data, images without the
+	/// property are uncompressed.
+	pub fn compression(&self) -> Result<Compression<'buf>, Error> {
+		Ok(string(&self.node, "compression")?.map_or(Compression::None, Compression::new))
+	}
+
+	/// Returns the 'load' address of the image, a 32 or 64 bit value.
+	pub fn load(&self) -> Result<Option<u64>, Error> {
+		address(&self.node, "load")
+	}
+
+	/// Returns the 'entry' point of the image, a 32 or 64 bit value.
+	pub fn entry(&self) -> Result<Option<u64>, Error> {
+		address(&self.node, "entry")
+	}
+
+	/// Returns where the data of the image is stored.
+	///
+	/// # Errors
+	///
+	/// Returns [Error::NotFound] if the image has no data and
+	/// [Error::BadLength] if 'data-offset', 'data-position' or 'data-size'
+	/// aren't single cells.
+	pub fn data_location(&self) -> Result<DataLocation, Error> {
+		if self.node.property("data").is_some() {
+			return Ok(DataLocation::Embedded);
+		}
+		let position = match self.node.property_value::<Option<u32>>("data-position")? {
+			Some(position) => position as usize,
+			None => {
+				let offset = self.node.property_value::<Option<u32>>("data-offset")?.ok_or(Error::NotFound)?;
+				self.fit.external + offset as usize
+			},
+		};
+		let size = self.node.property_value::<u32>("data-size")? as usize;
+		Ok(DataLocation::External { position, size })
+	}
+
+	/// Returns the data of the image, embedded or external.
+	///
+	/// # Errors
+	///
+	/// Returns the errors of [data_location()] and [Error::Truncated] if
+	/// external data lies beyond the end of the FIT buffer.
+	///
+	/// [data_location()]: Image::data_location
+	pub fn data(&self) -> Result<&'buf [u8], Error> {
+		match self.data_location()? {
+			DataLocation::Embedded => self.node.property_value("data"),
+			DataLocation::External { position, size } => position.checked_add(size)
+				.and_then(|end| self.fit.buf.get(position..end))
+				.ok_or(Error::Truncated),
+		}
+	}
+
+	/// Returns an iterator over the 'hash' subnodes of the image.
+	pub fn hashes(&self) -> Hashes<'buf> {
+		Hashes(self.node.children())
+	}
+
+	/// Checks the data of the image against every hash subnode, computing
+	/// digests with [backend].
+	///
+	/// Verification stops at the first hash which doesn't match.
+	pub fn verify<B: Backend + ?Sized>(&self, backend: &mut B) -> Verification<'buf> {
+		let data = match self.data() {
+			Ok(data) => data,
+			Err(error) => return Verification::BadData(error),
+		};
+		let mut hashes = 0;
+		for hash in self.hashes() {
+			if let Err(error) = hash.verify(data, backend) {
+				return Verification::Failed { hash: hash.name(), error };
+			}
+			hashes += 1;
+		}
+		match hashes {
+			0 => Verification::Unhashed,
+			hashes => Verification::Verified { hashes },
+		}
+	}
+
+	/// Returns an iterator over the 'signature' subnodes of the image, which
+	/// sign its data.
+	pub fn signatures(&self) -> Signatures<'buf> {
+		Signatures { children: self.node.children(), signed: Signed::Image(self.clone()) }
+	}
+
+	/// Checks the signatures of the image against the keys of [keys] whose
+	/// 'required' property is "image", like U-Boot's verified boot.
+	///
+	/// Returns the number of required keys which signed the image, 0 if no
+	/// key is required.
+	pub fn verify_required_signatures<D, C>(&self, keys: &Keys, digests: &mut D, crypto: &mut C)
+			-> Result<usize, SignatureError> where D: Backend + ?Sized, C: Crypto + ?Sized {
+		verify_required(self.signatures(), "image", keys, digests, crypto)
+	}
+}
+
+/// A hash subnode of an image, holding the digest of the image data.
+#[derive(Clone, Debug)]
+pub struct Hash<'buf> {
+	node: Node<'buf>,
+}
+
+impl<'buf> Hash<'buf> {
+	pub fn name(&self) -> &'buf str {
+		self.node.name()
+	}
+
+	/// Returns the name of the digest algorithm, the 'algo' property.
+	pub fn algo(&self) -> Option<&'buf str> {
+		string(&self.node, "algo").ok()?
+	}
+
+	/// Returns the expected digest, the 'value' property.
+	pub fn value(&self) -> Option<&'buf [u8]> {
+		self.node.property("value").map(|prop| prop.raw())
+	}
+
+	/// Checks that the digest of [data] computed by [backend] matches the
+	/// value of the hash.
+	pub fn verify<B: Backend + ?Sized>(&self, data: &[u8], backend: &mut B) -> Result<(), HashError<'buf>> {
+		let algo = self.algo().ok_or(HashError::Malformed("algo"))?;
+		let value = self.value().ok_or(HashError::Malformed("value"))?;
+		let digest = Algorithm::from_name(algo)
+			.and_then(|algorithm| backend.digest(algorithm, &mut Some(data).into_iter()))
+			.ok_or(HashError::Unsupported(algo))?;
+		if digest.as_ref() == value { Ok(()) } else { Err(HashError::Mismatch) }
+	}
+}
+
+/// Why a hash of an image failed to verify.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum HashError<'buf> {
+	/// The digest of the data differs from the 'value' of the hash.
+	Mismatch,
+	/// The 'algo' of the hash is unknown or not supported by the backend.
+	Unsupported(&'buf str),
+	/// The hash node lacks the named property.
+	Malformed(&'static str),
+}
+
+impl<'buf> fmt::Display for HashError<'buf> {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match *self {
+			HashError::Mismatch => write!(f, "digest mismatch"),
+			HashError::Unsupported(algo) => write!(f, "unsupported algorithm '{}'", algo),
+			HashError::Malformed(property) => write!(f, "missing property '{}'", property),
+		}
+	}
+}
+
+/// The outcome of verifying the hashes of an image, see [Image::verify()].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Verification<'buf> {
+	/// Each of the [hashes] of the image matched.
+	Verified { hashes: usize },
+	/// The image has no hash subnodes, so its data can't be checked.
+	Unhashed,
+	/// The subnode named [hash] failed to verify.
+	Failed { hash: &'buf str, error: HashError<'buf> },
+	/// The image data couldn't be located.
+	BadData(Error),
+}
+
+impl<'buf> Verification<'buf> {
+	/// Returns true if the image has hashes and all of them matched.
+	pub fn is_verified(&self) -> bool {
+		matches!(self, Verification::Verified { .. })
+	}
+}
+
+impl<'buf> fmt::Display for Verification<'buf> {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match *self {
+			Verification::Verified { hashes } => write!(f, "verified {} hashes", hashes),
+			Verification::Unhashed => write!(f, "no hashes"),
+			Verification::Failed { hash, error } => write!(f, "{}: {}", hash, error),
+			Verification::BadData(error) => write!(f, "bad data: {}", error),
+		}
+	}
+}
+
+/// An iterator over the hash subnodes of an image.
+#[derive(Clone, Debug)]
+pub struct Hashes<'buf>(Children<'buf>);
+
+impl<'buf> Iterator for Hashes<'buf> {
+	type Item = Hash<'buf>;
+
+	fn next(&mut self) -> Option<Self::Item> {
+		self.0.by_ref().find(|node| node.name().starts_with("hash")).map(|node| Hash { node })
+	}
+}
+
+/// A configuration of a FIT, a subnode of `/configurations`.
+#[derive(Clone, Debug)]
+pub struct Configuration<'buf> {
+	node: Node<'buf>,
+	images: Option<Node<'buf>>,
+	fit: Source<'buf>,
+}
+
+impl<'buf> Configuration<'buf> {
+	pub fn name(&self) -> &'buf str {
+		self.node.name()
+	}
+
+	/// Returns the configuration node.
+	pub fn node(&self) -> &Node<'buf> {
+		&self.node
+	}
+
+	pub fn description(&self) -> Option<&'buf str> {
+		string(&self.node, "description").ok()?
+	}
+
+	/// Returns the 'compatible' strings of the configuration, used to pick
+	/// the configuration matching a board.
+	pub fn compatible(&self) -> Option<StringList<'buf>> {
+		self.node.property_value("compatible").ok()?
+	}
+
+	/// Returns the 'kernel' image of the configuration.
+	///
+	/// # Errors
+	///
+	/// Returns [Error::NotFound] if the configuration names a missing image.
+	pub fn kernel(&self) -> Result<Option<Image<'buf>>, Error> {
+		self.images("kernel").next().transpose()
+	}
+
+	/// Returns the 'ramdisk' image of the configuration.
+	pub fn ramdisk(&self) -> Result<Option<Image<'buf>>, Error> {
+		self.images("ramdisk").next().transpose()
+	}
+
+	/// Returns the 'firmware' image of the configuration.
+	pub fn firmware(&self) -> Result<Option<Image<'buf>>, Error> {
+		self.images("firmware").next().transpose()
+	}
+
+	/// Returns the 'fdt' images of the configuration, a base tree followed by
+	/// any overlays to apply to it.
+	pub fn fdt(&self) -> ImageList<'buf> {
+		self.images("fdt")
+	}
+
+	/// Returns the 'loadables' images of the configuration.
+	pub fn loadables(&self) -> ImageList<'buf> {
+		self.images("loadables")
+	}
+
+	/// Returns the images named by property [name] of the configuration,
+	/// an empty list if the property is missing or not a string list.
+	pub fn images(&self, name: &str) -> ImageList<'buf> {
+		let names = self.node.property_value::<Option<StringList>>(name).ok().flatten();
+		ImageList { names: names.map(|names| names.strings()), images: self.images.clone(), fit: self.fit }
+	}
+
+	/// Returns an iterator over the 'signature' subnodes of the
+	/// configuration, which sign the configuration and its images.
+	pub fn signatures(&self) -> Signatures<'buf> {
+		Signatures { children: self.node.children(), signed: Signed::Configuration { name: self.name(), fit: self.fit } }
+	}
+
+	/// Checks the signatures of the configuration against the keys of [keys]
+	/// whose 'required' property is "conf", like U-Boot's verified boot.
+	///
+	/// Every required key must have signed the configuration, or one of them
+	/// if the 'required-mode' of the keys is "any". Returns the number of
+	/// required keys which signed the configuration, 0 if no key is
+	/// required. The images of a verified configuration are protected by
+	/// their hashes, which must be checked with [Image::verify()].
+	///
+	/// # Examples
+	///
+	/// ```no_run
+	/// use fdt::{digest, signature, FDT};
+	/// use fdt::fit::Fit;
+	/// use fdt::signature::Keys;
+	///
+	/// fn check(image: &[u8], control: &FDT) -> bool {
+	///     let fit = Fit::from_bytes(image).unwrap();
+	///     let config = fit.default_configuration().unwrap();
+	///     let keys = Keys::new(control);
+	///     config.verify_required_signatures(&keys, &mut digest::Software, &mut signature::Software).is_ok()
+	///         && config.kernel().unwrap().unwrap().verify(&mut digest::Software).is_verified()
+	/// }
+	/// ```
+	pub fn verify_required_signatures<D, C>(&self, keys: &Keys, digests: &mut D, crypto: &mut C)
+			-> Result<usize, SignatureError> where D: Backend + ?Sized, C: Crypto + ?Sized {
+		verify_required(self.signatures(), "conf", keys, digests, crypto)
+	}
+}
+
+/// What a signature signs.
+#[derive(Clone, Debug)]
+enum Signed<'buf> {
+	Image(Image<'buf>),
+	Configuration { name: &'buf str, fit: Source<'buf> },
+}
+
+/// A signature subnode of an image or a configuration.
+///
+/// Image signatures sign the image data. Configuration signatures sign the
+/// parts of the blob listed by 'hashed-nodes', the configuration node and
+/// the nodes of its images and their hashes, leaving out image data, see
+/// [digest()](Signature::digest).
+#[derive(Clone, Debug)]
+pub struct Signature<'buf> {
+	node: Node<'buf>,
+	signed: Signed<'buf>,
+}
+
+impl<'buf> Signature<'buf> {
+	pub fn name(&self) -> &'buf str {
+		self.node.name()
+	}
+
+	/// Returns the 'algo' of the signature, such as "sha256,rsa2048".
+	pub fn algo(&self) -> Option<&'buf str> {
+		string(&self.node, "algo").ok()?
+	}
+
+	/// Returns the 'key-name-hint', the name of the signing key.
+	pub fn key_name_hint(&self) -> Option<&'buf str> {
+		string(&self.node, "key-name-hint").ok()?
+	}
+
+	/// Returns the 'padding' of RSA signatures, "pkcs-1.5" or "pss".
+	pub fn padding(&self) -> Option<&'buf str> {
+		string(&self.node, "padding").ok()?
+	}
+
+	/// Returns the signature, the 'value' property.
+	pub fn value(&self) -> Option<&'buf [u8]> {
+		self.node.property("value").map(|prop| prop.raw())
+	}
+
+	/// Returns the 'sign-images' of a configuration signature, the kinds of
+	/// images covered such as "kernel" and "fdt".
+	pub fn signed_images(&self) -> Option<StringList<'buf>> {
+		self.node.property_value("sign-images").ok()?
+	}
+
+	/// Returns the 'hashed-nodes' of a configuration signature, the paths of
+	/// the signed nodes.
+	pub fn hashed_nodes(&self) -> Option<StringList<'buf>> {
+		self.node.property_value("hashed-nodes").ok()?
+	}
+
+	/// Computes the digest of the signed data with [digests].
+	///
+	/// For configurations these are the regions of the blob U-Boot's
+	/// `fdt_find_regions()` selects for 'hashed-nodes': the properties of the
+	/// listed nodes except 'data', 'data-size', 'data-offset' and
+	/// 'data-position', the begin and end tags of their children, followed by
+	/// the 'hashed-strings' part of the strings block.
+	///
+	/// # Errors
+	///
+	/// Returns [SignatureError::NotHashed] if the configuration isn't among
+	/// the 'hashed-nodes'.
+	pub fn digest<D: Backend + ?Sized>(&self, digests: &mut D) -> Result<Digest, SignatureError> {
+		let algo = self.algo().ok_or(SignatureError::Malformed("algo"))?;
+		let (checksum, _) = signature::parse_algo(algo).ok_or(SignatureError::Unsupported)?;
+		let (name, fit) = match self.signed {
+			Signed::Image(ref image) => {
+				let data = image.data().map_err(SignatureError::Fdt)?;
+				return digests.digest(checksum, &mut Some(data).into_iter()).ok_or(SignatureError::Unsupported);
+			},
+			Signed::Configuration { name, fit } => (name, fit),
+		};
+		let nodes = self.hashed_nodes().ok_or(SignatureError::Malformed("hashed-nodes"))?;
+		if !nodes.strings().any(|path| path.strip_prefix("/configurations/") == Some(name)) {
+			return Err(SignatureError::NotHashed);
+		}
+		let fdt = FDT::from_bytes(fit.buf).map_err(SignatureError::Fdt)?;
+		let strings = match self.node.property("hashed-strings") {
+			Some(prop) => {
+				let [start, size] = prop.parse::<[u32; 2]>().map_err(|_| SignatureError::Malformed("hashed-strings"))?;
+				let (start, size) = (start as usize, size as usize);
+				Some(fdt.blob.strings().get(start..start + size).ok_or(SignatureError::Malformed("hashed-strings"))?)
+			},
+			None => None,
+		};
+		let mut regions = Regions::new(&fdt, nodes, strings);
+		let digest = digests.digest(checksum, &mut regions).ok_or(SignatureError::Unsupported);
+		match regions.error {
+			Some(error) => Err(SignatureError::Fdt(error)),
+			None => digest,
+		}
+	}
+
+	/// Checks the signature against [key], computing digests with [digests]
+	/// and the public key operation with [crypto].
+	///
+	/// Like U-Boot since CVE-2021-27138, images and configurations with a
+	/// unit address in their name are rejected.
+	pub fn verify<D, C>(&self, key: &Key, digests: &mut D, crypto: &mut C) -> Result<(), SignatureError>
+			where D: Backend + ?Sized, C: Crypto + ?Sized {
+		let signed = match self.signed {
+			Signed::Image(ref image) => image.name(),
+			Signed::Configuration { name, .. } => name,
+		};
+		if signed.contains('@') {
+			return Err(SignatureError::UnitAddress);
+		}
+		let algo = self.algo().ok_or(SignatureError::Malformed("algo"))?;
+		let value = self.value().ok_or(SignatureError::Malformed("value"))?;
+		let digest = self.digest(digests)?;
+		signature::verify(key, algo, self.padding(), &digest, value, digests, crypto)
+	}
+}
+
+/// An iterator over the signature subnodes of an image or configuration.
+#[derive(Clone, Debug)]
+pub struct Signatures<'buf> {
+	children: Children<'buf>,
+	signed: Signed<'buf>,
+}
+
+impl<'buf> Iterator for Signatures<'buf> {
+	type Item = Signature<'buf>;
+
+	fn next(&mut self) -> Option<Self::Item> {
+		let node = self.children.by_ref().find(|node| node.name().starts_with("signature"))?;
+		Some(Signature { node, signed: self.signed.clone() })
+	}
+}
+
+/// Checks [signatures] against the keys whose 'required' is [required].
+fn verify_required<D, C>(signatures: Signatures, required: &str, keys: &Keys, digests: &mut D, crypto: &mut C)
+		-> Result<usize, SignatureError> where D: Backend + ?Sized, C: Crypto + ?Sized {
+	let all = keys.require_all();
+	let (mut required_keys, mut verified) = (0, 0);
+	let mut error = SignatureError::Missing;
+	for key in keys.iter().filter(|key| key.required() == Some(required)) {
+		required_keys += 1;
+		let signed = signatures.clone().any(|signature| match signature.verify(&key, digests, crypto) {
+			Ok(()) => true,
+			Err(err) => {
+				error = err;
+				false
+			},
+		});
+		match signed {
+			true if all => verified += 1,
+			true => return Ok(1),
+			false if all => return Err(error),
+			false => (),
+		}
+	}
+	if required_keys > 0 && verified == 0 { Err(error) } else { Ok(verified) }
+}
+
+/// The deepest nesting and longest path [Regions] supports.
+const MAX_DEPTH: usize = 64;
+const MAX_PATH: usize = 256;
+
+/// Properties left out of signed configurations, as image data is signed by
+/// the hashes of the images.
+const UNSIGNED_PROPERTIES: [&str; 4] = ["data", "data-size", "data-position", "data-offset"];
+
+/// An iterator over the regions of a blob signed by a configuration
+/// signature, a port of U-Boot's `fdt_find_regions()`.
+///
+/// Adjacent regions may be returned separately, which doesn't change their
+/// digest. Errors end the iteration and are kept in [error].
+struct Regions<'buf> {
+	tags: TagCursor<'buf>,
+	structure: &'buf [u8],
+	nodes: StringList<'buf>,
+	strings: Option<&'buf [u8]>,
+	path: [u8; MAX_PATH],
+	path_len: usize,
+	/// The 'want' of the enclosing nodes: 2 for hashed nodes, 1 for their
+	/// children, whose begin and end tags are included, 0 otherwise.
+	stack: [u8; MAX_DEPTH],
+	depth: usize,
+	want: u8,
+	start: Option<usize>,
+	error: Option<Error>,
+}
+
+impl<'buf> Regions<'buf> {
+	fn new(fdt: &FDT<'buf>, nodes: StringList<'buf>, strings: Option<&'buf [u8]>) -> Self {
+		Regions {
+			tags: fdt.tags(),
+			structure: fdt.blob.structure(),
+			nodes,
+			strings,
+			path: [0; MAX_PATH],
+			path_len: 0,
+			stack: [0; MAX_DEPTH],
+			depth: 0,
+			want: 0,
+			start: None,
+			error: None,
+		}
+	}
+
+	/// Enters node [name], returning whether it's in 'hashed-nodes'.
+	fn push(&mut self, name: &str) -> Result<bool, Error> {
+		if self.depth == MAX_DEPTH {
+			return Err(Error::BadStructure);
+		}
+		self.stack[self.depth] = self.want;
+		self.depth += 1;
+		let separator = usize::from(self.path_len != 1);
+		let len = self.path_len + separator + name.len();
+		if len > MAX_PATH {
+			return Err(Error::NoSpace);
+		}
+		if separator == 1 {
+			self.path[self.path_len] = b'/';
+		}
+		self.path[len - name.len()..len].copy_from_slice(name.as_bytes());
+		self.path_len = len;
+		let path = &self.path[..len];
+		Ok(self.nodes.strings().any(|node| node.as_bytes() == path))
+	}
+
+	fn pop(&mut self) -> Result<(), Error> {
+		self.depth = self.depth.checked_sub(1).ok_or(Error::BadStructure)?;
+		self.want = self.stack[self.depth];
+		self.path_len = self.path[..self.path_len].iter().rposition(|&b| b == b'/').unwrap_or(0);
+		Ok(())
+	}
+
+	/// Returns whether the tag at [offset] is signed and where a region
+	/// ending at it stops.
+	fn include(&mut self, tag: Tag, offset: usize, next: usize) -> Result<(bool, usize), Error> {
+		Ok(match tag {
+			Tag::Prop { name_off, .. } => {
+				let name = self.tags.string(name_off)?;
+				(self.want >= 2 && !UNSIGNED_PROPERTIES.contains(&name), offset)
+			},
+			Tag::Nop => (self.want >= 2, offset),
+			Tag::BeginNode { name, .. } => {
+				let mut stop = if self.want == 1 { offset } else { next };
+				if self.push(name)? {
+					self.want = 2;
+				} else if self.want > 0 {
+					self.want -= 1;
+				} else {
+					stop = offset;
+				}
+				(self.want > 0, stop)
+			},
+			Tag::EndNode => {
+				let include = self.want > 0;
+				self.pop()?;
+				(include, next)
+			},
+			Tag::End => (true, next),
+		})
+	}
+}
+
+impl<'buf> Iterator for Regions<'buf> {
+	type Item = &'buf [u8];
+
+	fn next(&mut self) -> Option<Self::Item> {
+		while self.error.is_none() {
+			let offset = self.tags.offset();
+			let result = match self.tags.next() {
+				Some(Ok(tag)) => self.include(tag, offset, self.tags.offset()).map(|include| (tag, include)),
+				Some(Err(error)) => Err(error),
+				None => return self.strings.take(),
+			};
+			let (tag, (include, stop)) = match result {
+				Ok(result) => result,
+				Err(error) => {
+					self.error = Some(error);
+					break;
+				},
+			};
+			match (include, self.start) {
+				(true, None) => self.start = Some(offset),
+				(false, Some(start)) => {
+					self.start = None;
+					return Some(&self.structure[start..stop]);
+				},
+				_ => (),
+			}
+			if tag == Tag::End {
+				let start = self.start.take().unwrap_or(offset);
+				return Some(&self.structure[start..self.tags.offset()]);
+			}
+		}
+		None
+	}
+}
+
+/// An iterator over the images of a FIT.
+#[derive(Clone, Debug)]
+pub struct Images<'buf> {
+	children: Option<Children<'buf>>,
+	fit: Source<'buf>,
+}
+
+impl<'buf> Iterator for Images<'buf> {
+	type Item = Image<'buf>;
+
+	fn next(&mut self) -> Option<Self::Item> {
+		self.children.as_mut()?.next().map(|node| Image { node, fit: self.fit })
+	}
+}
+
+/// An iterator over the configurations of a FIT.
+#[derive(Clone, Debug)]
+pub struct Configurations<'buf> {
+	children: Option<Children<'buf>>,
+	images: Option<Node<'buf>>,
+	fit: Source<'buf>,
+}
+
+impl<'buf> Iterator for Configurations<'buf> {
+	type Item = Configuration<'buf>;
+
+	fn next(&mut self) -> Option<Self::Item> {
+		let node = self.children.as_mut()?.next()?;
+		Some(Configuration { node, images: self.images.clone(), fit: self.fit })
+	}
+}
+
+/// An iterator over the images named by a property of a configuration.
+///
+/// Names of missing images are returned as [Error::NotFound].
+#[derive(Clone, Debug)]
+pub struct ImageList<'buf> {
+	names: Option<Strings<'buf>>,
+	images: Option<Node<'buf>>,
+	fit: Source<'buf>,
+}
+
+impl<'buf> Iterator for ImageList<'buf> {
+	type Item = Result<Image<'buf>, Error>;
+
+	fn next(&mut self) -> Option<Self::Item> {
+		let name = self.names.as_mut()?.next()?;
+		let node = self.images.as_ref().and_then(|images| images.children().find(|node| node.name() == name));
+		Some(node.map(|node| Image { node, fit: self.fit }).ok_or(Error::NotFound))
+	}
+}
+
+/// Returns the first string of property [name], or None if it's missing.
+fn string<'buf>(node: &Node<'buf>, name: &str) -> Result<Option<&'buf str>, Error> {
+	match node.property(name) {
+		Some(prop) => <&str>::from_raw_prefix(prop.raw()).map(|(string, _)| Some(string)),
+		None => Ok(None),
+	}
+}
+
+/// Reads an address of one or two cells, as U-Boot accepts either.
+fn address(node: &Node, name: &str) -> Result<Option<u64>, Error> {
+	match node.property(name).map(|prop| prop.raw()) {
+		Some(raw) if raw.len() == 4 => Ok(Some(BE::read_u32(raw).into())),
+		Some(raw) if raw.len() == 8 => Ok(Some(BE::read_u64(raw))),
+		Some(raw) => Err(Error::BadLength(raw.len())),
+		None => Ok(None),
+	}
+}
@@ -0,0 +1,432 @@
+//! Typed access to the GPIO bindings.
+//!
+//! Consumers refer to GPIO lines with phandle lists in properties named
+//! `<function>-gpios`, or `gpios` for unnamed lines, each entry being the
+//! phandle of a controller followed by `#gpio-cells` cells of the
+//! controller. The deprecated `<function>-gpio` and `gpio` spellings are
+//! accepted as well. A phandle of 0 leaves a hole in the list, as used by
+//! `cs-gpios` for chip selects driven by the controller itself.
+//!
+//! Controllers are the nodes with a `gpio-controller` property, see
+//! [GpioController] for their line names, `gpio-ranges` and hogs.
+//!
+//! # Examples
+//!
+//! ```
+//! use fdt::{FDT, NodeIterator};
+//! use fdt::gpio;
+//! let dtb = include_bytes!("../tests/dt.dtb");
+//!
+//! let fdt = FDT::from_bytes(dtb).unwrap();
+//! let spi = fdt.nodes().with_name("spi@7e204000").next().unwrap();
+//! let cs: Vec<_> = gpio::gpios(&spi, "cs").unwrap()
+//!     .map(|gpio| gpio.unwrap().unwrap())
+//!     .collect();
+//! assert_eq!(cs[0].controller().name(), "gpio@7e200000");
+//! assert_eq!(cs[0].line(), Some(8));
+//! assert!(cs[0].flags().active_low());
+//! assert_eq!(cs[1].line(), Some(7));
+//! ```
+
+use cells::Cells;
+use entries::{cells, PhandleArgs};
+use error::Error;
+use node::{Children, Node};
+use property::Property;
+use stringlist::Strings;
+
+/// The line is active when low, `GPIO_ACTIVE_LOW`.
+pub const GPIO_ACTIVE_LOW: u32 = 0x01;
+/// The line is only driven in one direction, `GPIO_SINGLE_ENDED`.
+pub const GPIO_SINGLE_ENDED: u32 = 0x02;
+/// A single ended line is driven high only, `GPIO_LINE_OPEN_SOURCE`.
+pub const GPIO_LINE_OPEN_SOURCE: u32 = 0x04;
+/// The line state may be lost in suspend, `GPIO_TRANSITORY`.
+pub const GPIO_TRANSITORY: u32 = 0x08;
+/// The line needs a pull-up, `GPIO_PULL_UP`.
+pub const GPIO_PULL_UP: u32 = 0x10;
+/// The line needs a pull-down, `GPIO_PULL_DOWN`.
+pub const GPIO_PULL_DOWN: u32 = 0x20;
+/// The line must not be pulled either way, `GPIO_PULL_DISABLE`.
+pub const GPIO_PULL_DISABLE: u32 = 0x40;
+
+/// The flags cell of a GPIO specifier, as defined by Linux's
+/// `dt-bindings/gpio/gpio.h`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct GpioFlags(pub u32);
+
+impl GpioFlags {
+	pub fn active_low(&self) -> bool {
+		self.0 & GPIO_ACTIVE_LOW != 0
+	}
+
+	/// Tests if the line is driven low only, `GPIO_OPEN_DRAIN`.
+	pub fn open_drain(&self) -> bool {
+		self.0 & (GPIO_SINGLE_ENDED | GPIO_LINE_OPEN_SOURCE) == GPIO_SINGLE_ENDED
+	}
+
+	/// Tests if the line is driven high only, `GPIO_OPEN_SOURCE`.
+	pub fn open_source(&self) -> bool {
+		self.0 & (GPIO_SINGLE_ENDED | GPIO_LINE_OPEN_SOURCE)
+			== GPIO_SINGLE_ENDED | GPIO_LINE_OPEN_SOURCE
+	}
+
+	pub fn transitory(&self) -> bool {
+		self.0 & GPIO_TRANSITORY != 0
+	}
+
+	pub fn pull_up(&self) -> bool {
+		self.0 & GPIO_PULL_UP != 0
+	}
+
+	pub fn pull_down(&self) -> bool {
+		self.0 & GPIO_PULL_DOWN != 0
+	}
+
+	pub fn pull_disable(&self) -> bool {
+		self.0 & GPIO_PULL_DISABLE != 0
+	}
+}
+
+/// A GPIO line: a controller and the specifier cells selecting the line.
+#[derive(Clone, Debug)]
+pub struct Gpio<'buf> {
+	controller: Node<'buf>,
+	args: Cells<'buf>,
+}
+
+impl<'buf> Gpio<'buf> {
+	pub fn controller(&self) -> &Node<'buf> {
+		&self.controller
+	}
+
+	/// Returns the `#gpio-cells` cells of the specifier.
+	pub fn args(&self) -> Cells<'buf> {
+		self.args.clone()
+	}
+
+	/// Returns the line offset within the controller, by convention the
+	/// first cell of the specifier.
+	pub fn line(&self) -> Option<u32> {
+		self.args().next()
+	}
+
+	/// Returns the flags of the line, by convention the last cell of
+	/// specifiers with two or more cells. Specifiers with a single cell have
+	/// no flags.
+	pub fn flags(&self) -> GpioFlags {
+		match self.args.len() {
+			0 | 1 => GpioFlags::default(),
+			_ => GpioFlags(self.args().next_back().unwrap_or(0)),
+		}
+	}
+
+	/// Returns the name of the line from the `gpio-line-names` of its
+	/// controller.
+	pub fn name(&self) -> Option<&'buf str> {
+		GpioController { node: self.controller.clone() }.line_name(self.line()?)
+	}
+}
+
+/// Returns the GPIO lines of [node] for [function], from the
+/// `<function>-gpios` property, or the `gpios` property if [function] is
+/// empty.
+///
+/// # Errors
+///
+/// Returns [Error::NotFound] if [node] has neither the property nor its
+/// deprecated `-gpio` spelling and [Error::BadLength] if the property isn't
+/// a list of cells.
+///
+/// # Examples
+///
+/// ```
+/// use fdt::{FDT, NodeIterator};
+/// use fdt::gpio;
+/// let dtb = include_bytes!("../tests/dt.dtb");
+///
+/// let fdt = FDT::from_bytes(dtb).unwrap();
+/// let hdmi = fdt.nodes().with_name("hdmi@7e902000").next().unwrap();
+/// let hpd = gpio::gpios(&hdmi, "hpd").unwrap().next().unwrap().unwrap();
+/// assert_eq!(hpd.unwrap().line(), Some(46));
+/// ```
+pub fn gpios<'buf>(node: &Node<'buf>, function: &str) -> Result<Gpios<'buf>, Error> {
+	let matches = |prop: &Property, suffix: &str| match function {
+		"" => prop.name() == &suffix[1..],
+		_ => prop.name().strip_suffix(suffix) == Some(function),
+	};
+	let prop = node.properties().find(|prop| matches(prop, "-gpios"))
+		.or_else(|| node.properties().find(|prop| matches(prop, "-gpio")))
+		.ok_or(Error::NotFound)?;
+	let raw = Cells::new(prop.raw())?.raw();
+	Ok(Gpios { list: PhandleArgs::new(node, raw, "#gpio-cells") })
+}
+
+/// An iterator over the entries of a GPIO phandle list, see [gpios()].
+///
+/// Holes in the list are returned as `None`. Iteration stops after the
+/// first error.
+#[derive(Clone, Debug)]
+pub struct Gpios<'buf> {
+	list: PhandleArgs<'buf>,
+}
+
+impl<'buf> Iterator for Gpios<'buf> {
+	type Item = Result<Option<Gpio<'buf>>, Error>;
+
+	fn next(&mut self) -> Option<Self::Item> {
+		let entry = self.list.next()?;
+		Some(entry.map(|entry| entry.map(|(controller, args)| Gpio { controller, args })))
+	}
+}
+
+/// A GPIO controller, a node with the `gpio-controller` property.
+#[derive(Clone, Debug)]
+pub struct GpioController<'buf> {
+	node: Node<'buf>,
+}
+
+impl<'buf> GpioController<'buf> {
+	/// Returns the controller of [node], or None if it isn't a GPIO
+	/// controller.
+	pub fn new(node: Node<'buf>) -> Option<Self> {
+		node.property("gpio-controller").map(|_| GpioController { node })
+	}
+
+	pub fn node(&self) -> &Node<'buf> {
+		&self.node
+	}
+
+	/// Returns the number of cells of the specifiers of the controller.
+	///
+	/// # Errors
+	///
+	/// Returns [Error::BadNCells] if `#gpio-cells` is missing or malformed.
+	pub fn gpio_cells(&self) -> Result<u32, Error> {
+		cells(&self.node, "#gpio-cells").ok_or(Error::BadNCells)
+	}
+
+	/// Returns the number of lines of the controller from `ngpios`.
+	pub fn ngpios(&self) -> Option<u32> {
+		cells(&self.node, "ngpios")
+	}
+
+	/// Returns the names of the lines from `gpio-line-names`, an empty
+	/// string for lines without a name.
+	pub fn line_names(&self) -> Strings<'buf> {
+		self.node.property("gpio-line-names")
+			.map(|prop| prop.as_stringlist())
+			.unwrap_or_default()
+			.strings()
+	}
+
+	/// Returns the name of line [line], or None if the line has no name.
+	pub fn line_name(&self, line: u32) -> Option<&'buf str> {
+		self.line_names().nth(line as usize).filter(|name| !name.is_empty())
+	}
+
+	/// Returns the line named [name].
+	pub fn find_line(&self, name: &str) -> Option<u32> {
+		self.line_names().position(|line| line == name).map(|line| line as u32)
+	}
+
+	/// Returns the ranges of lines mapped to pins of pin controllers by
+	/// `gpio-ranges`.
+	///
+	/// # Errors
+	///
+	/// Returns [Error::BadLength] if `gpio-ranges` isn't a list of cells.
+	pub fn ranges(&self) -> Result<GpioRanges<'buf>, Error> {
+		let raw = match self.node.property("gpio-ranges") {
+			Some(prop) => Cells::new(prop.raw())?.raw(),
+			None => &[],
+		};
+		let groups = self.node.property("gpio-ranges-group-names")
+			.map(|prop| prop.as_stringlist())
+			.unwrap_or_default()
+			.strings();
+		Ok(GpioRanges { node: self.node.clone(), raw, groups })
+	}
+
+	/// Returns the pin controller and pin of line [line], from the first
+	/// numeric range of `gpio-ranges` containing it.
+	pub fn pin(&self, line: u32) -> Result<Option<(Node<'buf>, u32)>, Error> {
+		for range in self.ranges()? {
+			let range = range?;
+			if let Some(pin) = range.pin(line) {
+				return Ok(Some((range.pin_controller, pin)));
+			}
+		}
+		Ok(None)
+	}
+
+	/// Returns the hogs of the controller, its children with the `gpio-hog`
+	/// property.
+	pub fn hogs(&self) -> Hogs<'buf> {
+		Hogs { controller: self.node.clone(), children: self.node.children() }
+	}
+}
+
+/// A range of `gpio-ranges`: [count] lines from [gpio_offset] mapped to the
+/// pins from [pin_offset] of [pin_controller].
+///
+/// Ranges with a [count] of 0 name a pin group of the pin controller
+/// instead, from `gpio-ranges-group-names`.
+#[derive(Clone, Debug)]
+pub struct GpioRange<'buf> {
+	pub pin_controller: Node<'buf>,
+	pub gpio_offset: u32,
+	pub pin_offset: u32,
+	pub count: u32,
+	pub group: Option<&'buf str>,
+}
+
+impl<'buf> GpioRange<'buf> {
+	/// Returns the pin of line [line], or None if the range doesn't contain
+	/// the line or its pin doesn't fit a u32.
+	pub fn pin(&self, line: u32) -> Option<u32> {
+		line.checked_sub(self.gpio_offset)
+			.filter(|&index| index < self.count)
+			.and_then(|index| self.pin_offset.checked_add(index))
+	}
+}
+
+/// An iterator over the ranges of `gpio-ranges`, see
+/// [GpioController::ranges()].
+///
+/// Iteration stops after the first error.
+#[derive(Clone, Debug)]
+pub struct GpioRanges<'buf> {
+	node: Node<'buf>,
+	raw: &'buf [u8],
+	groups: Strings<'buf>,
+}
+
+impl<'buf> Iterator for GpioRanges<'buf> {
+	type Item = Result<GpioRange<'buf>, Error>;
+
+	fn next(&mut self) -> Option<Self::Item> {
+		if self.raw.is_empty() {
+			return None;
+		}
+		if self.raw.len() < 16 {
+			self.raw = &[];
+			return Some(Err(Error::Truncated));
+		}
+		let mut entry = Cells::new(&self.raw[..16]).ok()?;
+		self.raw = &self.raw[16..];
+		let group = self.groups.next().filter(|group| !group.is_empty());
+		let pin_controller = match entry.next().and_then(|phandle| self.node.lookup_phandle(phandle)) {
+			Some(node) => node,
+			None => {
+				self.raw = &[];
+				return Some(Err(Error::BadPhandle));
+			},
+		};
+		let gpio_offset = entry.next()?;
+		let pin_offset = entry.next()?;
+		let count = entry.next()?;
+		let group = if count == 0 { group } else { None };
+		Some(Ok(GpioRange { pin_controller, gpio_offset, pin_offset, count, group }))
+	}
+}
+
+/// The state a hog puts its lines in.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum HogState {
+	Input,
+	OutputLow,
+	OutputHigh,
+}
+
+/// A hog: lines of a controller set up by the controller itself, without a
+/// consumer.
+#[derive(Clone, Debug)]
+pub struct GpioHog<'buf> {
+	controller: Node<'buf>,
+	node: Node<'buf>,
+}
+
+impl<'buf> GpioHog<'buf> {
+	pub fn node(&self) -> &Node<'buf> {
+		&self.node
+	}
+
+	/// Returns the hogged lines, the specifiers of the `gpios` property
+	/// without phandles.
+	///
+	/// # Errors
+	///
+	/// Returns [Error::BadNCells] if `#gpio-cells` of the controller is
+	/// missing or malformed and [Error::BadLength] if `gpios` isn't a
+	/// multiple of it.
+	pub fn lines(&self) -> Result<HogLines<'buf>, Error> {
+		let count = GpioController { node: self.controller.clone() }.gpio_cells()? as usize;
+		let raw = self.node.property("gpios").ok_or(Error::NotFound)?.raw();
+		if count == 0 || !raw.len().is_multiple_of(count * 4) {
+			return Err(Error::BadLength(raw.len()));
+		}
+		Ok(HogLines { controller: self.controller.clone(), raw, len: count * 4 })
+	}
+
+	/// Returns the state of the lines from the `input`, `output-low` and
+	/// `output-high` properties.
+	///
+	/// # Errors
+	///
+	/// Returns [Error::BadValue] if the hog has none or several of them.
+	pub fn state(&self) -> Result<HogState, Error> {
+		let states = [
+			("input", HogState::Input),
+			("output-low", HogState::OutputLow),
+			("output-high", HogState::OutputHigh),
+		];
+		let mut found = states.iter().filter(|state| self.node.property(state.0).is_some());
+		match (found.next(), found.next()) {
+			(Some(state), None) => Ok(state.1),
+			_ => Err(Error::BadValue),
+		}
+	}
+
+	/// Returns the `line-name` of the hog.
+	pub fn line_name(&self) -> Option<&'buf str> {
+		self.node.property("line-name").and_then(|prop| prop.as_stringlist().strings().next())
+	}
+}
+
+/// An iterator over the hogs of a controller, see [GpioController::hogs()].
+#[derive(Clone, Debug)]
+pub struct Hogs<'buf> {
+	controller: Node<'buf>,
+	children: Children<'buf>,
+}
+
+impl<'buf> Iterator for Hogs<'buf> {
+	type Item = GpioHog<'buf>;
+
+	fn next(&mut self) -> Option<Self::Item> {
+		let node = self.children.find(|child| child.property("gpio-hog").is_some())?;
+		Some(GpioHog { controller: self.controller.clone(), node })
+	}
+}
+
+/// An iterator over the lines of a hog, see [GpioHog::lines()].
+#[derive(Clone, Debug)]
+pub struct HogLines<'buf> {
+	controller: Node<'buf>,
+	raw: &'buf [u8],
+	len: usize,
+}
+
+impl<'buf> Iterator for HogLines<'buf> {
+	type Item = Gpio<'buf>;
+
+	fn next(&mut self) -> Option<Self::Item> {
+		if self.raw.is_empty() {
+			return None;
+		}
+		let (args, rest) = self.raw.split_at(self.len);
+		self.raw = rest;
+		Some(Gpio { controller: self.controller.clone(), args: Cells::new(args).ok()? })
+	}
+}
@@ -3,16 +3,30 @@ use blob::MAX_COMPAT_VERSION;
 
 use byteorder::{ByteOrder, BE};
 
+use core::fmt;
+
 pub const HEADER_V1_SIZE: usize = 28;
+pub const HEADER_V2_SIZE: usize = 32;
+pub const HEADER_V3_SIZE: usize = 36;
+pub const HEADER_V17_SIZE: usize = 40;
 pub const MAGIC: u32 = 0xd00dfeed;
 
+fn header_size(version: u32) -> usize {
+	match version {
+		0..=1 => HEADER_V1_SIZE,
+		2 => HEADER_V2_SIZE,
+		3..=16 => HEADER_V3_SIZE,
+		_ => HEADER_V17_SIZE,
+	}
+}
+
 pub struct Header<'blob> {
 	raw: &'blob [u8]
 }
 
 impl<'blob> Header<'blob> {
 	pub fn new(raw: &'blob [u8]) -> Header<'blob> {
-		Header {raw: raw}
+		Header { raw }
 	}
 
 	pub fn magic(&self) -> Magic {
@@ -59,9 +73,22 @@ impl<'blob> Header<'blob> {
 		}
 	}
 	
+	/// Tests if the blob uses the conventions of versions before 16: node
+	/// names are full paths and property values of 8 bytes or more are
+	/// aligned to 8 bytes within the structure block.
+	pub fn legacy(&self) -> bool {
+		self.version().val < 16
+	}
+
+	/// Returns the size of the header for its version, like libfdt's
+	/// `fdt_header_size`.
+	pub fn size(&self) -> usize {
+		header_size(self.version().val)
+	}
+
 	pub fn size_dt_struct(&self) -> Option<u32> {
 		if self.version().val >= 17 {
-		 	Some(BE::read_u32(&self.raw[28..]))
+		 	Some(BE::read_u32(&self.raw[36..]))
 		} else {
 			None
 		}
@@ -90,6 +117,10 @@ pub struct Magic {
 }
 
 impl Magic {
+	pub fn number(&self) -> u32 {
+		self.val
+	}
+	
 	pub fn valid(&self) -> Result<(), ()> { // Error> {
 		match self.val {
 			MAGIC => Ok(()),
@@ -103,10 +134,161 @@ pub struct Version {
 }
 
 impl Version {
+	pub fn number(&self) -> u32 {
+		self.val
+	}
+	
 	pub fn compatible(&self) -> Result<(), ()> { // Error> {
 		match self.val {
-			MIN_COMPAT_VERSION ... MAX_COMPAT_VERSION => Ok(()),
+			MIN_COMPAT_VERSION ..= MAX_COMPAT_VERSION => Ok(()),
 			other => panic!("Incompatible fdt version {}", other), //Error::BadVersion(other),
 		}
 	}
 }
+
+/// The fields of a device tree header, as read from the blob.
+///
+/// The fields newer than the version of the blob are None. See
+/// [FDT::header](::FDT::header).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct FdtHeader {
+	pub magic: u32,
+	pub totalsize: u32,
+	pub off_dt_struct: u32,
+	pub off_dt_strings: u32,
+	pub off_mem_rsvmap: u32,
+	pub version: u32,
+	pub last_comp_version: u32,
+	/// Since version 2
+	pub boot_cpuid_phys: Option<u32>,
+	/// Since version 3
+	pub size_dt_strings: Option<u32>,
+	/// Since version 17
+	pub size_dt_struct: Option<u32>,
+}
+
+impl FdtHeader {
+	pub(crate) fn read(header: &Header) -> Self {
+		FdtHeader {
+			magic: header.magic().number(),
+			totalsize: header.totalsize(),
+			off_dt_struct: header.off_dt_struct(),
+			off_dt_strings: header.off_dt_strings(),
+			off_mem_rsvmap: header.off_mem_rsvmap(),
+			version: header.version().number(),
+			last_comp_version: header.last_comp_version(),
+			boot_cpuid_phys: header.boot_cpuid_phys(),
+			size_dt_strings: header.size_dt_strings(),
+			size_dt_struct: header.size_dt_struct(),
+		}
+	}
+
+	/// Returns the size of the header itself, which depends on the version.
+	pub fn header_size(&self) -> usize {
+		header_size(self.version)
+	}
+}
+
+/// Prints the header like fdtdump does, one commented line per field.
+impl fmt::Display for FdtHeader {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		writeln!(f, "// magic:\t\t{:#x}", self.magic)?;
+		writeln!(f, "// totalsize:\t\t{:#x} ({})", self.totalsize, self.totalsize)?;
+		writeln!(f, "// off_dt_struct:\t{:#x}", self.off_dt_struct)?;
+		writeln!(f, "// off_dt_strings:\t{:#x}", self.off_dt_strings)?;
+		writeln!(f, "// off_mem_rsvmap:\t{:#x}", self.off_mem_rsvmap)?;
+		writeln!(f, "// version:\t\t{}", self.version)?;
+		writeln!(f, "// last_comp_version:\t{}", self.last_comp_version)?;
+		if let Some(cpuid) = self.boot_cpuid_phys {
+			writeln!(f, "// boot_cpuid_phys:\t{:#x}", cpuid)?;
+		}
+		if let Some(size) = self.size_dt_strings {
+			writeln!(f, "// size_dt_strings:\t{:#x}", size)?;
+		}
+		if let Some(size) = self.size_dt_struct {
+			writeln!(f, "// size_dt_struct:\t{:#x}", size)?;
+		}
+		Ok(())
+	}
+}
+
+/// The blocks of a device tree blob.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum BlockKind {
+	Header,
+	MemoryReserveMap,
+	Structure,
+	Strings,
+}
+
+/// The position of a block within the blob.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Block {
+	pub kind: BlockKind,
+	pub offset: usize,
+	pub size: usize,
+}
+
+impl Block {
+	/// Returns the offset just past the block.
+	pub fn end(&self) -> usize {
+		self.offset.saturating_add(self.size)
+	}
+}
+
+/// Where the blocks of a blob are, see [FDT::layout](::FDT::layout).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Layout {
+	blocks: [Block; 4],
+	totalsize: usize,
+}
+
+impl Layout {
+	pub(crate) fn new(mut blocks: [Block; 4], totalsize: usize) -> Self {
+		blocks.sort_unstable_by_key(|block| (block.offset, block.kind));
+		Layout { blocks, totalsize }
+	}
+
+	/// Returns the blocks in the order they appear in the blob.
+	pub fn blocks(&self) -> &[Block] {
+		&self.blocks
+	}
+
+	/// Returns the block of [kind].
+	pub fn block(&self, kind: BlockKind) -> Block {
+		self.blocks.iter().find(|block| block.kind == kind).copied().unwrap_or(Block { kind, offset: 0, size: 0 })
+	}
+
+	/// Returns the unused ranges between blocks as offsets and sizes, not
+	/// including the free space after the last block.
+	pub fn gaps(&self) -> impl Iterator<Item=(usize, usize)> + '_ {
+		self.blocks.iter().scan(0, |end, block| {
+			let gap = (*end, block.offset.saturating_sub(*end));
+			*end = block.end().max(*end);
+			Some(gap)
+		}).filter(|&(_, size)| size > 0)
+	}
+
+	/// Returns the number of unused bytes between the end of the last block
+	/// and the end of the blob.
+	pub fn free_space(&self) -> usize {
+		let end = self.blocks.iter().map(Block::end).max().unwrap_or(0);
+		self.totalsize.saturating_sub(end)
+	}
+
+	/// Tests if any blocks share bytes.
+	pub fn overlaps(&self) -> bool {
+		self.blocks.windows(2).any(|pair| pair[1].offset < pair[0].end())
+	}
+
+	/// Tests if the blocks follow each other in the order of [BlockKind]
+	/// within the blob.
+	///
+	/// libfdt only edits blobs in this order in place, others are refused
+	/// with `FDT_ERR_BADLAYOUT` until they are rebuilt by `fdt_open_into`.
+	pub fn in_order(&self) -> bool {
+		let order = [BlockKind::Header, BlockKind::MemoryReserveMap, BlockKind::Structure, BlockKind::Strings];
+		self.blocks.iter().map(|block| block.kind).eq(order.iter().cloned())
+			&& !self.overlaps() && self.blocks[3].end() <= self.totalsize
+	}
+}
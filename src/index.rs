@@ -0,0 +1,363 @@
+//! A pre-built index of the nodes of a tree.
+//!
+//! Lookups on [FDT] and [Node] rescan the structure block from its start.
+//! An [FdtIndex] scans it once, recording for every node its offset, depth,
+//! parent and the end of its subtree, so navigating the tree afterwards
+//! doesn't parse any tokens: a node is located by a binary search on its
+//! offset, after which its parent, siblings and the node following its
+//! subtree are found directly. Nodes are additionally ordered by a hash of
+//! their path and by phandle, making lookups of full paths and phandles
+//! O(log n).
+//!
+//! The index is stored in a caller provided slice of [IndexEntry], one per
+//! node, or in a [Vec] with the 'alloc' feature.
+//!
+//! # Examples
+//!
+//! ```
+//! use fdt::FDT;
+//! use fdt::index::{FdtIndex, IndexEntry};
+//! let dtb = include_bytes!("../tests/dt.dtb").as_ptr();
+//!
+//! let fdt;
+//! unsafe { fdt = FDT::from_raw(dtb).unwrap(); }
+//!
+//! let mut storage = [IndexEntry::default(); 256];
+//! let index = FdtIndex::in_buffer(&fdt, &mut storage).unwrap();
+//!
+//! let uart = index.path("/soc/serial@7e201000").unwrap();
+//! assert_eq!(index.parent(&uart).unwrap().name(), "soc");
+//! assert_eq!(index.phandle(67).unwrap().name(), "cpus");
+//! ```
+
+#[cfg(feature = "alloc")]
+use alloc::vec::Vec;
+
+use blob::StructReader;
+use error::Error;
+use node::{has_name, Node, Subnodes};
+use FDT;
+
+const NONE: u32 = u32::MAX;
+
+/// The index entry of one node, see [FdtIndex].
+///
+/// Entries are only meaningful to the index which filled them in, the
+/// default value is used to allocate storage.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct IndexEntry {
+	/// Offset of the node's FDT_BEGIN_NODE tag in the structure block.
+	offset: u32,
+	depth: u32,
+	/// Index of the parent entry, NONE for the root.
+	parent: u32,
+	/// Index one past the last descendant.
+	end: u32,
+	/// Phandle of the node, 0 if it has none.
+	phandle: u32,
+	/// FNV-1a hash of the full path.
+	hash: u32,
+	/// Indices of all entries in order of path hash and phandle
+	/// respectively; the n:th entry holds the n:th element.
+	by_hash: u32,
+	by_phandle: u32,
+}
+
+/// An index of the nodes of a tree, see the [module documentation](self).
+pub struct FdtIndex<'buf, S> {
+	blob: StructReader<'buf>,
+	entries: S,
+}
+
+impl<'buf, 'i> FdtIndex<'buf, &'i [IndexEntry]> {
+	/// Builds the index of [fdt] in [storage].
+	///
+	/// # Errors
+	///
+	/// Returns [Error::NoSpace] if [storage] has fewer entries than the tree
+	/// has nodes, see [node_count()].
+	pub fn in_buffer(fdt: &FDT<'buf>, storage: &'i mut [IndexEntry]) -> Result<Self, Error> {
+		let len = fill(fdt, storage)?;
+		let entries = &storage[..len];
+		Ok(FdtIndex { blob: fdt.blob.nodes(), entries })
+	}
+}
+
+#[cfg(feature = "alloc")]
+impl<'buf> FdtIndex<'buf, Vec<IndexEntry>> {
+	/// Builds the index of [fdt] in a [Vec].
+	pub fn new(fdt: &FDT<'buf>) -> Self {
+		let mut entries = alloc::vec![IndexEntry::default(); node_count(fdt)];
+		let len = fill(fdt, &mut entries).unwrap_or(0);
+		entries.truncate(len);
+		FdtIndex { blob: fdt.blob.nodes(), entries }
+	}
+}
+
+impl<'buf, S: AsRef<[IndexEntry]>> FdtIndex<'buf, S> {
+	/// Returns the number of indexed nodes.
+	pub fn len(&self) -> usize {
+		self.entries.as_ref().len()
+	}
+
+	/// Returns true if the tree has no nodes.
+	pub fn is_empty(&self) -> bool {
+		self.len() == 0
+	}
+
+	/// Returns the root node.
+	pub fn root(&self) -> Option<Node<'buf>> {
+		self.node(0)
+	}
+
+	/// Returns the node at [path].
+	///
+	/// Full paths are found by binary search. Paths leaving out unit
+	/// addresses, which are matched like [Node::has_name] does, fall back to
+	/// searching the children of each path component in turn.
+	pub fn path(&self, path: &str) -> Option<Node<'buf>> {
+		let path = path.trim_end_matches('/');
+		if !path.starts_with('/') && !path.is_empty() {
+			return None;
+		}
+		let entries = self.entries.as_ref();
+		let hash = fnv(FNV_BASIS, path.as_bytes());
+		let first = lower_bound(entries, |entry| entries[entry.by_hash as usize].hash, hash);
+		let exact = entries[first..].iter()
+			.map(|entry| entry.by_hash as usize)
+			.take_while(|&i| entries[i].hash == hash)
+			.find(|&i| self.has_path(i, path));
+		if let Some(i) = exact {
+			return self.node(i);
+		}
+		let mut current = 0;
+		for name in path.split('/').skip(1) {
+			current = self.child_indices(current).find(|&i| has_name(self.name(i), name))?;
+		}
+		self.node(current)
+	}
+
+	/// Returns the node with [phandle], found by binary search.
+	pub fn phandle(&self, phandle: u32) -> Option<Node<'buf>> {
+		let entries = self.entries.as_ref();
+		let first = lower_bound(entries, |entry| entries[entry.by_phandle as usize].phandle, phandle);
+		entries.get(first)
+			.map(|entry| entry.by_phandle as usize)
+			.filter(|&i| phandle != 0 && phandle != u32::MAX && entries[i].phandle == phandle)
+			.and_then(|i| self.node(i))
+	}
+
+	/// Returns the path [alias] refers to, like [FDT::alias].
+	pub fn alias(&self, alias: &str) -> Option<&'buf str> {
+		self.path("/aliases")
+			.and_then(|aliases| aliases.property(alias))
+			.and_then(|prop| prop.parse::<&str>().ok())
+			.and_then(|string| string.split('\0').next())
+	}
+
+	/// Returns the parent of [node], or None for the root.
+	pub fn parent(&self, node: &Node) -> Option<Node<'buf>> {
+		let entry = self.entry(node)?;
+		self.node(entry.parent as usize)
+	}
+
+	/// Returns the first child of [node].
+	pub fn first_child(&self, node: &Node) -> Option<Node<'buf>> {
+		let i = self.index_of(node)?;
+		self.child_indices(i).next().and_then(|child| self.node(child))
+	}
+
+	/// Returns the sibling following [node].
+	pub fn next_sibling(&self, node: &Node) -> Option<Node<'buf>> {
+		let entry = self.entry(node)?;
+		let next = self.entries.as_ref().get(entry.end as usize)?;
+		if next.parent == entry.parent {
+			self.node(entry.end as usize)
+		} else {
+			None
+		}
+	}
+
+	/// Returns the first node after the subtree of [node] in depth first
+	/// order, skipping all of its descendants.
+	pub fn skip_subtree(&self, node: &Node) -> Option<Node<'buf>> {
+		let entry = self.entry(node)?;
+		self.node(entry.end as usize)
+	}
+
+	/// Returns the number of descendants of [node].
+	pub fn descendant_count(&self, node: &Node) -> usize {
+		self.index_of(node).map_or(0, |i| self.entries.as_ref()[i].end as usize - i - 1)
+	}
+
+	/// Returns the nodes named [name] as [Node::has_name] matches them, in
+	/// depth first order.
+	///
+	/// Only node names are read, properties aren't parsed.
+	pub fn with_name<'a>(&'a self, name: &'a str) -> impl Iterator<Item=Node<'buf>> + 'a {
+		(0..self.len())
+			.filter(move |&i| has_name(self.name(i), name))
+			.filter_map(move |i| self.node(i))
+	}
+
+	/// Returns the index of [node], found by binary search on its offset.
+	fn index_of(&self, node: &Node) -> Option<usize> {
+		let entries = self.entries.as_ref();
+		let offset = node.offset() as u32;
+		entries.binary_search_by_key(&offset, |entry| entry.offset).ok()
+	}
+
+	fn entry(&self, node: &Node) -> Option<&IndexEntry> {
+		self.index_of(node).map(|i| &self.entries.as_ref()[i])
+	}
+
+	fn node(&self, i: usize) -> Option<Node<'buf>> {
+		let entry = self.entries.as_ref().get(i)?;
+		let mut blob = self.blob.rewind();
+		blob.skip(entry.offset as usize);
+		Subnodes::new(blob, entry.depth as usize).next()
+	}
+
+	fn name(&self, i: usize) -> &'buf str {
+		let mut blob = self.blob.rewind();
+		blob.skip(self.entries.as_ref()[i].offset as usize + 4);
+		blob.node_name().unwrap_or("")
+	}
+
+	fn child_indices(&self, parent: usize) -> impl Iterator<Item=usize> + '_ {
+		let entries = self.entries.as_ref();
+		let end = entries.get(parent).map_or(0, |entry| entry.end as usize);
+		let mut next = parent + 1;
+		core::iter::from_fn(move || {
+			if next < end {
+				let child = next;
+				next = entries[child].end as usize;
+				Some(child)
+			} else {
+				None
+			}
+		})
+	}
+
+	/// Tests if entry [i] has [path], comparing names from the leaf up.
+	fn has_path(&self, mut i: usize, path: &str) -> bool {
+		let entries = self.entries.as_ref();
+		let mut rest = path;
+		while entries[i].parent != NONE {
+			match rest.rfind('/') {
+				Some(pos) if &rest[pos + 1..] == self.name(i) => rest = &rest[..pos],
+				_ => return false,
+			}
+			i = entries[i].parent as usize;
+		}
+		rest.is_empty()
+	}
+}
+
+/// Returns the number of nodes of [fdt], which is the number of entries
+/// needed to index it.
+pub fn node_count(fdt: &FDT) -> usize {
+	fdt.nodes().count()
+}
+
+/// Fills [storage] with the entries of the nodes of [fdt], returning the
+/// number of nodes.
+fn fill(fdt: &FDT, storage: &mut [IndexEntry]) -> Result<usize, Error> {
+	let mut len = 0;
+	let mut last = NONE;
+	for node in fdt.nodes() {
+		let depth = node.depth() as u32;
+		// close the subtrees of the previous node and its ancestors up to
+		// the parent of this node
+		let mut parent = last;
+		while parent != NONE && storage[parent as usize].depth >= depth {
+			storage[parent as usize].end = len as u32;
+			parent = storage[parent as usize].parent;
+		}
+		let hash = match parent {
+			NONE => FNV_BASIS,
+			parent => fnv(fnv(storage[parent as usize].hash, b"/"), node.name().as_bytes()),
+		};
+		let entry = storage.get_mut(len).ok_or(Error::NoSpace)?;
+		*entry = IndexEntry {
+			offset: node.offset() as u32,
+			depth,
+			parent,
+			end: NONE,
+			phandle: node.phandle().unwrap_or(0),
+			hash,
+			by_hash: len as u32,
+			by_phandle: len as u32,
+		};
+		last = len as u32;
+		len += 1;
+	}
+	while last != NONE {
+		storage[last as usize].end = len as u32;
+		last = storage[last as usize].parent;
+	}
+	let entries = &mut storage[..len];
+	sort_column(entries, |entry| &mut entry.by_hash, |entry| entry.hash);
+	sort_column(entries, |entry| &mut entry.by_phandle, |entry| entry.phandle);
+	Ok(len)
+}
+
+/// Heap sorts the permutation of entry indices held in [column] by the
+/// [key] of the entries they refer to, without any extra storage.
+fn sort_column<C, K>(entries: &mut [IndexEntry], column: C, key: K)
+		where C: Fn(&mut IndexEntry) -> &mut u32, K: Fn(&IndexEntry) -> u32 {
+	let key_at = |entries: &mut [IndexEntry], pos: usize| {
+		let i = *column(&mut entries[pos]) as usize;
+		key(&entries[i])
+	};
+	let swap = |entries: &mut [IndexEntry], a: usize, b: usize| {
+		let (va, vb) = (*column(&mut entries[a]), *column(&mut entries[b]));
+		*column(&mut entries[a]) = vb;
+		*column(&mut entries[b]) = va;
+	};
+	let sift_down = |entries: &mut [IndexEntry], mut root: usize, end: usize| {
+		loop {
+			let mut child = 2 * root + 1;
+			if child >= end {
+				break;
+			}
+			if child + 1 < end && key_at(entries, child + 1) > key_at(entries, child) {
+				child += 1;
+			}
+			if key_at(entries, root) >= key_at(entries, child) {
+				break;
+			}
+			swap(entries, root, child);
+			root = child;
+		}
+	};
+	let len = entries.len();
+	for root in (0..len / 2).rev() {
+		sift_down(entries, root, len);
+	}
+	for end in (1..len).rev() {
+		swap(entries, 0, end);
+		sift_down(entries, 0, end);
+	}
+}
+
+/// Returns the first position in [entries] whose [key] isn't less than
+/// [value].
+fn lower_bound<K: Fn(&IndexEntry) -> u32>(entries: &[IndexEntry], key: K, value: u32) -> usize {
+	let (mut low, mut high) = (0, entries.len());
+	while low < high {
+		let mid = low + (high - low) / 2;
+		if key(&entries[mid]) < value {
+			low = mid + 1;
+		} else {
+			high = mid;
+		}
+	}
+	low
+}
+
+const FNV_BASIS: u32 = 0x811c_9dc5;
+
+fn fnv(hash: u32, bytes: &[u8]) -> u32 {
+	bytes.iter().fold(hash, |hash, &b| (hash ^ u32::from(b)).wrapping_mul(0x0100_0193))
+}
@@ -2,8 +2,38 @@
 
 extern crate byteorder;
 extern crate memchr;
+#[cfg(feature = "derive")]
+extern crate fdt_derive;
+#[cfg(feature = "alloc")]
+extern crate alloc;
+#[cfg(feature = "serde")]
+extern crate serde;
 
-//pub mod error;
+pub mod error;
+pub mod binding;
+pub mod export;
+pub mod schema;
+pub mod checks;
+pub mod diff;
+pub mod dts;
+pub mod index;
+pub mod tag;
+pub mod container;
+pub mod digest;
+pub mod fit;
+pub mod signature;
+pub mod gpio;
+pub mod clock;
+#[cfg(feature = "alloc")]
+pub mod mkimage;
+#[cfg(feature = "alloc")]
+pub mod owned;
+#[cfg(feature = "alloc")]
+pub mod overlay;
+#[cfg(feature = "serde")]
+pub mod de;
+#[cfg(feature = "serde")]
+mod ser;
 pub use node::NodeIterator;
 pub use property::PropertyIterator;
 
@@ -13,13 +43,23 @@ mod blob;
 mod property;
 mod node;
 mod stringlist;
+mod cells;
+mod entries;
+mod relocate;
 
 pub use property::{PropertyValue, IsValue};
+pub use stringlist::StringList;
+pub use cells::Cells;
+pub use node::Node;
+pub use memory_reserve_map::MemoryReserveMapEntry;
+pub use header::{FdtHeader, Layout, Block, BlockKind};
+pub use diff::diff;
+#[cfg(feature = "derive")]
+pub use fdt_derive::FromNode;
 use memory_reserve_map::MemoryReserveMap;
-use property::Property;
 
 use blob::Blob;
-use node::{Node, Subnodes};
+use node::Subnodes;
 
 /// An interface for parsing flat device trees from an in memory buffer.
 ///
@@ -69,10 +109,100 @@ impl<'buf> FDT<'buf> {
 	///     let fdt = FDT::from_raw(ptr);
 	/// }
 	/// ```
+	#[allow(clippy::result_unit_err)]
 	pub unsafe fn from_raw(ptr: *const u8) -> Result<FDT<'buf>, ()> { // FDTError> {
 		Ok(FDT { blob: Blob::from_raw(ptr)? })
 	}
 
+	/// Creates a new FDT from the start of [bytes].
+	///
+	/// Unlike [from_raw()](FDT::from_raw) this is safe: the header is checked
+	/// against the length of [bytes] and bad headers are reported as errors.
+	/// [bytes] may extend past the end of the blob.
+	///
+	/// # Errors
+	///
+	/// The errors match the return codes of libfdt's `fdt_check_header`:
+	/// [Error::BadMagic](error::Error::BadMagic),
+	/// [Error::BadVersion](error::Error::BadVersion) if the version is 0 or
+	/// the last compatible version is newer than 17, and
+	/// [Error::Truncated](error::Error::Truncated) if the blob doesn't fit in
+	/// [bytes] or a block lies outside of the blob. Unlike libfdt, version 1
+	/// blobs are accepted.
+	///
+	/// # Examples
+	///
+	/// ```
+	/// use fdt::FDT;
+	/// use fdt::error::Error;
+	/// let dtb = include_bytes!("../tests/dt.dtb");
+	///
+	/// assert!(FDT::from_bytes(dtb).is_ok());
+	/// assert_eq!(FDT::from_bytes(&dtb[..100]).err(), Some(Error::Truncated));
+	/// ```
+	pub fn from_bytes(bytes: &'buf [u8]) -> Result<FDT<'buf>, error::Error> {
+		Ok(FDT { blob: Blob::from_bytes(bytes)? })
+	}
+
+	/// Checks the structure block of the blob, like libfdt's `fdt_check_full`.
+	///
+	/// [from_bytes()](FDT::from_bytes) only checks the header, while the
+	/// readers end their iterations quietly on corrupt data. This walks every
+	/// tag and property name once, so a blob passing it reads the same as it
+	/// does with libfdt. The memory reserve map must also be terminated within
+	/// the blob, which libfdt checks in `fdt_num_mem_rsv`.
+	///
+	/// # Errors
+	///
+	/// [Error::Truncated](error::Error::Truncated) if a tag, a name or the
+	/// memory reserve map runs past its block,
+	/// [Error::BadStructure](error::Error::BadStructure) for unknown tags,
+	/// misnested nodes, a named root or a second root node, and
+	/// [Error::BadOffset](error::Error::BadOffset) for property names outside
+	/// of the strings block.
+	///
+	/// # Examples
+	///
+	/// ```
+	/// use fdt::FDT;
+	/// let dtb = include_bytes!("../tests/dt.dtb");
+	///
+	/// let fdt = FDT::from_bytes(dtb).unwrap();
+	/// assert_eq!(fdt.check_full(), Ok(()));
+	/// ```
+	pub fn check_full(&self) -> Result<(), error::Error> {
+		use error::Error;
+		use tag::Tag;
+
+		if self.memory_reserve_map().count() * 16 + 16 > self.blob.rsvmap_len() {
+			return Err(Error::Truncated);
+		}
+		let mut tags = self.tags();
+		let (mut depth, mut root_done) = (0usize, false);
+		while let Some(tag) = tags.next() {
+			match tag? {
+				Tag::Nop => (),
+				Tag::End if depth == 0 => return Ok(()),
+				_ if root_done => return Err(Error::BadStructure),
+				Tag::BeginNode { name, .. } => {
+					if depth == 0 && !name.is_empty() {
+						return Err(Error::BadStructure);
+					}
+					depth += 1;
+				},
+				Tag::EndNode => {
+					depth = depth.checked_sub(1).ok_or(Error::BadStructure)?;
+					root_done = depth == 0;
+				},
+				Tag::Prop { name_off, .. } => {
+					tags.string(name_off)?;
+				},
+				Tag::End => return Err(Error::BadStructure),
+			}
+		}
+		Err(Error::Truncated)
+	}
+
 	/// Returns the physical cpuid of the booting cpu. 
 	/// 
 	/// If the cpuid isn't available (device tree is of a version < 2) None is returned;
@@ -110,6 +240,76 @@ impl<'buf> FDT<'buf> {
 		self.blob.header().totalsize()
 	}
 	
+	/// Returns the fields of the header.
+	///
+	/// # Examples
+	///
+	/// ```
+	/// use fdt::FDT;
+	/// let dtb = include_bytes!("../tests/dt.dtb");
+	///
+	/// let header = FDT::from_bytes(dtb).unwrap().header();
+	/// assert_eq!((header.version, header.last_comp_version), (17, 16));
+	/// assert_eq!(header.size_dt_struct, Some(header.off_dt_strings - header.off_dt_struct));
+	/// println!("{}", header);
+	/// ```
+	pub fn header(&self) -> FdtHeader {
+		FdtHeader::read(&self.blob.header())
+	}
+
+	/// Returns where the blocks of the blob are, with the gaps between them
+	/// and the free space at the end of the blob.
+	///
+	/// Blocks whose sizes aren't in the header of older versions are
+	/// measured: the structure block up to its FDT_END tag and the strings
+	/// block up to the end of the last property name.
+	///
+	/// # Examples
+	///
+	/// ```
+	/// use fdt::{FDT, BlockKind};
+	/// let dtb = include_bytes!("../tests/dt.dtb");
+	///
+	/// let layout = FDT::from_bytes(dtb).unwrap().layout();
+	/// assert!(layout.in_order());
+	/// assert_eq!(layout.gaps().count(), 0);
+	/// assert_eq!(layout.free_space(), 0);
+	/// assert_eq!(layout.block(BlockKind::MemoryReserveMap).size, 32);
+	/// ```
+	pub fn layout(&self) -> Layout {
+		use tag::Tag;
+
+		let h = self.header();
+		let totalsize = h.totalsize as usize;
+		let rsvmap = h.off_mem_rsvmap as usize;
+		let entries = (self.memory_reserve_map().count() + 1) * 16;
+		let structure = match h.size_dt_struct {
+			Some(size) => size as usize,
+			None => {
+				let mut tags = self.tags();
+				while let Some(Ok(_)) = tags.next() {}
+				tags.offset()
+			},
+		};
+		let strings = match h.size_dt_strings {
+			Some(size) => size as usize,
+			None => {
+				let tags = self.tags();
+				tags.clone().filter_map(|tag| match tag {
+					Ok(Tag::Prop { name_off, .. }) => tags.string(name_off).ok()
+						.map(|name| name_off as usize + name.len() + 1),
+					_ => None,
+				}).max().unwrap_or(0)
+			},
+		};
+		Layout::new([
+			Block { kind: BlockKind::Header, offset: 0, size: h.header_size() },
+			Block { kind: BlockKind::MemoryReserveMap, offset: rsvmap, size: entries.min(totalsize - rsvmap) },
+			Block { kind: BlockKind::Structure, offset: h.off_dt_struct as usize, size: structure },
+			Block { kind: BlockKind::Strings, offset: h.off_dt_strings as usize, size: strings },
+		], totalsize)
+	}
+
 	/// Returns the reserved memory map of the device tree.
 	///
 	/// The reserved memory map contains a list of physical memory areas which 
@@ -131,7 +331,7 @@ impl<'buf> FDT<'buf> {
 	/// // one at address 0 with size 4096)
 	/// for entry in fdt.memory_reserve_map() {
 	///     assert_eq!(entry.address, 0);
-	///	    assert_eq!(entry.size, 0x1000);
+	///     assert_eq!(entry.size, 0x1000);
 	/// }
 	/// ```
 	pub fn memory_reserve_map(&self) -> MemoryReserveMap<'buf>{
@@ -156,10 +356,170 @@ impl<'buf> FDT<'buf> {
 	///     println!("{}", node.name());
 	/// }
 	/// ```
-	pub fn nodes(&'buf self) -> Subnodes<'buf> {
+	pub fn nodes(&self) -> Subnodes<'buf> {
 		Subnodes::new(self.blob.nodes(), 0)
 	}
 	
+	/// Returns a [TagCursor](tag::TagCursor) over the tags of the structure
+	/// block, for passes over the tree below the level of nodes.
+	///
+	/// # Examples
+	///
+	/// ```
+	/// use fdt::FDT;
+	/// use fdt::tag::Tag;
+	/// let dtb = include_bytes!("../tests/dt.dtb").as_ptr();
+	///
+	/// let fdt;
+	/// unsafe { fdt = FDT::from_raw(dtb).unwrap(); }
+	///
+	/// let props = fdt.tags().filter(|tag| matches!(tag, Ok(Tag::Prop { .. }))).count();
+	/// println!("{} properties", props);
+	/// ```
+	pub fn tags(&self) -> tag::TagCursor<'buf> {
+		self.blob.tags()
+	}
+	
+	/// Returns a copy of the blob converted to version 17, like libfdt's
+	/// `fdt_open_into` does for older versions.
+	///
+	/// The tree is rebuilt by the writer of [OwnedTree](owned::OwnedTree),
+	/// so legacy blobs get plain node names and 4 byte aligned values and the
+	/// header gets its newer fields. The copy has no free space.
+	///
+	/// # Examples
+	///
+	/// ```
+	/// use fdt::FDT;
+	/// let dtb = include_bytes!("../tests/dt.dtb");
+	///
+	/// let fdt = FDT::from_bytes(dtb).unwrap();
+	/// let latest = fdt.convert_to_latest();
+	/// let copy = FDT::from_bytes(&latest).unwrap();
+	/// assert_eq!(copy.nodes().count(), fdt.nodes().count());
+	/// ```
+	#[cfg(feature = "alloc")]
+	pub fn convert_to_latest(&self) -> alloc::vec::Vec<u8> {
+		owned::OwnedTree::from_fdt(self).to_dtb()
+	}
+	
+	/// Copies the blob to the start of [buf] as it is, like libfdt's
+	/// `fdt_move`, and returns the copy.
+	///
+	/// # Errors
+	///
+	/// Returns [Error::NoSpace](error::Error::NoSpace) if [buf] is smaller
+	/// than the blob.
+	///
+	/// # Examples
+	///
+	/// ```
+	/// use fdt::FDT;
+	/// let dtb = include_bytes!("../tests/dt.dtb");
+	///
+	/// let fdt = FDT::from_bytes(dtb).unwrap();
+	/// let mut buf = [0; 0x5000];
+	/// let moved = fdt.move_to(&mut buf).unwrap();
+	/// assert_eq!(moved.total_size(), fdt.total_size());
+	/// ```
+	pub fn move_to<'a>(&self, buf: &'a mut [u8]) -> Result<FDT<'a>, error::Error> {
+		relocate::move_to(self, buf)
+	}
+
+	/// Copies the blob into [buf] with all of [buf] as its size, like
+	/// libfdt's `fdt_open_into`, and returns the copy.
+	///
+	/// The copy is a version 17 blob with the header, the memory reserve
+	/// map, the structure block and the strings block following each other
+	/// in this order and the rest of [buf] as free space at its end, whatever
+	/// the layout and version of the original. The tags and strings are kept
+	/// as they are, NOP tags included.
+	///
+	/// # Errors
+	///
+	/// Returns the errors of [check_full()](FDT::check_full), which the blob
+	/// is checked with first, and [Error::NoSpace](error::Error::NoSpace) if
+	/// it doesn't fit in [buf].
+	///
+	/// # Examples
+	///
+	/// ```
+	/// use fdt::FDT;
+	/// let dtb = include_bytes!("../tests/dt.dtb");
+	///
+	/// let fdt = FDT::from_bytes(dtb).unwrap();
+	/// let mut buf = [0; 0x5000];
+	/// let opened = fdt.open_into(&mut buf).unwrap();
+	/// assert_eq!(opened.total_size(), 0x5000);
+	/// assert_eq!(opened.layout().free_space(), 0x5000 - dtb.len());
+	/// ```
+	pub fn open_into<'a>(&self, buf: &'a mut [u8]) -> Result<FDT<'a>, error::Error> {
+		relocate::rewrite(self, buf, true, false)
+	}
+
+	/// Copies the blob into [buf] as small as it gets, like libfdt's
+	/// `fdt_pack` after [open_into()](FDT::open_into), and returns the copy.
+	///
+	/// The blocks are written in order as by [open_into()](FDT::open_into),
+	/// but NOP tags are dropped, the strings block is rebuilt as by
+	/// [compact_strings()](FDT::compact_strings) and the blob ends with the
+	/// strings block. The copy is thus exactly the size of the header, the
+	/// memory reserve map, the tags other than NOP and the property names in
+	/// use, each once and left out where they end a longer name.
+	///
+	/// # Errors
+	///
+	/// As for [open_into()](FDT::open_into).
+	///
+	/// # Examples
+	///
+	/// ```
+	/// use fdt::FDT;
+	/// let dtb = include_bytes!("../tests/dt.dtb");
+	///
+	/// let fdt = FDT::from_bytes(dtb).unwrap();
+	/// let mut buf = [0; 0x5000];
+	/// let packed = fdt.pack(&mut buf).unwrap();
+	/// assert!(packed.total_size() <= fdt.total_size());
+	/// assert_eq!(packed.layout().free_space(), 0);
+	/// ```
+	pub fn pack<'a>(&self, buf: &'a mut [u8]) -> Result<FDT<'a>, error::Error> {
+		relocate::rewrite(self, buf, false, true)
+	}
+
+	/// Copies the blob into [buf] with its strings block rebuilt and returns
+	/// the copy.
+	///
+	/// The strings block only holds the property names in use, each once,
+	/// and names which are suffixes of others, as `cells` of `#address-cells`,
+	/// point into the longer name rather than taking space of their own. The
+	/// names are sorted by their reversed bytes rather than by first use as
+	/// dtc writes them, which keeps the rebuild in O(n log n) time without
+	/// allocating. The name offsets of all properties are updated to match.
+	/// Otherwise the copy is written as by [open_into()](FDT::open_into), NOP
+	/// tags included, but ends with the strings block. Use [pack()](FDT::pack)
+	/// to drop the NOP tags as well.
+	///
+	/// # Errors
+	///
+	/// As for [open_into()](FDT::open_into).
+	///
+	/// # Examples
+	///
+	/// ```
+	/// use fdt::FDT;
+	/// let dtb = include_bytes!("../tests/dt.dtb");
+	///
+	/// let fdt = FDT::from_bytes(dtb).unwrap();
+	/// let mut buf = [0; 0x5000];
+	/// let compacted = fdt.compact_strings(&mut buf).unwrap();
+	/// assert!(compacted.header().size_dt_strings <= fdt.header().size_dt_strings);
+	/// assert_eq!(compacted.layout().free_space(), 0);
+	/// ```
+	pub fn compact_strings<'a>(&self, buf: &'a mut [u8]) -> Result<FDT<'a>, error::Error> {
+		relocate::rewrite(self, buf, true, true)
+	}
+
 // Utility methods
 	/// Takes a phandle and returns the corresponding device [Node]
 	///
@@ -179,7 +539,7 @@ impl<'buf> FDT<'buf> {
 	/// // Fetch "cpus" node via it's phandle (in this particular .dtb 67)
 	/// println!("{}", fdt.phandle(67).unwrap().name()); // prints "cpus"
 	/// ```
-	pub fn phandle(&'buf self, phandle: u32) -> Option<Node<'buf>> {
+	pub fn phandle(&self, phandle: u32) -> Option<Node<'buf>> {
 		self.nodes().with_phandle(phandle)
 	}
 		
@@ -188,7 +548,7 @@ impl<'buf> FDT<'buf> {
 	/// Returns a [None] if the alias doesn't exist in the flat device tree.
 	///
 	/// # Examples
-	///	
+	///
 	/// ```
 	/// use fdt::{FDT, NodeIterator, PropertyValue};
 	/// let dtb = include_bytes!("../tests/dt.dtb").as_ptr();
@@ -201,7 +561,7 @@ impl<'buf> FDT<'buf> {
 	/// let audio = fdt.nodes().with_path(fdt.alias("audio").unwrap()).next().unwrap();
 	/// assert_eq!(audio.property("compatible").unwrap().parse::<&str>().unwrap(), "brcm,bcm2835-audio\u{0}"); 
 	/// ```
-	pub fn alias(&'buf self, alias: &str) -> Option<&'buf str> {
+	pub fn alias(&self, alias: &str) -> Option<&'buf str> {
 		self.nodes().with_path("/aliases").nth(0).and_then(
 		|aliases| aliases.property(alias)).and_then(
 		|property| property.parse::<&str>().ok()).and_then(
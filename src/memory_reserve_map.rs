@@ -1,6 +1,6 @@
 use blob::RsvMapReader;
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct MemoryReserveMapEntry {
 	pub address: u64,
 	pub size: u64
@@ -8,12 +8,13 @@ pub struct MemoryReserveMapEntry {
 
 #[derive(Clone)]
 pub struct MemoryReserveMap<'blob> {
-	blob: RsvMapReader<'blob>,
+	// None once the terminating entry or the end of the blob is reached
+	blob: Option<RsvMapReader<'blob>>,
 }
 
 impl<'blob> MemoryReserveMap<'blob> {
 	pub fn new(blob: RsvMapReader<'blob>) -> Self {
-		Self { blob: blob }
+		Self { blob: Some(blob) }
 	}
 }
 
@@ -21,9 +22,15 @@ impl<'blob> Iterator for MemoryReserveMap<'blob> {
 	type Item = MemoryReserveMapEntry;
 	
 	fn next(&mut self) -> Option<Self::Item> {
-		match (self.blob.read_u64(), self.blob.read_u64()) {
-			(0, 0) => None,
-			(a, s) => Some(Self::Item { address: a, size: s } ),
+		let blob = self.blob.as_mut()?;
+		match (blob.read_u64(), blob.read_u64()) {
+			// like libfdt any entry of size 0 ends the map, not only the
+			// all zero terminator
+			(_, Some(0)) | (None, _) | (_, None) => {
+				self.blob = None;
+				None
+			},
+			(Some(a), Some(s)) => Some(Self::Item { address: a, size: s } ),
 		}
 	}
 }
@@ -0,0 +1,440 @@
+//! Creation of FIT images, like `mkimage -f`.
+//!
+//! A [FitSource] describes the images and configurations of a FIT the way an
+//! image source (.its) file does, with the image data in memory. Building it
+//! adds a `hash` subnode with the digest of the data for every listed
+//! algorithm and, with a [Signer], the `signature` subnodes of images and
+//! configurations. Image data is embedded in the blob or, like `mkimage -E`,
+//! stored after it, see [Layout].
+//!
+//! # Examples
+//!
+//! ```
+//! use fdt::digest::{self, Algorithm};
+//! use fdt::fit::Fit;
+//! use fdt::mkimage::{ConfigurationSource, FitSource, ImageSource, Layout};
+//!
+//! let mut kernel = ImageSource::new("kernel-1", "kernel", b"kernel data");
+//! kernel.arch = Some("arm64".into());
+//! kernel.hashes = vec![Algorithm::Sha256];
+//! let mut config = ConfigurationSource::new("conf-1");
+//! config.kernel = Some("kernel-1".into());
+//!
+//! let source = FitSource {
+//!     description: "Linux".into(),
+//!     images: vec![kernel],
+//!     configurations: vec![config],
+//!     default: Some("conf-1".into()),
+//!     ..Default::default()
+//! };
+//! let image = source.build(Layout::External { alignment: 4, position: None }).unwrap();
+//!
+//! let fit = Fit::from_bytes(&image).unwrap();
+//! let kernel = fit.default_configuration().unwrap().kernel().unwrap().unwrap();
+//! assert_eq!(kernel.data(), Ok(&b"kernel data"[..]));
+//! assert!(kernel.verify(&mut digest::Software).is_verified());
+//! ```
+
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use core::fmt;
+
+use byteorder::{ByteOrder, BE};
+
+use digest::{self, Algorithm, Backend, Digest};
+use error::Error;
+use fit::Fit;
+use owned::{OwnedNode, OwnedProperty, OwnedTree};
+use signature::{self, SignatureError};
+
+/// The image properties of a configuration which may be signed.
+const IMAGE_PROPERTIES: [&str; 5] = ["kernel", "ramdisk", "firmware", "fdt", "loadables"];
+
+/// The description of a FIT, the equivalent of an image source file.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct FitSource {
+	pub description: String,
+	/// The 'timestamp' of the FIT in seconds since the epoch, left out if
+	/// None as there is no clock to read.
+	pub timestamp: Option<u32>,
+	pub images: Vec<ImageSource>,
+	pub configurations: Vec<ConfigurationSource>,
+	/// The name of the default configuration.
+	pub default: Option<String>,
+}
+
+/// An image of a [FitSource].
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ImageSource {
+	pub name: String,
+	pub description: Option<String>,
+	/// The image 'type', such as "kernel" or "flat_dt".
+	pub kind: String,
+	pub arch: Option<String>,
+	pub os: Option<String>,
+	pub compression: Option<String>,
+	pub load: Option<u64>,
+	pub entry: Option<u64>,
+	pub data: Vec<u8>,
+	/// The algorithms of the `hash-<n>` subnodes to add.
+	pub hashes: Vec<Algorithm>,
+	/// The `signature-<n>` subnodes to add, signing the data.
+	pub signatures: Vec<SignatureSource>,
+	/// Other properties of the image node.
+	pub properties: Vec<OwnedProperty>,
+}
+
+/// A configuration of a [FitSource], naming the images to boot together.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ConfigurationSource {
+	pub name: String,
+	pub description: Option<String>,
+	pub kernel: Option<String>,
+	pub ramdisk: Option<String>,
+	pub firmware: Option<String>,
+	pub fdt: Vec<String>,
+	pub loadables: Vec<String>,
+	pub compatible: Vec<String>,
+	/// The `signature-<n>` subnodes to add, signing the configuration.
+	pub signatures: Vec<SignatureSource>,
+	/// Other properties of the configuration node.
+	pub properties: Vec<OwnedProperty>,
+}
+
+/// A signature to add to an image or a configuration.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct SignatureSource {
+	/// The signature 'algo', such as "sha256,rsa2048".
+	pub algo: String,
+	/// The name of the signing key, 'key-name-hint'.
+	pub key_name_hint: String,
+	/// The RSA 'padding', "pkcs-1.5" if None.
+	pub padding: Option<String>,
+	/// The image properties of a configuration covered by the signature,
+	/// such as "kernel" and "fdt". Every image property of the
+	/// configuration is covered if empty.
+	pub signed_images: Vec<String>,
+}
+
+/// Where image data is stored.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Layout {
+	/// In the 'data' property of the images.
+	Embedded,
+	/// After the blob, located by 'data-size' and 'data-offset' like
+	/// `mkimage -E`, or 'data-position' if [position] is given like
+	/// `mkimage -E -p`.
+	///
+	/// Each image starts at a multiple of [alignment] from the end of the
+	/// blob, whose 'totalsize' is padded to [alignment] too (`mkimage -B`).
+	/// [alignment] is rounded up to a multiple of 4, as readers look for
+	/// the data from the end of the blob aligned to 4.
+	/// [position] is the offset of the data from the start of the blob.
+	External { alignment: usize, position: Option<usize> },
+}
+
+/// Signs the digests of signed images and configurations, typically with a
+/// private key named by the signature's 'key-name-hint'.
+pub trait Signer {
+	/// Signs [digest], computed with the checksum algorithm of
+	/// [signature]'s 'algo', returning the 'value' of the signature node.
+	fn sign(&mut self, signature: &SignatureSource, digest: &Digest) -> Result<Vec<u8>, SignatureError>;
+}
+
+/// A [Signer] for sources without signatures.
+struct Unsigned;
+
+impl Signer for Unsigned {
+	fn sign(&mut self, _: &SignatureSource, _: &Digest) -> Result<Vec<u8>, SignatureError> {
+		Err(SignatureError::Unsupported)
+	}
+}
+
+/// Why a FIT couldn't be built.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum BuildError {
+	/// A configuration refers to an image which isn't in the source.
+	MissingImage(String),
+	/// The default configuration isn't in the source.
+	MissingConfiguration(String),
+	/// The external data position lies inside the blob.
+	Overlap,
+	/// A signature couldn't be computed.
+	Signature(SignatureError),
+}
+
+impl fmt::Display for BuildError {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match *self {
+			BuildError::MissingImage(ref name) => write!(f, "no image '{}'", name),
+			BuildError::MissingConfiguration(ref name) => write!(f, "no configuration '{}'", name),
+			BuildError::Overlap => write!(f, "external data position inside the blob"),
+			BuildError::Signature(err) => write!(f, "signing failed: {}", err),
+		}
+	}
+}
+
+impl ::core::error::Error for BuildError {}
+
+impl From<SignatureError> for BuildError {
+	fn from(err: SignatureError) -> Self {
+		BuildError::Signature(err)
+	}
+}
+
+impl FitSource {
+	/// Builds the FIT, which must not have signatures.
+	///
+	/// # Errors
+	///
+	/// Returns [BuildError::Signature] if the source has signatures, see
+	/// [build_signed()](FitSource::build_signed) for the other errors.
+	pub fn build(&self, layout: Layout) -> Result<Vec<u8>, BuildError> {
+		self.build_signed(layout, &mut Unsigned)
+	}
+
+	/// Builds the FIT, signing its signatures with [signer].
+	///
+	/// Like mkimage, configuration signatures cover the root node, the
+	/// configuration and the nodes and hashes of its images listed by
+	/// 'sign-images', and the whole strings block. The 'hashed-nodes' and
+	/// 'hashed-strings' of the signatures record this.
+	///
+	/// # Errors
+	///
+	/// Returns [BuildError::MissingImage] or
+	/// [BuildError::MissingConfiguration] for dangling references,
+	/// [BuildError::Overlap] if the external data [position](Layout::External)
+	/// is inside the blob and [BuildError::Signature] for unknown signature
+	/// algorithms and errors of [signer].
+	pub fn build_signed<S: Signer + ?Sized>(&self, layout: Layout, signer: &mut S) -> Result<Vec<u8>, BuildError> {
+		if let Some(default) = &self.default {
+			if !self.configurations.iter().any(|config| &config.name == default) {
+				return Err(BuildError::MissingConfiguration(default.clone()));
+			}
+		}
+		let mut tree = OwnedTree::default();
+		tree.root.set_property("description", &string(&self.description));
+		if let Some(timestamp) = self.timestamp {
+			tree.root.set_property("timestamp", &timestamp.to_be_bytes());
+		}
+		let mut images = OwnedNode::new("images");
+		for image in &self.images {
+			images.children.push(image.node(layout)?);
+		}
+		let mut configurations = OwnedNode::new("configurations");
+		if let Some(default) = &self.default {
+			configurations.set_property("default", &string(default));
+		}
+		for config in &self.configurations {
+			configurations.children.push(config.node(&self.images)?);
+		}
+		tree.root.children = alloc::vec![images, configurations];
+		self.sign(&mut tree, signer)?;
+
+		let (alignment, position) = match layout {
+			Layout::Embedded => return Ok(tree.to_dtb()),
+			Layout::External { alignment, position } => (alignment.max(1).next_multiple_of(4), position),
+		};
+		// the offsets don't change the size of the blob
+		let size = tree.to_dtb().len().div_ceil(alignment) * alignment;
+		let start = match position {
+			Some(position) if position < size => return Err(BuildError::Overlap),
+			Some(position) => position,
+			None => size,
+		};
+		let mut offset = 0;
+		for (source, node) in self.images.iter().zip(&mut tree.root.children[0].children) {
+			match position {
+				Some(position) => node.set_property("data-position", &((position + offset) as u32).to_be_bytes()),
+				None => node.set_property("data-offset", &(offset as u32).to_be_bytes()),
+			}
+			offset += source.data.len().div_ceil(alignment) * alignment;
+		}
+		let mut image = tree.to_dtb();
+		image.resize(size, 0);
+		BE::write_u32(&mut image[4..8], size as u32);
+		image.resize(start, 0);
+		for source in &self.images {
+			image.extend_from_slice(&source.data);
+			image.resize(image.len().div_ceil(alignment) * alignment, 0);
+		}
+		Ok(image)
+	}
+
+	/// Fills in the values of the signature nodes of [tree].
+	fn sign<S: Signer + ?Sized>(&self, tree: &mut OwnedTree, signer: &mut S) -> Result<(), BuildError> {
+		for (source, node) in self.images.iter().zip(&mut tree.root.children[0].children) {
+			for (signature, sig_node) in source.signatures.iter().zip(signatures(node)) {
+				let (checksum, _) = signature::parse_algo(&signature.algo).ok_or(SignatureError::Unsupported)?;
+				let digest = digest::Software.digest(checksum, &mut Some(&source.data[..]).into_iter())
+					.ok_or(SignatureError::Unsupported)?;
+				sig_node.set_property("value", &signer.sign(signature, &digest)?);
+			}
+		}
+		if self.configurations.iter().all(|config| config.signatures.is_empty()) {
+			return Ok(());
+		}
+
+		// the placeholders of the unsigned tree already name every property,
+		// so the strings block and the signed regions stay the same
+		let strings = BE::read_u32(&tree.to_dtb()[32..36]);
+		for node in &mut tree.root.children[1].children {
+			for sig_node in signatures(node) {
+				sig_node.set_property("hashed-strings", &[0u32.to_be_bytes(), strings.to_be_bytes()].concat());
+			}
+		}
+		let dtb = tree.to_dtb();
+		let fit = Fit::from_bytes(&dtb).map_err(SignatureError::Fdt)?;
+		for (source, node) in self.configurations.iter().zip(&mut tree.root.children[1].children) {
+			let config = fit.configuration(&source.name).ok_or(SignatureError::Fdt(Error::NotFound))?;
+			for ((signature, signed), sig_node) in source.signatures.iter().zip(config.signatures()).zip(signatures(node)) {
+				sig_node.set_property("value", &signer.sign(signature, &signed.digest(&mut digest::Software)?)?);
+			}
+		}
+		Ok(())
+	}
+}
+
+impl ImageSource {
+	/// Creates an image named [name] of type [kind] holding [data].
+	pub fn new(name: &str, kind: &str, data: &[u8]) -> Self {
+		ImageSource { name: name.to_string(), kind: kind.to_string(), data: data.to_vec(), ..Default::default() }
+	}
+
+	/// Returns the image node, with 'data-size' and a placeholder for the
+	/// location of external data for [layout].
+	fn node(&self, layout: Layout) -> Result<OwnedNode, BuildError> {
+		let mut node = OwnedNode::new(&self.name);
+		if let Some(description) = &self.description {
+			node.set_property("description", &string(description));
+		}
+		match layout {
+			Layout::Embedded => node.set_property("data", &self.data),
+			Layout::External { position, .. } => {
+				node.set_property("data-size", &(self.data.len() as u32).to_be_bytes());
+				node.set_property(if position.is_some() { "data-position" } else { "data-offset" }, &[0; 4]);
+			},
+		}
+		node.set_property("type", &string(&self.kind));
+		for (name, value) in [("arch", &self.arch), ("os", &self.os), ("compression", &self.compression)] {
+			if let Some(value) = value {
+				node.set_property(name, &string(value));
+			}
+		}
+		for (name, address) in [("load", self.load), ("entry", self.entry)] {
+			match address {
+				Some(address) if address > u64::from(u32::MAX) => node.set_property(name, &address.to_be_bytes()),
+				Some(address) => node.set_property(name, &(address as u32).to_be_bytes()),
+				None => (),
+			}
+		}
+		node.properties.extend(self.properties.iter().cloned());
+		for (i, &algorithm) in self.hashes.iter().enumerate() {
+			let value = digest::Software.digest(algorithm, &mut Some(&self.data[..]).into_iter())
+				.ok_or(SignatureError::Unsupported)?;
+			let mut hash = OwnedNode::new(&alloc::format!("hash-{}", i + 1));
+			hash.set_property("algo", &string(algorithm.name()));
+			hash.set_property("value", value.as_ref());
+			node.children.push(hash);
+		}
+		for (i, signature) in self.signatures.iter().enumerate() {
+			node.children.push(signature.node(i, None));
+		}
+		Ok(node)
+	}
+}
+
+impl ConfigurationSource {
+	/// Creates an empty configuration named [name].
+	pub fn new(name: &str) -> Self {
+		ConfigurationSource { name: name.to_string(), ..Default::default() }
+	}
+
+	/// Returns the names of the images referred to by image property [name].
+	fn images(&self, name: &str) -> &[String] {
+		match name {
+			"kernel" => self.kernel.as_slice(),
+			"ramdisk" => self.ramdisk.as_slice(),
+			"firmware" => self.firmware.as_slice(),
+			"fdt" => &self.fdt,
+			"loadables" => &self.loadables,
+			_ => &[],
+		}
+	}
+
+	/// Returns the configuration node, checking its references to [images].
+	fn node(&self, images: &[ImageSource]) -> Result<OwnedNode, BuildError> {
+		let mut node = OwnedNode::new(&self.name);
+		if let Some(description) = &self.description {
+			node.set_property("description", &string(description));
+		}
+		for name in IMAGE_PROPERTIES {
+			let names = self.images(name);
+			if let Some(missing) = names.iter().find(|&name| !images.iter().any(|image| &image.name == name)) {
+				return Err(BuildError::MissingImage(missing.clone()));
+			}
+			if !names.is_empty() {
+				node.set_property(name, &string_list(names));
+			}
+		}
+		if !self.compatible.is_empty() {
+			node.set_property("compatible", &string_list(&self.compatible));
+		}
+		node.properties.extend(self.properties.iter().cloned());
+		for (i, signature) in self.signatures.iter().enumerate() {
+			node.children.push(signature.node(i, Some((self, images))));
+		}
+		Ok(node)
+	}
+}
+
+impl SignatureSource {
+	/// Returns the signature node with a placeholder value. Configuration
+	/// signatures, of [config] with [images], also get their 'hashed-nodes'.
+	fn node(&self, index: usize, config: Option<(&ConfigurationSource, &[ImageSource])>) -> OwnedNode {
+		let mut node = OwnedNode::new(&alloc::format!("signature-{}", index + 1));
+		node.set_property("algo", &string(&self.algo));
+		node.set_property("key-name-hint", &string(&self.key_name_hint));
+		if let Some(padding) = &self.padding {
+			node.set_property("padding", &string(padding));
+		}
+		if let Some((config, images)) = config {
+			let kinds: Vec<&str> = match self.signed_images.is_empty() {
+				true => IMAGE_PROPERTIES.iter().copied().filter(|kind| !config.images(kind).is_empty()).collect(),
+				false => self.signed_images.iter().map(String::as_str).collect(),
+			};
+			let mut nodes = alloc::vec![String::from("/"), alloc::format!("/configurations/{}", config.name)];
+			for kind in &kinds {
+				for name in config.images(kind) {
+					nodes.push(alloc::format!("/images/{}", name));
+					let hashes = images.iter().find(|image| &image.name == name).map_or(0, |image| image.hashes.len());
+					nodes.extend((1..=hashes).map(|i| alloc::format!("/images/{}/hash-{}", name, i)));
+				}
+			}
+			node.set_property("sign-images", &string_list(&kinds));
+			node.set_property("hashed-nodes", &string_list(&nodes));
+			node.set_property("hashed-strings", &[0; 8]);
+		}
+		node.set_property("value", &[]);
+		node
+	}
+}
+
+/// Returns the subnodes of [node] which are signatures.
+fn signatures(node: &mut OwnedNode) -> impl Iterator<Item=&mut OwnedNode> {
+	node.children.iter_mut().filter(|child| child.name.starts_with("signature"))
+}
+
+fn string(value: &str) -> Vec<u8> {
+	string_list(&[value])
+}
+
+fn string_list<S: AsRef<str>>(values: &[S]) -> Vec<u8> {
+	let mut list = Vec::new();
+	for value in values {
+		list.extend_from_slice(value.as_ref().as_bytes());
+		list.push(0);
+	}
+	list
+}
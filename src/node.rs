@@ -1,20 +1,36 @@
 use core::str;
 use property::{Property, Properties, PropertyIterator, PropertyValue, IsValue};
+use error::Error;
 
 use blob::{StructReader, Token};
 
 use core::fmt;
 use core::cmp::Ordering;
 
+/// The #address-cells of nodes without the property.
+pub(crate) const DEFAULT_ADDRESS_CELLS: u32 = 2;
+/// The #size-cells of nodes without the property.
+pub(crate) const DEFAULT_SIZE_CELLS: u32 = 1;
+
+/// Checks if the node name [node] matches [name] as [Node::has_name] does.
+pub(crate) fn has_name(node: &str, name: &str) -> bool {
+	match name.find('@') {
+		Some(_) => node == name,
+		None => node.split('@').next() == Some(name),
+	}
+}
+
+#[derive(Clone)]
 pub struct Node<'buf> {
 	name: &'buf str,
 	props: StructReader<'buf>,
 	subnodes: StructReader<'buf>,
 	depth: usize,
+	offset: usize,
 }
 
 impl<'buf> Node<'buf> {
-	pub fn name(&self) -> &str {
+	pub fn name(&self) -> &'buf str {
 		self.name
 	}
 	
@@ -28,13 +44,7 @@ impl<'buf> Node<'buf> {
 	///
 	/// todo: match on both specific and non-specific name
 	pub fn has_name(&self, name: &str) -> bool {
-		let (mut a, mut b) = (self.name.split('@'), name.split('@'));
-
-		match (a.next(), b.next(), a.next(), b.next()) {
-			(Some(na), Some(nb), Some(aa), Some(ab)) => na == nb && aa == ab,
-			(Some(na), Some(nb), _, None) => na == nb,
-			_ => false
-		}
+		has_name(self.name, name)
 	}
 	/// Returns an [PropertyIterator] of the properties of the node.
 	///
@@ -64,18 +74,18 @@ impl<'buf> Node<'buf> {
 	/// # Examples
 	///
 	/// todo: iterate over two children of root
-	pub fn subnodes(&'buf self) -> Subnodes<'buf> {
+	pub fn subnodes(&self) -> Subnodes<'buf> {
 		Subnodes::from_node(self)
 	}
 	
-	/// Returns a [NodeIterator] of all supernodes (parents of the node).
-	///
-	/// The order of iteration is in falling generation, so [.next()] will
-	/// return the parent of the current node
-	///
-	/// # Examples
-	///
-	/// todo: get the parent of an aliased node
+	// Returns a [NodeIterator] of all supernodes (parents of the node).
+	//
+	// The order of iteration is in falling generation, so [.next()] will
+	// return the parent of the current node
+	//
+	// # Examples
+	//
+	// todo: get the parent of an aliased node
 	//fn supernodes(&self) -> impl Iterator<Item=Node>;
 	
 // utility functions
@@ -90,15 +100,67 @@ impl<'buf> Node<'buf> {
 	pub fn property(&self, name: &str) -> Option<Property<'buf>> {
 		self.properties().with_name(name).next()
 	}
+	
+	/// Returns the value of the property with name [name] parsed as [T].
+	///
+	/// If the node doesn't have the property [PropertyValue::from_missing]
+	/// decides the outcome, this is an [Error::NotFound] for most types while
+	/// [Option] returns [None] and [bool] returns false.
+	///
+	/// # Examples
+	///
+	/// ```
+	/// use fdt::{FDT, NodeIterator};
+	/// let dtb = include_bytes!("../tests/dt.dtb").as_ptr();
+	///
+	/// let fdt;
+	/// unsafe { fdt = FDT::from_raw(dtb).unwrap(); }
+	///
+	/// let i2c = fdt.nodes().with_name("i2c@7e205000").next().unwrap();
+	/// assert_eq!(i2c.property_value::<u32>("clock-frequency"), Ok(100000));
+	/// assert_eq!(i2c.property_value::<Option<u32>>("current-speed"), Ok(None));
+	/// assert_eq!(i2c.property_value::<bool>("interrupt-controller"), Ok(false));
+	/// ```
+	pub fn property_value<T: PropertyValue<'buf>>(&self, name: &str) -> Result<T, Error> {
+		match self.property(name) {
+			Some(prop) => prop.parse(),
+			None => T::from_missing(),
+		}
+	}
 	/// Returns the phandle of the node.
 	///
-	/// Returns None if the node doesn't have a phandle.
+	/// Like libfdt the legacy 'linux,phandle' property is used if the node
+	/// doesn't have a valid 'phandle' property. Returns None if the node
+	/// doesn't have a phandle.
 	///
 	/// # Examples
 	///
 	/// todo: get a the phandle from a node and find the node using the phandle
 	pub fn phandle(&self) -> Option<u32> {
 		self.property("phandle").and_then(|val| val.as_u32().ok())
+			.or_else(|| self.property("linux,phandle").and_then(|val| val.as_u32().ok()))
+	}
+	
+	/// Returns the node with [phandle] in the tree this node belongs to.
+	///
+	/// Returns [None] if no node in the tree has the phandle. The whole tree
+	/// is searched, so this is as expensive as [FDT::phandle].
+	///
+	/// # Examples
+	///
+	/// ```
+	/// use fdt::{FDT, NodeIterator};
+	/// let dtb = include_bytes!("../tests/dt.dtb").as_ptr();
+	///
+	/// let fdt;
+	/// unsafe { fdt = FDT::from_raw(dtb).unwrap(); }
+	///
+	/// let i2c = fdt.nodes().with_name("i2c@7e205000").next().unwrap();
+	/// let pinctrl = i2c.property("pinctrl-0").unwrap().as_u32().unwrap();
+	/// assert_eq!(i2c.lookup_phandle(pinctrl).unwrap().name(), "i2c0");
+	/// ```
+	pub fn lookup_phandle(&self, phandle: u32) -> Option<Node<'buf>> {
+		Subnodes::new(self.props.rewind(), 0).with_phandle(phandle)
 	}
 	
 	/// Tests if [compatible] is contained in the nodes [compatible] property.
@@ -109,58 +171,148 @@ impl<'buf> Node<'buf> {
 	///
 	/// todo: get a custom property after compatibility has been checked
 	pub fn is_compatible_with(&self, name: &str) -> bool {
-		self.property("compatible").map_or(false,
+		self.property("compatible").is_some_and(
 			|prop| prop.as_stringlist().contains(name)
 		)
 	}
 	
+	/// Returns the data of the entry in [table] which best matches the node.
+	///
+	/// The strings of the node's 'compatible' property are ordered from the
+	/// most specific to the most general, the first of those strings present
+	/// in [table] decides the match regardless of the order of the table.
+	/// Returns [None] if no string matches or the node doesn't have the
+	/// 'compatible' property.
+	///
+	/// # Examples
+	///
+	/// ```
+	/// use fdt::{FDT, NodeIterator};
+	/// let dtb = include_bytes!("../tests/dt.dtb").as_ptr();
+	///
+	/// let fdt;
+	/// unsafe { fdt = FDT::from_raw(dtb).unwrap(); }
+	///
+	/// let table = [("arm,pl011", "generic"), ("brcm,bcm2835-pl011", "bcm2835")];
+	/// let uart = fdt.nodes().with_name("serial@7e201000").next().unwrap();
+	/// assert_eq!(uart.match_compatible(&table), Some(&"bcm2835"));
+	/// ```
+	pub fn match_compatible<'t, T>(&self, table: &'t [(&str, T)]) -> Option<&'t T> {
+		self.property("compatible").and_then(|prop| {
+			prop.as_stringlist().strings()
+				.filter_map(|comp| table.iter().find(|entry| entry.0 == comp))
+				.map(|entry| &entry.1)
+				.next()
+		})
+	}
+	
 	/// Returns the #address-cells property value of the node
 	///
 	/// If the node doen't have the #address-cells property, 2 is assumed as
-	/// a default value.
+	/// a default value, as by the devicetree specification.
 	///
 	/// # Examples
 	///
-	/// todo: get the address_cells property of a node
+	/// ```
+	/// use fdt::{FDT, NodeIterator};
+	/// let dtb = include_bytes!("../tests/dt.dtb");
+	///
+	/// let fdt = FDT::from_bytes(dtb).unwrap();
+	/// assert_eq!(fdt.nodes().with_name("soc").next().unwrap().address_cells(), 1);
+	/// assert_eq!(fdt.nodes().with_name("chosen").next().unwrap().address_cells(), 2);
+	/// ```
 	pub fn address_cells(&self) -> u32 {
 		self.property("#address-cells")
 			.and_then(|prop| prop.as_u32().ok())
-			.unwrap_or(2)
+			.unwrap_or(DEFAULT_ADDRESS_CELLS)
 	}
 	
 	/// Returns the #size-cells property value of the node
 	///
-	/// If the node doen't have the #size-cells property, 2 is assumed as
-	/// a default value.
+	/// If the node doen't have the #size-cells property, 1 is assumed as
+	/// a default value, as by the devicetree specification.
 	///
 	/// # Examples
 	///
-	/// todo: get the size_cells property of a node
+	/// ```
+	/// use fdt::{FDT, NodeIterator};
+	/// let dtb = include_bytes!("../tests/dt.dtb");
+	///
+	/// let fdt = FDT::from_bytes(dtb).unwrap();
+	/// assert_eq!(fdt.nodes().with_name("chosen").next().unwrap().size_cells(), 1);
+	/// ```
 	pub fn size_cells(&self) -> u32 {
 		self.property("#size-cells")
 			.and_then(|prop| prop.as_u32().ok())
-			.unwrap_or(2)
+			.unwrap_or(DEFAULT_SIZE_CELLS)
 	}
-	/// Formats the full path of this node on a [Formatter]
+	
+	/// Returns the full path of the node, which implements [Display].
 	///
-	/// Somewhat expensive since the whole tree up untill this node needs to 
-	/// be traversed.
+	/// Somewhat expensive to format since the whole tree up untill this node
+	/// needs to be traversed for every level of the path.
 	///
 	/// # Examples
 	///
-	/// todo: print the whole path of a node
-	//fn path_format(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result;
+	/// ```
+	/// use fdt::{FDT, NodeIterator};
+	/// let dtb = include_bytes!("../tests/dt.dtb").as_ptr();
+	///
+	/// let fdt;
+	/// unsafe { fdt = FDT::from_raw(dtb).unwrap(); }
+	///
+	/// let uart = fdt.nodes().with_name("serial@7e201000").next().unwrap();
+	/// assert_eq!(uart.path().to_string(), "/soc/serial@7e201000");
+	/// ```
+	pub fn path(&self) -> Path<'_, 'buf> {
+		Path(self)
+	}
 	
 	/// Returns a [NodeIterator] which iterates the direct descendants of the node.
 	///
-	/// The iterator skips subnodes which are not direct descendants of the
-	/// node. For iteration over all subnodes, use [subnodes()].
+	/// The descendants of each child are skipped by reading their tags only,
+	/// no nodes are created for them. For iteration over all subnodes, use
+	/// [subnodes()].
 	///
 	/// # Examples
 	///
-	/// todo: iterate the children of the root node
-	pub fn children(&'buf self) -> Children<'buf> {
-		Children(self.subnodes())
+	/// ```
+	/// use fdt::FDT;
+	/// let dtb = include_bytes!("../tests/dt.dtb").as_ptr();
+	///
+	/// let fdt;
+	/// unsafe { fdt = FDT::from_raw(dtb).unwrap(); }
+	///
+	/// let root = fdt.nodes().next().unwrap();
+	/// let mut children = root.children();
+	/// assert_eq!(children.next().unwrap().name(), "chosen");
+	/// assert_eq!(children.next().unwrap().name(), "soc");
+	/// ```
+	pub fn children(&self) -> Children<'buf> {
+		Children { blob: self.subnodes.clone(), depth: self.depth + 1 }
+	}
+	
+	/// Returns the next sibling of the node.
+	///
+	/// Returns None for the last child of a node and for the root. The
+	/// descendants of the node are skipped like [children()] does.
+	///
+	/// # Examples
+	///
+	/// ```
+	/// use fdt::{FDT, NodeIterator};
+	/// let dtb = include_bytes!("../tests/dt.dtb").as_ptr();
+	///
+	/// let fdt;
+	/// unsafe { fdt = FDT::from_raw(dtb).unwrap(); }
+	///
+	/// let chosen = fdt.nodes().with_name("chosen").next().unwrap();
+	/// assert_eq!(chosen.next_sibling().unwrap().name(), "soc");
+	/// ```
+	pub fn next_sibling(&self) -> Option<Node<'buf>> {
+		let mut blob = self.subnodes.clone();
+		blob.skip_subtree();
+		Nodes::new(blob, self.depth).next()
 	}
 	
 	/// Returns the supernode with [depth].
@@ -169,10 +321,48 @@ impl<'buf> Node<'buf> {
 	///
 	/// # Examples
 	///
-	/// todo: return a supernode at depth 2
+	/// ```
+	/// use fdt::{FDT, NodeIterator};
+	/// let dtb = include_bytes!("../tests/dt.dtb").as_ptr();
+	///
+	/// let fdt;
+	/// unsafe { fdt = FDT::from_raw(dtb).unwrap(); }
+	///
+	/// let ether = fdt.nodes().with_name("usbether@1").next().unwrap();
+	/// assert_eq!(ether.supernode_at_depth(2).unwrap().name(), "usb@7e980000");
+	/// assert!(ether.supernode_at_depth(4).is_none());
+	/// ```
 	pub fn supernode_at_depth(&self, depth: usize) -> Option<Node<'buf>> {
+		if depth >= self.depth {
+			return None;
+		}
+		let mut supernode = None;
+		for node in Subnodes::new(self.props.rewind(), 0) {
+			if node.offset == self.offset {
+				return supernode;
+			} else if node.depth == depth {
+				supernode = Some(node);
+			}
+		}
 		None
 	}
+	
+	/// Returns the parent of the node.
+	///
+	/// Returns None for the root node. Like [supernode_at_depth()] the tree
+	/// is scanned from the root to find the parent.
+	pub fn parent(&self) -> Option<Node<'buf>> {
+		self.depth.checked_sub(1).and_then(|depth| self.supernode_at_depth(depth))
+	}
+	
+	/// Returns the offset of the node's FDT_BEGIN_NODE tag within the
+	/// structure block.
+	///
+	/// The offset identifies the node within its tree, like the node offsets
+	/// used by libfdt.
+	pub fn offset(&self) -> usize {
+		self.offset
+	}
 }
 
 impl<'buf> fmt::Display for Node<'buf> {
@@ -185,27 +375,48 @@ impl<'buf> fmt::Display for Node<'buf> {
 	}
 }
 
+/// The full path of a [Node], see [Node::path()].
+pub struct Path<'a, 'buf: 'a>(&'a Node<'buf>);
+
+impl<'a, 'buf> fmt::Display for Path<'a, 'buf> {
+	fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+		if self.0.depth == 0 {
+			return f.write_str("/");
+		}
+		for depth in 1..self.0.depth {
+			if let Some(node) = self.0.supernode_at_depth(depth) {
+				write!(f, "/{}", node.name())?;
+			}
+		}
+		write!(f, "/{}", self.0.name())
+	}
+}
+
 impl<'buf> fmt::Debug for Node<'buf> {
 	fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
 		write!(f, "{}", self.name())
 	}
 }
 
+/// Iterates the nodes from [blob] until the end of the node containing them.
 #[derive(Clone, Debug)]
 pub struct Nodes<'buf> {
 	blob: StructReader<'buf>,
 	depth: usize,
+	// the depth of the first node, the FDT_END_NODE of its parent ends the iteration
+	floor: usize,
 }
 
 impl<'buf> Nodes<'buf> {
 	pub fn new(blob: StructReader<'buf>, initial_depth: usize) -> Nodes<'buf> {
 		Nodes {
-			blob: blob,
+			blob,
 			depth: initial_depth,
+			floor: initial_depth,
 		}
 	}
 	
-	fn after_node(node: &'buf Node<'buf>) -> Nodes<'buf> {
+	fn after_node(node: &Node<'buf>) -> Nodes<'buf> {
 		Nodes::new(node.subnodes.clone(), node.depth + 1)
 	}
 }
@@ -218,17 +429,32 @@ impl<'buf> Iterator for Nodes<'buf> {
 			match self.blob.token() {
 				Token::BeginNode => {
 					let d = self.depth;
+					let offset = self.blob.offs() - 4;
+					let name = match self.blob.node_name() {
+						Some(name) => name,
+						None => {
+							let o = self.blob.offs();
+							self.blob.back(o - offset);
+							return None;
+						},
+					};
 					self.depth += 1;
 					return Some(Node {
-						name: &self.blob.string(),
+						name,
 						props: self.blob.align(4).clone(),
 						subnodes: self.blob.skip_props().clone(),
 						depth: d,
+						offset,
 					});
 				},
-				Token::EndNode if self.depth >= 1 => self.depth -= 1,
-				Token::End if self.depth == 0 => return None,
-				e => panic!("Parse error, unexpected {} at offs {}", e, self.blob.offs()),
+				Token::EndNode if self.depth > self.floor => self.depth -= 1,
+				// the end of the nodes, or a corrupt structure block which
+				// ends the iteration as well
+				_ => {
+					// leave the token for the next call, keeping the iterator fused
+					self.blob.back(4);
+					return None;
+				},
 			}
 		}
 	}
@@ -241,7 +467,7 @@ pub struct Subnodes<'buf> {
 }
 
 impl<'buf, 'path> Subnodes<'buf> {
-	fn from_node(node: &'buf Node<'buf>) -> Self {
+	fn from_node(node: &Node<'buf>) -> Self {
 		Self {
 			iter: Nodes::after_node(node),
 			min_depth: node.depth + 1,
@@ -259,8 +485,8 @@ impl<'buf, 'path> Subnodes<'buf> {
 		let depth = self.min_depth;
 		WithPath {
 			iter: self,
-			path: path,
-			depth: depth,
+			path,
+			depth,
 		}
 	}
 }
@@ -279,14 +505,19 @@ impl<'buf> Iterator for Subnodes<'buf> {
 }
 
 #[derive(Clone, Debug)]
-pub struct Children<'buf> (Subnodes<'buf>);
+pub struct Children<'buf> {
+	blob: StructReader<'buf>,
+	depth: usize,
+}
 
 impl<'buf> Iterator for Children<'buf> {
 	type Item = Node<'buf>;
 	
 	fn next(&mut self) -> Option<Self::Item> {
-		let depth = self.0.min_depth + 1;
-		self.0.find(|node| node.depth == depth)
+		let child = Nodes::new(self.blob.clone(), self.depth).next()?;
+		self.blob = child.subnodes.clone();
+		self.blob.skip_subtree();
+		Some(child)
 	}
 }
 
@@ -409,6 +640,23 @@ impl<'str, 'buf, I: Iterator<Item=Node<'buf>>> Iterator for
 	}
 }
 
+#[derive(Clone, Debug)]
+pub struct Matching<'table, I, T: 'table> {
+	iter: I,
+	table: &'table [(&'table str, T)],
+}
+
+impl<'table, 'buf, I: Iterator<Item=Node<'buf>>, T> Iterator for
+		Matching<'table, I, T>
+{
+	type Item = (I::Item, &'table T);
+	
+	fn next(&mut self) -> Option<Self::Item> {
+		let table = self.table;
+		self.iter.find_map(|node| node.match_compatible(table).map(|data| (node, data)))
+	}
+}
+
 #[derive(Clone, Debug)]
 pub struct WithProperty<'name, I> {
 	iter: I,
@@ -447,7 +695,7 @@ impl<'buf, I: Iterator<Item=Node<'buf>>, V: IsValue + Copy> Iterator for
 	fn next(&mut self) -> Option<Self::Item> {
 		let name = self.name;
 		let val = self.val;
-		self.iter.find(|node| node.property(name).map_or(false, |prop| prop.is_equal(val)))
+		self.iter.find(|node| node.property(name).is_some_and(|prop| prop.is_equal(val)))
 	}
 }
 
@@ -465,7 +713,7 @@ pub trait NodeIterator<'arg, 'buf>: Iterator<Item=Node<'buf>> {
 	fn with_name(self, name: &'arg str) -> WithName<'arg, Self> 
 		where Self: Sized
 	{
-		WithName { iter: self, name: name}
+		WithName { iter: self, name }
 	}
 	
 	/// Filters on nodes compatible with [compatible].
@@ -481,6 +729,32 @@ pub trait NodeIterator<'arg, 'buf>: Iterator<Item=Node<'buf>> {
 		CompatibleWith { iter: self, comp: compatible }
 	}
 	
+	/// Filters on nodes matching an entry of [table].
+	///
+	/// Consumes the iterator and returns an Iterator which iterates over
+	/// nodes compatible with any of the strings in [table], together with
+	/// the data of the best matching entry as decided by
+	/// [Node::match_compatible].
+	///
+	/// # Examples
+	///
+	/// ```
+	/// use fdt::{FDT, NodeIterator};
+	/// let dtb = include_bytes!("../tests/dt.dtb").as_ptr();
+	///
+	/// let fdt;
+	/// unsafe { fdt = FDT::from_raw(dtb).unwrap(); }
+	///
+	/// let table = [("arm,pl011", 0), ("brcm,bcm2835-aux-uart", 1)];
+	/// for (node, driver) in fdt.nodes().matching(&table) {
+	///     println!("{} is handled by driver {}", node.name(), driver);
+	/// }
+	/// ```
+	fn matching<T>(self, table: &'arg [(&'arg str, T)]) -> Matching<'arg, Self, T>
+			where Self: Sized {
+		Matching { iter: self, table }
+	}
+	
 	/// Filters on nodes based on [property].
 	///
 	/// Consumes the iterator and returns a NodeIterator which iterates over
@@ -498,13 +772,17 @@ pub trait NodeIterator<'arg, 'buf>: Iterator<Item=Node<'buf>> {
 	/// Returns the node with [phandle] if it is iterated by this iterator.
 	///
 	/// Consumes the iterator and returns the matching node if it is found,
-	/// returns None otherwise.
+	/// returns None otherwise. The phandles 0 and 0xffffffff are invalid and
+	/// never match, as in libfdt.
 	///
 	/// # Examples
 	///
 	/// todo: get a node using its phandle.
-	fn with_phandle(self, phandle: u32) -> Option<Node<'buf>> where Self: Sized {
-		(WithProperty { iter: self, name: "phandle" }).with_value(phandle).nth(0)
+	fn with_phandle(mut self, phandle: u32) -> Option<Node<'buf>> where Self: Sized {
+		if phandle == 0 || phandle == u32::MAX {
+			return None;
+		}
+		self.find(|node| node.phandle() == Some(phandle))
 	}
 }
 
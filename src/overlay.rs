@@ -0,0 +1,256 @@
+//! Application of device tree overlays, like libfdt's `fdt_overlay_apply`.
+//!
+//! An overlay is a tree of fragments, each holding a `target` phandle or a
+//! `target-path` and an `__overlay__` node whose properties and children are
+//! merged into the target node of the base tree. Overlays compiled with
+//! symbols carry the bookkeeping nodes which tie them to the base tree:
+//!
+//! - `__fixups__` lists the places referring to labels of the base tree,
+//!   which are resolved through the base tree's `__symbols__` node.
+//! - `__local_fixups__` lists the places referring to nodes of the overlay
+//!   itself, which are adjusted when the overlay's phandles are renumbered
+//!   past those of the base tree.
+//! - `__symbols__` holds the overlay's labels, which are added to the base
+//!   tree's `__symbols__` so later overlays can refer to them.
+//!
+//! # Examples
+//!
+//! ```
+//! use fdt::FDT;
+//! use fdt::owned::{OwnedNode, OwnedTree};
+//! let dtb = include_bytes!("../tests/dt.dtb").as_ptr();
+//!
+//! let fdt;
+//! unsafe { fdt = FDT::from_raw(dtb).unwrap(); }
+//! let mut base = OwnedTree::from_fdt(&fdt);
+//!
+//! let mut overlay = OwnedTree::default();
+//! let mut fragment = OwnedNode::new("fragment@0");
+//! fragment.set_property("target-path", b"/soc/i2c@7e205000\0");
+//! let mut contents = OwnedNode::new("__overlay__");
+//! contents.set_property("status", b"okay\0");
+//! fragment.children.push(contents);
+//! overlay.root.children.push(fragment);
+//!
+//! fdt::overlay::apply(&mut base, &overlay).unwrap();
+//! let i2c = base.node("/soc/i2c@7e205000").unwrap();
+//! assert_eq!(i2c.property("status").unwrap().value, b"okay\0");
+//! ```
+
+use alloc::borrow::ToOwned;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::str;
+
+use byteorder::{ByteOrder, BE};
+
+use error::Error;
+use owned::{OwnedNode, OwnedTree};
+
+const FIXUPS: &str = "__fixups__";
+const LOCAL_FIXUPS: &str = "__local_fixups__";
+const SYMBOLS: &str = "__symbols__";
+const OVERLAY: &str = "__overlay__";
+
+/// Applies [overlay] to [base].
+///
+/// # Errors
+///
+/// Returns [Error::NotFound] if a fragment's target or a label referred to
+/// by the overlay doesn't exist in [base], [Error::BadPhandle] if a target
+/// phandle doesn't exist and [Error::BadValue] if the bookkeeping nodes of
+/// the overlay are malformed. [base] is left unmodified on errors.
+pub fn apply(base: &mut OwnedTree, overlay: &OwnedTree) -> Result<(), Error> {
+	let mut overlay = overlay.root.clone();
+	let delta = base.max_phandle();
+
+	renumber_phandles(&mut overlay, delta);
+	if let Some(local_fixups) = overlay.remove_child(LOCAL_FIXUPS) {
+		apply_local_fixups(&mut overlay, &local_fixups, delta)?;
+	}
+	if let Some(fixups) = overlay.remove_child(FIXUPS) {
+		apply_fixups(&mut overlay, &fixups, base)?;
+	}
+	let symbols = overlay.remove_child(SYMBOLS);
+
+	// resolve every target before touching the base tree
+	let mut fragments = Vec::new();
+	for fragment in &overlay.children {
+		if let Some(contents) = fragment.children.iter().find(|child| child.name == OVERLAY) {
+			fragments.push((fragment.name.as_str(), target_path(fragment, base)?, contents));
+		}
+	}
+	let mut symbol_paths = Vec::new();
+	if let Some(symbols) = &symbols {
+		for symbol in &symbols.properties {
+			let path = string(&symbol.value)?;
+			symbol_paths.push((symbol.name.as_str(), symbol_path(path, &fragments)?));
+		}
+	}
+
+	for (_, target, contents) in &fragments {
+		merge(base.exact_node_mut(target).ok_or(Error::NotFound)?, contents);
+	}
+	if !symbol_paths.is_empty() {
+		if base.root.child(SYMBOLS).is_none() {
+			base.root.children.push(OwnedNode::new(SYMBOLS));
+		}
+		let base_symbols = base.root.child_mut(SYMBOLS).ok_or(Error::Internal)?;
+		for (name, path) in symbol_paths {
+			let mut value = path.into_bytes();
+			value.push(0);
+			base_symbols.set_property(name, &value);
+		}
+	}
+	Ok(())
+}
+
+fn renumber_phandles(node: &mut OwnedNode, delta: u32) {
+	for prop in &mut node.properties {
+		if (prop.name == "phandle" || prop.name == "linux,phandle") && prop.value.len() == 4 {
+			let phandle = BE::read_u32(&prop.value);
+			BE::write_u32(&mut prop.value, phandle.wrapping_add(delta));
+		}
+	}
+	for child in &mut node.children {
+		renumber_phandles(child, delta);
+	}
+}
+
+/// Adds [delta] to the references to nodes of the overlay, which are listed
+/// in [fixups] mirroring the structure of the overlay.
+fn apply_local_fixups(node: &mut OwnedNode, fixups: &OwnedNode, delta: u32) -> Result<(), Error> {
+	for fixup in &fixups.properties {
+		let prop = node.properties.iter_mut().find(|prop| prop.name == fixup.name)
+			.ok_or(Error::BadValue)?;
+		if !fixup.value.len().is_multiple_of(4) {
+			return Err(Error::BadValue);
+		}
+		for offset in fixup.value.chunks(4) {
+			let cell = cell_mut(&mut prop.value, BE::read_u32(offset) as usize)?;
+			let phandle = BE::read_u32(cell);
+			BE::write_u32(cell, phandle.wrapping_add(delta));
+		}
+	}
+	for fixup in &fixups.children {
+		let child = node.children.iter_mut().find(|child| child.name == fixup.name)
+			.ok_or(Error::BadValue)?;
+		apply_local_fixups(child, fixup, delta)?;
+	}
+	Ok(())
+}
+
+/// Fills in the references to labels of [base], which are listed in
+/// [fixups] as `path:property:offset` strings.
+fn apply_fixups(overlay: &mut OwnedNode, fixups: &OwnedNode, base: &OwnedTree) -> Result<(), Error> {
+	for fixup in &fixups.properties {
+		let path = base.root.child(SYMBOLS)
+			.and_then(|symbols| symbols.property(&fixup.name))
+			.ok_or(Error::NotFound)
+			.and_then(|symbol| string(&symbol.value))?;
+		let phandle = base.node(path).and_then(OwnedNode::phandle).ok_or(Error::NotFound)?;
+		if fixup.value.last() != Some(&0) {
+			return Err(Error::BadValue);
+		}
+		for location in fixup.value[..fixup.value.len() - 1].split(|&b| b == 0) {
+			let location = str::from_utf8(location).map_err(Error::BadUtf8)?;
+			let mut parts = location.rsplitn(3, ':');
+			let (offset, prop, path) = match (parts.next(), parts.next(), parts.next()) {
+				(Some(offset), Some(prop), Some(path)) => (offset, prop, path),
+				_ => return Err(Error::BadValue),
+			};
+			let offset = offset.parse::<usize>().map_err(|_| Error::BadValue)?;
+			let node = path.split('/').filter(|name| !name.is_empty())
+				.try_fold(&mut *overlay, |node, name| {
+					node.children.iter_mut().find(|child| child.name == name)
+				})
+				.ok_or(Error::BadValue)?;
+			let prop = node.properties.iter_mut().find(|p| p.name == prop).ok_or(Error::BadValue)?;
+			BE::write_u32(cell_mut(&mut prop.value, offset)?, phandle);
+		}
+	}
+	Ok(())
+}
+
+/// Returns the full path of the node of [base] which [fragment] applies
+/// to, with the unit addresses of every node.
+fn target_path(fragment: &OwnedNode, base: &OwnedTree) -> Result<String, Error> {
+	if let Some(target) = fragment.property("target") {
+		if target.value.len() != 4 {
+			return Err(Error::BadValue);
+		}
+		path_of(&base.root, BE::read_u32(&target.value), &mut String::new())
+			.ok_or(Error::BadPhandle)
+	} else if let Some(path) = fragment.property("target-path") {
+		let mut node = &base.root;
+		let mut full = String::new();
+		for name in string(&path.value)?.split('/').filter(|name| !name.is_empty()) {
+			node = node.child(name).ok_or(Error::NotFound)?;
+			full.push('/');
+			full.push_str(&node.name);
+		}
+		Ok(if full.is_empty() { "/".to_owned() } else { full })
+	} else {
+		Err(Error::BadValue)
+	}
+}
+
+/// Returns the path of the node with [phandle] below [node], which has path
+/// [prefix].
+fn path_of(node: &OwnedNode, phandle: u32, prefix: &mut String) -> Option<String> {
+	if node.phandle() == Some(phandle) {
+		return Some(if prefix.is_empty() { "/".to_owned() } else { prefix.clone() });
+	}
+	let len = prefix.len();
+	for child in &node.children {
+		prefix.push('/');
+		prefix.push_str(&child.name);
+		let found = path_of(child, phandle, prefix);
+		prefix.truncate(len);
+		if found.is_some() {
+			return found;
+		}
+	}
+	None
+}
+
+/// Translates the path of an overlay symbol, which points into the
+/// `__overlay__` node of a fragment, to its path in the base tree.
+fn symbol_path(path: &str, fragments: &[(&str, String, &OwnedNode)]) -> Result<String, Error> {
+	let mut parts = path.trim_start_matches('/').splitn(3, '/');
+	let (fragment, rest) = match (parts.next(), parts.next(), parts.next()) {
+		(Some(fragment), Some(OVERLAY), rest) => (fragment, rest.unwrap_or("")),
+		_ => return Err(Error::BadValue),
+	};
+	let target = fragments.iter().find(|entry| entry.0 == fragment)
+		.map(|entry| entry.1.trim_end_matches('/'))
+		.ok_or(Error::BadValue)?;
+	Ok(match (target, rest) {
+		("", "") => "/".to_owned(),
+		(target, "") => target.to_owned(),
+		(target, rest) => [target, "/", rest].concat(),
+	})
+}
+
+fn merge(target: &mut OwnedNode, contents: &OwnedNode) {
+	for prop in &contents.properties {
+		target.set_property(&prop.name, &prop.value);
+	}
+	for child in &contents.children {
+		match target.children.iter_mut().find(|existing| existing.name == child.name) {
+			Some(existing) => merge(existing, child),
+			None => target.children.push(child.clone()),
+		}
+	}
+}
+
+fn cell_mut(value: &mut [u8], offset: usize) -> Result<&mut [u8], Error> {
+	value.get_mut(offset..offset + 4).ok_or(Error::BadValue)
+}
+
+fn string(value: &[u8]) -> Result<&str, Error> {
+	match value.split_last() {
+		Some((&0, string)) => str::from_utf8(string).map_err(Error::BadUtf8),
+		_ => Err(Error::BadValue),
+	}
+}
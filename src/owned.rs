@@ -0,0 +1,297 @@
+use alloc::collections::BTreeMap;
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use byteorder::{ByteOrder, BE};
+
+use memory_reserve_map::MemoryReserveMapEntry;
+use node::{has_name, Node};
+use property::Property;
+use blob::{FDT_BEGIN_NODE, FDT_END_NODE, FDT_PROP, FDT_END, MAX_COMPAT_VERSION};
+use header::MAGIC;
+use FDT;
+
+const HEADER_SIZE: usize = 40;
+const LAST_COMP_VERSION: u32 = 16;
+
+/// A heap allocated copy of a device tree node and all of its descendants.
+///
+/// Unlike [Node], which parses the blob on every access, an owned node can be
+/// inspected and modified freely once it has been built.
+#[derive(Clone, Debug, PartialEq, Eq, Default)]
+pub struct OwnedNode {
+	pub name: String,
+	pub properties: Vec<OwnedProperty>,
+	pub children: Vec<OwnedNode>,
+}
+
+/// A heap allocated copy of a whole device tree, which can be modified and
+/// flattened into a new blob.
+#[derive(Clone, Debug, PartialEq, Eq, Default)]
+pub struct OwnedTree {
+	pub memory_reserve: Vec<MemoryReserveMapEntry>,
+	pub boot_cpuid_phys: u32,
+	pub root: OwnedNode,
+}
+
+/// A heap allocated copy of a device tree property.
+#[derive(Clone, Debug, PartialEq, Eq, Default)]
+pub struct OwnedProperty {
+	pub name: String,
+	pub value: Vec<u8>,
+}
+
+impl OwnedNode {
+	/// Creates an empty node with name [name].
+	pub fn new(name: &str) -> Self {
+		OwnedNode { name: String::from(name), ..Default::default() }
+	}
+
+	/// Copies [node] and all of its descendants.
+	///
+	/// # Examples
+	///
+	/// ```
+	/// use fdt::FDT;
+	/// use fdt::owned::OwnedNode;
+	/// let dtb = include_bytes!("../tests/dt.dtb").as_ptr();
+	///
+	/// let fdt;
+	/// unsafe { fdt = FDT::from_raw(dtb).unwrap(); }
+	///
+	/// let root = OwnedNode::from_node(&fdt.nodes().next().unwrap());
+	/// assert_eq!(root.child("cpus").unwrap().children.len(), 4);
+	/// ```
+	pub fn from_node(node: &Node) -> Self {
+		OwnedNode {
+			name: String::from(node.name()),
+			properties: node.properties().map(|prop| OwnedProperty::from_property(&prop)).collect(),
+			children: node.children().map(|child| OwnedNode::from_node(&child)).collect(),
+		}
+	}
+
+	/// Returns the property with name [name].
+	pub fn property(&self, name: &str) -> Option<&OwnedProperty> {
+		self.properties.iter().find(|prop| prop.name == name)
+	}
+
+	/// Returns the first child matching [name] as [Node::has_name] would.
+	pub fn child(&self, name: &str) -> Option<&OwnedNode> {
+		self.children.iter().find(|child| has_name(&child.name, name))
+	}
+
+	/// Returns the first child matching [name] as [Node::has_name] would.
+	pub fn child_mut(&mut self, name: &str) -> Option<&mut OwnedNode> {
+		self.children.iter_mut().find(|child| has_name(&child.name, name))
+	}
+
+	/// Sets property [name] to [value], adding it if it doesn't exist.
+	pub fn set_property(&mut self, name: &str, value: &[u8]) {
+		match self.properties.iter_mut().find(|prop| prop.name == name) {
+			Some(prop) => prop.value = Vec::from(value),
+			None => self.properties.push(OwnedProperty {
+				name: String::from(name),
+				value: Vec::from(value),
+			}),
+		}
+	}
+
+	/// Removes property [name], returning it if it existed.
+	pub fn remove_property(&mut self, name: &str) -> Option<OwnedProperty> {
+		let pos = self.properties.iter().position(|prop| prop.name == name)?;
+		Some(self.properties.remove(pos))
+	}
+}
+
+impl OwnedTree {
+	/// Copies the whole tree of [fdt].
+	///
+	/// # Examples
+	///
+	/// ```
+	/// use fdt::FDT;
+	/// use fdt::owned::OwnedTree;
+	/// let dtb = include_bytes!("../tests/dt.dtb").as_ptr();
+	///
+	/// let fdt;
+	/// unsafe { fdt = FDT::from_raw(dtb).unwrap(); }
+	///
+	/// let mut tree = OwnedTree::from_fdt(&fdt);
+	/// tree.node_mut("/chosen").unwrap().set_property("bootargs", b"quiet\0");
+	///
+	/// let dtb = tree.to_dtb();
+	/// let fdt;
+	/// unsafe { fdt = FDT::from_raw(dtb.as_ptr()).unwrap(); }
+	/// assert_eq!(fdt.total_size() as usize, dtb.len());
+	/// ```
+	pub fn from_fdt(fdt: &FDT) -> Self {
+		OwnedTree {
+			memory_reserve: fdt.memory_reserve_map().collect(),
+			boot_cpuid_phys: fdt.boot_cpuid_phys().unwrap_or(0),
+			root: fdt.nodes().next().map(|root| OwnedNode::from_node(&root)).unwrap_or_default(),
+		}
+	}
+
+	/// Returns the node at [path].
+	///
+	/// Path components match child names as [Node::has_name] does, so the
+	/// unit address may be left out when it is unambiguous.
+	pub fn node(&self, path: &str) -> Option<&OwnedNode> {
+		path.split('/').filter(|name| !name.is_empty())
+			.try_fold(&self.root, |node, name| node.child(name))
+	}
+
+	/// Returns the node at [path], see [node()].
+	pub fn node_mut(&mut self, path: &str) -> Option<&mut OwnedNode> {
+		path.split('/').filter(|name| !name.is_empty())
+			.try_fold(&mut self.root, |node, name| node.child_mut(name))
+	}
+
+	/// Returns the node at [path], whose components must be the full names
+	/// of the nodes, unit address included.
+	pub fn exact_node(&self, path: &str) -> Option<&OwnedNode> {
+		path.split('/').filter(|name| !name.is_empty())
+			.try_fold(&self.root, |node, name| node.children.iter().find(|child| child.name == name))
+	}
+
+	/// Returns the node at [path], see [exact_node()].
+	pub fn exact_node_mut(&mut self, path: &str) -> Option<&mut OwnedNode> {
+		path.split('/').filter(|name| !name.is_empty())
+			.try_fold(&mut self.root, |node, name| node.children.iter_mut().find(|child| child.name == name))
+	}
+
+	/// Returns the largest phandle used in the tree, or 0 if no node has a
+	/// phandle.
+	pub fn max_phandle(&self) -> u32 {
+		self.root.max_phandle()
+	}
+
+	/// Flattens the tree into a version 17 blob.
+	///
+	/// Property names are shared in the strings block, the blob has no free
+	/// space at its end.
+	pub fn to_dtb(&self) -> Vec<u8> {
+		let mut structure = Vec::new();
+		let mut strings = Strings::default();
+		self.root.flatten(&mut structure, &mut strings, true);
+		let strings = strings.block;
+		push_u32(&mut structure, FDT_END);
+
+		let off_mem_rsvmap = HEADER_SIZE;
+		let off_dt_struct = off_mem_rsvmap + (self.memory_reserve.len() + 1) * 16;
+		let off_dt_strings = off_dt_struct + structure.len();
+		let totalsize = off_dt_strings + strings.len();
+
+		let mut dtb = Vec::with_capacity(totalsize);
+		for field in &[MAGIC, totalsize as u32, off_dt_struct as u32, off_dt_strings as u32,
+				off_mem_rsvmap as u32, MAX_COMPAT_VERSION, LAST_COMP_VERSION,
+				self.boot_cpuid_phys, strings.len() as u32, structure.len() as u32] {
+			push_u32(&mut dtb, *field);
+		}
+		for entry in self.memory_reserve.iter().chain(&[MemoryReserveMapEntry { address: 0, size: 0 }]) {
+			let mut buf = [0; 16];
+			BE::write_u64(&mut buf[..8], entry.address);
+			BE::write_u64(&mut buf[8..], entry.size);
+			dtb.extend_from_slice(&buf);
+		}
+		dtb.extend_from_slice(&structure);
+		dtb.extend_from_slice(&strings);
+		dtb
+	}
+}
+
+impl OwnedNode {
+	/// Returns the largest phandle of the node and its descendants, or 0 if
+	/// none of them has a phandle.
+	pub fn max_phandle(&self) -> u32 {
+		let own = self.properties.iter()
+			.filter(|prop| prop.name == "phandle" || prop.name == "linux,phandle")
+			.filter(|prop| prop.value.len() == 4)
+			.map(|prop| BE::read_u32(&prop.value))
+			.max()
+			.unwrap_or(0);
+		self.children.iter().map(OwnedNode::max_phandle).fold(own, u32::max)
+	}
+
+	/// Returns the phandle of the node.
+	pub fn phandle(&self) -> Option<u32> {
+		self.property("phandle").or_else(|| self.property("linux,phandle"))
+			.filter(|prop| prop.value.len() == 4)
+			.map(|prop| BE::read_u32(&prop.value))
+	}
+
+	/// Removes the first child matching [name] as [Node::has_name] would,
+	/// returning it if it existed.
+	pub fn remove_child(&mut self, name: &str) -> Option<OwnedNode> {
+		let pos = self.children.iter().position(|child| has_name(&child.name, name))?;
+		Some(self.children.remove(pos))
+	}
+
+	fn flatten<'t>(&'t self, structure: &mut Vec<u8>, strings: &mut Strings<'t>, root: bool) {
+		push_u32(structure, FDT_BEGIN_NODE);
+		if !root {
+			structure.extend_from_slice(self.name.as_bytes());
+		}
+		structure.push(0);
+		align(structure);
+		for prop in &self.properties {
+			push_u32(structure, FDT_PROP);
+			push_u32(structure, prop.value.len() as u32);
+			push_u32(structure, strings.offset(&prop.name) as u32);
+			structure.extend_from_slice(&prop.value);
+			align(structure);
+		}
+		for child in &self.children {
+			child.flatten(structure, strings, false);
+		}
+		push_u32(structure, FDT_END_NODE);
+	}
+}
+
+impl OwnedProperty {
+	/// Copies [prop].
+	pub fn from_property(prop: &Property) -> Self {
+		OwnedProperty {
+			name: String::from(prop.name()),
+			value: Vec::from(prop.raw()),
+		}
+	}
+}
+
+fn push_u32(buf: &mut Vec<u8>, val: u32) {
+	let mut cell = [0; 4];
+	BE::write_u32(&mut cell, val);
+	buf.extend_from_slice(&cell);
+}
+
+fn align(buf: &mut Vec<u8>) {
+	while !buf.len().is_multiple_of(4) {
+		buf.push(0);
+	}
+}
+
+/// The strings block of a blob being flattened.
+#[derive(Default)]
+struct Strings<'t> {
+	block: Vec<u8>,
+	/// The offset of every name in [block], and of every tail of them.
+	offsets: BTreeMap<&'t str, usize>,
+}
+
+impl<'t> Strings<'t> {
+	/// Returns the offset of [name], adding it if it isn't there.
+	///
+	/// Like dtc, a name may share the tail of a longer string.
+	fn offset(&mut self, name: &'t str) -> usize {
+		if let Some(&offset) = self.offsets.get(name) {
+			return offset;
+		}
+		let offset = self.block.len();
+		self.block.extend_from_slice(name.as_bytes());
+		self.block.push(0);
+		for (start, _) in name.char_indices() {
+			self.offsets.entry(&name[start..]).or_insert(offset + start);
+		}
+		offset
+	}
+}
@@ -9,6 +9,10 @@ use core::fmt;
 use core::str;
 
 use ::stringlist::StringList;
+use ::cells::Cells;
+use ::error::Error;
+
+use memchr::memchr;
 
 pub struct Property<'a> {
 	name: &'a str,
@@ -24,58 +28,222 @@ impl<'a> Property<'a> {
 		self.value
 	}
 	
-	pub fn as_u32(&self) -> Result<u32, &str> {
-		if self.value.len() < size_of::<u32>() {
-			Err("Parse error, property value to small to be parsed as u32")
-		} else {
-			Ok(BE::read_u32(self.value))
-		}
+	pub fn as_u32(&self) -> Result<u32, Error> {
+		self.parse::<u32>()
 	}
 	
+	/// Returns the value as a string, cut at the first byte which isn't
+	/// valid utf8. Use [Property::parse] to get an error instead.
 	pub fn as_str(&self) -> &'a str {
-		str::from_utf8(self.value).unwrap()
+		valid_utf8(self.value)
 	}
 	
+	/// Returns the value as a [StringList], cut at the first byte which
+	/// isn't valid utf8.
 	pub fn as_stringlist(&self) -> StringList<'a> {
-		StringList::from_utf8(self.value).unwrap()
+		StringList::from_utf8(valid_utf8(self.value).as_bytes()).unwrap()
 	}
 	
-	pub fn parse<I: PropertyValue<'a>>(&self) -> Result<I, &str> {
+	pub fn parse<I: PropertyValue<'a>>(&self) -> Result<I, Error> {
 		I::from_raw(self.value)
 	}
 	
 	pub fn is_equal<I: IsValue>(&self, other: I) -> bool {
 		other.is_value(self.value)
 	}
+	
+	/// Returns the value of the property, which implements [Display].
+	///
+	/// Well known properties are formatted according to their type, other
+	/// properties as strings, cells or bytes depending on their content.
+	pub fn display_value(&self) -> DisplayValue<'a> {
+		DisplayValue { name: self.name, value: self.value }
+	}
 }
 
+/// Types which can be parsed from the raw value of a property.
 pub trait PropertyValue<'a> {
-	fn from_raw(raw: &'a [u8]) -> Result<Self, &'static str> where Self: Sized;
+	/// Parses the whole property value [raw].
+	fn from_raw(raw: &'a [u8]) -> Result<Self, Error> where Self: Sized;
+	
+	/// Parses a value from the start of [raw], returning the value and the
+	/// unparsed remainder of [raw].
+	///
+	/// Used when several values are packed into one property, such as for
+	/// tuples. By default the whole of [raw] is consumed.
+	fn from_raw_prefix(raw: &'a [u8]) -> Result<(Self, &'a [u8]), Error> where Self: Sized {
+		Self::from_raw(raw).map(|val| (val, &raw[raw.len()..]))
+	}
+	
+	/// Returns the value of a property which is absent from its node.
+	///
+	/// By default absence is an [Error::NotFound] error, types such as
+	/// [Option] and [bool] treat absence as a regular value.
+	fn from_missing() -> Result<Self, Error> where Self: Sized {
+		Err(Error::NotFound)
+	}
+}
+
+fn split_prefix(raw: &[u8], len: usize) -> Result<(&[u8], &[u8]), Error> {
+	if raw.len() < len {
+		Err(Error::Truncated)
+	} else {
+		Ok(raw.split_at(len))
+	}
+}
+
+fn exact<'a, T: PropertyValue<'a>>(raw: &'a [u8]) -> Result<T, Error> {
+	match T::from_raw_prefix(raw)? {
+		(val, &[]) => Ok(val),
+		_ => Err(Error::BadLength(raw.len())),
+	}
 }
 
 impl<'a> PropertyValue<'a> for u32 {
-	fn from_raw(raw: &'a [u8]) -> Result<Self, &'static str> {
-		if raw.len() < 4 {
-			Err("Value to small Error")
+	fn from_raw(raw: &'a [u8]) -> Result<Self, Error> {
+		exact(raw)
+	}
+	
+	fn from_raw_prefix(raw: &'a [u8]) -> Result<(Self, &'a [u8]), Error> {
+		split_prefix(raw, size_of::<u32>()).map(|(val, rest)| (BE::read_u32(val), rest))
+	}
+}
+
+impl<'a> PropertyValue<'a> for u64 {
+	fn from_raw(raw: &'a [u8]) -> Result<Self, Error> {
+		exact(raw)
+	}
+	
+	fn from_raw_prefix(raw: &'a [u8]) -> Result<(Self, &'a [u8]), Error> {
+		split_prefix(raw, size_of::<u64>()).map(|(val, rest)| (BE::read_u64(val), rest))
+	}
+}
+
+impl<'a, const N: usize> PropertyValue<'a> for [u32; N] {
+	fn from_raw(raw: &'a [u8]) -> Result<Self, Error> {
+		exact(raw)
+	}
+	
+	fn from_raw_prefix(raw: &'a [u8]) -> Result<(Self, &'a [u8]), Error> {
+		let (val, rest) = split_prefix(raw, N * size_of::<u32>())?;
+		let mut cells = [0; N];
+		BE::read_u32_into(val, &mut cells);
+		Ok((cells, rest))
+	}
+}
+
+impl<'a> PropertyValue<'a> for &'a [u8] {
+	fn from_raw(raw: &'a [u8]) -> Result<Self, Error> {
+		Ok(raw)
+	}
+}
+
+impl<'a> PropertyValue<'a> for Cells<'a> {
+	fn from_raw(raw: &'a [u8]) -> Result<Self, Error> {
+		Cells::new(raw)
+	}
+}
+
+impl<'a> PropertyValue<'a> for &'a str {
+	fn from_raw(raw: &'a [u8]) -> Result<&'a str, Error> {
+		str::from_utf8(raw).map_err(Error::BadUtf8)
+	}
+	
+	fn from_raw_prefix(raw: &'a [u8]) -> Result<(Self, &'a [u8]), Error> {
+		let len = memchr(b'\0', raw).ok_or(Error::BadValue)?;
+		let string = str::from_utf8(&raw[..len]).map_err(Error::BadUtf8)?;
+		Ok((string, &raw[len + 1..]))
+	}
+}
+
+impl<'a> PropertyValue<'a> for StringList<'a> {
+	fn from_raw(raw: &'a [u8]) -> Result<Self, Error> {
+		match raw.last() {
+			Some(&0) | None => StringList::from_utf8(raw).map_err(Error::BadUtf8),
+			Some(_) => Err(Error::BadValue),
+		}
+	}
+}
+
+impl<'a> PropertyValue<'a> for bool {
+	fn from_raw(raw: &'a [u8]) -> Result<Self, Error> {
+		if raw.is_empty() {
+			Ok(true)
 		} else {
-			Ok(BE::read_u32(raw))
+			Err(Error::BadLength(raw.len()))
 		}
 	}
+	
+	fn from_missing() -> Result<Self, Error> {
+		Ok(false)
+	}
 }
 
-impl<'a> PropertyValue<'a> for &'a str {
-	fn from_raw(raw: &'a [u8]) -> Result<&'a str, &'static str> {
-		str::from_utf8(raw).map_err(|e| "Utf8 Error")
+impl<'a, T: PropertyValue<'a>> PropertyValue<'a> for Option<T> {
+	fn from_raw(raw: &'a [u8]) -> Result<Self, Error> {
+		T::from_raw(raw).map(Some)
+	}
+	
+	fn from_raw_prefix(raw: &'a [u8]) -> Result<(Self, &'a [u8]), Error> {
+		T::from_raw_prefix(raw).map(|(val, rest)| (Some(val), rest))
+	}
+	
+	fn from_missing() -> Result<Self, Error> {
+		Ok(None)
+	}
+}
+
+impl<'a, A: PropertyValue<'a>, B: PropertyValue<'a>> PropertyValue<'a> for (A, B) {
+	fn from_raw(raw: &'a [u8]) -> Result<Self, Error> {
+		let (a, rest) = A::from_raw_prefix(raw)?;
+		Ok((a, B::from_raw(rest)?))
+	}
+	
+	fn from_raw_prefix(raw: &'a [u8]) -> Result<(Self, &'a [u8]), Error> {
+		let (a, rest) = A::from_raw_prefix(raw)?;
+		let (b, rest) = B::from_raw_prefix(rest)?;
+		Ok(((a, b), rest))
 	}
 }
 
+/// Types which can be compared to the raw value of a property.
 pub trait IsValue {
 	fn is_value(&self, raw: &[u8]) -> bool;
 }
 
 impl IsValue for u32 {
 	fn is_value(&self, raw: &[u8]) -> bool {
-		*self == BE::read_u32(raw) 
+		u32::from_raw(raw) == Ok(*self)
+	}
+}
+
+impl IsValue for u64 {
+	fn is_value(&self, raw: &[u8]) -> bool {
+		u64::from_raw(raw) == Ok(*self)
+	}
+}
+
+/// Strings match property values with or without a trailing NUL character.
+impl IsValue for &str {
+	fn is_value(&self, raw: &[u8]) -> bool {
+		let raw = match raw.split_last() {
+			Some((&0, string)) => string,
+			_ => raw,
+		};
+		raw == self.as_bytes()
+	}
+}
+
+impl IsValue for &[u8] {
+	fn is_value(&self, raw: &[u8]) -> bool {
+		raw == *self
+	}
+}
+
+fn valid_utf8(raw: &[u8]) -> &str {
+	match str::from_utf8(raw) {
+		Ok(string) => string,
+		Err(e) => str::from_utf8(&raw[..e.valid_up_to()]).unwrap(),
 	}
 }
 
@@ -85,7 +253,7 @@ pub struct Properties<'buf> {
 
 impl<'buf> Properties<'buf> {
 	pub fn new(blob: StructReader<'buf>) -> Self {
-		Properties {blob: blob}
+		Properties { blob }
 	}
 }
 
@@ -95,13 +263,28 @@ impl<'buf> Iterator for Properties<'buf> {
 	fn next(&mut self) -> Option<Self::Item> {
 		match self.blob.token() {
 			Token::Prop => {
-				let len = self.blob.read_u32() as usize;
-				Some(Property {
-					name: self.blob.string_ref(),
-					value: self.blob.slice(len),
-				})
+				let start = self.blob.offs() - 4;
+				let prop = self.blob.read_u32().and_then(|len| {
+					let name = self.blob.string_ref()?;
+					let value = self.blob.value(len as usize)?;
+					Some(Property { name, value })
+				});
+				match prop {
+					Some(_) => self.blob.align(4),
+					// a truncated property or a bad name offset ends the
+					// properties, the token is left for the next call
+					None => {
+						let o = self.blob.offs();
+						self.blob.back(o - start)
+					},
+				};
+				prop
+			},
+			_ => {
+				// leave the token for the next call, keeping the iterator fused
+				self.blob.back(4);
+				None
 			},
-			_ => None,
 		}
 	}
 }
@@ -137,7 +320,7 @@ pub trait PropertyIterator<'arg, 'buf>: Iterator<Item=Property<'buf>> {
 	fn with_name(self, name: &'arg str) -> WithName<'arg, Self> 
 		where Self: Sized
 	{
-		WithName { iter: self, name: name}
+		WithName { iter: self, name }
 	}
 }
 
@@ -145,25 +328,32 @@ impl<'name, 'buf, I> PropertyIterator<'name, 'buf> for I where I: Iterator<Item=
 
 impl<'a> fmt::Display for Property<'a> {
 	fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
-		write!(f, "{}: ", self.name())?;
-		match self.name() {
-			"compatible" 	=> write!(f, "{}", self.as_stringlist()), //todo: stringlist
-			"model" 		=> write!(f, "{}", self.parse::<&str>().unwrap()),
-			"phandle" 		=> write!(f, "{}", self.as_u32().unwrap()),
-			"status" 		=> write!(f, "{}", self.as_str()),
-			"#address-cells" => write!(f, "{}", self.as_u32().unwrap()),
-			"#size-cells" 	=> write!(f, "{}", self.as_u32().unwrap()),
-			"reg" 			=> write!(f, "{:?}", self.value), // todo: prop_enc_array
-			"virtual-reg" 	=> write!(f, "{}", self.as_u32().unwrap()),
-			"ranges" 		=> write!(f, "[{} bytes]", self.value.len()), // todo: prop_enc_array
-			"dma-ranges"	=> write!(f, "[{} bytes]", self.value.len()), // todo: prop_enc_array
-			"name"			=> write!(f, "{}", self.as_str()),
-			"device_type"	=> write!(f, "{}", self.as_str()),
-			"interrupts"	=> write!(f, "{:?}", self.value),
-			"interrupt-parent" => write!(f, "{}", self.as_u32().unwrap()),
-			"interrupts-extended" => write!(f, "{:?}", self.value),
-			"#interrupt-cells" => write!(f, "{}", self.as_u32().unwrap()),
-			"interrupt-controller" => write!(f, "{:?}", self.value),
+		write!(f, "{}: {}", self.name(), self.display_value())
+	}
+}
+
+/// The value of a [Property] formatted according to its type, see
+/// [Property::display_value()].
+pub struct DisplayValue<'a> {
+	name: &'a str,
+	value: &'a [u8],
+}
+
+impl<'a> fmt::Display for DisplayValue<'a> {
+	fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+		let string = str::from_utf8(self.value).ok().filter(|_| self.value.last() == Some(&0));
+		let cell = u32::from_raw(self.value).ok();
+		match (self.name, string, cell) {
+			("compatible", Some(string), _) => write!(f, "{}", StringList::from_utf8(string.as_bytes()).unwrap()),
+			("model", Some(string), _) | ("status", Some(string), _) | ("name", Some(string), _)
+				| ("device_type", Some(string), _) => write!(f, "{}", string.trim_end_matches('\0')),
+			("phandle", _, Some(cell)) | ("#address-cells", _, Some(cell)) | ("#size-cells", _, Some(cell))
+				| ("virtual-reg", _, Some(cell)) | ("interrupt-parent", _, Some(cell))
+				| ("#interrupt-cells", _, Some(cell)) => write!(f, "{}", cell),
+			_ if ::stringlist::is_printable(self.value) =>
+				write!(f, "{}", StringList::from_utf8(self.value).unwrap()),
+			_ if !self.value.is_empty() && self.value.len().is_multiple_of(4) =>
+				write!(f, "{}", Cells::new(self.value).unwrap()),
 			_ => write!(f, "{:?}", self.value),
 		}
 	}
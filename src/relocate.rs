@@ -0,0 +1,189 @@
+//! Copies of blobs into caller provided buffers, like libfdt's `fdt_move`,
+//! `fdt_open_into` and `fdt_pack`.
+
+use byteorder::{ByteOrder, BE};
+use memchr::memchr;
+
+use blob::{FDT_BEGIN_NODE, FDT_END_NODE, FDT_PROP, FDT_NOP, FDT_END, MAX_COMPAT_VERSION};
+use error::Error;
+use header::{MAGIC, HEADER_V17_SIZE};
+use tag::Tag;
+use {BlockKind, FDT};
+
+const LAST_COMP_VERSION: u32 = 16;
+
+fn align4(len: usize) -> usize {
+	(len + 3) & !3
+}
+
+/// A cursor writing into the buffer, failing with [Error::NoSpace] rather
+/// than writing past its end.
+struct Out<'a> {
+	buf: &'a mut [u8],
+	pos: usize,
+}
+
+impl<'a> Out<'a> {
+	fn bytes(&mut self, bytes: &[u8]) -> Result<(), Error> {
+		let end = self.pos + bytes.len();
+		self.buf.get_mut(self.pos..end).ok_or(Error::NoSpace)?.copy_from_slice(bytes);
+		self.pos = end;
+		Ok(())
+	}
+
+	fn u32(&mut self, value: u32) -> Result<(), Error> {
+		self.bytes(&value.to_be_bytes())
+	}
+
+	fn align(&mut self) -> Result<(), Error> {
+		let end = align4(self.pos);
+		self.buf.get_mut(self.pos..end).ok_or(Error::NoSpace)?.fill(0);
+		self.pos = end;
+		Ok(())
+	}
+}
+
+pub fn move_to<'a>(fdt: &FDT, buf: &'a mut [u8]) -> Result<FDT<'a>, Error> {
+	let raw = fdt.blob.raw();
+	buf.get_mut(..raw.len()).ok_or(Error::NoSpace)?.copy_from_slice(raw);
+	FDT::from_bytes(buf)
+}
+
+/// Writes [fdt] into [buf] in the canonical block order as version 17.
+///
+/// Without [nops] NOP tags are dropped. With [merge] the strings block is
+/// rebuilt from the names in use with suffixes merged, sorted by their
+/// reversed bytes so the names can be looked up by binary search, and the
+/// blob ends with its last block. Otherwise the strings block is kept as it
+/// is and the blob fills [buf].
+pub fn rewrite<'a>(fdt: &FDT, buf: &'a mut [u8], nops: bool, merge: bool) -> Result<FDT<'a>, Error> {
+	fdt.check_full()?;
+	let tags = fdt.tags();
+	let mut size_dt_struct = 0;
+	for tag in tags.clone() {
+		size_dt_struct += match tag? {
+			Tag::BeginNode { name, .. } => 4 + align4(name.len() + 1),
+			Tag::Prop { value, .. } => 12 + align4(value.len()),
+			Tag::Nop if !nops => 0,
+			Tag::EndNode | Tag::Nop | Tag::End => 4,
+		};
+	}
+	let strings = fdt.layout().block(BlockKind::Strings);
+	let strings = fdt.blob.raw().get(strings.offset..strings.end()).ok_or(Error::Truncated)?;
+
+	let mut out = Out { buf, pos: HEADER_V17_SIZE };
+	out.buf.get_mut(..HEADER_V17_SIZE).ok_or(Error::NoSpace)?.fill(0);
+	let off_mem_rsvmap = out.pos;
+	for entry in fdt.memory_reserve_map() {
+		out.bytes(&entry.address.to_be_bytes())?;
+		out.bytes(&entry.size.to_be_bytes())?;
+	}
+	out.bytes(&[0; 16])?;
+
+	let off_dt_struct = out.pos;
+	let off_dt_strings = off_dt_struct + size_dt_struct;
+	let mut size_dt_strings = 0;
+	if merge {
+		let name_offs = tags.clone().filter_map(|tag| match tag {
+			Ok(Tag::Prop { name_off, .. }) => Some(name_off),
+			_ => None,
+		});
+		let props = name_offs.clone().count();
+		// the structure block is written last, until then it holds the name
+		// offsets of the properties, each taking 4 of its 12 or more bytes
+		if out.buf.len() < off_dt_strings {
+			return Err(Error::NoSpace);
+		}
+		let (structure, target) = out.buf.split_at_mut(off_dt_strings);
+		let (names, _) = structure[off_dt_struct..off_dt_struct + props * 4].as_chunks_mut::<4>();
+		for (name, name_off) in names.iter_mut().zip(name_offs) {
+			*name = name_off.to_be_bytes();
+		}
+		// sorted by their reversed bytes, a name ending another comes right
+		// before the names it ends, which leaves the longest to be written
+		let string = |off: &[u8; 4]| string_at(strings, u32::from_be_bytes(*off));
+		names.sort_unstable_by(|a, b| string(a).iter().rev().cmp(string(b).iter().rev()));
+		for (i, off) in names.iter().enumerate() {
+			let name = string(off);
+			if names.get(i + 1).is_some_and(|next| string(next).ends_with(name)) {
+				continue;
+			}
+			let end = size_dt_strings + name.len() + 1;
+			let target = target.get_mut(size_dt_strings..end).ok_or(Error::NoSpace)?;
+			target[..name.len()].copy_from_slice(name);
+			target[name.len()] = 0;
+			size_dt_strings = end;
+		}
+	} else {
+		size_dt_strings = strings.len();
+		out.buf.get_mut(off_dt_strings..off_dt_strings + strings.len()).ok_or(Error::NoSpace)?
+			.copy_from_slice(strings);
+	}
+	for tag in tags.clone() {
+		match tag? {
+			Tag::BeginNode { name, .. } => {
+				out.u32(FDT_BEGIN_NODE)?;
+				out.bytes(name.as_bytes())?;
+				out.bytes(&[0])?;
+				out.align()?;
+			},
+			Tag::Prop { name_off, value, .. } => {
+				let name_off = match merge {
+					true => {
+						let merged = &out.buf[off_dt_strings..off_dt_strings + size_dt_strings];
+						find(merged, string_at(strings, name_off)).ok_or(Error::Internal)?
+					},
+					false => name_off,
+				};
+				out.u32(FDT_PROP)?;
+				out.u32(value.len() as u32)?;
+				out.u32(name_off)?;
+				out.bytes(value)?;
+				out.align()?;
+			},
+			Tag::Nop if !nops => (),
+			Tag::Nop => out.u32(FDT_NOP)?,
+			Tag::EndNode => out.u32(FDT_END_NODE)?,
+			Tag::End => out.u32(FDT_END)?,
+		}
+	}
+
+	let totalsize = match merge {
+		true => off_dt_strings + size_dt_strings,
+		false => out.buf.len().min(u32::MAX as usize),
+	};
+	let header = fdt.header();
+	for (i, field) in [MAGIC, totalsize as u32, off_dt_struct as u32, off_dt_strings as u32,
+			off_mem_rsvmap as u32, MAX_COMPAT_VERSION, LAST_COMP_VERSION,
+			header.boot_cpuid_phys.unwrap_or(0), size_dt_strings as u32, size_dt_struct as u32].iter().enumerate() {
+		BE::write_u32(&mut out.buf[i * 4..], *field);
+	}
+	let buf: &'a [u8] = out.buf;
+	FDT::from_bytes(&buf[..totalsize])
+}
+
+/// Returns the NUL terminated string at [offset] of [strings], without the
+/// NUL, or an empty one if [offset] is out of bounds.
+fn string_at(strings: &[u8], offset: u32) -> &[u8] {
+	let raw = strings.get(offset as usize..).unwrap_or_default();
+	&raw[..memchr(0, raw).unwrap_or(raw.len())]
+}
+
+/// Returns the offset of [name] in [strings], a strings block sorted by
+/// the reversed bytes of its strings, as the tail of the first string not
+/// sorting before [name].
+fn find(strings: &[u8], name: &[u8]) -> Option<u32> {
+	let (mut low, mut high) = (0, strings.len());
+	while low < high {
+		let mid = (low + high) / 2;
+		let start = strings[..mid].iter().rposition(|&b| b == 0).map_or(0, |nul| nul + 1);
+		let end = start + memchr(0, &strings[start..])?;
+		if strings[start..end].iter().rev().lt(name.iter().rev()) {
+			low = end + 1;
+		} else {
+			high = start;
+		}
+	}
+	let end = low + memchr(0, strings.get(low..)?)?;
+	strings[low..end].ends_with(name).then(|| (end - name.len()) as u32)
+}
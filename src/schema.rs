@@ -0,0 +1,423 @@
+//! Validation of nodes against declarative binding schemas.
+//!
+//! A [Schema] describes the nodes of one binding the way a dt-schema YAML
+//! file does: the `compatible` values it covers, the properties it requires
+//! and allows along with their types and entry counts, and the child nodes it
+//! expects. Schemas are plain statics so a set of bindings can be compiled
+//! into a checker without any allocation or a Python toolchain.
+//!
+//! Besides the checks described by each schema, every selected node has its
+//! `*-names` properties checked against the number of entries of the property
+//! they name, e.g. `clock-names` against `clocks`.
+//!
+//! # Examples
+//!
+//! ```
+//! use fdt::FDT;
+//! use fdt::schema::{self, Schema, PropertySchema, Type};
+//! let dtb = include_bytes!("../tests/dt.dtb").as_ptr();
+//!
+//! let fdt;
+//! unsafe { fdt = FDT::from_raw(dtb).unwrap(); }
+//!
+//! static PL011: Schema = Schema {
+//!     compatible: &["brcm,bcm2835-pl011", "arm,pl011", "arm,primecell"],
+//!     required: &["reg", "interrupts", "clocks"],
+//!     properties: &[
+//!         PropertySchema::reg(1, 1),
+//!         PropertySchema::new("interrupts", Type::Matrix).items(1, 1),
+//!         PropertySchema::new("clocks", Type::PhandleArray).items(1, 2),
+//!         PropertySchema::new("clock-names", Type::StringList),
+//!         PropertySchema::new("arm,primecell-periphid", Type::U32),
+//!         PropertySchema::new("cts-event-workaround", Type::Flag),
+//!     ],
+//!     ..Schema::EMPTY
+//! };
+//!
+//! let violations = schema::validate(&fdt, &[&PL011], |report| {
+//!     println!("{}: {}", report.node.name(), report.violation);
+//! });
+//! assert_eq!(violations, 0);
+//! ```
+
+use core::fmt;
+
+use byteorder::{ByteOrder, BE};
+
+use entries::{Context, Entries};
+use node::Node;
+use property::Property;
+use stringlist::StringList;
+use FDT;
+
+/// Properties allowed in every node regardless of its schema.
+const COMMON_PROPERTIES: &[&str] = &[
+	"compatible",
+	"status",
+	"phandle",
+	"linux,phandle",
+	"name",
+	"pinctrl-names",
+	"pinctrl-[0-9]*",
+];
+
+/// The binding of a kind of node.
+#[derive(Clone, Copy, Debug)]
+pub struct Schema {
+	/// The allowed `compatible` values. A node with any of these values is
+	/// selected by the schema and must not have any other value.
+	pub compatible: &'static [&'static str],
+	/// Selects nodes by name, as [Node::has_name] matches, instead of by
+	/// `compatible`.
+	pub node_name: Option<&'static str>,
+	/// Properties which must be present.
+	pub required: &'static [&'static str],
+	/// The properties described by the schema.
+	pub properties: &'static [PropertySchema],
+	/// Whether properties not described by the schema are allowed.
+	pub additional_properties: bool,
+	/// The child nodes described by the schema.
+	pub children: &'static [ChildSchema],
+	/// Whether child nodes not described by the schema are allowed.
+	pub additional_children: bool,
+}
+
+/// The type and entry count of a property.
+#[derive(Clone, Copy, Debug)]
+pub struct PropertySchema {
+	/// The property name, which may contain '*' wildcards.
+	pub name: &'static str,
+	pub ty: Type,
+	pub min_items: usize,
+	pub max_items: usize,
+}
+
+/// The type of a property value.
+#[derive(Clone, Copy, Debug)]
+pub enum Type {
+	/// An empty property.
+	Flag,
+	/// A single cell.
+	U32,
+	/// A single 64 bit value.
+	U64,
+	/// Any number of cells, each counting as an item.
+	U32Array,
+	/// A single string, limited to the listed values unless the list is empty.
+	String(&'static [&'static str]),
+	/// A list of strings, each counting as an item.
+	StringList,
+	/// A single phandle which must refer to a node.
+	Phandle,
+	/// Entries of a phandle followed by the `#*-cells` of the referenced
+	/// provider, each entry counting as an item.
+	PhandleArray,
+	/// Entries of a size given by the `#*-cells` which apply to the property,
+	/// like `reg` or `interrupts`, each entry counting as an item.
+	Matrix,
+}
+
+/// The child nodes with names matching a pattern.
+#[derive(Clone, Copy, Debug)]
+pub struct ChildSchema {
+	/// The node name pattern, which may contain '*' wildcards.
+	pub pattern: &'static str,
+	/// The schema of the matching children, if any.
+	pub schema: Option<&'static Schema>,
+	pub min: usize,
+	pub max: usize,
+}
+
+/// A schema violation found in a node.
+pub struct Report<'a, 'buf: 'a> {
+	pub node: &'a Node<'buf>,
+	pub violation: Violation<'buf>,
+}
+
+/// The ways a node can fail to match its schema.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Violation<'buf> {
+	/// A required property is missing.
+	MissingProperty(&'static str),
+	/// A property isn't described by the schema.
+	UnexpectedProperty(&'buf str),
+	/// A `compatible` value isn't allowed by the schema.
+	BadCompatible(&'buf str),
+	/// A property value doesn't have the type of the schema.
+	BadType(&'buf str),
+	/// A property has a number of items outside of the schema's bounds.
+	BadItemCount { property: &'buf str, count: usize },
+	/// A property value isn't allowed or refers to a missing node.
+	BadValue(&'buf str),
+	/// A `*-names` property doesn't have one name per entry of its property.
+	NamesMismatch { property: &'buf str, names: usize, entries: usize },
+	/// A child node isn't described by the schema.
+	UnexpectedChild(&'buf str),
+	/// The number of children matching a pattern is outside of its bounds.
+	ChildCount { pattern: &'static str, count: usize },
+}
+
+impl Schema {
+	/// A schema which selects nothing and allows everything, to be used as
+	/// the base of schema definitions.
+	pub const EMPTY: Schema = Schema {
+		compatible: &[],
+		node_name: None,
+		required: &[],
+		properties: &[],
+		additional_properties: true,
+		children: &[],
+		additional_children: true,
+	};
+
+	/// Tests if [node] is covered by the schema.
+	pub fn selects(&self, node: &Node) -> bool {
+		match self.node_name {
+			Some(name) => node.has_name(name),
+			None => self.compatible.iter().any(|comp| node.is_compatible_with(comp)),
+		}
+	}
+
+	/// Checks [node] and the children described by the schema, calling
+	/// [report] for every violation found.
+	///
+	/// Returns the number of violations.
+	pub fn validate_node<'buf, F>(&self, node: &Node<'buf>, mut report: F) -> usize
+			where F: FnMut(Report<'_, 'buf>) {
+		self.check(node, Context::of(node), &mut report)
+	}
+
+	fn check<'buf, F>(&self, node: &Node<'buf>, ctx: Context, report: &mut F) -> usize
+			where F: FnMut(Report<'_, 'buf>) {
+		let mut count = 0;
+		self.check_properties(node, ctx, &mut |violation| {
+			count += 1;
+			report(Report { node, violation });
+		});
+
+		let child_ctx = ctx.child(node);
+		for child in node.children() {
+			match self.children.iter().find(|schema| glob(schema.pattern, child.name())) {
+				Some(&ChildSchema { schema: Some(schema), .. }) =>
+					count += schema.check(&child, child_ctx, report),
+				Some(_) => {},
+				None if !self.additional_children => {
+					count += 1;
+					report(Report { node, violation: Violation::UnexpectedChild(child.name()) });
+				},
+				None => {},
+			}
+		}
+		for schema in self.children {
+			let matching = node.children().filter(|child| glob(schema.pattern, child.name())).count();
+			if matching < schema.min || matching > schema.max {
+				count += 1;
+				report(Report {
+					node,
+					violation: Violation::ChildCount { pattern: schema.pattern, count: matching },
+				});
+			}
+		}
+		count
+	}
+
+	fn check_properties<'buf>(&self, node: &Node<'buf>, ctx: Context,
+			emit: &mut dyn FnMut(Violation<'buf>)) {
+		if !self.compatible.is_empty() {
+			if let Some(prop) = node.property("compatible") {
+				match StringList::from_utf8(prop.raw()) {
+					Ok(compatible) => for comp in compatible.strings() {
+						if !self.compatible.contains(&comp) {
+							emit(Violation::BadCompatible(comp));
+						}
+					},
+					Err(_) => emit(Violation::BadType(prop.name())),
+				}
+			}
+		}
+		for &name in self.required {
+			if node.property(name).is_none() {
+				emit(Violation::MissingProperty(name));
+			}
+		}
+		for prop in node.properties() {
+			let mut bad_type = false;
+			match self.properties.iter().find(|schema| glob(schema.name, prop.name())) {
+				Some(schema) => {
+					if let Some(violation) = schema.check(&prop, node, ctx) {
+						bad_type = violation == Violation::BadType(prop.name());
+						emit(violation);
+					}
+				},
+				None if !self.additional_properties
+					&& !COMMON_PROPERTIES.iter().any(|&name| glob(name, prop.name())) =>
+					emit(Violation::UnexpectedProperty(prop.name())),
+				None => {},
+			}
+			match check_names(&prop, node, ctx) {
+				Some(violation) if !bad_type => emit(violation),
+				_ => {},
+			}
+		}
+	}
+}
+
+impl PropertySchema {
+	/// A property of type [ty] with any number of items.
+	pub const fn new(name: &'static str, ty: Type) -> Self {
+		PropertySchema { name, ty, min_items: 0, max_items: usize::MAX }
+	}
+
+	/// A `reg` property with between [min] and [max] entries.
+	pub const fn reg(min: usize, max: usize) -> Self {
+		PropertySchema::new("reg", Type::Matrix).items(min, max)
+	}
+
+	/// Limits the number of items to between [min] and [max].
+	pub const fn items(self, min: usize, max: usize) -> Self {
+		PropertySchema { min_items: min, max_items: max, ..self }
+	}
+
+	fn check<'buf>(&self, prop: &Property<'buf>, node: &Node<'buf>, ctx: Context)
+			-> Option<Violation<'buf>> {
+		let name = prop.name();
+		let raw = prop.raw();
+		let items = match self.ty {
+			Type::Flag if raw.is_empty() => 1,
+			Type::U32 if raw.len() == 4 => 1,
+			Type::U64 if raw.len() == 8 => 1,
+			Type::U32Array if raw.len().is_multiple_of(4) => raw.len() / 4,
+			Type::String(values) if raw.last() == Some(&0) => {
+				let mut strings = match StringList::from_utf8(raw) {
+					Ok(strings) => strings.strings(),
+					Err(_) => return Some(Violation::BadType(name)),
+				};
+				match (strings.next(), strings.next()) {
+					(Some(string), None) if values.is_empty() || values.contains(&string) => 1,
+					(Some(_), None) => return Some(Violation::BadValue(name)),
+					_ => return Some(Violation::BadType(name)),
+				}
+			},
+			Type::StringList if raw.last() == Some(&0) => match StringList::from_utf8(raw) {
+				Ok(strings) => strings.strings().count(),
+				Err(_) => return Some(Violation::BadType(name)),
+			},
+			Type::Phandle if raw.len() == 4 => {
+				if node.lookup_phandle(BE::read_u32(raw)).is_none() {
+					return Some(Violation::BadValue(name));
+				}
+				1
+			},
+			Type::PhandleArray | Type::Matrix => {
+				let mut entries = Entries::new(prop, node, ctx);
+				let mut count = 0;
+				for entry in &mut entries {
+					if let Type::PhandleArray = self.ty {
+						// a zero phandle is allowed as a placeholder for unused entries
+						let phandle = BE::read_u32(entry);
+						if phandle != 0 && node.lookup_phandle(phandle).is_none() {
+							return Some(Violation::BadValue(name));
+						}
+					}
+					count += 1;
+				}
+				if entries.is_malformed() {
+					return Some(Violation::BadType(name));
+				}
+				count
+			},
+			_ => return Some(Violation::BadType(name)),
+		};
+		if items < self.min_items || items > self.max_items {
+			return Some(Violation::BadItemCount { property: name, count: items });
+		}
+		None
+	}
+}
+
+/// Checks that a `*-names` property has one name per entry of the property
+/// it names.
+fn check_names<'buf>(prop: &Property<'buf>, node: &Node<'buf>, ctx: Context)
+		-> Option<Violation<'buf>> {
+	let base = prop.name().strip_suffix("-names")?;
+	if prop.raw().last() != Some(&0) {
+		return None;
+	}
+	let names = match StringList::from_utf8(prop.raw()) {
+		Ok(names) => names.strings().count(),
+		Err(_) => return Some(Violation::BadType(prop.name())),
+	};
+	let entries = if base == "pinctrl" {
+		node.properties()
+			.filter(|prop| glob("pinctrl-[0-9]*", prop.name()))
+			.count()
+	} else {
+		// "clock-names" names "clocks", "reg-names" names "reg", etc.
+		let named = ["", "s", "es"].iter()
+			.filter_map(|suffix| node.properties()
+				.find(|prop| prop.name().strip_prefix(base) == Some(suffix)))
+			.next()?;
+		let mut entries = Entries::new(&named, node, ctx);
+		let count = (&mut entries).count();
+		if entries.is_malformed() {
+			return None;
+		}
+		count
+	};
+	if names != entries {
+		return Some(Violation::NamesMismatch { property: prop.name(), names, entries });
+	}
+	None
+}
+
+/// Matches [name] against [pattern], where '*' matches any number of
+/// characters and "[0-9]" matches a single digit.
+fn glob(pattern: &str, name: &str) -> bool {
+	if let Some(rest) = pattern.strip_prefix('*') {
+		return (0..=name.len())
+			.filter(|&i| name.is_char_boundary(i))
+			.any(|i| glob(rest, &name[i..]));
+	}
+	if let Some(rest) = pattern.strip_prefix("[0-9]") {
+		return name.starts_with(|c: char| c.is_ascii_digit()) && glob(rest, &name[1..]);
+	}
+	let mut chars = pattern.chars();
+	match chars.next() {
+		None => name.is_empty(),
+		Some(c) => name.strip_prefix(c).is_some_and(|name| glob(chars.as_str(), name)),
+	}
+}
+
+/// Validates every node of [fdt] selected by any of [schemas], calling
+/// [report] for every violation found.
+///
+/// Returns the number of violations.
+pub fn validate<'buf, F>(fdt: &FDT<'buf>, schemas: &[&Schema], mut report: F) -> usize
+		where F: FnMut(Report<'_, 'buf>) {
+	let mut count = 0;
+	for node in fdt.nodes() {
+		for schema in schemas.iter().filter(|schema| schema.selects(&node)) {
+			count += schema.validate_node(&node, &mut report);
+		}
+	}
+	count
+}
+
+impl<'buf> fmt::Display for Violation<'buf> {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match *self {
+			Violation::MissingProperty(name) => write!(f, "missing required property '{}'", name),
+			Violation::UnexpectedProperty(name) => write!(f, "unexpected property '{}'", name),
+			Violation::BadCompatible(comp) => write!(f, "compatible value '{}' not allowed", comp),
+			Violation::BadType(name) => write!(f, "property '{}' has the wrong type", name),
+			Violation::BadItemCount { property, count } =>
+				write!(f, "property '{}' has a bad number of items ({})", property, count),
+			Violation::BadValue(name) => write!(f, "property '{}' has a bad value", name),
+			Violation::NamesMismatch { property, names, entries } =>
+				write!(f, "'{}' has {} names for {} entries", property, names, entries),
+			Violation::UnexpectedChild(name) => write!(f, "unexpected child node '{}'", name),
+			Violation::ChildCount { pattern, count } =>
+				write!(f, "{} child nodes match '{}'", count, pattern),
+		}
+	}
+}
@@ -0,0 +1,67 @@
+// Serialization of nodes using serde, mirroring the format guessed by the
+// deserializer for values whose type isn't known.
+
+use byteorder::{ByteOrder, BE};
+use serde::ser::{Serialize, Serializer, SerializeMap, SerializeSeq};
+
+use node::Node;
+use owned::OwnedNode;
+use stringlist::is_printable;
+
+struct Value<'a>(&'a [u8]);
+
+impl<'a> Serialize for Value<'a> {
+	fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+		let raw = self.0;
+		if raw.is_empty() {
+			serializer.serialize_bool(true)
+		} else if is_printable(raw) {
+			// printable values are valid utf8
+			let strings = raw[..raw.len() - 1].split(|&b| b == 0)
+				.map(|string| core::str::from_utf8(string).unwrap_or(""));
+			if raw.iter().filter(|&&b| b == 0).count() == 1 {
+				serializer.serialize_str(strings.clone().next().unwrap_or(""))
+			} else {
+				serializer.collect_seq(strings)
+			}
+		} else if raw.len() == 4 {
+			serializer.serialize_u32(BE::read_u32(raw))
+		} else if raw.len().is_multiple_of(4) {
+			let mut seq = serializer.serialize_seq(Some(raw.len() / 4))?;
+			for cell in raw.chunks(4) {
+				seq.serialize_element(&BE::read_u32(cell))?;
+			}
+			seq.end()
+		} else {
+			serializer.serialize_bytes(raw)
+		}
+	}
+}
+
+/// Serializes the node as a map of its properties followed by its children.
+impl<'buf> Serialize for Node<'buf> {
+	fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+		let mut map = serializer.serialize_map(None)?;
+		for prop in self.properties() {
+			map.serialize_entry(prop.name(), &Value(prop.raw()))?;
+		}
+		for child in self.children() {
+			map.serialize_entry(child.name(), &child)?;
+		}
+		map.end()
+	}
+}
+
+/// Serializes the node as a map of its properties followed by its children.
+impl Serialize for OwnedNode {
+	fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+		let mut map = serializer.serialize_map(Some(self.properties.len() + self.children.len()))?;
+		for prop in &self.properties {
+			map.serialize_entry(&prop.name, &Value(&prop.value))?;
+		}
+		for child in &self.children {
+			map.serialize_entry(&child.name, child)?;
+		}
+		map.end()
+	}
+}
@@ -0,0 +1,426 @@
+//! Public keys and signature checks for verified boot, like U-Boot's.
+//!
+//! Keys are read from the `/signature` node of a control device tree, where
+//! mkimage stores them as `key-<name>` subnodes. RSA keys carry the
+//! Montgomery parameters 'rsa,n0-inverse' and 'rsa,r-squared' so that
+//! verifiers don't need general big number arithmetic, ECDSA keys carry
+//! the coordinates of their public point.
+//!
+//! The public key operations go through a [Crypto] backend. The [Software]
+//! backend implements RSA, ECDSA needs a user supplied backend. Padding
+//! (PKCS #1 v1.5 or PSS) is checked by this module.
+//!
+//! See [fit](::fit) for verifying the signatures of FIT images.
+
+use core::error::Error as CoreError;
+use core::fmt;
+
+use byteorder::{ByteOrder, BE};
+
+use digest::{Algorithm, Backend, Digest};
+use error::Error;
+use node::Node;
+use FDT;
+
+/// The largest supported RSA key, in bytes.
+const MAX_RSA_BYTES: usize = 4096 / 8;
+const MAX_RSA_WORDS: usize = MAX_RSA_BYTES / 4;
+
+/// Why a signature failed to verify.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SignatureError {
+	/// The signature doesn't match the signed data.
+	Invalid,
+	/// The algorithm or padding is unknown or not supported by the backends.
+	Unsupported,
+	/// A signature or key node lacks the named property or it is malformed.
+	Malformed(&'static str),
+	/// The 'algo' of the key differs from the one of the signature.
+	KeyMismatch,
+	/// The signed configuration isn't among the 'hashed-nodes' of its
+	/// signature.
+	NotHashed,
+	/// No signature verified with a required key.
+	Missing,
+	/// The signed image or configuration has a unit address in its name.
+	UnitAddress,
+	/// The signed blob is corrupt.
+	Fdt(Error),
+}
+
+impl fmt::Display for SignatureError {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match *self {
+			SignatureError::Invalid => write!(f, "invalid signature"),
+			SignatureError::Unsupported => write!(f, "unsupported algorithm"),
+			SignatureError::Malformed(property) => write!(f, "missing or malformed property '{}'", property),
+			SignatureError::KeyMismatch => write!(f, "key algorithm doesn't match the signature"),
+			SignatureError::NotHashed => write!(f, "configuration not in hashed nodes"),
+			SignatureError::Missing => write!(f, "no signature verified with a required key"),
+			SignatureError::UnitAddress => write!(f, "node name contains @"),
+			SignatureError::Fdt(err) => write!(f, "corrupt blob: {}", err),
+		}
+	}
+}
+
+impl CoreError for SignatureError {}
+
+/// The public key algorithm of a signature.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Scheme {
+	Rsa,
+	Ecdsa,
+}
+
+/// Splits a signature 'algo' such as "sha256,rsa2048" into its checksum
+/// algorithm and public key scheme.
+pub fn parse_algo(algo: &str) -> Option<(Algorithm, Scheme)> {
+	let (checksum, crypto) = algo.split_once(',')?;
+	let scheme = if crypto.starts_with("rsa") {
+		Scheme::Rsa
+	} else if crypto.starts_with("ecdsa") {
+		Scheme::Ecdsa
+	} else {
+		return None;
+	};
+	Some((Algorithm::from_name(checksum)?, scheme))
+}
+
+/// The keys of a control device tree, the subnodes of its `/signature` node.
+#[derive(Clone, Debug)]
+pub struct Keys<'k> {
+	node: Option<Node<'k>>,
+}
+
+impl<'k> Keys<'k> {
+	/// Returns the keys of [control], none if it has no `/signature` node.
+	pub fn new(control: &FDT<'k>) -> Self {
+		Keys { node: control.nodes().with_path("/signature").next() }
+	}
+
+	/// Returns an iterator over the keys.
+	pub fn iter(&self) -> impl Iterator<Item=Key<'k>> + '_ {
+		self.node.iter().flat_map(|node| node.children()).map(|node| Key { node })
+	}
+
+	/// Returns the key named `key-<hint>`, as named by the 'key-name-hint' of
+	/// signatures.
+	pub fn get(&self, hint: &str) -> Option<Key<'k>> {
+		self.iter().find(|key| key.hint() == hint)
+	}
+
+	/// Returns true unless the 'required-mode' of the keys is "any", in which
+	/// case a single required key suffices.
+	pub fn require_all(&self) -> bool {
+		let mode = self.node.as_ref().and_then(|node| node.property("required-mode"));
+		!mode.is_some_and(|mode| mode.is_equal("any"))
+	}
+}
+
+/// A public key of a control device tree.
+#[derive(Clone, Debug)]
+pub struct Key<'k> {
+	node: Node<'k>,
+}
+
+impl<'k> Key<'k> {
+	/// Returns the key node.
+	pub fn node(&self) -> &Node<'k> {
+		&self.node
+	}
+
+	/// Returns the name of the key without the `key-` prefix of its node.
+	pub fn hint(&self) -> &'k str {
+		let name = self.node.name();
+		name.strip_prefix("key-").unwrap_or(name)
+	}
+
+	/// Returns the signature 'algo' the key is meant for.
+	pub fn algo(&self) -> Option<&'k str> {
+		string(&self.node, "algo")
+	}
+
+	/// Returns what the key must sign for images to boot, "conf" or
+	/// "image", or None if the key is optional.
+	pub fn required(&self) -> Option<&'k str> {
+		string(&self.node, "required")
+	}
+
+	/// Returns the RSA parameters of the key.
+	pub fn rsa(&self) -> Result<RsaKey<'k>, SignatureError> {
+		let bits = cell(&self.node, "rsa,num-bits")?;
+		if bits == 0 {
+			return Err(SignatureError::Malformed("rsa,num-bits"));
+		}
+		let n0_inverse = cell(&self.node, "rsa,n0-inverse")?;
+		let exponent = match self.node.property("rsa,exponent") {
+			Some(prop) if prop.raw().len() == 8 => BE::read_u64(prop.raw()),
+			Some(_) => return Err(SignatureError::Malformed("rsa,exponent")),
+			None => 65537,
+		};
+		let bytes = |name| match self.node.property(name) {
+			Some(prop) if prop.raw().len() * 8 == bits as usize => Ok(prop.raw()),
+			_ => Err(SignatureError::Malformed(name)),
+		};
+		Ok(RsaKey { bits, n0_inverse, exponent, modulus: bytes("rsa,modulus")?, r_squared: bytes("rsa,r-squared")? })
+	}
+
+	/// Returns the ECDSA parameters of the key.
+	pub fn ecdsa(&self) -> Result<EcdsaKey<'k>, SignatureError> {
+		let bytes = |name| self.node.property(name).map(|prop| prop.raw()).ok_or(SignatureError::Malformed(name));
+		Ok(EcdsaKey {
+			curve: string(&self.node, "ecdsa,curve").ok_or(SignatureError::Malformed("ecdsa,curve"))?,
+			x: bytes("ecdsa,x-point")?,
+			y: bytes("ecdsa,y-point")?,
+		})
+	}
+}
+
+/// The parameters of an RSA public key.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RsaKey<'k> {
+	/// The size of the modulus in bits.
+	pub bits: u32,
+	/// -1 / modulus mod 2^32.
+	pub n0_inverse: u32,
+	pub exponent: u64,
+	/// The big endian modulus.
+	pub modulus: &'k [u8],
+	/// The big endian value of 2^(2 * bits) mod modulus.
+	pub r_squared: &'k [u8],
+}
+
+/// The parameters of an ECDSA public key.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct EcdsaKey<'k> {
+	/// The name of the curve, such as "prime256v1".
+	pub curve: &'k str,
+	/// The big endian coordinates of the public point.
+	pub x: &'k [u8],
+	pub y: &'k [u8],
+}
+
+/// Public key operations, implemented by [Software] and by users for
+/// hardware accelerators.
+pub trait Crypto {
+	/// Computes [signature] raised to the exponent of [key] modulo its
+	/// modulus into [out], all big endian and as long as the modulus.
+	///
+	/// Returns false if the backend doesn't support [key].
+	fn rsa_public(&mut self, key: &RsaKey, signature: &[u8], out: &mut [u8]) -> bool;
+
+	/// Checks that [signature], the concatenated r and s values, signs
+	/// [digest] with [key].
+	///
+	/// Returns None if the backend doesn't support [key]. The default
+	/// implementation supports no keys.
+	fn ecdsa_verify(&mut self, key: &EcdsaKey, digest: &[u8], signature: &[u8]) -> Option<bool> {
+		let _ = (key, digest, signature);
+		None
+	}
+}
+
+/// The software implementation of RSA for keys of up to 4096 bits.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Software;
+
+impl Crypto for Software {
+	fn rsa_public(&mut self, key: &RsaKey, signature: &[u8], out: &mut [u8]) -> bool {
+		let len = key.modulus.len();
+		if len == 0 || !len.is_multiple_of(4) || len > MAX_RSA_BYTES || key.exponent == 0
+				|| key.r_squared.len() != len || signature.len() != len || out.len() != len {
+			return false;
+		}
+		let words = len / 4;
+		let load = |bytes: &[u8]| {
+			let mut value = [0u32; MAX_RSA_WORDS];
+			for (word, chunk) in value.iter_mut().zip(bytes.rchunks(4)) {
+				*word = BE::read_u32(chunk);
+			}
+			value
+		};
+		let (n, rr, s) = (load(key.modulus), load(key.r_squared), load(signature));
+		let (n, rr, s) = (&n[..words], &rr[..words], &s[..words]);
+		// RSAVP1 requires the signature to be a representative below n
+		if !less(s, n) {
+			return false;
+		}
+
+		// s * R mod n, the Montgomery form of the signature
+		let mut base = [0u32; MAX_RSA_WORDS];
+		montgomery_mul(&mut base[..words], s, rr, n, key.n0_inverse);
+		let base = &base[..words];
+		let mut acc = [0u32; MAX_RSA_WORDS];
+		acc[..words].copy_from_slice(base);
+		let mut tmp = [0u32; MAX_RSA_WORDS];
+		for bit in (0..63 - key.exponent.leading_zeros()).rev() {
+			montgomery_mul(&mut tmp[..words], &acc[..words], &acc[..words], n, key.n0_inverse);
+			if key.exponent >> bit & 1 == 1 {
+				montgomery_mul(&mut acc[..words], &tmp[..words], base, n, key.n0_inverse);
+			} else {
+				acc[..words].copy_from_slice(&tmp[..words]);
+			}
+		}
+		let mut one = [0u32; MAX_RSA_WORDS];
+		one[0] = 1;
+		montgomery_mul(&mut tmp[..words], &acc[..words], &one[..words], n, key.n0_inverse);
+
+		for (chunk, word) in out.rchunks_mut(4).zip(&tmp[..words]) {
+			BE::write_u32(chunk, *word);
+		}
+		true
+	}
+}
+
+/// Computes a * b / R mod n for little endian words, R being 2^(32 * words).
+fn montgomery_mul(out: &mut [u32], a: &[u32], b: &[u32], n: &[u32], n0_inverse: u32) {
+	let len = n.len();
+	let mut t = [0u32; MAX_RSA_WORDS + 2];
+	for &a in a {
+		let mut carry = 0u64;
+		for j in 0..len {
+			let sum = t[j] as u64 + a as u64 * b[j] as u64 + carry;
+			t[j] = sum as u32;
+			carry = sum >> 32;
+		}
+		let sum = t[len] as u64 + carry;
+		t[len] = sum as u32;
+		t[len + 1] = (sum >> 32) as u32;
+
+		let m = t[0].wrapping_mul(n0_inverse);
+		let mut carry = (t[0] as u64 + m as u64 * n[0] as u64) >> 32;
+		for j in 1..len {
+			let sum = t[j] as u64 + m as u64 * n[j] as u64 + carry;
+			t[j - 1] = sum as u32;
+			carry = sum >> 32;
+		}
+		let sum = t[len] as u64 + carry;
+		t[len - 1] = sum as u32;
+		t[len] = t[len + 1] + (sum >> 32) as u32;
+		t[len + 1] = 0;
+	}
+	// t < 2n, the borrow of the subtraction cancels t[len]
+	if t[len] != 0 || !less(&t[..len], n) {
+		let mut borrow = 0i64;
+		for j in 0..len {
+			let diff = t[j] as i64 - n[j] as i64 + borrow;
+			t[j] = diff as u32;
+			borrow = diff >> 32;
+		}
+	}
+	out.copy_from_slice(&t[..len]);
+}
+
+fn less(a: &[u32], b: &[u32]) -> bool {
+	a.iter().rev().cmp(b.iter().rev()).is_lt()
+}
+
+/// The DER encoded DigestInfo preceding digests in PKCS #1 v1.5 signatures.
+fn digest_info(algorithm: Algorithm) -> Option<&'static [u8]> {
+	match algorithm {
+		Algorithm::Sha1 => Some(&[0x30, 0x21, 0x30, 0x09, 0x06, 0x05, 0x2b, 0x0e, 0x03, 0x02, 0x1a, 0x05, 0x00, 0x04, 0x14]),
+		Algorithm::Sha256 => Some(&[0x30, 0x31, 0x30, 0x0d, 0x06, 0x09, 0x60, 0x86, 0x48, 0x01, 0x65, 0x03, 0x04, 0x02, 0x01, 0x05, 0x00, 0x04, 0x20]),
+		Algorithm::Sha384 => Some(&[0x30, 0x41, 0x30, 0x0d, 0x06, 0x09, 0x60, 0x86, 0x48, 0x01, 0x65, 0x03, 0x04, 0x02, 0x02, 0x05, 0x00, 0x04, 0x30]),
+		Algorithm::Sha512 => Some(&[0x30, 0x51, 0x30, 0x0d, 0x06, 0x09, 0x60, 0x86, 0x48, 0x01, 0x65, 0x03, 0x04, 0x02, 0x03, 0x05, 0x00, 0x04, 0x40]),
+		Algorithm::Crc32 => None,
+	}
+}
+
+/// Checks that [signature] signs [digest] with [key].
+///
+/// [algo] is the 'algo' of the signature, such as "sha256,rsa2048", and
+/// [padding] its 'padding', "pkcs-1.5" (the default) or "pss". [digests]
+/// computes the mask of PSS signatures and [crypto] the public key
+/// operation.
+pub fn verify<D, C>(key: &Key, algo: &str, padding: Option<&str>, digest: &Digest, signature: &[u8],
+		digests: &mut D, crypto: &mut C) -> Result<(), SignatureError>
+		where D: Backend + ?Sized, C: Crypto + ?Sized {
+	let (checksum, scheme) = parse_algo(algo).ok_or(SignatureError::Unsupported)?;
+	if key.algo().is_some_and(|key_algo| key_algo != algo) {
+		return Err(SignatureError::KeyMismatch);
+	}
+	match scheme {
+		Scheme::Rsa => {
+			let key = key.rsa()?;
+			let len = key.modulus.len();
+			if signature.len() != len || len > MAX_RSA_BYTES {
+				return Err(SignatureError::Invalid);
+			}
+			let mut message = [0u8; MAX_RSA_BYTES];
+			let message = &mut message[..len];
+			if !crypto.rsa_public(&key, signature, message) {
+				return Err(SignatureError::Unsupported);
+			}
+			match padding.unwrap_or("pkcs-1.5") {
+				"pkcs-1.5" => verify_pkcs1(message, checksum, digest.as_ref()),
+				"pss" => verify_pss(message, key.bits as usize, checksum, digest.as_ref(), digests),
+				_ => Err(SignatureError::Unsupported),
+			}
+		},
+		Scheme::Ecdsa => match crypto.ecdsa_verify(&key.ecdsa()?, digest.as_ref(), signature) {
+			Some(true) => Ok(()),
+			Some(false) => Err(SignatureError::Invalid),
+			None => Err(SignatureError::Unsupported),
+		},
+	}
+}
+
+/// Checks the EMSA-PKCS1-v1_5 encoding of [digest]: 00 01 ff.. 00 info digest.
+fn verify_pkcs1(message: &[u8], checksum: Algorithm, digest: &[u8]) -> Result<(), SignatureError> {
+	let info = digest_info(checksum).ok_or(SignatureError::Unsupported)?;
+	let padding = message.len().checked_sub(info.len() + digest.len() + 3)
+		.filter(|&padding| padding >= 8)
+		.ok_or(SignatureError::Invalid)?;
+	let (head, rest) = message.split_at(2 + padding + 1);
+	let (prefix, tail) = rest.split_at(info.len());
+	let valid = head[..2] == [0, 1] && head[2..2 + padding].iter().all(|&b| b == 0xff) && head[2 + padding] == 0
+		&& prefix == info && tail == digest;
+	if valid { Ok(()) } else { Err(SignatureError::Invalid) }
+}
+
+/// Checks the EMSA-PSS encoding of [digest], accepting any salt length.
+fn verify_pss<D: Backend + ?Sized>(message: &[u8], bits: usize, checksum: Algorithm, digest: &[u8],
+		digests: &mut D) -> Result<(), SignatureError> {
+	let hash_len = checksum.size();
+	let em_bits = bits - 1;
+	let em = match message.len() - em_bits.div_ceil(8) {
+		0 => message,
+		1 if message[0] == 0 => &message[1..],
+		_ => return Err(SignatureError::Invalid),
+	};
+	if em.len() < hash_len + 2 || em[em.len() - 1] != 0xbc {
+		return Err(SignatureError::Invalid);
+	}
+	let (masked, hash) = em[..em.len() - 1].split_at(em.len() - hash_len - 1);
+	let zero_bits = 8 * em.len() - em_bits;
+	if zero_bits > 0 && masked[0] >> (8 - zero_bits) != 0 {
+		return Err(SignatureError::Invalid);
+	}
+
+	let mut db = [0u8; MAX_RSA_BYTES];
+	let db = &mut db[..masked.len()];
+	db.copy_from_slice(masked);
+	for (counter, chunk) in db.chunks_mut(hash_len).enumerate() {
+		let counter = (counter as u32).to_be_bytes();
+		let mask = digests.digest(checksum, &mut [hash, &counter[..]].iter().copied())
+			.ok_or(SignatureError::Unsupported)?;
+		chunk.iter_mut().zip(mask.as_ref()).for_each(|(byte, mask)| *byte ^= mask);
+	}
+	db[0] &= 0xff >> zero_bits;
+	let salt = match db.iter().position(|&b| b != 0) {
+		Some(i) if db[i] == 1 => &db[i + 1..],
+		_ => return Err(SignatureError::Invalid),
+	};
+
+	let expected = digests.digest(checksum, &mut [&[0u8; 8][..], digest, salt].iter().copied())
+		.ok_or(SignatureError::Unsupported)?;
+	if expected.as_ref() == hash { Ok(()) } else { Err(SignatureError::Invalid) }
+}
+
+fn string<'k>(node: &Node<'k>, name: &str) -> Option<&'k str> {
+	node.property_value::<Option<::StringList>>(name).ok()??.strings().next()
+}
+
+fn cell(node: &Node, name: &'static str) -> Result<u32, SignatureError> {
+	node.property_value(name).map_err(|_| SignatureError::Malformed(name))
+}
@@ -1,17 +1,17 @@
 use core::str;
 use core::fmt;
 
-#[derive(Debug)]
+#[derive(Debug, Default)]
 pub struct StringList<'a> {
 	raw: &'a str,
 }
 
 impl<'a> StringList<'a> {
 	pub fn from_utf8(data: &'a [u8]) -> Result<Self, str::Utf8Error> {
-		str::from_utf8(data).map(|raw| Self { raw: raw })
+		str::from_utf8(data).map(|raw| Self { raw })
 	}
 	
-	pub fn strings(&self) -> Strings {
+	pub fn strings(&self) -> Strings<'a> {
 		Strings(self.raw.split_terminator('\0'))
 	}
 
@@ -27,7 +27,7 @@ impl<'a> fmt::Display for StringList<'a> {
 	fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
 		write!(f, "[")?;
 		let mut strings = self.strings();
-		for string in strings.nth(0) {
+		if let Some(string) = strings.next() {
 			write!(f, "\"{}\"", string)?;
 		}
 		for string in strings {
@@ -37,7 +37,7 @@ impl<'a> fmt::Display for StringList<'a> {
 	}
 }
 
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub struct Strings<'a> (str::SplitTerminator<'a, char>);
 
 impl<'a> Iterator for Strings<'a> {
@@ -47,3 +47,13 @@ impl<'a> Iterator for Strings<'a> {
 		self.0.next()
 	}
 }
+
+/// Tests if [raw] is a list of printable ASCII strings.
+///
+/// Used to guess the type of values without a known type, like dtc does when
+/// decompiling. All strings must be non-empty and NUL-terminated.
+pub fn is_printable(raw: &[u8]) -> bool {
+	raw.last() == Some(&0) && raw[0] != 0
+		&& !raw.windows(2).any(|w| w == [0, 0])
+		&& raw.iter().all(|&b| b == 0 || (0x20..0x7f).contains(&b))
+}
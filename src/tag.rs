@@ -0,0 +1,158 @@
+//! Low level access to the tags of the structure block, like libfdt's
+//! `fdt_next_tag`.
+//!
+//! A [TagCursor] yields the tags of the structure block one by one without
+//! building nodes or resolving property names, for passes over the whole
+//! tree such as statistics, validators or serializers. Every read is bounds
+//! checked, a corrupt blob ends the iteration with an error rather than a
+//! panic.
+//!
+//! # Examples
+//!
+//! ```
+//! use fdt::FDT;
+//! use fdt::tag::Tag;
+//! let dtb = include_bytes!("../tests/dt.dtb").as_ptr();
+//!
+//! let fdt;
+//! unsafe { fdt = FDT::from_raw(dtb).unwrap(); }
+//!
+//! // find the largest property value
+//! let mut cursor = fdt.tags();
+//! let mut largest = (0, "");
+//! while let Some(tag) = cursor.next() {
+//!     if let Tag::Prop { name_off, value, .. } = tag.unwrap() {
+//!         if value.len() > largest.0 {
+//!             largest = (value.len(), cursor.string(name_off).unwrap());
+//!         }
+//!     }
+//! }
+//! println!("largest property: {} ({} bytes)", largest.1, largest.0);
+//! ```
+
+use core::str;
+
+use byteorder::{ByteOrder, BE};
+use memchr::memchr;
+
+use blob::{FDT_BEGIN_NODE, FDT_END_NODE, FDT_PROP, FDT_NOP, FDT_END};
+use error::Error;
+
+/// A tag of the structure block.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Tag<'buf> {
+	/// The start of a node named [name], whose tag is at [offset].
+	BeginNode { name: &'buf str, offset: usize },
+	/// A property whose tag is at [offset]. The name is at [name_off] in the
+	/// strings block, see [TagCursor::string()].
+	Prop { name_off: u32, value: &'buf [u8], offset: usize },
+	/// The end of the innermost open node.
+	EndNode,
+	/// A tag to be ignored, left by in place edits.
+	Nop,
+	/// The end of the structure block.
+	End,
+}
+
+/// A cursor over the tags of the structure block, see the
+/// [module documentation](self).
+///
+/// The iterator ends after [Tag::End] or the first error.
+#[derive(Clone, Debug)]
+pub struct TagCursor<'buf> {
+	structure: &'buf [u8],
+	strings: &'buf [u8],
+	offset: usize,
+	done: bool,
+	legacy: bool,
+}
+
+impl<'buf> TagCursor<'buf> {
+	pub(crate) fn new(structure: &'buf [u8], strings: &'buf [u8], legacy: bool) -> Self {
+		TagCursor { structure, strings, offset: 0, done: false, legacy }
+	}
+
+	/// Returns the offset of the next tag within the structure block.
+	pub fn offset(&self) -> usize {
+		self.offset
+	}
+
+	/// Moves the cursor to the tag at [offset], such as the offset of a
+	/// [Node](::Node).
+	///
+	/// # Errors
+	///
+	/// Returns [Error::BadOffset] if [offset] isn't aligned to a tag or is
+	/// outside of the structure block.
+	pub fn seek(&mut self, offset: usize) -> Result<(), Error> {
+		if !offset.is_multiple_of(4) || offset >= self.structure.len() {
+			return Err(Error::BadOffset);
+		}
+		self.offset = offset;
+		self.done = false;
+		Ok(())
+	}
+
+	/// Returns the string at [offset] of the strings block, such as the
+	/// name of a property.
+	///
+	/// Like libfdt's `fdt_get_string` this returns [Error::BadOffset] for
+	/// offsets outside of the block and [Error::Truncated] for strings which
+	/// aren't terminated within it.
+	pub fn string(&self, offset: u32) -> Result<&'buf str, Error> {
+		let raw = self.strings.get(offset as usize..).filter(|raw| !raw.is_empty()).ok_or(Error::BadOffset)?;
+		let len = memchr(b'\0', raw).ok_or(Error::Truncated)?;
+		str::from_utf8(&raw[..len]).map_err(Error::BadUtf8)
+	}
+
+	fn u32_at(&self, offset: usize) -> Result<u32, Error> {
+		self.structure.get(offset..offset + 4).map(BE::read_u32).ok_or(Error::Truncated)
+	}
+
+	fn read(&mut self) -> Result<Tag<'buf>, Error> {
+		let offset = self.offset;
+		let (tag, next) = match self.u32_at(offset)? {
+			FDT_BEGIN_NODE => {
+				let raw = self.structure.get(offset + 4..).ok_or(Error::Truncated)?;
+				let len = memchr(b'\0', raw).ok_or(Error::Truncated)?;
+				let mut name = str::from_utf8(&raw[..len]).map_err(Error::BadUtf8)?;
+				if self.legacy {
+					// full paths, as for Node::name only the last component
+					name = name.rsplit('/').next().unwrap_or(name);
+				}
+				(Tag::BeginNode { name, offset }, offset + 4 + len + 1)
+			},
+			FDT_PROP => {
+				let len = self.u32_at(offset + 4)? as usize;
+				let name_off = self.u32_at(offset + 8)?;
+				let mut start = offset + 12;
+				if self.legacy && len >= 8 && !start.is_multiple_of(8) {
+					start += 4;
+				}
+				let value = start.checked_add(len)
+					.and_then(|end| self.structure.get(start..end))
+					.ok_or(Error::Truncated)?;
+				(Tag::Prop { name_off, value, offset }, start + len)
+			},
+			FDT_END_NODE => (Tag::EndNode, offset + 4),
+			FDT_NOP => (Tag::Nop, offset + 4),
+			FDT_END => (Tag::End, offset + 4),
+			_ => return Err(Error::BadStructure),
+		};
+		self.offset = (next + 3) & !3;
+		Ok(tag)
+	}
+}
+
+impl<'buf> Iterator for TagCursor<'buf> {
+	type Item = Result<Tag<'buf>, Error>;
+
+	fn next(&mut self) -> Option<Self::Item> {
+		if self.done {
+			return None;
+		}
+		let tag = self.read();
+		self.done = matches!(tag, Ok(Tag::End) | Err(_));
+		Some(tag)
+	}
+}
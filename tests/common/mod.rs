@@ -0,0 +1,70 @@
+//! Helpers shared by the integration tests.
+#![allow(dead_code)]
+
+use std::ops::Range;
+
+use fdt::FDT;
+#[cfg(feature = "alloc")]
+use fdt::owned::OwnedNode;
+
+/// Returns a copy of [dtb] modified by [patch].
+pub fn patched<F: FnOnce(&mut Vec<u8>)>(dtb: &[u8], patch: F) -> Vec<u8> {
+	let mut dtb = dtb.to_vec();
+	patch(&mut dtb);
+	dtb
+}
+
+/// Returns the big endian word at [offset] of [dtb], such as a header field.
+pub fn get(dtb: &[u8], offset: usize) -> u32 {
+	u32::from_be_bytes([dtb[offset], dtb[offset + 1], dtb[offset + 2], dtb[offset + 3]])
+}
+
+/// Overwrites the big endian word at [offset] of [dtb] with [value].
+pub fn set(dtb: &mut [u8], offset: usize, value: u32) {
+	dtb[offset..offset + 4].copy_from_slice(&value.to_be_bytes());
+}
+
+/// Parses [dtb], leaking it so the tree lives until the end of the test.
+pub fn leak(dtb: Vec<u8>) -> FDT<'static> {
+	let dtb: &'static [u8] = Box::leak(dtb.into_boxed_slice());
+	FDT::from_bytes(dtb).unwrap()
+}
+
+/// Returns the offset of [part], a slice of [dtb], in [dtb].
+pub fn offset_of(dtb: &[u8], part: &[u8]) -> usize {
+	part.as_ptr() as usize - dtb.as_ptr() as usize
+}
+
+/// Returns the range of the value of property [prop] of the node at [path]
+/// in [dtb].
+pub fn value_range(dtb: &[u8], path: &str, prop: &str) -> Range<usize> {
+	let fdt = FDT::from_bytes(dtb).unwrap();
+	let node = match path {
+		"/" => fdt.nodes().next().unwrap(),
+		path => fdt.nodes().with_path(path).next().unwrap(),
+	};
+	let raw = node.property(prop).unwrap().raw();
+	let start = offset_of(dtb, raw);
+	start..start + raw.len()
+}
+
+/// Returns a node named [name] with [properties] and [children].
+#[cfg(feature = "alloc")]
+pub fn node(name: &str, properties: &[(&str, &[u8])], children: Vec<OwnedNode>) -> OwnedNode {
+	let mut node = OwnedNode::new(name);
+	for &(prop, value) in properties {
+		node.set_property(prop, value);
+	}
+	node.children = children;
+	node
+}
+
+/// Returns [cells] as the big endian value of a property.
+pub fn cells(cells: &[u32]) -> Vec<u8> {
+	cells.iter().flat_map(|cell| cell.to_be_bytes()).collect()
+}
+
+/// Decodes the hex string [digest].
+pub fn hex(digest: &str) -> Vec<u8> {
+	(0..digest.len()).step_by(2).map(|i| u8::from_str_radix(&digest[i..i + 2], 16).unwrap()).collect()
+}
@@ -0,0 +1,271 @@
+#!/usr/bin/env python3
+"""Generates signed.itb and signed.dtb, a signed FIT and its control tree.
+
+The blobs are built without this crate, the way `mkimage -f signed.its -k keys
+-K signed.dtb -r` lays them out: dtc writes the source tree, then libfdt's
+fdt_setprop() inserts each property mkimage adds in front of the properties of
+its node and appends new names to the strings block. The kernel and the
+configuration conf-1 are signed with a fresh sha256,rsa2048 key whose public
+part goes into signed.dtb, required for configurations.
+
+Needs the python 'cryptography' package. Run from the tests directory.
+"""
+
+import hashlib
+import struct
+
+from cryptography.hazmat.primitives import hashes
+from cryptography.hazmat.primitives.asymmetric import padding, rsa, utils
+
+FDT_BEGIN_NODE, FDT_END_NODE, FDT_PROP, FDT_END = 1, 2, 3, 9
+ALGO = "sha256,rsa2048"
+TIMESTAMP = 0x65000000
+
+
+class Node:
+    def __init__(self, name, props=(), children=()):
+        self.name = name
+        self.props = list(props)
+        self.children = list(children)
+
+    def child(self, name):
+        return next(child for child in self.children if child.name == name)
+
+    def prop(self, name):
+        return next(value for prop, value in self.props if prop == name)
+
+
+def string(value):
+    return value.encode() + b"\0"
+
+
+def u32(value):
+    return struct.pack(">I", value)
+
+
+class Blob:
+    """A device tree with the strings block kept apart, as libfdt edits it."""
+
+    def __init__(self, root):
+        self.root = root
+        self.strings = b""
+        # dtc adds the names in the order of the source
+        self.walk(root)
+
+    def walk(self, node):
+        for prop, _ in node.props:
+            self.intern(prop)
+        for child in node.children:
+            self.walk(child)
+
+    def intern(self, name):
+        # like fdt_find_string_(), names may share the tail of another one
+        offset = self.strings.find(string(name))
+        if offset < 0:
+            offset = len(self.strings)
+            self.strings += string(name)
+        return offset
+
+    def setprop(self, node, name, value):
+        for i, (prop, _) in enumerate(node.props):
+            if prop == name:
+                node.props[i] = (name, value)
+                return
+        self.intern(name)
+        node.props.insert(0, (name, value))
+
+    def structure(self):
+        out = bytearray()
+
+        def pad():
+            out.extend(b"\0" * (-len(out) % 4))
+
+        def emit(node):
+            out.extend(u32(FDT_BEGIN_NODE) + string(node.name))
+            pad()
+            for prop, value in node.props:
+                out.extend(u32(FDT_PROP) + u32(len(value)) + u32(self.intern(prop)) + value)
+                pad()
+            for child in node.children:
+                emit(child)
+            out.extend(u32(FDT_END_NODE))
+
+        emit(self.root)
+        out.extend(u32(FDT_END))
+        return bytes(out)
+
+    def dtb(self):
+        structure = self.structure()
+        off_rsvmap = 40
+        off_struct = off_rsvmap + 16
+        off_strings = off_struct + len(structure)
+        total = off_strings + len(self.strings)
+        header = struct.pack(">10I", 0xd00dfeed, total, off_struct, off_strings, off_rsvmap,
+                             17, 16, 0, len(self.strings), len(structure))
+        return header + b"\0" * 16 + structure + self.strings
+
+
+def regions(structure, strings, nodes):
+    """Returns the parts of [structure] U-Boot's fdt_find_regions() selects."""
+    unsigned = ("data", "data-size", "data-position", "data-offset")
+    stack, names, want, start, found = [], [], 0, None, []
+    after = 0
+    while True:
+        offset = after
+        tag = struct.unpack(">I", structure[offset:offset + 4])[0]
+        if tag == FDT_BEGIN_NODE:
+            end = structure.index(b"\0", offset + 4)
+            after = (end + 4) & ~3
+            names.append(structure[offset + 4:end].decode())
+            path = "/" + "/".join(names[1:])
+            stop = offset if want == 1 else after
+            stack.append(want)
+            if path in nodes:
+                want = 2
+            elif want:
+                want -= 1
+            else:
+                stop = offset
+            include = want
+        elif tag == FDT_END_NODE:
+            after = stop = offset + 4
+            include = want
+            want = stack.pop()
+            names.pop()
+        elif tag == FDT_PROP:
+            length, name = struct.unpack(">II", structure[offset + 4:offset + 12])
+            after = (offset + 12 + length + 3) & ~3
+            stop = offset
+            include = want >= 2 and strings[name:strings.index(b"\0", name)].decode() not in unsigned
+        else:
+            after = stop = offset + 4
+            include = True
+        if include and start is None:
+            start = offset
+        if not include and start is not None:
+            found.append(structure[start:stop])
+            start = None
+        if tag == FDT_END:
+            found.append(structure[start:after])
+            return found
+
+
+def sign(key, digest):
+    return key.sign(digest, padding.PKCS1v15(), utils.Prehashed(hashes.SHA256()))
+
+
+def write_sig(blob, node, value, hashed=None):
+    # the order of fit_image_write_sig(), each property going in front
+    blob.setprop(node, "value", value)
+    blob.setprop(node, "signer-name", string("mkimage"))
+    blob.setprop(node, "signer-version", string("2024.01"))
+    blob.setprop(node, "timestamp", u32(TIMESTAMP))
+    if hashed:
+        nodes, strings = hashed
+        blob.setprop(node, "hashed-nodes", b"".join(string(path) for path in nodes))
+        blob.setprop(node, "hashed-strings", u32(0) + u32(strings))
+
+
+def fit(key):
+    empty = Blob(Node("")).dtb()
+    kernel = bytes(range(256)) * 4
+    root = Node("", [
+        ("description", string("FIT signed like mkimage -k")),
+        ("#address-cells", u32(1)),
+    ], [
+        Node("images", [], [
+            Node("kernel-1", [
+                ("description", string("kernel")),
+                ("data", kernel),
+                ("type", string("kernel")),
+                ("arch", string("arm64")),
+                ("os", string("linux")),
+                ("compression", string("none")),
+                ("load", u32(0x80080000)),
+                ("entry", u32(0x80080000)),
+            ], [
+                Node("hash-1", [("algo", string("sha256"))]),
+                Node("signature-1", [("algo", string(ALGO)), ("key-name-hint", string("dev"))]),
+            ]),
+            Node("fdt-1", [
+                ("description", string("device tree")),
+                ("data", empty),
+                ("type", string("flat_dt")),
+                ("arch", string("arm64")),
+                ("compression", string("none")),
+            ], [
+                Node("hash-1", [("algo", string("sha256"))]),
+            ]),
+        ]),
+        Node("configurations", [("default", string("conf-1"))], [
+            Node("conf-1", [
+                ("description", string("boot")),
+                ("kernel", string("kernel-1")),
+                ("fdt", string("fdt-1")),
+            ], [
+                Node("signature-1", [
+                    ("algo", string(ALGO)),
+                    ("key-name-hint", string("dev")),
+                    ("sign-images", string("fdt") + string("kernel")),
+                ]),
+            ]),
+        ]),
+    ])
+    blob = Blob(root)
+    blob.setprop(root, "timestamp", u32(TIMESTAMP))
+
+    images = root.child("images")
+    for image in images.children:
+        data = image.prop("data")
+        for sub in image.children:
+            if sub.name.startswith("hash"):
+                blob.setprop(sub, "value", hashlib.sha256(data).digest())
+            else:
+                write_sig(blob, sub, sign(key, hashlib.sha256(data).digest()))
+
+    conf = root.child("configurations").child("conf-1")
+    signature = conf.child("signature-1")
+    nodes = ["/", "/configurations/conf-1"]
+    for kind in ("fdt", "kernel"):
+        name = conf.prop(kind).rstrip(b"\0").decode()
+        nodes.append("/images/" + name)
+        nodes += ["/images/%s/%s" % (name, hash.name) for hash in images.child(name).children
+                  if hash.name.startswith("hash")]
+    strings = len(blob.strings)
+    digest = hashlib.sha256()
+    for region in regions(blob.structure(), blob.strings, nodes):
+        digest.update(region)
+    digest.update(blob.strings[:strings])
+    write_sig(blob, signature, sign(key, digest.digest()), (nodes, strings))
+    return blob.dtb()
+
+
+def control(key):
+    numbers = key.public_key().public_numbers()
+    bits = key.key_size
+    root = Node("", [("model", string("control"))])
+    blob = Blob(root)
+    node = Node("key-dev")
+    root.children.insert(0, Node("signature", [], [node]))
+    # the order of rsa_add_verify_data()
+    blob.setprop(node, "key-name-hint", string("dev"))
+    blob.setprop(node, "rsa,num-bits", u32(bits))
+    blob.setprop(node, "rsa,n0-inverse", u32(-pow(numbers.n, -1, 1 << 32) % (1 << 32)))
+    blob.setprop(node, "rsa,exponent", struct.pack(">Q", numbers.e))
+    blob.setprop(node, "rsa,modulus", numbers.n.to_bytes(bits // 8, "big"))
+    blob.setprop(node, "rsa,r-squared", pow(2, 2 * bits, numbers.n).to_bytes(bits // 8, "big"))
+    blob.setprop(node, "algo", string(ALGO))
+    blob.setprop(node, "required", string("conf"))
+    return blob.dtb()
+
+
+def main():
+    key = rsa.generate_private_key(public_exponent=65537, key_size=2048)
+    with open("signed.itb", "wb") as out:
+        out.write(fit(key))
+    with open("signed.dtb", "wb") as out:
+        out.write(control(key))
+
+
+if __name__ == "__main__":
+    main()
@@ -2,7 +2,7 @@ extern crate fdt;
 
 use fdt::{FDT, NodeIterator};
 
-const DTB: &'static [u8] = include_bytes!("dt.dtb");
+const DTB: &[u8] = include_bytes!("dt.dtb");
 
 fn init() -> FDT<'static> {
 	unsafe {
@@ -39,3 +39,83 @@ fn test_fetching_node() {
 	assert_eq!(cpus.unwrap().name(), "cpus");
 	assert!(with_name.next().is_none());
 }
+
+#[test]
+fn test_children_and_siblings() {
+	let fdt = init();
+	let soc = fdt.nodes().with_name("soc").next().unwrap();
+	let children: Vec<&str> = soc.children().map(|child| child.name()).collect();
+	let direct: Vec<&str> = soc.subnodes()
+		.filter(|node| node.depth() == soc.depth() + 1)
+		.map(|node| node.name())
+		.collect();
+	assert_eq!(children, direct);
+
+	let mut siblings = vec![];
+	let mut child = soc.children().next();
+	while let Some(node) = child {
+		siblings.push(node.name());
+		child = node.next_sibling();
+	}
+	assert_eq!(siblings, children);
+	assert!(fdt.nodes().next().unwrap().next_sibling().is_none());
+}
+
+#[test]
+fn test_subnodes_stop_at_subtree() {
+	let fdt = init();
+	let cpus = fdt.nodes().with_name("cpus").next().unwrap();
+	let mut subnodes = cpus.subnodes();
+	assert_eq!(subnodes.by_ref().count(), 4);
+	assert!(subnodes.next().is_none());
+}
+
+fn be32(raw: &[u8], offset: usize) -> usize {
+	u32::from_be_bytes([raw[offset], raw[offset + 1], raw[offset + 2], raw[offset + 3]]) as usize
+}
+
+#[test]
+fn test_cell_defaults() {
+	let fdt = init();
+	let root = fdt.nodes().next().unwrap();
+	assert_eq!((root.address_cells(), root.size_cells()), (1, 1));
+	// the defaults of the devicetree specification, as used to split reg
+	let chosen = fdt.nodes().with_name("chosen").next().unwrap();
+	assert_eq!((chosen.address_cells(), chosen.size_cells()), (2, 1));
+}
+
+#[test]
+fn test_malformed() {
+	let nodes = init().nodes().count();
+	// the root node's first property follows its 8 byte BEGIN_NODE and name
+	let prop = be32(DTB, 8) + 8;
+
+	let mut dtb = DTB.to_vec();
+	dtb[prop + 8..prop + 12].copy_from_slice(&0xffff_fff0u32.to_be_bytes());
+	let fdt = FDT::from_bytes(&dtb).unwrap();
+	let root = fdt.nodes().next().unwrap();
+	let mut props = root.properties();
+	assert!(props.next().is_none());
+	assert!(props.next().is_none());
+
+	let mut dtb = DTB.to_vec();
+	dtb[prop..prop + 4].copy_from_slice(&9u32.to_be_bytes());
+	let fdt = FDT::from_bytes(&dtb).unwrap();
+	let mut iter = fdt.nodes();
+	assert_eq!(iter.by_ref().count(), 1);
+	assert!(iter.next().is_none());
+
+	let mut dtb = DTB.to_vec();
+	let size = be32(DTB, 36);
+	dtb[36..40].copy_from_slice(&(size as u32 / 2).to_be_bytes());
+	let fdt = FDT::from_bytes(&dtb).unwrap();
+	let count = fdt.nodes().count();
+	assert!(count > 0 && count < nodes);
+
+	let mut dtb = DTB.to_vec();
+	let value = prop + 12;
+	dtb[value] = 0xff;
+	let fdt = FDT::from_bytes(&dtb).unwrap();
+	let root = fdt.nodes().next().unwrap();
+	assert_eq!(root.properties().next().unwrap().as_str(), "");
+}
@@ -0,0 +1,95 @@
+extern crate fdt;
+
+use fdt::{FDT, NodeIterator};
+use fdt::checks::{self, Check, Problem, Severity};
+
+mod common;
+use common::{leak, offset_of, patched, value_range};
+
+static DTB: &[u8] = include_bytes!("dt.dtb");
+
+fn init() -> FDT<'static> {
+	unsafe {
+		FDT::from_raw(DTB.as_ptr()).unwrap()
+	}
+}
+
+fn collect(fdt: &FDT<'static>) -> Vec<(String, Problem<'static>)> {
+	let mut problems = Vec::new();
+	let count = checks::check(fdt, |diagnostic| {
+		problems.push((diagnostic.node.path().to_string(), diagnostic.problem));
+	});
+	assert_eq!(count, problems.len());
+	problems
+}
+
+#[test]
+fn test_clean_tree() {
+	let fdt = init();
+	let problems = collect(&fdt);
+	assert!(problems.iter().all(|problem| problem.1.check().severity() == Severity::Warning));
+	assert!(problems.contains(&("/memory".to_string(), Problem::MissingUnitAddress)));
+	assert!(problems.contains(&("/cpus/cpu@1".to_string(),
+		Problem::UnitAddressMismatch { unit: "1", reg: 0xf01 })));
+	assert!(!problems.iter().any(|problem| problem.0.starts_with("/soc/serial")));
+}
+
+#[test]
+fn test_diagnostic_format() {
+	let fdt = init();
+	let mut lines = Vec::new();
+	checks::check(&fdt, |diagnostic| lines.push(diagnostic.to_string()));
+	assert!(lines.contains(&"Warning (unit_address_vs_reg): /memory: \
+		node has a reg or ranges property, but no unit name".to_string()));
+	assert_eq!(Problem::DuplicatePhandle(1).check(), Check::DuplicatePhandles);
+	assert_eq!(Check::DuplicatePhandles.name(), "explicit_phandles");
+}
+
+#[test]
+fn test_dangling_and_duplicate_phandles() {
+	// point the first clock of the pl011 at a missing provider
+	let value = value_range(DTB, "/soc/serial@7e201000", "clocks");
+	let fdt = leak(patched(DTB, |dtb| dtb[value.start + 3] = 0x7f));
+	let problems = collect(&fdt);
+	assert!(problems.contains(&("/soc/serial@7e201000".to_string(),
+		Problem::DanglingPhandle { property: "clocks", phandle: 0x7f })));
+
+	// give the pl011 the phandle of the spi controller
+	let value = value_range(DTB, "/soc/serial@7e201000", "phandle");
+	let fdt = leak(patched(DTB, |dtb| dtb[value.start + 3] = 0x1f));
+	let problems = collect(&fdt);
+	assert!(problems.contains(&("/soc/spi@7e204000".to_string(), Problem::DuplicatePhandle(0x1f))));
+}
+
+#[test]
+fn test_reg_and_interrupts() {
+	// move the spi controller's registers to address 0
+	let value = value_range(DTB, "/soc/spi@7e204000", "reg");
+	let fdt = leak(patched(DTB, |dtb| dtb[value].copy_from_slice(&[0; 8])));
+	let problems = collect(&fdt);
+	assert!(problems.contains(&("/soc/spi@7e204000".to_string(),
+		Problem::UnitAddressMismatch { unit: "7e204000", reg: 0 })));
+
+	let value = value_range(DTB, "/", "interrupt-parent");
+	let fdt = leak(patched(DTB, |dtb| dtb[value.start + 3] = 0x7f));
+	let problems = collect(&fdt);
+	assert!(problems.contains(&("/".to_string(),
+		Problem::DanglingPhandle { property: "interrupt-parent", phandle: 0x7f })));
+	assert_eq!(checks::check_node(&fdt.nodes().with_name("spidev@0").next().unwrap(), |_| {}), 0);
+}
+
+#[test]
+fn test_node_names() {
+	let fdt = init();
+	let spi = fdt.nodes().with_name("spi@7e204000").next().unwrap();
+	let name = spi.children().nth(1).unwrap().name();
+	let start = offset_of(DTB, name.as_bytes());
+	let fdt = leak(patched(DTB, |dtb| {
+		dtb[start + name.len() - 1] = b'0';
+		dtb[start + 3] = b'!';
+	}));
+	let problems = collect(&fdt);
+	assert!(problems.contains(&("/soc/spi@7e204000/spi!ev@0".to_string(), Problem::BadNodeName)));
+	assert!(problems.contains(&("/soc/spi@7e204000/spi!ev@0".to_string(),
+		Problem::UnitAddressMismatch { unit: "0", reg: 1 })));
+}
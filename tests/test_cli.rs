@@ -0,0 +1,182 @@
+#![cfg(feature = "cli")]
+
+extern crate fdt;
+
+use std::fs;
+use std::path::PathBuf;
+use std::process::{Command, Output};
+
+use fdt::owned::{OwnedNode, OwnedTree};
+
+mod common;
+use common::get;
+
+const DTB: &[u8] = include_bytes!("dt.dtb");
+
+fn fdt(args: &[&str]) -> Output {
+	Command::new(env!("CARGO_BIN_EXE_fdt")).args(args).output().unwrap()
+}
+
+fn stdout(output: &Output) -> &str {
+	std::str::from_utf8(&output.stdout).unwrap()
+}
+
+/// A scratch directory named after a test, removed with its files when
+/// dropped.
+struct Scratch(PathBuf);
+
+impl Scratch {
+	fn new(test: &str) -> Self {
+		let dir = std::env::temp_dir().join(format!("fdt-cli-{}-{}", test, std::process::id()));
+		fs::create_dir_all(&dir).unwrap();
+		Scratch(dir)
+	}
+
+	/// Returns the path of file [name] in the directory.
+	fn path(&self, name: &str) -> String {
+		self.0.join(name).to_str().unwrap().to_string()
+	}
+
+	/// Writes [contents] to file [name] and returns its path.
+	fn file(&self, name: &str, contents: &[u8]) -> String {
+		let path = self.path(name);
+		fs::write(&path, contents).unwrap();
+		path
+	}
+}
+
+impl Drop for Scratch {
+	fn drop(&mut self) {
+		let _ = fs::remove_dir_all(&self.0);
+	}
+}
+
+#[test]
+fn test_dump() {
+	let scratch = Scratch::new("dump");
+	let output = fdt(&["dump", &scratch.file("test.dtb", DTB)]);
+	assert!(output.status.success());
+	let dts = stdout(&output);
+	assert!(dts.starts_with("/dts-v1/;\n\n/memreserve/ 0x0 0x1000;\n\n/ {\n\tcompatible = \"brcm,bcm2836\";\n"));
+	assert!(dts.contains("\n\tchosen {\n\t\tbootargs = \"\";\n\t};\n"));
+	assert!(dts.ends_with("\n};\n"));
+}
+
+#[test]
+fn test_get() {
+	let scratch = Scratch::new("get");
+	let dtb = &scratch.file("test.dtb", DTB);
+	let output = fdt(&["get", dtb, "/soc/i2c@7e205000", "clock-frequency", "compatible"]);
+	assert_eq!(stdout(&output), "100000\nbrcm,bcm2835-i2c\n");
+	let output = fdt(&["get", "-tx", dtb, "i2c0", "reg"]);
+	assert_eq!(stdout(&output), "7e205000 1000\n");
+	let output = fdt(&["get", dtb, "/soc/i2c@7e205000", "nope"]);
+	assert_eq!(output.status.code(), Some(1));
+	let output = fdt(&["get", "-tq", dtb, "/", "model"]);
+	assert_eq!(output.status.code(), Some(2));
+}
+
+#[test]
+fn test_put() {
+	let scratch = Scratch::new("put");
+	let dtb = &scratch.file("test.dtb", DTB);
+	assert!(fdt(&["put", dtb, "/chosen", "bootargs", "console=ttyAMA0", "quiet"]).status.success());
+	assert!(fdt(&["put", "-thi", "-p", dtb, "/extra/node", "values", "-2", "0x10"]).status.success());
+	assert!(fdt(&["put", "-d", dtb, "/soc/i2c@7e205000", "status"]).status.success());
+	assert!(fdt(&["put", "-r", dtb, "/leds"]).status.success());
+
+	assert_eq!(stdout(&fdt(&["get", dtb, "/chosen", "bootargs"])), "console=ttyAMA0 quiet\n");
+	assert_eq!(stdout(&fdt(&["get", "-thx", dtb, "/extra/node", "values"])), "fffe 10\n");
+	assert_eq!(fdt(&["get", dtb, "/soc/i2c@7e205000", "status"]).status.code(), Some(1));
+	assert_eq!(fdt(&["ls", dtb, "/leds"]).status.code(), Some(1));
+
+	assert_eq!(fdt(&["put", "-c", dtb, "/extra"]).status.code(), Some(1));
+	assert_eq!(fdt(&["put", "-c", dtb, "/missing/node"]).status.code(), Some(1));
+}
+
+#[test]
+fn test_ls_and_find() {
+	let scratch = Scratch::new("ls");
+	let dtb = &scratch.file("test.dtb", DTB);
+	let output = fdt(&["ls", "-l", dtb, "/cpus"]);
+	assert!(stdout(&output).starts_with("#address-cells = <0x1>\n#size-cells = <0x0>\nphandle = <0x43>\ncpu@0/\n"));
+	let output = fdt(&["find", "--compatible", "arm,pl011", dtb]);
+	assert_eq!(stdout(&output), "/soc/serial@7e201000\n");
+	assert_eq!(fdt(&["find", "--compatible", "nope", dtb]).status.code(), Some(1));
+}
+
+#[test]
+fn test_check_and_diff() {
+	let scratch = Scratch::new("diff");
+	let (a, b) = (&scratch.file("a.dtb", DTB), &scratch.file("b.dtb", DTB));
+	assert!(fdt(&["check", a]).status.success());
+	assert!(fdt(&["diff", a, b]).status.success());
+
+	fdt(&["put", "-tu", b, "/", "#size-cells", "2"]);
+	let output = fdt(&["diff", a, b]);
+	assert_eq!(output.status.code(), Some(1));
+	assert_eq!(stdout(&output), "~ /:#size-cells = 1 -> 2\n");
+}
+
+#[test]
+fn test_overlay() {
+	let scratch = Scratch::new("overlay");
+	let base = scratch.file("base.dtb", DTB);
+	let out = scratch.path("out.dtb");
+
+	let mut overlay = OwnedTree::default();
+	let mut fragment = OwnedNode::new("fragment@0");
+	fragment.set_property("target-path", b"/soc/i2c@7e205000\0");
+	let mut contents = OwnedNode::new("__overlay__");
+	contents.set_property("status", b"okay\0");
+	fragment.children.push(contents);
+	overlay.root.children.push(fragment);
+	let dtbo = scratch.file("overlay.dtbo", &overlay.to_dtb());
+
+	let output = fdt(&["overlay", "-i", &base, "-o", &out, &dtbo]);
+	assert!(output.status.success());
+	let output = fdt(&["get", &out, "i2c0", "status"]);
+	assert_eq!(stdout(&output), "okay\n");
+}
+
+/// A version 2 blob as old firmware writes it: a 36 byte header, nodes
+/// named by their full paths and values of 8 bytes or more aligned to 8.
+fn legacy() -> Vec<u8> {
+	let words = |words: &[u32]| words.iter().flat_map(|word| word.to_be_bytes()).collect::<Vec<_>>();
+	let structure = [
+		&words(&[1])[..], b"/\0\0\0",
+		&words(&[3, 12, 0, 0])[..], b"test,legacy\0",
+		&words(&[1])[..], b"/chosen\0",
+		&words(&[3, 14, 11, 0])[..], b"console=ttyS0\0\0\0",
+		&words(&[2, 2, 9])[..],
+	].concat();
+	let strings = b"compatible\0bootargs\0";
+	let off_dt_struct = 40 + 16;
+	let off_dt_strings = off_dt_struct + structure.len();
+	let totalsize = off_dt_strings + strings.len();
+	let header = words(&[0xd00dfeed, totalsize as u32, off_dt_struct as u32, off_dt_strings as u32, 40, 2, 1, 0]);
+	[&header[..], &[0; 8], &[0; 16], &structure, strings].concat()
+}
+
+#[test]
+fn test_legacy_version() {
+	let scratch = Scratch::new("legacy");
+	let dtb = &scratch.file("v2.dtb", &legacy());
+	let output = fdt(&["get", dtb, "/chosen", "bootargs"]);
+	assert_eq!(stdout(&output), "console=ttyS0\n");
+	assert!(stdout(&fdt(&["dump", dtb])).contains("\tcompatible = \"test,legacy\";\n"));
+
+	// what is written is converted to the latest version
+	let out = &scratch.path("v17.dtb");
+	assert!(fdt(&["put", "-o", out, dtb, "/chosen", "stdout-path", "serial0"]).status.success());
+	assert_eq!(get(&fs::read(out).unwrap(), 20), 17);
+	assert_eq!(stdout(&fdt(&["get", out, "/chosen", "bootargs"])), "console=ttyS0\n");
+}
+
+#[test]
+fn test_bad_input() {
+	let scratch = Scratch::new("bad-input");
+	let output = fdt(&["dump", &scratch.file("junk", b"not a device tree")]);
+	assert_eq!(output.status.code(), Some(1));
+	assert_eq!(fdt(&["frobnicate"]).status.code(), Some(2));
+}
@@ -0,0 +1,237 @@
+extern crate fdt;
+
+use fdt::{FDT, NodeIterator};
+use fdt::clock::{self, ClockKind, ClockProvider};
+use fdt::error::Error;
+#[cfg(feature = "alloc")]
+use fdt::owned::OwnedTree;
+
+mod common;
+#[cfg(feature = "alloc")]
+use common::{cells, node};
+
+const DTB: &[u8] = include_bytes!("dt.dtb");
+
+/// A uart clocked by a chain of fixed factor clocks from an oscillator, a
+/// provider with `clock-indices` and consumers assigning clocks.
+#[cfg(feature = "alloc")]
+fn tree() -> Vec<u8> {
+	let mut tree = OwnedTree::default();
+	tree.root.children = vec![
+		node("osc", &[
+			("compatible", b"fixed-clock\0"),
+			("#clock-cells", &cells(&[0])),
+			("clock-frequency", &cells(&[48_000_000])),
+			("clock-accuracy", &cells(&[100])),
+			("phandle", &cells(&[1])),
+		], vec![]),
+		node("pll", &[
+			("compatible", b"fixed-factor-clock\0"),
+			("#clock-cells", &cells(&[0])),
+			("clocks", &cells(&[1])),
+			("clock-mult", &cells(&[25])),
+			("clock-div", &cells(&[2])),
+			("phandle", &cells(&[2])),
+		], vec![]),
+		node("uart-div", &[
+			("compatible", b"fixed-factor-clock\0"),
+			("#clock-cells", &cells(&[0])),
+			("clocks", &cells(&[2])),
+			("clock-mult", &cells(&[1])),
+			("clock-div", &cells(&[12])),
+			("clock-output-names", b"uart_clk\0"),
+			("phandle", &cells(&[3])),
+		], vec![]),
+		node("gates", &[
+			("compatible", b"vendor,gates\0"),
+			("#clock-cells", &cells(&[1])),
+			("clock-indices", &cells(&[4, 7, 9])),
+			("clock-output-names", b"gate_a\0gate_b\0gate_c\0"),
+			("phandle", &cells(&[4])),
+		], vec![]),
+		node("big", &[
+			("compatible", b"fixed-clock\0"),
+			("#clock-cells", &cells(&[0])),
+			("clock-frequency", &cells(&[1, 0])),
+			("phandle", &cells(&[5])),
+		], vec![]),
+		node("loop", &[
+			("compatible", b"fixed-factor-clock\0"),
+			("#clock-cells", &cells(&[0])),
+			("clocks", &cells(&[6])),
+			("clock-mult", &cells(&[1])),
+			("clock-div", &cells(&[1])),
+			("phandle", &cells(&[6])),
+		], vec![]),
+		node("zero", &[
+			("compatible", b"fixed-factor-clock\0"),
+			("#clock-cells", &cells(&[0])),
+			("clocks", &cells(&[1])),
+			("clock-mult", &cells(&[1])),
+			("clock-div", &cells(&[0])),
+			("phandle", &cells(&[7])),
+		], vec![]),
+		node("serial", &[
+			("clocks", &cells(&[3, 4, 7])),
+			("clock-names", b"baud\0bus\0"),
+			("assigned-clocks", &cells(&[4, 9, 0, 4, 4])),
+			("assigned-clock-parents", &cells(&[1, 0, 5])),
+			("assigned-clock-rates", &cells(&[0, 0, 1_000_000])),
+		], vec![]),
+		node("wide", &[
+			("assigned-clocks", &cells(&[4, 7])),
+			("assigned-clock-rates-u64", &cells(&[2, 0])),
+		], vec![]),
+		node("broken", &[
+			("clocks", &cells(&[6, 7, 0])),
+			("assigned-clocks", &cells(&[4, 7, 8])),
+		], vec![]),
+	];
+	tree.to_dtb()
+}
+
+#[test]
+fn test_consumers() {
+	let fdt = FDT::from_bytes(DTB).unwrap();
+	let uart = fdt.nodes().with_name("serial@7e201000").next().unwrap();
+	let clocks: Vec<_> = clock::clocks(&uart).unwrap().map(Result::unwrap).collect();
+	assert_eq!(clocks.len(), 2);
+	assert_eq!(clocks[0].provider().name(), "cprman@7e101000");
+	assert_eq!(clocks[0].args().collect::<Vec<_>>(), [19]);
+	assert_eq!(clocks[0].index(), Some(19));
+	assert_eq!(clocks[0].name(), None);
+	assert_eq!(clocks[0].rate(), Ok(None));
+	assert_eq!(clock::clock_names(&uart).collect::<Vec<_>>(), ["uartclk", "apb_pclk"]);
+	assert_eq!(clock::clock(&uart, "uartclk").unwrap().index(), Some(19));
+	assert_eq!(clock::clock(&uart, "baud").err(), Some(Error::NotFound));
+
+	// the parents of the cprman, the oscillator and the dsi outputs
+	let cprman = clocks[0].provider();
+	let parents: Vec<_> = clock::clocks(cprman).unwrap().map(Result::unwrap).collect();
+	assert_eq!(parents.len(), 7);
+	assert_eq!(parents[0].name(), Some("osc"));
+	assert_eq!(parents[0].rate(), Ok(Some(19_200_000)));
+	assert_eq!(parents[2].provider().name(), "dsi@7e209000");
+	assert_eq!(parents[2].name(), Some("dsi0_ddr2"));
+	assert_eq!(parents[6].name(), Some("dsi1_ddr"));
+
+	let smi = fdt.nodes().with_name("smi@7e600000").next().unwrap();
+	assert_eq!(clock::clock(&smi, "").err(), Some(Error::NotFound));
+	let assigned: Vec<_> = clock::assigned_clocks(&smi).unwrap().map(Result::unwrap).collect();
+	assert_eq!(assigned.len(), 1);
+	assert_eq!(assigned[0].clock.index(), Some(0x2a));
+	assert_eq!(assigned[0].rate, Some(125_000_000));
+	assert_eq!(clock::assigned_clocks(&uart).err(), Some(Error::NotFound));
+	assert_eq!(clock::clocks(&fdt.nodes().next().unwrap()).err(), Some(Error::NotFound));
+}
+
+#[test]
+fn test_providers() {
+	let fdt = FDT::from_bytes(DTB).unwrap();
+	let providers: Vec<_> = clock::providers(&fdt).collect();
+	let names: Vec<_> = providers.iter().map(|provider| provider.node().name()).collect();
+	assert_eq!(names, ["cprman@7e101000", "dsi@7e209000", "aux@0x7e215000", "dsi@7e700000", "clock@3", "clock@4"]);
+	assert_eq!(providers[0].clock_cells(), Ok(1));
+	assert_eq!(providers[0].kind(), Ok(ClockKind::Other));
+	assert_eq!(providers[1].output_names().collect::<Vec<_>>(), ["dsi0_byte", "dsi0_ddr2", "dsi0_ddr"]);
+	assert!(providers[1].indices().unwrap().is_none());
+	assert_eq!(providers[1].output_name(3), None);
+	assert_eq!(providers[4].clock_cells(), Ok(0));
+	assert_eq!(providers[4].kind(), Ok(ClockKind::Fixed { frequency: 19_200_000, accuracy: None }));
+	assert_eq!(providers[5].output_name(0), Some("otg"));
+
+	let uart = fdt.nodes().with_name("serial@7e201000").next().unwrap();
+	assert!(ClockProvider::new(uart).is_none());
+}
+
+#[cfg(feature = "alloc")]
+#[test]
+fn test_rates() {
+	let dtb = tree();
+	let fdt = FDT::from_bytes(&dtb).unwrap();
+	let serial = fdt.nodes().with_name("serial").next().unwrap();
+
+	// 48 MHz * 25 / 2 / 12
+	let baud = clock::clock(&serial, "baud").unwrap();
+	assert_eq!(baud.name(), Some("uart_clk"));
+	assert_eq!(baud.rate(), Ok(Some(50_000_000)));
+
+	let osc = clock::providers(&fdt).next().unwrap();
+	assert_eq!(osc.kind(), Ok(ClockKind::Fixed { frequency: 48_000_000, accuracy: Some(100) }));
+	let pll = clock::providers(&fdt).nth(1).unwrap();
+	assert_eq!(pll.kind(), Ok(ClockKind::FixedFactor { mult: 25, div: 2 }));
+	let big = clock::providers(&fdt).nth(4).unwrap();
+	assert_eq!(big.kind(), Ok(ClockKind::Fixed { frequency: 1 << 32, accuracy: None }));
+
+	let bus = clock::clock(&serial, "bus").unwrap();
+	assert_eq!(bus.index(), Some(7));
+	assert_eq!(bus.name(), Some("gate_b"));
+	assert_eq!(bus.rate(), Ok(None));
+
+	let broken = fdt.nodes().with_name("broken").next().unwrap();
+	let mut clocks = clock::clocks(&broken).unwrap();
+	assert_eq!(clocks.next().unwrap().unwrap().rate(), Err(Error::BadValue));
+	assert_eq!(clocks.next().unwrap().unwrap().rate(), Err(Error::BadValue));
+	assert_eq!(clocks.next().unwrap().err(), Some(Error::BadPhandle));
+	assert!(clocks.next().is_none());
+}
+
+#[cfg(feature = "alloc")]
+#[test]
+fn test_rate_rounding() {
+	let factor = |name, parent, mult, div, phandle| node(name, &[
+		("compatible", b"fixed-factor-clock\0"),
+		("#clock-cells", &cells(&[0])),
+		("clocks", &cells(&[parent])),
+		("clock-mult", &cells(&[mult])),
+		("clock-div", &cells(&[div])),
+		("phandle", &cells(&[phandle])),
+	], vec![]);
+	let mut tree = OwnedTree::default();
+	tree.root.children = vec![
+		node("osc", &[
+			("compatible", b"fixed-clock\0"),
+			("#clock-cells", &cells(&[0])),
+			("clock-frequency", &cells(&[100])),
+			("phandle", &cells(&[1])),
+		], vec![]),
+		factor("third", 1, 1, 3, 2),
+		factor("triple", 2, 3, 1, 3),
+		node("consumer", &[("clocks", &cells(&[3]))], vec![]),
+	];
+	let dtb = tree.to_dtb();
+	let fdt = FDT::from_bytes(&dtb).unwrap();
+	let consumer = fdt.nodes().with_name("consumer").next().unwrap();
+	// the divider drops the remainder before the multiplier sees the rate
+	let clock = clock::clocks(&consumer).unwrap().next().unwrap().unwrap();
+	assert_eq!(clock.rate(), Ok(Some(99)));
+}
+
+#[cfg(feature = "alloc")]
+#[test]
+fn test_assigned() {
+	let dtb = tree();
+	let fdt = FDT::from_bytes(&dtb).unwrap();
+	let serial = fdt.nodes().with_name("serial").next().unwrap();
+	let assigned: Vec<_> = clock::assigned_clocks(&serial).unwrap().map(Result::unwrap).collect();
+	assert_eq!(assigned.len(), 2);
+	assert_eq!(assigned[0].clock.name(), Some("gate_c"));
+	assert_eq!(assigned[0].parent.as_ref().unwrap().name(), None);
+	assert_eq!(assigned[0].parent.as_ref().unwrap().provider().name(), "osc");
+	assert_eq!(assigned[0].rate, None);
+	// the hole in assigned-clocks takes its parent and rate along
+	assert_eq!(assigned[1].clock.name(), Some("gate_a"));
+	assert_eq!(assigned[1].parent.as_ref().unwrap().provider().name(), "big");
+	assert_eq!(assigned[1].rate, Some(1_000_000));
+
+	let wide = fdt.nodes().with_name("wide").next().unwrap();
+	let assigned = clock::assigned_clocks(&wide).unwrap().next().unwrap().unwrap();
+	assert_eq!(assigned.rate, Some(2 << 32));
+	assert!(assigned.parent.is_none());
+
+	let broken = fdt.nodes().with_name("broken").next().unwrap();
+	let mut assigned = clock::assigned_clocks(&broken).unwrap();
+	assert!(assigned.next().unwrap().is_ok());
+	assert_eq!(assigned.next().unwrap().err(), Some(Error::BadPhandle));
+	assert!(assigned.next().is_none());
+}
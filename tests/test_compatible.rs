@@ -0,0 +1,40 @@
+extern crate fdt;
+
+use fdt::{FDT, NodeIterator};
+
+const DTB: &[u8] = include_bytes!("dt.dtb");
+
+fn init() -> FDT<'static> {
+	unsafe {
+		FDT::from_raw(DTB.as_ptr()).unwrap()
+	}
+}
+
+#[test]
+fn test_match_compatible_prefers_node_order() {
+	let fdt = init();
+	let uart = fdt.nodes().with_name("serial@7e201000").next().unwrap();
+	let table = [("arm,primecell", 0), ("arm,pl011", 1), ("brcm,bcm2835-pl011", 2)];
+	assert_eq!(uart.match_compatible(&table), Some(&2));
+	assert_eq!(uart.match_compatible(&table[..2]), Some(&1));
+	assert_eq!(uart.match_compatible(&[("ns16550a", 3)]), None);
+}
+
+#[test]
+fn test_match_compatible_without_property() {
+	let fdt = init();
+	let cpus = fdt.nodes().with_name("cpus").next().unwrap();
+	assert_eq!(cpus.match_compatible(&[("arm,cortex-a7", ())]), None);
+}
+
+#[test]
+fn test_matching_table() {
+	let fdt = init();
+	let table = [("arm,pl011", "pl011"), ("brcm,bcm2835-aux-uart", "aux")];
+	let mut matches = fdt.nodes().matching(&table);
+	let (node, driver) = matches.next().unwrap();
+	assert_eq!((node.name(), *driver), ("serial@7e201000", "pl011"));
+	let (node, driver) = matches.next().unwrap();
+	assert_eq!((node.name(), *driver), ("serial@7e215040", "aux"));
+	assert!(matches.next().is_none());
+}
@@ -0,0 +1,125 @@
+extern crate fdt;
+
+use fdt::container::{self, Board, DT_TABLE_MAGIC};
+use fdt::error::Error;
+
+const DTB: &[u8] = include_bytes!("dt.dtb");
+
+/// Returns a copy of the test tree with the root 'model' and 'compatible'
+/// patched in place, both keep their length.
+fn variant(model: &str, compatible: &str) -> Vec<u8> {
+	let mut dtb = DTB.to_vec();
+	dtb[120..142].copy_from_slice(model.as_bytes());
+	dtb[92..104].copy_from_slice(compatible.as_bytes());
+	dtb
+}
+
+fn model(entry: &container::Entry) -> String {
+	let root = entry.fdt.nodes().next().unwrap();
+	root.property("model").unwrap().as_str().trim_end_matches('\0').to_string()
+}
+
+fn push_u32s(buf: &mut Vec<u8>, values: &[u32]) {
+	for value in values {
+		buf.extend_from_slice(&value.to_be_bytes());
+	}
+}
+
+/// Builds a version 0 Android DTB image of [blobs] with their id and rev.
+fn table(blobs: &[(&[u8], u32, u32)]) -> Vec<u8> {
+	let entries = 32 + 32 * blobs.len();
+	let total = entries + blobs.iter().map(|blob| blob.0.len()).sum::<usize>();
+	let mut image = Vec::new();
+	push_u32s(&mut image, &[DT_TABLE_MAGIC, total as u32, 32, 32, blobs.len() as u32, 32, 2048, 0]);
+	let mut offset = entries;
+	for &(blob, id, rev) in blobs {
+		push_u32s(&mut image, &[blob.len() as u32, offset as u32, id, rev, 0xa, 0xb, 0xc, 0xd]);
+		offset += blob.len();
+	}
+	for blob in blobs {
+		image.extend_from_slice(blob.0);
+	}
+	image
+}
+
+#[test]
+fn test_concatenated() {
+	let mut image = DTB.to_vec();
+	image.resize(image.len().div_ceil(8) * 8, 0);
+	image.extend(variant("Raspberry Pi 2 Model X", "brcm,bcm2837"));
+	image.extend(variant("Raspberry Pi 2 Model Y", "brcm,bcm2836"));
+
+	let entries: Vec<_> = container::entries(&image).unwrap().map(Result::unwrap).collect();
+	assert_eq!(entries.len(), 3);
+	assert_eq!(model(&entries[1]), "Raspberry Pi 2 Model X");
+	assert!(entries.iter().all(|entry| entry.table.is_none()));
+
+	let board = Board { compatible: &["brcm,bcm2837", "brcm,bcm2836"], ..Board::default() };
+	let entry = container::entries(&image).unwrap().select(&board).unwrap();
+	assert_eq!(entry.index, 1);
+
+	let board = Board { compatible: &["brcm,bcm2836"], model: Some("Raspberry Pi 2 Model Y"), ..Board::default() };
+	let entry = container::entries(&image).unwrap().select(&board).unwrap();
+	assert_eq!(entry.index, 2);
+
+	let board = Board { compatible: &["brcm,bcm2711"], ..Board::default() };
+	assert!(container::entries(&image).unwrap().select(&board).is_none());
+	let board = Board { id: Some(0), ..Board::default() };
+	assert!(container::entries(&image).unwrap().select(&board).is_none());
+}
+
+#[test]
+fn test_concatenated_trailing_garbage() {
+	let mut image = DTB.to_vec();
+	image.extend_from_slice(b"junk");
+	let mut entries = container::entries(&image).unwrap();
+	assert!(entries.next().unwrap().is_ok());
+	assert!(matches!(entries.next(), Some(Err(_))));
+	assert!(entries.next().is_none());
+}
+
+#[test]
+fn test_table() {
+	let other = variant("Raspberry Pi 2 Model X", "brcm,bcm2837");
+	let image = table(&[(DTB, 1, 0), (&other, 2, 0), (DTB, 2, 1)]);
+
+	let entries: Vec<_> = container::entries(&image).unwrap().map(Result::unwrap).collect();
+	assert_eq!(entries.len(), 3);
+	let table = entries[2].table.unwrap();
+	assert_eq!((table.id, table.rev, table.custom), (2, 1, [0xa, 0xb, 0xc, 0xd]));
+	assert_eq!(table.compression(), 0);
+	assert_eq!(model(&entries[1]), "Raspberry Pi 2 Model X");
+
+	let board = Board { id: Some(2), ..Board::default() };
+	assert_eq!(container::entries(&image).unwrap().select(&board).unwrap().index, 1);
+	let board = Board { id: Some(2), rev: Some(1), ..Board::default() };
+	assert_eq!(container::entries(&image).unwrap().select(&board).unwrap().index, 2);
+	let board = Board { compatible: &["brcm,bcm2836"], id: Some(2), ..Board::default() };
+	assert_eq!(container::entries(&image).unwrap().select(&board).unwrap().index, 2);
+}
+
+#[test]
+fn test_errors() {
+	assert_eq!(container::entries(b"nope").err(), Some(Error::BadMagic(u32::from_be_bytes(*b"nope"))));
+	assert_eq!(container::entries(&DT_TABLE_MAGIC.to_be_bytes()).err(), Some(Error::Truncated));
+
+	let mut image = table(&[(DTB, 1, 0)]);
+	image.truncate(image.len() - 1);
+	assert_eq!(container::entries(&image).err(), Some(Error::Truncated));
+
+	let mut image = table(&[(DTB, 1, 0)]);
+	image[28..32].copy_from_slice(&1u32.to_be_bytes());
+	image[48..52].copy_from_slice(&1u32.to_be_bytes());
+	let mut entries = container::entries(&image).unwrap();
+	assert_eq!(entries.next().map(|entry| entry.err()), Some(Some(Error::BadValue)));
+
+	// offsets and sizes reaching past the address space
+	let mut image = table(&[(DTB, 1, 0)]);
+	image[32..40].copy_from_slice(&[0xff; 8]);
+	let mut entries = container::entries(&image).unwrap();
+	assert_eq!(entries.next().map(|entry| entry.err()), Some(Some(Error::Truncated)));
+	let mut image = table(&[(DTB, 1, 0)]);
+	image[20..24].copy_from_slice(&[0xff; 4]);
+	let mut entries = container::entries(&image).unwrap();
+	assert_eq!(entries.next().map(|entry| entry.err()), Some(Some(Error::Truncated)));
+}
@@ -0,0 +1,103 @@
+extern crate fdt;
+
+use fdt::{FDT, NodeIterator};
+use fdt::diff::Change;
+
+mod common;
+use common::{leak, offset_of, patched, value_range};
+
+static DTB: &[u8] = include_bytes!("dt.dtb");
+
+fn init() -> FDT<'static> {
+	unsafe {
+		FDT::from_raw(DTB.as_ptr()).unwrap()
+	}
+}
+
+fn read_u32(raw: &[u8]) -> u32 {
+	u32::from_be_bytes([raw[0], raw[1], raw[2], raw[3]])
+}
+
+fn collect(a: &FDT<'static>, b: &FDT<'static>) -> Vec<String> {
+	let mut changes = Vec::new();
+	let count = fdt::diff(a, b, |change| changes.push(change.to_string()));
+	assert_eq!(count, changes.len());
+	changes
+}
+
+#[test]
+fn test_identical() {
+	let fdt = init();
+	assert_eq!(collect(&fdt, &fdt), Vec::<String>::new());
+}
+
+#[test]
+fn test_properties() {
+	let b = leak(patched(DTB, |dtb| {
+		let status = value_range(DTB, "/soc/serial@7e201000", "status").start;
+		dtb[status..status + 4].copy_from_slice(b"fail");
+		// rename cts-event-workaround to Cts-event-workaround
+		let workaround = value_range(DTB, "/soc/serial@7e201000", "cts-event-workaround").start;
+		let name = read_u32(&dtb[workaround - 4..]) as usize;
+		let strings = read_u32(&dtb[12..]) as usize;
+		dtb[strings + name] = b'C';
+	}));
+	let changes = collect(&init(), &b);
+	assert!(changes.contains(&"~ /soc/serial@7e201000:status = okay -> fail".to_string()));
+	assert!(changes.contains(&"- /soc/serial@7e201000:cts-event-workaround = []".to_string()));
+	assert!(changes.contains(&"+ /soc/serial@7e201000:Cts-event-workaround = []".to_string()));
+}
+
+#[test]
+fn test_nodes() {
+	let b = leak(patched(DTB, |dtb| {
+		let spidev = init().nodes().with_name("spidev@1").next().unwrap().name();
+		let offset = offset_of(DTB, spidev.as_bytes());
+		dtb[offset + spidev.len() - 1] = b'2';
+	}));
+	let mut changes = Vec::new();
+	fdt::diff(&init(), &b, |change| match change {
+		Change::NodeRemoved(node) | Change::NodeAdded(node) => changes.push(node.path().to_string()),
+		_ => panic!("unexpected change {}", change),
+	});
+	assert_eq!(changes, ["/soc/spi@7e204000/spidev@1", "/soc/spi@7e204000/spidev@2"]);
+}
+
+#[test]
+fn test_phandle_renumbering() {
+	// renumber cprman and update the references of the pl011 to it
+	let b = leak(patched(DTB, |dtb| {
+		let phandle = value_range(DTB, "/soc/cprman@7e101000", "phandle").start;
+		dtb[phandle + 3] = 0x7f;
+		let clocks = value_range(DTB, "/soc/serial@7e201000", "clocks").start;
+		dtb[clocks + 3] = 0x7f;
+		dtb[clocks + 11] = 0x7f;
+	}));
+	let changes = collect(&init(), &b);
+	assert!(!changes.iter().any(|change| change.starts_with("~ /soc/cprman@7e101000:phandle")));
+	assert!(!changes.iter().any(|change| change.starts_with("~ /soc/serial@7e201000:")));
+	// the other references to cprman now dangle
+	assert!(changes.iter().any(|change| change.starts_with("~ /soc/spi@7e204000:clocks")));
+
+	// point the first clock of the pl011 at another provider
+	let b = leak(patched(DTB, |dtb| {
+		let clocks = value_range(DTB, "/soc/serial@7e201000", "clocks").start;
+		dtb[clocks + 3] = 0x3;
+	}));
+	assert_eq!(collect(&init(), &b),
+		["~ /soc/serial@7e201000:clocks = <0x7 0x13 0x7 0x14> -> <0x3 0x13 0x7 0x14>"]);
+}
+
+#[test]
+fn test_header_and_reserve_map() {
+	let b = leak(patched(DTB, |dtb| {
+		dtb[31] = 1;
+		let rsvmap = read_u32(&dtb[16..]) as usize;
+		dtb[rsvmap + 14] = 0x20;
+	}));
+	assert_eq!(collect(&init(), &b), [
+		"~ header boot_cpuid_phys: 0 -> 1",
+		"- /memreserve/ 0x0 0x1000",
+		"+ /memreserve/ 0x0 0x2000",
+	]);
+}
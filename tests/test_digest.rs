@@ -0,0 +1,60 @@
+extern crate fdt;
+
+use fdt::digest::{Algorithm, Backend, Digest, Software};
+
+mod common;
+use common::{hex};
+
+fn digest(algorithm: Algorithm, regions: &[&[u8]]) -> Digest {
+	Software.digest(algorithm, &mut regions.iter().copied()).unwrap()
+}
+
+#[test]
+fn test_known_digests() {
+	let cases = [
+		(Algorithm::Sha1, &b"abc"[..], "a9993e364706816aba3e25717850c26c9cd0d89d"),
+		(Algorithm::Sha256, b"", "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"),
+		(Algorithm::Sha256, b"abcdbcdecdefdefgefghfghighijhijkijkljklmklmnlmnomnopnopq",
+			"248d6a61d20638b8e5c026930c3e6039a33ce45964ff2167f6ecedd419db06c1"),
+		(Algorithm::Sha384, b"abc",
+			"cb00753f45a35e8bb5a03d699ac65007272c32ab0eded1631a8b605a43ff5bed8086072ba1e7cc2358baeca134c825a7"),
+		(Algorithm::Sha512, b"abc",
+			"ddaf35a193617abacc417349ae20413112e6fa4e89a97ea20a9eeee64b55d39a\
+			2192992a274fc1a836ba3c23a3feebbd454d4423643ce80e2a9ac94fa54ca49f"),
+		(Algorithm::Crc32, b"", "00000000"),
+	];
+	for (algorithm, data, expected) in cases {
+		assert_eq!(digest(algorithm, &[data]).as_ref(), hex(expected), "{} of {:?}", algorithm, data);
+	}
+}
+
+#[test]
+fn test_regions() {
+	let data: Vec<u8> = (0..1000).map(|i| (i % 251) as u8).collect();
+	let cases = [
+		(Algorithm::Crc32, "721746a6"),
+		(Algorithm::Sha1, "c9c960a0b925474fab83942cc27d504fc24ac37b"),
+		(Algorithm::Sha256, "4e4c294b331f7a2099a379bec34b9f9fc03dc46ab465d998f4d683da53487e6d"),
+		(Algorithm::Sha384, "7a2f8c7f12344964a13cb9260492b845e56615d6152b9eb9e54b580fc88405e64f31813bfda10de2a642fdf1676c61b4"),
+		(Algorithm::Sha512, "5096498d96f50f9a137c4db5b8b0cd38383ad55350fb5a98805fedc31fa1262f\
+			1f0cf4d6f12d7ecd8dedd933a4c9126344fe22e937a8ad35fdeae1e876ae698b"),
+	];
+	for (algorithm, expected) in cases {
+		assert_eq!(digest(algorithm, &[&data]).as_ref(), hex(expected));
+		assert_eq!(digest(algorithm, &[&data]).as_ref().len(), algorithm.size());
+		for split in [1, 63, 64, 65, 127, 128, 129, 500] {
+			let (a, b) = data.split_at(split);
+			let (b, c) = b.split_at(split.min(b.len()));
+			assert_eq!(digest(algorithm, &[a, &[], b, c]).as_ref(), hex(expected), "{} split at {}", algorithm, split);
+		}
+	}
+}
+
+#[test]
+fn test_names() {
+	for algorithm in [Algorithm::Crc32, Algorithm::Sha1, Algorithm::Sha256, Algorithm::Sha384, Algorithm::Sha512] {
+		assert_eq!(Algorithm::from_name(algorithm.name()), Some(algorithm));
+	}
+	assert_eq!(Algorithm::from_name("md5"), None);
+	assert_eq!(format!("{:?}", Digest::new(&[0xde, 0xad])), "dead");
+}
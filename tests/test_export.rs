@@ -0,0 +1,62 @@
+extern crate fdt;
+
+use fdt::{FDT, NodeIterator};
+use fdt::export;
+
+const DTB: &[u8] = include_bytes!("dt.dtb");
+
+fn init() -> FDT<'static> {
+	unsafe {
+		FDT::from_raw(DTB.as_ptr()).unwrap()
+	}
+}
+
+#[test]
+fn test_yaml_tree() {
+	let fdt = init();
+	let yaml = export::yaml(&fdt).to_string();
+	let mut lines = yaml.lines();
+	assert_eq!(lines.next(), Some("- $nodename: [\"/\"]"));
+	assert_eq!(lines.next(), Some("  compatible: [\"brcm,bcm2836\"]"));
+	assert!(yaml.contains("\n  chosen:\n    $nodename: [\"chosen\"]\n    bootargs: [\"\"]\n"));
+	assert!(yaml.contains("\n  \"#address-cells\": [[0x1]]\n"));
+	assert!(!yaml.lines().any(|line| line.ends_with(' ')));
+}
+
+#[test]
+fn test_yaml_node_grouping() {
+	let fdt = init();
+	let uart = fdt.nodes().with_name("serial@7e201000").next().unwrap();
+	let yaml = export::node_yaml(&uart).to_string();
+	assert_eq!(yaml, "$nodename: [\"serial@7e201000\"]\n\
+		compatible: [\"brcm,bcm2835-pl011\", \"arm,pl011\", \"arm,primecell\"]\n\
+		reg: [[0x7e201000, 0x1000]]\n\
+		interrupts: [[0x2, 0x19]]\n\
+		clocks: [[0x7, 0x13], [0x7, 0x14]]\n\
+		clock-names: [\"uartclk\", \"apb_pclk\"]\n\
+		arm,primecell-periphid: [[0x241011]]\n\
+		cts-event-workaround: true\n\
+		status: [\"okay\"]\n\
+		phandle: [[0x1c]]\n");
+}
+
+#[test]
+fn test_json_node() {
+	let fdt = init();
+	let spi = fdt.nodes().with_name("spi@7e204000").next().unwrap();
+	let json = export::node_json(&spi).to_string();
+	assert!(json.starts_with("{\"$nodename\": [\"spi@7e204000\"], "));
+	assert!(json.contains("\"cs-gpios\": [[10, 8, 1], [10, 7, 1]]"));
+	assert!(json.contains("\"pinctrl-0\": [[11], [12]]"));
+	assert!(json.contains("\"spidev@1\": {\"$nodename\": [\"spidev@1\"], \"compatible\": [\"spidev\"], \"reg\": [[1]]"));
+	assert!(json.ends_with("}}\n"));
+}
+
+#[test]
+fn test_json_tree_ranges() {
+	let fdt = init();
+	let json = export::json(&fdt).to_string();
+	assert!(json.starts_with("[{\"$nodename\": [\"/\"]"));
+	assert!(json.contains("\"ranges\": [[2113929216, 1056964608, 16777216], [1073741824, 1073741824, 262144]]"));
+	assert!(json.ends_with("}]\n"));
+}
@@ -0,0 +1,169 @@
+#![cfg(feature = "alloc")]
+
+extern crate fdt;
+
+use fdt::digest::{Algorithm, Backend, Digest, Software};
+use fdt::error::Error;
+use fdt::fit::{Compression, DataLocation, Fit, HashError, ImageType, Verification};
+use fdt::owned::{OwnedNode, OwnedTree};
+
+mod common;
+use common::{hex, node};
+
+fn hash(name: &str, algo: &str, value: &[u8]) -> OwnedNode {
+	let algo = [algo.as_bytes(), b"\0"].concat();
+	node(name, &[("algo", &algo), ("value", value)], vec![])
+}
+
+/// A FIT like mkimage builds for a kernel, two device trees and a ramdisk,
+/// the ramdisk stored externally by 'data-offset' and the overlay by
+/// 'data-position'.
+fn fit() -> Vec<u8> {
+	let mut tree = OwnedTree::default();
+	tree.root.set_property("description", b"test image\0");
+	tree.root.set_property("timestamp", &0x5f000000u32.to_be_bytes());
+	tree.root.children = vec![
+		node("images", &[], vec![
+			node("kernel-1", &[
+				("description", b"Linux\0"), ("data", b"kernel data"), ("type", b"kernel\0"),
+				("arch", b"arm64\0"), ("os", b"linux\0"), ("compression", b"gzip\0"),
+				("load", &0x80080000u32.to_be_bytes()), ("entry", &0x1_0008_0000u64.to_be_bytes()),
+			], vec![
+				hash("hash-1", "sha256", &hex("dc2d3aebca53ecefb16a5cf228314ff8fb8cd15ede0392787d46844fd46756a3")),
+				hash("hash-2", "crc32", &0xdf469966u32.to_be_bytes()),
+			]),
+			node("fdt-1", &[("data", b"fdt data"), ("type", b"flat_dt\0"), ("compression", b"none\0")], vec![
+				hash("hash-1", "crc32", &[0; 4]),
+			]),
+			node("fdt-2", &[("type", b"flat_dt\0"), ("data-position", &0x1000u32.to_be_bytes()),
+				("data-size", &3u32.to_be_bytes())], vec![hash("hash-1", "md5", &[0; 16])]),
+			node("ramdisk-1", &[("type", b"ramdisk\0"), ("data-offset", &4u32.to_be_bytes()),
+				("data-size", &7u32.to_be_bytes())], vec![
+				hash("hash-1", "sha1", &hex("a18820fc6693da237a77220f830a8aaf108917f6")),
+				node("signature-1", &[], vec![]),
+			]),
+		]),
+		node("configurations", &[("default", b"conf-1\0")], vec![
+			node("conf-1", &[("description", b"Boot Linux\0"), ("kernel", b"kernel-1\0"),
+				("fdt", b"fdt-1\0fdt-2\0"), ("ramdisk", b"ramdisk-1\0"), ("compatible", b"acme,board\0")], vec![]),
+			node("conf-2", &[("kernel", b"kernel-1\0"), ("fdt", b"fdt-3\0"), ("loadables", b"fdt-1\0")], vec![]),
+		]),
+	];
+	let mut image = tree.to_dtb();
+	image.resize(image.len().div_ceil(4) * 4, 0);
+	image.extend_from_slice(b"....ramdisk");
+	image.resize(0x1000, 0);
+	image.extend_from_slice(b"dto");
+	image
+}
+
+#[test]
+fn test_images() {
+	let image = fit();
+	let fit = Fit::from_bytes(&image).unwrap();
+	assert_eq!(fit.description(), Some("test image"));
+	assert_eq!(fit.timestamp(), Some(0x5f000000));
+	let names: Vec<_> = fit.images().map(|image| image.name()).collect();
+	assert_eq!(names, ["kernel-1", "fdt-1", "fdt-2", "ramdisk-1"]);
+
+	let kernel = fit.image("kernel-1").unwrap();
+	assert_eq!(kernel.description(), Some("Linux"));
+	assert_eq!(kernel.kind(), Ok(ImageType::Kernel));
+	assert_eq!(kernel.arch(), Some("arm64"));
+	assert_eq!(kernel.os(), Some("linux"));
+	assert_eq!(kernel.compression(), Ok(Compression::Gzip));
+	assert_eq!(kernel.load(), Ok(Some(0x80080000)));
+	assert_eq!(kernel.entry(), Ok(Some(0x1_0008_0000)));
+	assert_eq!(kernel.data_location(), Ok(DataLocation::Embedded));
+	assert_eq!(kernel.data(), Ok(&b"kernel data"[..]));
+
+	let ramdisk = fit.image("ramdisk-1").unwrap();
+	assert_eq!(ramdisk.kind(), Ok(ImageType::Ramdisk));
+	assert_eq!(ramdisk.compression(), Ok(Compression::None));
+	assert_eq!(ramdisk.load(), Ok(None));
+	assert_eq!(ramdisk.data(), Ok(&b"ramdisk"[..]));
+	assert_eq!(fit.image("fdt-2").unwrap().data_location(), Ok(DataLocation::External { position: 0x1000, size: 3 }));
+	assert_eq!(fit.image("fdt-2").unwrap().data(), Ok(&b"dto"[..]));
+}
+
+#[test]
+fn test_configurations() {
+	let image = fit();
+	let fit = Fit::from_bytes(&image).unwrap();
+	let names: Vec<_> = fit.configurations().map(|config| config.name()).collect();
+	assert_eq!(names, ["conf-1", "conf-2"]);
+
+	let config = fit.select_configuration(None).unwrap();
+	assert_eq!(config.name(), "conf-1");
+	assert_eq!(config.description(), Some("Boot Linux"));
+	assert!(config.compatible().unwrap().contains("acme,board"));
+	assert_eq!(config.kernel().unwrap().unwrap().name(), "kernel-1");
+	assert_eq!(config.ramdisk().unwrap().unwrap().data(), Ok(&b"ramdisk"[..]));
+	assert!(config.firmware().unwrap().is_none());
+	let fdts: Vec<_> = config.fdt().map(|fdt| fdt.unwrap().name()).collect();
+	assert_eq!(fdts, ["fdt-1", "fdt-2"]);
+	assert_eq!(config.loadables().count(), 0);
+
+	let config = fit.select_configuration(Some("conf-2")).unwrap();
+	assert_eq!(config.fdt().next().map(|fdt| fdt.err()), Some(Some(Error::NotFound)));
+	assert_eq!(config.loadables().next().unwrap().unwrap().name(), "fdt-1");
+	assert!(fit.select_configuration(Some("conf-3")).is_err());
+}
+
+#[test]
+fn test_errors() {
+	let image = fit();
+	assert_eq!(Fit::from_bytes(&image[..0x1001]).unwrap().image("fdt-2").unwrap().data(), Err(Error::Truncated));
+
+	let plain = OwnedTree::default().to_dtb();
+	assert_eq!(Fit::from_bytes(&plain).err(), Some(Error::NotFound));
+
+	let mut tree = OwnedTree::default();
+	tree.root.children = vec![node("images", &[], vec![node("empty", &[], vec![])])];
+	let dtb = tree.to_dtb();
+	let fit = Fit::from_bytes(&dtb).unwrap();
+	assert_eq!(fit.default_configuration().err(), Some(Error::NotFound));
+	let empty = fit.image("empty").unwrap();
+	assert_eq!(empty.kind(), Err(Error::NotFound));
+	assert_eq!(empty.data(), Err(Error::NotFound));
+}
+
+/// A backend standing in for a crc32 accelerator, counting its uses.
+struct Accelerator(usize);
+
+impl Backend for Accelerator {
+	fn digest(&mut self, algorithm: Algorithm, regions: &mut dyn Iterator<Item=&[u8]>) -> Option<Digest> {
+		self.0 += 1;
+		match algorithm {
+			Algorithm::Crc32 => Software.digest(algorithm, regions),
+			_ => None,
+		}
+	}
+}
+
+#[test]
+fn test_verify() {
+	let image = fit();
+	let fit = Fit::from_bytes(&image).unwrap();
+	let hashes: Vec<_> = fit.image("kernel-1").unwrap().hashes().map(|hash| (hash.name(), hash.algo())).collect();
+	assert_eq!(hashes, [("hash-1", Some("sha256")), ("hash-2", Some("crc32"))]);
+
+	let report: Vec<_> = fit.verify_images(&mut Software).map(|(image, verification)| (image.name(), verification)).collect();
+	assert_eq!(report, [
+		("kernel-1", Verification::Verified { hashes: 2 }),
+		("fdt-1", Verification::Failed { hash: "hash-1", error: HashError::Mismatch }),
+		("fdt-2", Verification::Failed { hash: "hash-1", error: HashError::Unsupported("md5") }),
+		("ramdisk-1", Verification::Verified { hashes: 1 }),
+	]);
+	assert!(report[0].1.is_verified() && !report[1].1.is_verified());
+	assert_eq!(report[1].1.to_string(), "hash-1: digest mismatch");
+
+	let mut accelerator = Accelerator(0);
+	let kernel = fit.image("kernel-1").unwrap();
+	assert_eq!(kernel.verify(&mut accelerator), Verification::Failed { hash: "hash-1", error: HashError::Unsupported("sha256") });
+	assert_eq!(kernel.hashes().nth(1).unwrap().verify(kernel.data().unwrap(), &mut accelerator), Ok(()));
+	assert_eq!(accelerator.0, 2);
+
+	assert_eq!(Fit::from_bytes(&image[..0x1001]).unwrap().image("fdt-2").unwrap().verify(&mut Software),
+		Verification::BadData(Error::Truncated));
+}
@@ -0,0 +1,191 @@
+extern crate fdt;
+
+use fdt::{FDT, NodeIterator};
+use fdt::error::Error;
+use fdt::gpio::{self, GpioController, GpioFlags};
+#[cfg(feature = "alloc")]
+use fdt::gpio::{GpioRange, HogState};
+#[cfg(feature = "alloc")]
+use fdt::owned::OwnedTree;
+
+mod common;
+#[cfg(feature = "alloc")]
+use common::{cells, node};
+
+const DTB: &[u8] = include_bytes!("dt.dtb");
+
+/// A controller with three cell specifiers, line names, ranges and hogs,
+/// a pin controller and consumers of the lines.
+#[cfg(feature = "alloc")]
+fn tree() -> Vec<u8> {
+	let mut tree = OwnedTree::default();
+	tree.root.children = vec![
+		node("pinctrl", &[("phandle", &cells(&[1]))], vec![]),
+		node("gpio", &[
+			("gpio-controller", b""),
+			("#gpio-cells", &cells(&[3])),
+			("ngpios", &cells(&[6])),
+			("gpio-line-names", b"RESET\0\0LED\0"),
+			("gpio-ranges", &cells(&[1, 0, 20, 2, 1, 4, 40, 2, 1, 8, 0, 0])),
+			("gpio-ranges-group-names", b"\0\0uart\0"),
+			("phandle", &cells(&[2])),
+		], vec![
+			node("hog-reset", &[
+				("gpio-hog", b""),
+				("gpios", &cells(&[0, 0, 0, 0, 1, 1])),
+				("output-high", b""),
+				("line-name", b"reset\0"),
+			], vec![]),
+			node("not-a-hog", &[], vec![]),
+			node("hog-bad", &[("gpio-hog", b""), ("gpios", &cells(&[0, 5])), ("input", b""), ("output-low", b"")], vec![]),
+		]),
+		node("consumer", &[
+			("reset-gpios", &cells(&[2, 0, 0, 0x11])),
+			("led-gpio", &cells(&[2, 2, 0, 0])),
+			("cs-gpios", &cells(&[0, 2, 1, 0, 0x26, 0])),
+			("gpios", &cells(&[2, 0, 5, 0x6])),
+			("bad-gpios", &cells(&[3, 0, 0, 0])),
+			("short-gpios", &cells(&[2, 0, 0])),
+		], vec![]),
+	];
+	tree.to_dtb()
+}
+
+#[test]
+fn test_gpios() {
+	let fdt = FDT::from_bytes(DTB).unwrap();
+	let spi = fdt.nodes().with_name("spi@7e204000").next().unwrap();
+	let cs: Vec<_> = gpio::gpios(&spi, "cs").unwrap().map(|gpio| gpio.unwrap().unwrap()).collect();
+	assert_eq!(cs.len(), 2);
+	assert_eq!(cs[0].controller().name(), "gpio@7e200000");
+	assert_eq!(cs[0].args().collect::<Vec<_>>(), [8, 1]);
+	assert_eq!(cs[0].flags(), GpioFlags(1));
+	assert!(cs[0].flags().active_low());
+	assert_eq!(cs[0].name(), None);
+	assert_eq!(cs[1].line(), Some(7));
+
+	let i2c = fdt.nodes().with_name("i2cdsi").next().unwrap();
+	let lines: Vec<_> = gpio::gpios(&i2c, "").unwrap().map(|gpio| gpio.unwrap().unwrap().line()).collect();
+	assert_eq!(lines, [Some(28), Some(29)]);
+	assert!(!gpio::gpios(&i2c, "").unwrap().any(|gpio| gpio.unwrap().unwrap().flags().active_low()));
+
+	assert_eq!(gpio::gpios(&spi, "reset").err(), Some(Error::NotFound));
+	assert_eq!(gpio::gpios(&spi, "").err(), Some(Error::NotFound));
+
+	let controller = GpioController::new(cs[0].controller().clone()).unwrap();
+	assert_eq!(controller.gpio_cells(), Ok(2));
+	assert_eq!(controller.ngpios(), None);
+	assert_eq!(controller.line_names().count(), 0);
+	assert_eq!(controller.ranges().unwrap().count(), 0);
+	assert_eq!(controller.hogs().count(), 0);
+	assert!(GpioController::new(spi).is_none());
+}
+
+#[test]
+fn test_flags() {
+	assert!(GpioFlags(0x2).open_drain());
+	assert!(!GpioFlags(0x2).open_source());
+	assert!(GpioFlags(0x6).open_source());
+	assert!(!GpioFlags(0x6).open_drain());
+	assert!(!GpioFlags(0x4).open_source());
+	assert!(GpioFlags(0x8).transitory());
+	assert!(GpioFlags(0x10).pull_up());
+	assert!(GpioFlags(0x20).pull_down());
+	assert!(GpioFlags(0x40).pull_disable());
+	assert_eq!(GpioFlags::default(), GpioFlags(0));
+}
+
+#[cfg(feature = "alloc")]
+#[test]
+fn test_consumers() {
+	let dtb = tree();
+	let fdt = FDT::from_bytes(&dtb).unwrap();
+	let consumer = fdt.nodes().with_name("consumer").next().unwrap();
+	let first = |function| gpio::gpios(&consumer, function).unwrap().next().unwrap();
+
+	let reset = first("reset").unwrap().unwrap();
+	assert_eq!(reset.args().collect::<Vec<_>>(), [0, 0, 0x11]);
+	assert_eq!(reset.line(), Some(0));
+	assert!(reset.flags().active_low());
+	assert!(reset.flags().pull_up());
+	assert_eq!(reset.name(), Some("RESET"));
+
+	// the deprecated -gpio spelling and the unnamed gpios property
+	let led = first("led").unwrap().unwrap();
+	assert_eq!(led.name(), Some("LED"));
+	assert!(first("").unwrap().unwrap().flags().open_source());
+
+	// holes are kept in place
+	let cs: Vec<_> = gpio::gpios(&consumer, "cs").unwrap().map(Result::unwrap).collect();
+	assert_eq!(cs.len(), 3);
+	assert!(cs[0].is_none() && cs[2].is_none());
+	assert_eq!(cs[1].as_ref().unwrap().args().collect::<Vec<_>>(), [1, 0, 0x26]);
+	assert_eq!(cs[1].as_ref().unwrap().name(), None);
+
+	// errors end the iteration
+	let mut bad = gpio::gpios(&consumer, "bad").unwrap();
+	assert_eq!(bad.next().unwrap().err(), Some(Error::BadPhandle));
+	assert!(bad.next().is_none());
+	assert_eq!(first("short").err(), Some(Error::Truncated));
+}
+
+#[cfg(feature = "alloc")]
+#[test]
+fn test_controller() {
+	let dtb = tree();
+	let fdt = FDT::from_bytes(&dtb).unwrap();
+	let node = fdt.nodes().with_name("gpio").next().unwrap();
+	let controller = GpioController::new(node).unwrap();
+	assert_eq!(controller.gpio_cells(), Ok(3));
+	assert_eq!(controller.ngpios(), Some(6));
+	assert_eq!(controller.line_names().collect::<Vec<_>>(), ["RESET", "", "LED"]);
+	assert_eq!(controller.line_name(0), Some("RESET"));
+	assert_eq!(controller.line_name(1), None);
+	assert_eq!(controller.line_name(3), None);
+	assert_eq!(controller.find_line("LED"), Some(2));
+	assert_eq!(controller.find_line("uart"), None);
+
+	let ranges: Vec<_> = controller.ranges().unwrap().map(Result::unwrap).collect();
+	assert_eq!(ranges.len(), 3);
+	assert_eq!(ranges[0].pin_controller.name(), "pinctrl");
+	assert_eq!((ranges[0].gpio_offset, ranges[0].pin_offset, ranges[0].count), (0, 20, 2));
+	assert_eq!(ranges[0].group, None);
+	assert_eq!(ranges[2].count, 0);
+	assert_eq!(ranges[2].group, Some("uart"));
+	assert_eq!(ranges[1].pin(5), Some(41));
+	assert_eq!(ranges[1].pin(6), None);
+	assert_eq!(ranges[1].pin(3), None);
+	let last = GpioRange { pin_offset: u32::MAX, ..ranges[1].clone() };
+	assert_eq!(last.pin(4), Some(u32::MAX));
+	assert_eq!(last.pin(5), None);
+	let (pinctrl, pin) = controller.pin(1).unwrap().unwrap();
+	assert_eq!((pinctrl.name(), pin), ("pinctrl", 21));
+	assert_eq!(controller.pin(4).unwrap().unwrap().1, 40);
+	assert!(controller.pin(2).unwrap().is_none());
+	assert!(controller.pin(8).unwrap().is_none());
+}
+
+#[cfg(feature = "alloc")]
+#[test]
+fn test_hogs() {
+	let dtb = tree();
+	let fdt = FDT::from_bytes(&dtb).unwrap();
+	let node = fdt.nodes().with_name("gpio").next().unwrap();
+	let controller = GpioController::new(node).unwrap();
+	let hogs: Vec<_> = controller.hogs().collect();
+	assert_eq!(hogs.len(), 2);
+
+	assert_eq!(hogs[0].node().name(), "hog-reset");
+	assert_eq!(hogs[0].state(), Ok(HogState::OutputHigh));
+	assert_eq!(hogs[0].line_name(), Some("reset"));
+	let lines: Vec<_> = hogs[0].lines().unwrap().collect();
+	assert_eq!(lines.len(), 2);
+	assert_eq!(lines[0].controller().name(), "gpio");
+	assert_eq!(lines[0].name(), Some("RESET"));
+	assert_eq!(lines[1].line(), Some(0));
+	assert!(lines[1].flags().active_low());
+
+	assert_eq!(hogs[1].state(), Err(Error::BadValue));
+	assert_eq!(hogs[1].line_name(), None);
+	assert_eq!(hogs[1].lines().err(), Some(Error::BadLength(8)));
+}
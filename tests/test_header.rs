@@ -0,0 +1,124 @@
+extern crate fdt;
+
+use fdt::{FDT, Block, BlockKind, FdtHeader};
+
+mod common;
+use common::set;
+
+const DTB: &[u8] = include_bytes!("dt.dtb");
+
+#[test]
+fn test_fields() {
+	let header = FDT::from_bytes(DTB).unwrap().header();
+	assert_eq!(header, FdtHeader {
+		magic: 0xd00dfeed,
+		totalsize: 0x4215,
+		off_dt_struct: 0x48,
+		off_dt_strings: 0x3c08,
+		off_mem_rsvmap: 0x28,
+		version: 17,
+		last_comp_version: 16,
+		boot_cpuid_phys: Some(0),
+		size_dt_strings: Some(0x60d),
+		size_dt_struct: Some(0x3bc0),
+	});
+	assert_eq!(header.header_size(), 40);
+	assert_eq!(header.to_string(), "\
+// magic:\t\t0xd00dfeed
+// totalsize:\t\t0x4215 (16917)
+// off_dt_struct:\t0x48
+// off_dt_strings:\t0x3c08
+// off_mem_rsvmap:\t0x28
+// version:\t\t17
+// last_comp_version:\t16
+// boot_cpuid_phys:\t0x0
+// size_dt_strings:\t0x60d
+// size_dt_struct:\t0x3bc0
+");
+
+	let mut v2 = DTB.to_vec();
+	set(&mut v2, 20, 2);
+	set(&mut v2, 24, 1);
+	let header = FDT::from_bytes(&v2).unwrap().header();
+	assert_eq!((header.boot_cpuid_phys, header.size_dt_strings, header.size_dt_struct), (Some(0), None, None));
+	assert_eq!(header.header_size(), 32);
+	assert!(header.to_string().ends_with("// last_comp_version:\t1\n// boot_cpuid_phys:\t0x0\n"));
+}
+
+#[test]
+fn test_layout() {
+	let layout = FDT::from_bytes(DTB).unwrap().layout();
+	assert_eq!(layout.blocks(), [
+		Block { kind: BlockKind::Header, offset: 0, size: 40 },
+		Block { kind: BlockKind::MemoryReserveMap, offset: 0x28, size: 32 },
+		Block { kind: BlockKind::Structure, offset: 0x48, size: 0x3bc0 },
+		Block { kind: BlockKind::Strings, offset: 0x3c08, size: 0x60d },
+	]);
+	assert!(layout.in_order() && !layout.overlaps());
+	assert_eq!(layout.gaps().count(), 0);
+	assert_eq!(layout.free_space(), 0);
+
+	// older versions without block sizes measure the blocks
+	let mut v16 = DTB.to_vec();
+	set(&mut v16, 20, 16);
+	set(&mut v16, 36, 0);
+	let measured = FDT::from_bytes(&v16).unwrap().layout();
+	assert_eq!(measured.blocks()[0].size, 36);
+	assert_eq!(measured.blocks()[1..], layout.blocks()[1..]);
+	assert_eq!(measured.gaps().collect::<Vec<_>>(), [(36, 4)]);
+
+	let mut v2 = Vec::new();
+	for field in [0xd00dfeed, 0x60, 0x38, 0x58, 0x28, 2, 1, 0] {
+		v2.extend_from_slice(&u32::to_be_bytes(field));
+	}
+	v2.resize(0x38, 0);
+	for word in [1, 0x2f000000, 3, 4, 0, 0x12345678, 2, 9] {
+		v2.extend_from_slice(&u32::to_be_bytes(word));
+	}
+	v2.extend_from_slice(b"prop\0\0\0\0");
+	let layout = FDT::from_bytes(&v2).unwrap().layout();
+	assert_eq!(layout.block(BlockKind::Structure), Block { kind: BlockKind::Structure, offset: 0x38, size: 32 });
+	assert_eq!(layout.block(BlockKind::Strings), Block { kind: BlockKind::Strings, offset: 0x58, size: 5 });
+	assert_eq!(layout.free_space(), 3);
+}
+
+/// A blob as left by a bootloader which moved the strings block to the end
+/// of a larger buffer and padded the reserve map.
+#[test]
+fn test_relocated() {
+	let mut dtb = DTB[..0x3c08].to_vec();
+	dtb.resize(0x5000, 0);
+	dtb.extend_from_slice(&DTB[0x3c08..]);
+	dtb.resize(0x6000, 0);
+	set(&mut dtb, 4, 0x6000);
+	set(&mut dtb, 12, 0x5000);
+	let fdt = FDT::from_bytes(&dtb).unwrap();
+	assert_eq!(fdt.nodes().count(), FDT::from_bytes(DTB).unwrap().nodes().count());
+	let layout = fdt.layout();
+	assert!(layout.in_order());
+	assert_eq!(layout.gaps().collect::<Vec<_>>(), [(0x3c08, 0x5000 - 0x3c08)]);
+	assert_eq!(layout.free_space(), 0x6000 - 0x5000 - 0x60d);
+
+	// strings before the structure block
+	let mut swapped = DTB[..0x48].to_vec();
+	swapped.extend_from_slice(&DTB[0x3c08..]);
+	swapped.resize(0x658, 0);
+	swapped.extend_from_slice(&DTB[0x48..0x3c08]);
+	set(&mut swapped, 8, 0x658);
+	set(&mut swapped, 12, 0x48);
+	let size = swapped.len() as u32;
+	set(&mut swapped, 4, size);
+	let fdt = FDT::from_bytes(&swapped).unwrap();
+	let layout = fdt.layout();
+	let kinds: Vec<_> = layout.blocks().iter().map(|block| block.kind).collect();
+	assert_eq!(kinds, [BlockKind::Header, BlockKind::MemoryReserveMap, BlockKind::Strings, BlockKind::Structure]);
+	assert!(!layout.in_order() && !layout.overlaps());
+	assert_eq!(layout.gaps().collect::<Vec<_>>(), [(0x655, 3)]);
+	assert_eq!(layout.block(BlockKind::Structure).end(), swapped.len());
+
+	// a reserve map running into the structure block
+	let mut overlapping = DTB.to_vec();
+	set(&mut overlapping, 16, 0x40);
+	let layout = FDT::from_bytes(&overlapping).unwrap().layout();
+	assert!(layout.overlaps() && !layout.in_order());
+}
@@ -0,0 +1,89 @@
+extern crate fdt;
+
+use fdt::{FDT, NodeIterator};
+use fdt::error::Error;
+use fdt::index::{self, FdtIndex, IndexEntry};
+
+const DTB: &[u8] = include_bytes!("dt.dtb");
+
+fn init() -> FDT<'static> {
+	unsafe {
+		FDT::from_raw(DTB.as_ptr()).unwrap()
+	}
+}
+
+#[test]
+fn test_matches_scanning() {
+	let fdt = init();
+	let mut storage = [IndexEntry::default(); 256];
+	let index = FdtIndex::in_buffer(&fdt, &mut storage).unwrap();
+	assert_eq!(index.len(), index::node_count(&fdt));
+
+	for node in fdt.nodes() {
+		let path = node.path().to_string();
+		assert_eq!(index.path(&path).unwrap().offset(), node.offset(), "{}", path);
+		assert_eq!(index.parent(&node).map(|p| p.offset()), node.parent().map(|p| p.offset()));
+		assert_eq!(index.first_child(&node).map(|c| c.offset()),
+			node.children().next().map(|c| c.offset()));
+		assert_eq!(index.descendant_count(&node), node.subnodes().count());
+		if let Some(phandle) = node.phandle() {
+			assert_eq!(index.phandle(phandle).unwrap().offset(), node.offset());
+		}
+	}
+	assert!(index.phandle(0).is_none());
+	assert!(index.phandle(0xdead).is_none());
+}
+
+#[test]
+fn test_navigation() {
+	let fdt = init();
+	let mut storage = [IndexEntry::default(); 256];
+	let index = FdtIndex::in_buffer(&fdt, &mut storage).unwrap();
+
+	let root = index.root().unwrap();
+	assert_eq!(index.path("/").unwrap().offset(), root.offset());
+	assert!(index.parent(&root).is_none());
+
+	let cpu0 = index.path("/cpus/cpu@0").unwrap();
+	let cpu1 = index.next_sibling(&cpu0).unwrap();
+	assert_eq!(cpu1.name(), "cpu@1");
+	let cpus = index.path("/cpus").unwrap();
+	let after = index.skip_subtree(&cpus).unwrap();
+	assert_eq!(index.parent(&after).unwrap().offset(), root.offset());
+	assert_eq!(index.next_sibling(&cpus).unwrap().offset(), after.offset());
+	assert!(index.next_sibling(&index.path("/cpus/cpu@3").unwrap()).is_none());
+}
+
+#[test]
+fn test_partial_paths_and_names() {
+	let fdt = init();
+	let mut storage = [IndexEntry::default(); 256];
+	let index = FdtIndex::in_buffer(&fdt, &mut storage).unwrap();
+
+	assert_eq!(index.path("/cpus/cpu").unwrap().name(), "cpu@0");
+	assert_eq!(index.path("/soc/serial@7e201000/").unwrap().name(), "serial@7e201000");
+	assert!(index.path("/soc/nope").is_none());
+	assert!(index.path("soc").is_none());
+	assert_eq!(index.alias("audio"), fdt.alias("audio"));
+
+	let indexed: Vec<usize> = index.with_name("cpu").map(|node| node.offset()).collect();
+	let scanned: Vec<usize> = fdt.nodes().with_name("cpu").map(|node| node.offset()).collect();
+	assert_eq!(indexed.len(), 4);
+	assert_eq!(indexed, scanned);
+}
+
+#[test]
+fn test_no_space() {
+	let fdt = init();
+	let mut storage = [IndexEntry::default(); 8];
+	assert_eq!(FdtIndex::in_buffer(&fdt, &mut storage).err(), Some(Error::NoSpace));
+}
+
+#[cfg(feature = "alloc")]
+#[test]
+fn test_vec() {
+	let fdt = init();
+	let index = FdtIndex::new(&fdt);
+	assert_eq!(index.len(), index::node_count(&fdt));
+	assert_eq!(index.phandle(67).unwrap().name(), "cpus");
+}
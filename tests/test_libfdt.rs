@@ -0,0 +1,467 @@
+extern crate fdt;
+
+use fdt::{FDT, MemoryReserveMapEntry};
+use fdt::error::Error;
+use fdt::tag::Tag;
+
+mod common;
+use common::{get, patched, set};
+
+// Conformance with libfdt, the blobs are assembled here after the trees of
+// the libfdt test suite (tests/test_tree1.dts, tests/trees.S) and the
+// expected errors are the return codes of the matching libfdt calls.
+
+const TOTALSIZE: usize = 4;
+const OFF_DT_STRUCT: usize = 8;
+const OFF_DT_STRINGS: usize = 12;
+const OFF_MEM_RSVMAP: usize = 16;
+const VERSION: usize = 20;
+const LAST_COMP_VERSION: usize = 24;
+const SIZE_DT_STRINGS: usize = 32;
+const SIZE_DT_STRUCT: usize = 36;
+
+#[derive(Default)]
+struct Asm {
+	structure: Vec<u8>,
+	strings: Vec<u8>,
+	rsvmap: Vec<(u64, u64)>,
+	// versions before 16 name nodes by their full paths and align values of
+	// 8 bytes or more to 8 bytes
+	legacy: bool,
+	path: Vec<String>,
+}
+
+impl Asm {
+	fn tag(&mut self, tag: u32) -> &mut Self {
+		self.structure.extend_from_slice(&tag.to_be_bytes());
+		self
+	}
+
+	fn begin(&mut self, name: &str) -> &mut Self {
+		self.tag(1);
+		self.path.push(name.into());
+		let name = match self.legacy {
+			true if self.path.len() == 1 => "/".into(),
+			true => self.path.join("/"),
+			false => name.into(),
+		};
+		self.structure.extend_from_slice(name.as_bytes());
+		self.structure.push(0);
+		self.align()
+	}
+
+	fn end(&mut self) -> &mut Self {
+		self.path.pop();
+		self.tag(2)
+	}
+
+	fn name(&mut self, name: &str) -> u32 {
+		let needle = [name.as_bytes(), b"\0"].concat();
+		match self.strings.windows(needle.len()).position(|w| w == &needle[..]) {
+			Some(offset) => offset as u32,
+			None => {
+				self.strings.extend_from_slice(&needle);
+				(self.strings.len() - needle.len()) as u32
+			},
+		}
+	}
+
+	fn prop(&mut self, name: &str, value: &[u8]) -> &mut Self {
+		let name = self.name(name);
+		self.prop_at(name, value)
+	}
+
+	fn prop_at(&mut self, name_off: u32, value: &[u8]) -> &mut Self {
+		self.tag(3).tag(value.len() as u32).tag(name_off);
+		if self.legacy && value.len() >= 8 && !self.structure.len().is_multiple_of(8) {
+			self.tag(0);
+		}
+		self.structure.extend_from_slice(value);
+		self.align()
+	}
+
+	fn u32(&mut self, name: &str, value: u32) -> &mut Self {
+		self.prop(name, &value.to_be_bytes())
+	}
+
+	fn string(&mut self, name: &str, value: &str) -> &mut Self {
+		self.prop(name, &[value.as_bytes(), b"\0"].concat())
+	}
+
+	fn align(&mut self) -> &mut Self {
+		while !self.structure.len().is_multiple_of(4) {
+			self.structure.push(0);
+		}
+		self
+	}
+
+	/// Flattens into a blob of [version], with a 40 byte header whatever the
+	/// version.
+	fn versioned(&self, version: u32) -> Vec<u8> {
+		let mut dtb = self.dtb();
+		set(&mut dtb, VERSION, version);
+		set(&mut dtb, LAST_COMP_VERSION, if version >= 16 { 16 } else { 1 });
+		dtb
+	}
+
+	/// Flattens into a version 17 blob.
+	fn dtb(&self) -> Vec<u8> {
+		let off_mem_rsvmap = 40;
+		let off_dt_struct = off_mem_rsvmap + 16 * (self.rsvmap.len() + 1);
+		let off_dt_strings = off_dt_struct + self.structure.len();
+		let totalsize = off_dt_strings + self.strings.len();
+		let mut dtb = Vec::new();
+		for field in [0xd00dfeed, totalsize, off_dt_struct, off_dt_strings, off_mem_rsvmap, 17, 16, 0,
+				self.strings.len(), self.structure.len()] {
+			dtb.extend_from_slice(&(field as u32).to_be_bytes());
+		}
+		for &(address, size) in self.rsvmap.iter().chain(&[(0, 0)]) {
+			dtb.extend_from_slice(&address.to_be_bytes());
+			dtb.extend_from_slice(&size.to_be_bytes());
+		}
+		dtb.extend_from_slice(&self.structure);
+		dtb.extend_from_slice(&self.strings);
+		dtb
+	}
+}
+
+fn header(dtb: &[u8]) -> Result<(), Error> {
+	FDT::from_bytes(dtb).map(|_| ())
+}
+
+fn check_full(dtb: &[u8]) -> Result<(), Error> {
+	FDT::from_bytes(dtb)?.check_full()
+}
+
+fn test_tree1() -> Vec<u8> {
+	tree1(17)
+}
+
+fn tree1(version: u32) -> Vec<u8> {
+	let mut asm = Asm {
+		rsvmap: vec![(0xdeadbeef00000000, 0x100000), (123456789, 0o10000)],
+		legacy: version < 16,
+		..Default::default()
+	};
+	asm.begin("")
+		.string("compatible", "test_tree1")
+		.u32("prop-int", 0xdeadbeef)
+		.prop("prop-int64", &0xdeadbeef01abcdefu64.to_be_bytes())
+		.string("prop-str", "hello world")
+		.u32("#address-cells", 1)
+		.u32("#size-cells", 0)
+		.begin("subnode@1")
+			.string("compatible", "subnode1")
+			.u32("reg", 1)
+			.prop("prop-int", &[0xde, 0xad, 0xbe, 0xef])
+			.begin("subsubnode")
+				.prop("compatible", b"subsubnode1\0subsubnode\0")
+				.prop("placeholder", b"this is a placeholder string\0string2\0")
+				.u32("prop-int", 0xdeadbeef)
+			.end()
+			.begin("ss1")
+			.end()
+		.end()
+		.begin("subnode@2")
+			.u32("reg", 2)
+			.u32("linux,phandle", 0x2000)
+			.u32("prop-int", 123456789)
+			.u32("#address-cells", 1)
+			.u32("#size-cells", 0)
+			.begin("subsubnode@0")
+				.u32("reg", 0)
+				.u32("phandle", 0x2001)
+				.prop("compatible", b"subsubnode2\0subsubnode\0")
+				.u32("prop-int", 0o726746425)
+			.end()
+			.begin("ss2")
+			.end()
+		.end()
+	.end()
+	.tag(9);
+	asm.versioned(version)
+}
+
+#[test]
+fn test_tree1_reads() {
+	for version in [1, 2, 3, 16, 17] {
+		check_tree1(&tree1(version));
+	}
+}
+
+fn check_tree1(dtb: &[u8]) {
+	let fdt = FDT::from_bytes(dtb).unwrap();
+	assert_eq!(fdt.check_full(), Ok(()));
+
+	// get_mem_rsv
+	let rsvmap: Vec<_> = fdt.memory_reserve_map().collect();
+	assert_eq!(rsvmap, [
+		MemoryReserveMapEntry { address: 0xdeadbeef00000000, size: 0x100000 },
+		MemoryReserveMapEntry { address: 123456789, size: 0o10000 },
+	]);
+
+	// root_node, getprop
+	let root = fdt.nodes().next().unwrap();
+	assert_eq!((root.name(), root.offset()), ("", 0));
+	assert!(root.is_compatible_with("test_tree1"));
+	assert_eq!(root.property_value::<u32>("prop-int"), Ok(0xdeadbeef));
+	assert_eq!(root.property_value::<u64>("prop-int64"), Ok(0xdeadbeef01abcdef));
+	assert_eq!(root.property("prop-str").unwrap().as_str(), "hello world\0");
+	assert!(root.property("nonexistant-property").is_none());
+
+	// path_offset, get_name, get_path
+	for path in ["/subnode@1", "/subnode@1/subsubnode", "/subnode@1/ss1", "/subnode@2",
+			"/subnode@2/subsubnode@0", "/subnode@2/ss2"] {
+		let node = fdt.nodes().with_path(path).next().unwrap();
+		assert_eq!(node.path().to_string(), path);
+		assert_eq!(node.name(), path.rsplit('/').next().unwrap());
+	}
+	// names without unit addresses match the first node of that name
+	assert_eq!(fdt.nodes().with_path("/subnode").next().unwrap().name(), "subnode@1");
+	assert_eq!(fdt.nodes().with_path("/subnode@2/subsubnode").next().unwrap().name(), "subsubnode@0");
+	assert!(fdt.nodes().with_path("/subnode@3").next().is_none());
+	assert!(fdt.nodes().with_path("/subnode@1/subsubnode/ss1").next().is_none());
+
+	// supernode_atdepth_offset, parent_offset
+	let subsubnode = fdt.nodes().with_path("/subnode@2/subsubnode@0").next().unwrap();
+	assert_eq!(subsubnode.depth(), 2);
+	assert_eq!(subsubnode.parent().unwrap().name(), "subnode@2");
+	assert_eq!(subsubnode.supernode_at_depth(0).unwrap().offset(), 0);
+	assert!(root.parent().is_none());
+
+	// get_phandle, node_offset_by_phandle, including linux,phandle
+	assert_eq!(subsubnode.phandle(), Some(0x2001));
+	assert_eq!(fdt.phandle(0x2000).unwrap().name(), "subnode@2");
+	assert_eq!(fdt.phandle(0x2001).unwrap().offset(), subsubnode.offset());
+	assert!(fdt.phandle(0).is_none());
+	assert!(fdt.phandle(u32::MAX).is_none());
+	assert!(root.phandle().is_none());
+
+	// node_check_compatible, node_offset_by_compatible
+	let compatible: Vec<_> = fdt.nodes().filter(|node| node.is_compatible_with("subsubnode"))
+		.map(|node| node.name()).collect();
+	assert_eq!(compatible, ["subsubnode", "subsubnode@0"]);
+	assert!(!subsubnode.is_compatible_with("subsubnode1"));
+
+	// node_offset_by_prop_value
+	let ints: Vec<_> = fdt.nodes().filter(|node| node.property_value::<u32>("prop-int") == Ok(0xdeadbeef))
+		.map(|node| node.name()).collect();
+	assert_eq!(ints, ["", "subnode@1", "subsubnode"]);
+	let placeholder = fdt.nodes().find_map(|node| node.property("placeholder")).unwrap();
+	assert_eq!(placeholder.as_stringlist().strings().collect::<Vec<_>>(), ["this is a placeholder string", "string2"]);
+}
+
+#[test]
+fn test_legacy_tags() {
+	let dtb = tree1(2);
+	let fdt = FDT::from_bytes(&dtb).unwrap();
+	let structure = dtb.as_ptr() as usize + get(&dtb, OFF_DT_STRUCT) as usize;
+	let mut names = Vec::new();
+	let mut aligned = 0;
+	for tag in fdt.tags() {
+		match tag.unwrap() {
+			Tag::BeginNode { name, .. } => names.push(name),
+			Tag::Prop { value, offset, .. } if value.len() >= 8 => {
+				if (offset + 12) % 8 != 0 {
+					aligned += 1;
+				}
+				assert!((value.as_ptr() as usize - structure).is_multiple_of(8));
+			},
+			_ => (),
+		}
+	}
+	assert_eq!(names, ["", "subnode@1", "subsubnode", "ss1", "subnode@2", "subsubnode@0", "ss2"]);
+	assert!(aligned > 0);
+	let v17 = test_tree1();
+	let latest = FDT::from_bytes(&v17).unwrap();
+	assert_eq!(fdt.nodes().map(|node| node.path().to_string()).collect::<Vec<_>>(),
+		latest.nodes().map(|node| node.path().to_string()).collect::<Vec<_>>());
+}
+
+#[cfg(feature = "alloc")]
+#[test]
+fn test_convert_to_latest() {
+	let v17 = test_tree1();
+	for version in [1, 2, 3, 16, 17] {
+		let dtb = tree1(version);
+		let fdt = FDT::from_bytes(&dtb).unwrap();
+		let latest = fdt.convert_to_latest();
+		let converted = FDT::from_bytes(&latest).unwrap();
+		assert_eq!(get(&latest, VERSION), 17);
+		assert_eq!(converted.boot_cpuid_phys(), Some(0));
+		assert_eq!(converted.check_full(), Ok(()));
+		assert_eq!(latest, v17);
+	}
+}
+
+#[test]
+fn test_check_header() {
+	let dtb = test_tree1();
+	let size = dtb.len() as u32;
+	assert_eq!(header(&dtb), Ok(()));
+
+	assert_eq!(header(&patched(&dtb, |dtb| set(dtb, 0, 0xdeadbeef))), Err(Error::BadMagic(0xdeadbeef)));
+
+	// versions, libfdt rejects version 1 as well
+	assert_eq!(header(&patched(&dtb, |dtb| set(dtb, VERSION, 0))), Err(Error::BadVersion(0)));
+	let mut future = patched(&dtb, |dtb| set(dtb, VERSION, 18));
+	assert_eq!(header(&future), Ok(()));
+	set(&mut future, LAST_COMP_VERSION, 18);
+	assert_eq!(header(&future), Err(Error::BadVersion(18)));
+	assert_eq!(header(&patched(&dtb, |dtb| set(dtb, LAST_COMP_VERSION, 18))), Err(Error::BadVersion(17)));
+
+	// totalsize
+	assert_eq!(header(&patched(&dtb, |dtb| set(dtb, TOTALSIZE, 0))), Err(Error::Truncated));
+	assert_eq!(header(&patched(&dtb, |dtb| set(dtb, TOTALSIZE, 39))), Err(Error::Truncated));
+	assert_eq!(header(&patched(&dtb, |dtb| set(dtb, TOTALSIZE, size + 1))), Err(Error::Truncated));
+	assert_eq!(header(&dtb[..dtb.len() - 1]), Err(Error::Truncated));
+	assert_eq!(header(&dtb[..20]), Err(Error::Truncated));
+
+	// blocks must lie between the header and totalsize
+	for field in [OFF_MEM_RSVMAP, OFF_DT_STRUCT, OFF_DT_STRINGS] {
+		assert_eq!(header(&patched(&dtb, |dtb| set(dtb, field, 0))), Err(Error::Truncated));
+		assert_eq!(header(&patched(&dtb, |dtb| set(dtb, field, 36))), Err(Error::Truncated));
+		assert_eq!(header(&patched(&dtb, |dtb| set(dtb, field, size + 1))), Err(Error::Truncated));
+	}
+	assert_eq!(header(&patched(&dtb, |dtb| set(dtb, OFF_MEM_RSVMAP, size))), Ok(()));
+	assert_eq!(header(&patched(&dtb, |dtb| set(dtb, SIZE_DT_STRUCT, size))), Err(Error::Truncated));
+	assert_eq!(header(&patched(&dtb, |dtb| set(dtb, SIZE_DT_STRINGS, u32::MAX))), Err(Error::Truncated));
+	let strings = get(&dtb, SIZE_DT_STRINGS);
+	assert_eq!(header(&patched(&dtb, |dtb| set(dtb, SIZE_DT_STRINGS, strings + 1))), Err(Error::Truncated));
+
+	// the buffer may extend past the blob
+	let mut padded = dtb.clone();
+	padded.resize(dtb.len() + 100, 0xff);
+	assert_eq!(FDT::from_bytes(&padded).unwrap().total_size(), size);
+}
+
+#[test]
+fn test_versions() {
+	let mut asm = Asm::default();
+	asm.begin("").u32("prop", 1).begin("node").end().end().tag(9);
+	let dtb = asm.dtb();
+
+	for (version, cpuid, header_size) in [(1, None, 28), (2, Some(7), 32), (3, Some(7), 36), (16, Some(7), 36), (17, Some(7), 40)] {
+		let mut dtb = patched(&dtb, |dtb| set(dtb, VERSION, version));
+		set(&mut dtb, LAST_COMP_VERSION, version.min(16));
+		set(&mut dtb, 28, 7);
+		let fdt = FDT::from_bytes(&dtb).unwrap();
+		assert_eq!(fdt.boot_cpuid_phys(), cpuid);
+		assert_eq!(fdt.check_full(), Ok(()));
+		assert_eq!(fdt.nodes().map(|node| node.name()).collect::<Vec<_>>(), ["", "node"]);
+
+		// only the fields of the version are part of the header
+		set(&mut dtb, TOTALSIZE, header_size);
+		for field in [OFF_MEM_RSVMAP, OFF_DT_STRUCT, OFF_DT_STRINGS] {
+			set(&mut dtb, field, header_size);
+		}
+		set(&mut dtb, SIZE_DT_STRINGS, 0);
+		set(&mut dtb, SIZE_DT_STRUCT, 0);
+		assert_eq!(header(&dtb[..header_size as usize]), Ok(()));
+		set(&mut dtb, TOTALSIZE, header_size - 1);
+		assert_eq!(header(&dtb), Err(Error::Truncated));
+	}
+
+	// version 16 blobs have no size_dt_struct, the structure block ends with
+	// the blob
+	let mut v16 = patched(&dtb, |dtb| set(dtb, VERSION, 16));
+	set(&mut v16, SIZE_DT_STRUCT, u32::MAX);
+	assert_eq!(check_full(&v16), Ok(()));
+	assert_eq!(check_full(&patched(&dtb, |dtb| set(dtb, SIZE_DT_STRUCT, 4))), Err(Error::Truncated));
+}
+
+#[test]
+fn test_bad_strings() {
+	let mut asm = Asm::default();
+	asm.begin("").prop("prop", b"value\0");
+	let good = asm.strings.len() as u32;
+	asm.prop_at(good, &[]).end().tag(9);
+	let dtb = asm.dtb();
+	let fdt = FDT::from_bytes(&dtb).unwrap();
+	assert_eq!(fdt.tags().string(0), Ok("prop"));
+	// fdt_get_string: offsets at or past the end of the block
+	assert_eq!(fdt.tags().string(good), Err(Error::BadOffset));
+	assert_eq!(fdt.tags().string(u32::MAX), Err(Error::BadOffset));
+	assert_eq!(fdt.check_full(), Err(Error::BadOffset));
+	// the readers skip the rest of the node's properties
+	assert_eq!(fdt.nodes().next().unwrap().properties().count(), 1);
+
+	// a string not terminated within the block
+	let dtb = patched(&test_tree1(), |dtb| set(dtb, SIZE_DT_STRINGS, get(&test_tree1(), SIZE_DT_STRINGS) - 1));
+	let fdt = FDT::from_bytes(&dtb).unwrap();
+	assert_eq!(fdt.check_full(), Err(Error::Truncated));
+	let last = fdt.tags().filter_map(|tag| match tag.unwrap() {
+		Tag::Prop { name_off, .. } => Some(name_off),
+		_ => None,
+	}).max().unwrap();
+	assert_eq!(fdt.tags().string(last), Err(Error::Truncated));
+}
+
+#[test]
+fn test_bad_structure() {
+	fn tree(build: fn(&mut Asm)) -> Vec<u8> {
+		let mut asm = Asm::default();
+		build(&mut asm);
+		asm.dtb()
+	}
+
+	// an empty structure block is accepted by libfdt
+	assert_eq!(check_full(&tree(|asm| { asm.tag(9); })), Ok(()));
+	assert_eq!(check_full(&tree(|asm| { asm.tag(4).begin("").tag(4).end().tag(4).tag(9); })), Ok(()));
+
+	// misnested nodes
+	assert_eq!(check_full(&tree(|asm| { asm.begin("").end().end().tag(9); })), Err(Error::BadStructure));
+	assert_eq!(check_full(&tree(|asm| { asm.begin("").begin("node").end().tag(9); })), Err(Error::BadStructure));
+	assert_eq!(check_full(&tree(|asm| { asm.end().tag(9); })), Err(Error::BadStructure));
+	// two_roots and named_root
+	assert_eq!(check_full(&tree(|asm| { asm.begin("").end().begin("").end().tag(9); })), Err(Error::BadStructure));
+	assert_eq!(check_full(&tree(|asm| { asm.begin("root").end().tag(9); })), Err(Error::BadStructure));
+	// a property after the root node
+	assert_eq!(check_full(&tree(|asm| { asm.begin("").end().u32("prop", 1).tag(9); })), Err(Error::BadStructure));
+	// unknown tags
+	assert_eq!(check_full(&tree(|asm| { asm.begin("").tag(7).end().tag(9); })), Err(Error::BadStructure));
+
+	// truncated_property, truncated names and a missing FDT_END
+	let truncated = tree(|asm| { asm.begin("").u32("prop", 1).end().tag(9); });
+	let value = truncated.len() - get(&truncated, SIZE_DT_STRINGS) as usize - 12;
+	let mut dtb = truncated.clone();
+	set(&mut dtb, value - 8, 0x1000);
+	assert_eq!(check_full(&dtb), Err(Error::Truncated));
+	assert_eq!(check_full(&tree(|asm| { asm.tag(1).tag(0x6e6f6465); })), Err(Error::Truncated));
+	assert_eq!(check_full(&tree(|asm| { asm.begin("").end(); })), Err(Error::Truncated));
+
+	// the readers stop at the corruption without panicking
+	let dtb = tree(|asm| { asm.begin("").begin("a").tag(7).begin("b").end().end().end().tag(9); });
+	let fdt = FDT::from_bytes(&dtb).unwrap();
+	assert_eq!(fdt.nodes().map(|node| node.name()).collect::<Vec<_>>(), ["", "a"]);
+}
+
+#[test]
+fn test_rsvmap() {
+	let mut asm = Asm::default();
+	asm.begin("").end().tag(9);
+	let dtb = asm.dtb();
+	let fdt = FDT::from_bytes(&dtb).unwrap();
+	assert_eq!(fdt.memory_reserve_map().count(), 0);
+	assert_eq!(fdt.check_full(), Ok(()));
+
+	// like fdt_num_mem_rsv any entry of size 0 ends the map
+	asm.rsvmap = vec![(0x1000, 0x1000), (0x2000, 0), (0x3000, 0x1000)];
+	let dtb = asm.dtb();
+	let fdt = FDT::from_bytes(&dtb).unwrap();
+	assert_eq!(fdt.memory_reserve_map().collect::<Vec<_>>(), [MemoryReserveMapEntry { address: 0x1000, size: 0x1000 }]);
+
+	// truncated_memrsv: the map isn't terminated within the blob
+	asm.rsvmap = vec![(0x1000, 0x1000)];
+	let mut dtb = asm.dtb();
+	let end = dtb.len() as u32;
+	dtb.extend_from_slice(&[0; 8]);
+	dtb.extend_from_slice(&[0xff; 16]);
+	set(&mut dtb, OFF_MEM_RSVMAP, end);
+	set(&mut dtb, TOTALSIZE, end + 24);
+	let fdt = FDT::from_bytes(&dtb).unwrap();
+	assert_eq!(fdt.memory_reserve_map().count(), 1);
+	assert_eq!(fdt.check_full(), Err(Error::Truncated));
+	set(&mut dtb, OFF_MEM_RSVMAP, 40);
+	assert_eq!(check_full(&dtb), Ok(()));
+}
@@ -0,0 +1,227 @@
+#![cfg(feature = "alloc")]
+
+extern crate fdt;
+
+use fdt::digest::{self, Algorithm, Digest};
+use fdt::fit::{DataLocation, Fit, ImageType, Verification};
+use fdt::mkimage::{BuildError, ConfigurationSource, FitSource, ImageSource, Layout, SignatureSource, Signer};
+use fdt::owned::{OwnedNode, OwnedTree};
+use fdt::signature::{Crypto, Keys, RsaKey, SignatureError};
+use fdt::FDT;
+
+fn source() -> FitSource {
+	let mut kernel = ImageSource::new("kernel-1", "kernel", b"kernel data");
+	kernel.description = Some("Linux".into());
+	kernel.arch = Some("arm64".into());
+	kernel.os = Some("linux".into());
+	kernel.compression = Some("gzip".into());
+	kernel.load = Some(0x80080000);
+	kernel.entry = Some(0x1_0008_0000);
+	kernel.hashes = vec![Algorithm::Sha256, Algorithm::Crc32];
+	let mut fdt = ImageSource::new("fdt-1", "flat_dt", b"fdt");
+	fdt.hashes = vec![Algorithm::Sha1];
+	let ramdisk = ImageSource::new("ramdisk-1", "ramdisk", b"ramdisk data");
+
+	let mut config = ConfigurationSource::new("conf-1");
+	config.description = Some("Boot Linux".into());
+	config.kernel = Some("kernel-1".into());
+	config.fdt = vec!["fdt-1".into()];
+	config.ramdisk = Some("ramdisk-1".into());
+	config.compatible = vec!["acme,board".into()];
+	let mut other = ConfigurationSource::new("conf-2");
+	other.kernel = Some("kernel-1".into());
+	other.loadables = vec!["fdt-1".into(), "ramdisk-1".into()];
+	FitSource {
+		description: "test image".into(),
+		timestamp: Some(0x5f000000),
+		images: vec![kernel, fdt, ramdisk],
+		configurations: vec![config, other],
+		default: Some("conf-1".into()),
+	}
+}
+
+fn check(image: &[u8]) {
+	let fit = Fit::from_bytes(image).unwrap();
+	assert_eq!(fit.description(), Some("test image"));
+	assert_eq!(fit.timestamp(), Some(0x5f000000));
+	let report: Vec<_> = fit.verify_images(&mut digest::Software).map(|(image, verification)| (image.name(), verification)).collect();
+	assert_eq!(report, [
+		("kernel-1", Verification::Verified { hashes: 2 }),
+		("fdt-1", Verification::Verified { hashes: 1 }),
+		("ramdisk-1", Verification::Unhashed),
+	]);
+
+	let kernel = fit.image("kernel-1").unwrap();
+	assert_eq!(kernel.description(), Some("Linux"));
+	assert_eq!(kernel.kind(), Ok(ImageType::Kernel));
+	assert_eq!((kernel.arch(), kernel.os()), (Some("arm64"), Some("linux")));
+	assert_eq!(kernel.load(), Ok(Some(0x80080000)));
+	assert_eq!(kernel.entry(), Ok(Some(0x1_0008_0000)));
+	assert_eq!(kernel.data(), Ok(&b"kernel data"[..]));
+
+	let config = fit.default_configuration().unwrap();
+	assert_eq!(config.description(), Some("Boot Linux"));
+	assert!(config.compatible().unwrap().contains("acme,board"));
+	assert_eq!(config.ramdisk().unwrap().unwrap().data(), Ok(&b"ramdisk data"[..]));
+	assert_eq!(config.fdt().next().unwrap().unwrap().data(), Ok(&b"fdt"[..]));
+	let loadables: Vec<_> = fit.configuration("conf-2").unwrap().loadables().map(|image| image.unwrap().name()).collect();
+	assert_eq!(loadables, ["fdt-1", "ramdisk-1"]);
+}
+
+#[test]
+fn test_embedded() {
+	let image = source().build(Layout::Embedded).unwrap();
+	check(&image);
+	let fdt = FDT::from_bytes(&image).unwrap();
+	assert_eq!(fdt.total_size() as usize, image.len());
+	let fit = Fit::from_bytes(&image).unwrap();
+	assert!(fit.images().all(|image| image.data_location() == Ok(DataLocation::Embedded)));
+}
+
+#[test]
+fn test_external() {
+	let image = source().build(Layout::External { alignment: 4, position: None }).unwrap();
+	check(&image);
+	let fit = Fit::from_bytes(&image).unwrap();
+	let size = fit.fdt().total_size() as usize;
+	assert_eq!(size % 4, 0);
+	let locations: Vec<_> = fit.images().map(|image| image.data_location().unwrap()).collect();
+	assert_eq!(locations, [
+		DataLocation::External { position: size, size: 11 },
+		DataLocation::External { position: size + 12, size: 3 },
+		DataLocation::External { position: size + 16, size: 12 },
+	]);
+	assert_eq!(image.len(), size + 28);
+	assert!(fit.image("kernel-1").unwrap().node().property("data").is_none());
+
+	let image = source().build(Layout::External { alignment: 512, position: None }).unwrap();
+	check(&image);
+	let fit = Fit::from_bytes(&image).unwrap();
+	let size = fit.fdt().total_size() as usize;
+	assert_eq!(size % 512, 0);
+	let offsets: Vec<_> = fit.images().map(|image| image.node().property_value::<u32>("data-offset").unwrap()).collect();
+	assert_eq!(offsets, [0, 512, 1024]);
+	assert_eq!(image.len(), size + 1536);
+
+	// data-offset counts from the end of the blob aligned to 4
+	let image = source().build(Layout::External { alignment: 1, position: None }).unwrap();
+	check(&image);
+	assert_eq!(image, source().build(Layout::External { alignment: 4, position: None }).unwrap());
+	let fit = Fit::from_bytes(&image).unwrap();
+	assert_eq!(fit.image("kernel-1").unwrap().data(), Ok(&b"kernel data"[..]));
+}
+
+#[test]
+fn test_position() {
+	let image = source().build(Layout::External { alignment: 4, position: Some(0x1000) }).unwrap();
+	check(&image);
+	let fit = Fit::from_bytes(&image).unwrap();
+	let positions: Vec<_> = fit.images().map(|image| image.node().property_value::<u32>("data-position").unwrap()).collect();
+	assert_eq!(positions, [0x1000, 0x100c, 0x1010]);
+	assert_eq!(&image[0x1000..0x100b], b"kernel data");
+	assert_eq!(source().build(Layout::External { alignment: 4, position: Some(8) }), Err(BuildError::Overlap));
+}
+
+#[test]
+fn test_errors() {
+	let mut source = source();
+	source.default = Some("conf-3".into());
+	assert_eq!(source.build(Layout::Embedded), Err(BuildError::MissingConfiguration("conf-3".into())));
+	source.default = None;
+	source.configurations[1].loadables.push("fpga-1".into());
+	assert_eq!(source.build(Layout::Embedded), Err(BuildError::MissingImage("fpga-1".into())));
+	assert_eq!(BuildError::MissingImage("fpga-1".into()).to_string(), "no image 'fpga-1'");
+
+	let mut source = self::source();
+	source.configurations[0].signatures.push(signature(&[]));
+	assert_eq!(source.build(Layout::Embedded), Err(BuildError::Signature(SignatureError::Unsupported)));
+	source.configurations[0].signatures[0].algo = "sha256,dsa1024".into();
+	assert_eq!(source.build_signed(Layout::Embedded, &mut Pkcs1),
+		Err(BuildError::Signature(SignatureError::Unsupported)));
+}
+
+fn signature(signed_images: &[&str]) -> SignatureSource {
+	SignatureSource {
+		algo: "sha256,rsa1024".into(),
+		key_name_hint: "dev".into(),
+		padding: None,
+		signed_images: signed_images.iter().map(|&kind| kind.into()).collect(),
+	}
+}
+
+/// A signer producing the PKCS #1 v1.5 encoding of sha256 digests for a
+/// 1024 bit key, without the private key operation.
+struct Pkcs1;
+
+impl Signer for Pkcs1 {
+	fn sign(&mut self, signature: &SignatureSource, digest: &Digest) -> Result<Vec<u8>, SignatureError> {
+		assert_eq!(signature.key_name_hint, "dev");
+		let info = [0x30, 0x31, 0x30, 0x0d, 0x06, 0x09, 0x60, 0x86, 0x48, 0x01, 0x65, 0x03, 0x04, 0x02, 0x01, 0x05, 0x00, 0x04, 0x20];
+		let mut value = vec![0, 1];
+		value.resize(128 - info.len() - digest.as_ref().len() - 1, 0xff);
+		value.push(0);
+		value.extend_from_slice(&info);
+		value.extend_from_slice(digest.as_ref());
+		Ok(value)
+	}
+}
+
+/// The public key operation matching [Pkcs1].
+struct Identity;
+
+impl Crypto for Identity {
+	fn rsa_public(&mut self, _: &RsaKey, signature: &[u8], out: &mut [u8]) -> bool {
+		out.copy_from_slice(signature);
+		true
+	}
+}
+
+fn control(required: &str) -> Vec<u8> {
+	let mut key = OwnedNode::new("key-dev");
+	key.set_property("required", &[required.as_bytes(), b"\0"].concat());
+	key.set_property("algo", b"sha256,rsa1024\0");
+	key.set_property("rsa,num-bits", &1024u32.to_be_bytes());
+	key.set_property("rsa,n0-inverse", &[0; 4]);
+	key.set_property("rsa,modulus", &[0xff; 128]);
+	key.set_property("rsa,r-squared", &[0; 128]);
+	let mut signature = OwnedNode::new("signature");
+	signature.children.push(key);
+	let mut tree = OwnedTree::default();
+	tree.root.children.push(signature);
+	tree.to_dtb()
+}
+
+#[test]
+fn test_signed() {
+	let mut source = source();
+	source.configurations[0].signatures = vec![signature(&[]), signature(&["kernel", "fdt"])];
+	source.images[0].signatures = vec![signature(&[])];
+	let conf = control("conf");
+	let conf = FDT::from_bytes(&conf).unwrap();
+	let image = control("image");
+	let image = FDT::from_bytes(&image).unwrap();
+
+	for layout in [Layout::Embedded, Layout::External { alignment: 8, position: None },
+			Layout::External { alignment: 4, position: Some(0x2000) }] {
+		let blob = source.build_signed(layout, &mut Pkcs1).unwrap();
+		check(&blob);
+		let fit = Fit::from_bytes(&blob).unwrap();
+		let config = fit.default_configuration().unwrap();
+		assert_eq!(config.verify_required_signatures(&Keys::new(&conf), &mut digest::Software, &mut Identity), Ok(1));
+		let kernel = fit.image("kernel-1").unwrap();
+		assert_eq!(kernel.verify_required_signatures(&Keys::new(&image), &mut digest::Software, &mut Identity), Ok(1));
+
+		let signatures: Vec<_> = config.signatures().collect();
+		let nodes: Vec<_> = signatures[0].hashed_nodes().unwrap().strings().collect();
+		assert_eq!(nodes, ["/", "/configurations/conf-1", "/images/kernel-1", "/images/kernel-1/hash-1",
+			"/images/kernel-1/hash-2", "/images/ramdisk-1", "/images/fdt-1", "/images/fdt-1/hash-1"]);
+		let kinds: Vec<_> = signatures[0].signed_images().unwrap().strings().collect();
+		assert_eq!(kinds, ["kernel", "ramdisk", "fdt"]);
+		let nodes: Vec<_> = signatures[1].hashed_nodes().unwrap().strings().collect();
+		assert_eq!(nodes, ["/", "/configurations/conf-1", "/images/kernel-1", "/images/kernel-1/hash-1",
+			"/images/kernel-1/hash-2", "/images/fdt-1", "/images/fdt-1/hash-1"]);
+		for signature in &signatures {
+			assert_eq!(signature.verify(&Keys::new(&conf).get("dev").unwrap(), &mut digest::Software, &mut Identity), Ok(()));
+		}
+	}
+}
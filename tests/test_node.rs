@@ -0,0 +1,27 @@
+extern crate fdt;
+
+use fdt::{FDT, NodeIterator};
+
+const DTB: &[u8] = include_bytes!("dt.dtb");
+
+fn init() -> FDT<'static> {
+	unsafe {
+		FDT::from_raw(DTB.as_ptr()).unwrap()
+	}
+}
+
+#[test]
+fn test_children() {
+	let fdt = init();
+	let root = fdt.nodes().next().unwrap();
+	let names: Vec<_> = root.children().map(|node| node.name().to_string()).collect();
+	assert_eq!(names, ["chosen", "soc", "clocks", "timer", "cpus", "fixedregulator_5v0", "fixedregulator_3v3",
+		"memory", "aliases", "leds", "__overrides__", "__symbols__"]);
+
+	// the spidev nodes are children of the spi controller, not grandchildren
+	let spi = fdt.nodes().with_name("spi@7e204000").next().unwrap();
+	let names: Vec<_> = spi.children().map(|node| node.name().to_string()).collect();
+	assert_eq!(names, ["spidev@0", "spidev@1"]);
+	let spidev = fdt.nodes().with_name("spidev@0").next().unwrap();
+	assert_eq!(spidev.children().count(), 0);
+}
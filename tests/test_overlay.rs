@@ -0,0 +1,122 @@
+#![cfg(feature = "alloc")]
+
+extern crate fdt;
+
+use fdt::FDT;
+use fdt::error::Error;
+use fdt::owned::OwnedTree;
+use fdt::overlay;
+
+mod common;
+use common::{cells, node};
+
+const DTB: &[u8] = include_bytes!("dt.dtb");
+
+fn init() -> FDT<'static> {
+	unsafe {
+		FDT::from_raw(DTB.as_ptr()).unwrap()
+	}
+}
+
+/// An overlay as dtc compiles it with symbols: an led node referring to the
+/// base tree's gpio controller by label and a consumer referring to the led.
+fn leds_overlay() -> OwnedTree {
+	let mut tree = OwnedTree::default();
+	tree.root.children = vec![
+		node("fragment@0", &[("target", &cells(&[0xffffffff]))], vec![
+			node("__overlay__", &[], vec![
+				node("led@5", &[("gpios", &cells(&[0xffffffff, 5, 0])), ("phandle", &cells(&[1]))], vec![]),
+			]),
+		]),
+		node("fragment@1", &[("target-path", b"/soc/i2c@7e205000\0")], vec![
+			node("__overlay__", &[("status", b"okay\0"), ("leds", &cells(&[1]))], vec![]),
+		]),
+		node("__symbols__", &[("led5", b"/fragment@0/__overlay__/led@5\0")], vec![]),
+		node("__fixups__", &[("gpio", b"/fragment@0:target:0\0/fragment@0/__overlay__/led@5:gpios:0\0")], vec![]),
+		node("__local_fixups__", &[], vec![
+			node("fragment@1", &[], vec![
+				node("__overlay__", &[("leds", &cells(&[0]))], vec![]),
+			]),
+		]),
+	];
+	tree
+}
+
+#[test]
+fn test_to_dtb_round_trip() {
+	let fdt = init();
+	let tree = OwnedTree::from_fdt(&fdt);
+	let dtb = tree.to_dtb();
+	let copy = unsafe { FDT::from_raw(dtb.as_ptr()).unwrap() };
+	assert_eq!(copy.total_size() as usize, dtb.len());
+	assert_eq!(fdt::diff(&fdt, &copy, |change| panic!("{}", change)), 0);
+	assert_eq!(OwnedTree::from_fdt(&copy), tree);
+}
+
+#[test]
+fn test_to_dtb_shares_strings() {
+	let tree = OwnedTree {
+		root: node("", &[("#size-cells", &cells(&[1])), ("size-cells", &cells(&[2]))], vec![]),
+		..Default::default()
+	};
+	let dtb = tree.to_dtb();
+	assert_eq!(&dtb[dtb.len() - 12..], b"#size-cells\0");
+}
+
+#[test]
+fn test_apply() {
+	let mut base = OwnedTree::from_fdt(&init());
+	let max = base.max_phandle();
+	overlay::apply(&mut base, &leds_overlay()).unwrap();
+
+	let led = base.node("/soc/gpio@7e200000/led@5").unwrap();
+	assert_eq!(led.phandle(), Some(max + 1));
+	assert_eq!(led.property("gpios").unwrap().value, cells(&[0xa, 5, 0]));
+
+	let i2c = base.node("/soc/i2c@7e205000").unwrap();
+	assert_eq!(i2c.property("status").unwrap().value, b"okay\0");
+	assert_eq!(i2c.property("leds").unwrap().value, cells(&[max + 1]));
+
+	let symbols = base.node("/__symbols__").unwrap();
+	assert_eq!(symbols.property("led5").unwrap().value, b"/soc/gpio@7e200000/led@5\0");
+	assert!(base.node("/fragment@0").is_none());
+}
+
+#[test]
+fn test_apply_errors() {
+	let mut base = OwnedTree::from_fdt(&init());
+	let copy = base.clone();
+
+	let mut missing_label = leds_overlay();
+	missing_label.root.child_mut("__fixups__").unwrap().properties[0].name = "nope".into();
+	assert_eq!(overlay::apply(&mut base, &missing_label), Err(Error::NotFound));
+
+	let mut missing_target = leds_overlay();
+	missing_target.root.child_mut("fragment@1").unwrap().set_property("target-path", b"/nope\0");
+	assert_eq!(overlay::apply(&mut base, &missing_target), Err(Error::NotFound));
+
+	let mut bad_offset = leds_overlay();
+	bad_offset.root.child_mut("__local_fixups__").unwrap().children[0].children[0]
+		.set_property("leds", &cells(&[4]));
+	assert_eq!(overlay::apply(&mut base, &bad_offset), Err(Error::BadValue));
+
+	assert_eq!(base, copy);
+}
+
+#[test]
+fn test_apply_exact_target() {
+	let mut base = OwnedTree::default();
+	base.root.children = vec![
+		node("foo@1", &[], vec![]),
+		node("foo", &[("phandle", &cells(&[1]))], vec![]),
+	];
+	let mut overlay = OwnedTree::default();
+	overlay.root.children = vec![
+		node("fragment@0", &[("target", &cells(&[1]))], vec![
+			node("__overlay__", &[("status", b"okay\0")], vec![]),
+		]),
+	];
+	overlay::apply(&mut base, &overlay).unwrap();
+	assert!(base.exact_node("/foo@1").unwrap().property("status").is_none());
+	assert_eq!(base.exact_node("/foo").unwrap().property("status").unwrap().value, b"okay\0");
+}
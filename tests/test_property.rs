@@ -0,0 +1,75 @@
+extern crate fdt;
+
+use fdt::{FDT, NodeIterator, PropertyIterator, PropertyValue, Cells, StringList};
+use fdt::error::Error;
+
+const DTB: &[u8] = include_bytes!("dt.dtb");
+
+fn init() -> FDT<'static> {
+	unsafe {
+		FDT::from_raw(DTB.as_ptr()).unwrap()
+	}
+}
+
+#[test]
+fn test_fixed_size_values() {
+	assert_eq!(u32::from_raw(&[0, 0, 1, 0]), Ok(256));
+	assert_eq!(u32::from_raw(&[0, 1]), Err(Error::Truncated));
+	assert_eq!(u32::from_raw(&[0, 0, 0, 1, 0]), Err(Error::BadLength(5)));
+	assert_eq!(u64::from_raw(&[0, 0, 0, 1, 0, 0, 0, 2]), Ok(0x1_0000_0002));
+	assert_eq!(<[u32; 2]>::from_raw(&[0, 0, 0, 1, 0, 0, 0, 2]), Ok([1, 2]));
+	assert_eq!(<[u32; 3]>::from_raw(&[0, 0, 0, 1, 0, 0, 0, 2]), Err(Error::Truncated));
+}
+
+#[test]
+fn test_cells() {
+	let fdt = init();
+	let soc = fdt.nodes().with_name("soc").next().unwrap();
+	let ranges = soc.property("ranges").unwrap().parse::<Cells>().unwrap();
+	assert_eq!(ranges.len(), 6);
+	assert_eq!(ranges.collect::<Vec<_>>(), [0x7e000000, 0x3f000000, 0x01000000, 0x40000000, 0x40000000, 0x00040000]);
+	assert_eq!(Cells::from_raw(&[0, 0, 0]).err(), Some(Error::BadLength(3)));
+}
+
+#[test]
+fn test_tuples() {
+	let fdt = init();
+	let dma = fdt.nodes().with_name("dma@7e007000").next().unwrap();
+	assert_eq!(dma.property_value::<(u32, u32)>("reg"), Ok((0x7e007000, 0xf00)));
+	assert_eq!(<(&str, u32)>::from_raw(b"gpios\0\0\0\0\x04"), Ok(("gpios", 4)));
+	assert_eq!(<(u32, u32)>::from_raw(&[0, 0, 0, 1]), Err(Error::Truncated));
+}
+
+#[test]
+fn test_presence_and_options() {
+	let fdt = init();
+	let gpio = fdt.nodes().with_name("gpio@7e200000").next().unwrap();
+	assert_eq!(gpio.property_value::<bool>("gpio-controller"), Ok(true));
+	assert_eq!(gpio.property_value::<bool>("dma-coherent"), Ok(false));
+	assert_eq!(gpio.property_value::<bool>("#gpio-cells"), Err(Error::BadLength(4)));
+	assert_eq!(gpio.property_value::<Option<u32>>("#gpio-cells"), Ok(Some(2)));
+	assert_eq!(gpio.property_value::<Option<u32>>("#pwm-cells"), Ok(None));
+	assert_eq!(gpio.property_value::<u32>("#pwm-cells"), Err(Error::NotFound));
+}
+
+#[test]
+fn test_strings() {
+	let fdt = init();
+	let uart = fdt.nodes().with_name("serial@7e201000").next().unwrap();
+	let compatible = uart.property_value::<StringList>("compatible").unwrap();
+	assert_eq!(compatible.strings().collect::<Vec<_>>(), ["brcm,bcm2835-pl011", "arm,pl011", "arm,primecell"]);
+	assert_eq!(StringList::from_raw(b"unterminated").err(), Some(Error::BadValue));
+	assert!(matches!(<&str>::from_raw(&[0xff, 0]), Err(Error::BadUtf8(_))));
+}
+
+#[test]
+fn test_is_value() {
+	let fdt = init();
+	let okay = fdt.nodes().with_property("status").with_value("okay").count();
+	assert_eq!(okay, 4);
+	let mut uart = fdt.nodes().with_property("clocks").with_value(&[0, 0, 0, 0xe, 0, 0, 0, 0][..]);
+	assert_eq!(uart.next().unwrap().name(), "serial@7e215040");
+	let props = fdt.nodes().with_name("chosen").next().unwrap();
+	assert!(props.properties().with_name("bootargs").next().unwrap().is_equal(""));
+	assert!(!props.properties().with_name("bootargs").next().unwrap().is_equal(0u64));
+}
@@ -0,0 +1,273 @@
+extern crate fdt;
+
+use fdt::{FDT, BlockKind};
+use fdt::dts::dts;
+use fdt::error::Error;
+use fdt::tag::Tag;
+
+mod common;
+use common::{get, set};
+
+const DTB: &[u8] = include_bytes!("dt.dtb");
+
+/// The test tree with its strings block moved in front of the structure
+/// block and free space in between.
+fn misordered() -> Vec<u8> {
+	let (structure, strings) = (0x48..0x3c08, 0x3c08..DTB.len());
+	let mut dtb = DTB[..0x48].to_vec();
+	dtb.extend_from_slice(&DTB[strings]);
+	dtb.resize(0x700, 0);
+	dtb.extend_from_slice(&DTB[structure]);
+	dtb.resize(dtb.len() + 0x100, 0);
+	let size = dtb.len() as u32;
+	set(&mut dtb, 4, size);
+	set(&mut dtb, 8, 0x700);
+	set(&mut dtb, 12, 0x48);
+	dtb
+}
+
+/// The test tree with the properties named [name] replaced by NOP tags.
+fn nopped(name: &str) -> Vec<u8> {
+	let mut dtb = DTB.to_vec();
+	let fdt = FDT::from_bytes(DTB).unwrap();
+	let tags = fdt.tags();
+	for tag in fdt.tags() {
+		if let Tag::Prop { name_off, value, offset } = tag.unwrap() {
+			if tags.string(name_off) == Ok(name) {
+				let start = 0x48 + offset;
+				for word in (start..start + 12 + value.len().div_ceil(4) * 4).step_by(4) {
+					set(&mut dtb, word, 4);
+				}
+			}
+		}
+	}
+	dtb
+}
+
+/// The test tree with a copy of its name for every property and an unused
+/// name in its strings block, as some tools write them.
+fn bloated() -> Vec<u8> {
+	let fdt = FDT::from_bytes(DTB).unwrap();
+	let tags = fdt.tags();
+	let mut dtb = DTB.to_vec();
+	dtb.extend_from_slice(b"unused\0");
+	for tag in fdt.tags() {
+		if let Tag::Prop { name_off, offset, .. } = tag.unwrap() {
+			let copy = (dtb.len() - 0x3c08) as u32;
+			dtb.extend_from_slice(tags.string(name_off).unwrap().as_bytes());
+			dtb.push(0);
+			set(&mut dtb, 0x48 + offset + 8, copy);
+		}
+	}
+	let size = dtb.len() as u32;
+	set(&mut dtb, 4, size);
+	set(&mut dtb, 32, size - 0x3c08);
+	dtb
+}
+
+/// The size of the strings block holding the property names of [fdt] with
+/// suffixes merged.
+fn merged_size(fdt: &FDT) -> usize {
+	let tags = fdt.tags();
+	let mut names: Vec<&str> = fdt.tags().filter_map(|tag| match tag.unwrap() {
+		Tag::Prop { name_off, .. } => Some(tags.string(name_off).unwrap()),
+		_ => None,
+	}).collect();
+	names.sort();
+	names.dedup();
+	names.iter()
+		.filter(|name| !names.iter().any(|other| other.len() > name.len() && other.ends_with(*name)))
+		.map(|name| name.len() + 1)
+		.sum()
+}
+
+/// A blob whose root has [count] properties named `prop-<i>` and as many
+/// named `<i>`, which end the former.
+fn many_names(count: usize) -> Vec<u8> {
+	let (mut structure, mut strings) = (Vec::new(), Vec::new());
+	let word = |structure: &mut Vec<u8>, word: u32| structure.extend_from_slice(&word.to_be_bytes());
+	word(&mut structure, 1);
+	word(&mut structure, 0);
+	for name in (0..count).map(|i| format!("prop-{}", i)).chain((0..count).map(|i| i.to_string())) {
+		word(&mut structure, 3);
+		word(&mut structure, 0);
+		word(&mut structure, strings.len() as u32);
+		strings.extend_from_slice(name.as_bytes());
+		strings.push(0);
+	}
+	word(&mut structure, 2);
+	word(&mut structure, 9);
+	let mut dtb = vec![0; 56];
+	for (i, field) in [0xd00dfeed, 56 + structure.len() + strings.len(), 56, 56 + structure.len(), 40, 17, 16, 0,
+			strings.len(), structure.len()].iter().enumerate() {
+		set(&mut dtb, i * 4, *field as u32);
+	}
+	dtb.extend_from_slice(&structure);
+	dtb.extend_from_slice(&strings);
+	dtb
+}
+
+#[test]
+fn test_move_to() {
+	let fdt = FDT::from_bytes(DTB).unwrap();
+	let mut buf = vec![0xff; DTB.len() + 10];
+	let moved = fdt.move_to(&mut buf).unwrap();
+	assert_eq!(moved.total_size() as usize, DTB.len());
+	assert_eq!(&buf[..DTB.len()], DTB);
+	assert_eq!(buf[DTB.len()], 0xff);
+	assert_eq!(fdt.move_to(&mut vec![0; DTB.len() - 1]).err(), Some(Error::NoSpace));
+}
+
+#[test]
+fn test_open_into() {
+	let fdt = FDT::from_bytes(DTB).unwrap();
+	let mut buf = vec![0xff; 0x5000];
+	let opened = fdt.open_into(&mut buf).unwrap();
+	assert_eq!(opened.total_size(), 0x5000);
+	assert_eq!(opened.layout().free_space(), 0x5000 - DTB.len());
+	assert_eq!(&buf[..4], &DTB[..4]);
+	assert_eq!(&buf[8..DTB.len()], &DTB[8..]);
+	assert_eq!(fdt.open_into(&mut vec![0; DTB.len() - 1]).err(), Some(Error::NoSpace));
+
+	// blobs are checked with check_full first
+	let mut named = DTB.to_vec();
+	named[0x4c] = b'n';
+	let named = FDT::from_bytes(&named).unwrap();
+	assert_eq!(named.open_into(&mut buf).err(), Some(Error::BadStructure));
+	assert_eq!(named.pack(&mut buf).err(), Some(Error::BadStructure));
+
+	// the blocks are put in order, the free space in between is dropped
+	let dtb = misordered();
+	let fdt = FDT::from_bytes(&dtb).unwrap();
+	assert!(!fdt.layout().in_order());
+	let mut buf = vec![0; DTB.len() + 0x40];
+	let opened = fdt.open_into(&mut buf).unwrap();
+	let layout = opened.layout();
+	assert!(layout.in_order());
+	assert_eq!(layout.gaps().count(), 0);
+	assert_eq!(layout.free_space(), 0x40);
+	assert_eq!(dts(&opened).to_string(), dts(&FDT::from_bytes(DTB).unwrap()).to_string());
+
+	// NOP tags stay
+	let dtb = nopped("pinctrl-names");
+	let fdt = FDT::from_bytes(&dtb).unwrap();
+	let mut buf = vec![0; dtb.len()];
+	let opened = fdt.open_into(&mut buf).unwrap();
+	assert_eq!(opened.tags().filter(|tag| tag == &Ok(Tag::Nop)).count(), fdt.tags().filter(|tag| tag == &Ok(Tag::Nop)).count());
+	assert_eq!(buf, dtb);
+}
+
+#[test]
+fn test_open_legacy() {
+	let mut v2 = Vec::new();
+	for field in [0xd00dfeed, 0x74, 0x38, 0x6c, 0x28, 2, 1, 3] {
+		v2.extend_from_slice(&u32::to_be_bytes(field));
+	}
+	v2.resize(0x38, 0);
+	// the root node and "/n" with an 8 byte value aligned to 8 bytes
+	for word in [1, 0x2f000000, 1, 0x2f6e0000, 3, 8, 0, 0, 1, 2, 2, 2, 9] {
+		v2.extend_from_slice(&u32::to_be_bytes(word));
+	}
+	v2.extend_from_slice(b"reg\0\0\0\0\0");
+	let fdt = FDT::from_bytes(&v2).unwrap();
+	let mut buf = vec![0; 0x100];
+	let opened = fdt.open_into(&mut buf).unwrap();
+	let header = opened.header();
+	assert_eq!((header.version, header.last_comp_version, header.boot_cpuid_phys), (17, 16, Some(3)));
+	assert_eq!(header.size_dt_strings, Some(4));
+	assert_eq!(opened.check_full(), Ok(()));
+	let node = opened.nodes().nth(1).unwrap();
+	assert_eq!(node.name(), "n");
+	assert_eq!(node.property_value::<u64>("reg"), Ok(0x1_0000_0002));
+}
+
+#[test]
+fn test_pack() {
+	let fdt = FDT::from_bytes(DTB).unwrap();
+	let mut buf = vec![0; DTB.len()];
+	let packed = fdt.pack(&mut buf).unwrap();
+	assert_eq!(dts(&packed).to_string(), dts(&fdt).to_string());
+	assert_eq!(packed.layout().free_space(), 0);
+
+	// NOP tags and the names only they used are dropped
+	let dtb = nopped("pinctrl-names");
+	let nops = FDT::from_bytes(&dtb).unwrap();
+	let mut buf = vec![0; dtb.len()];
+	let packed = nops.pack(&mut buf).unwrap();
+	assert_eq!(packed.check_full(), Ok(()));
+	assert_eq!(dts(&packed).to_string(), dts(&nops).to_string());
+	assert!(packed.tags().all(|tag| tag != Ok(Tag::Nop)));
+	let strings = packed.layout().block(BlockKind::Strings);
+	let size = packed.total_size() as usize;
+	let nop_size = nops.tags().filter(|tag| tag == &Ok(Tag::Nop)).count() * 4;
+	assert_eq!(size, strings.offset + merged_size(&nops));
+	assert_eq!(strings.offset, 0x3c08 - nop_size);
+	assert_eq!(get(&buf, 32) as usize, strings.size);
+	let strings = &buf[strings.offset..strings.end()];
+	assert!(!strings.split(|&b| b == 0).any(|name| name == b"pinctrl-names"));
+
+	// packing a packed blob changes nothing
+	let mut again = vec![0; size];
+	FDT::from_bytes(&buf).unwrap().pack(&mut again).unwrap();
+	assert_eq!(again, &buf[..size]);
+	assert_eq!(nops.pack(&mut vec![0; size - 1]).err(), Some(Error::NoSpace));
+
+	let dtb = misordered();
+	let mut buf = vec![0; dtb.len()];
+	let packed = FDT::from_bytes(&dtb).unwrap().pack(&mut buf).unwrap();
+	assert!(packed.layout().in_order());
+	assert_eq!(packed.total_size() as usize, 0x3c08 + merged_size(&fdt));
+}
+
+#[test]
+fn test_compact_strings() {
+	let dtb = bloated();
+	let bloated = FDT::from_bytes(&dtb).unwrap();
+	let mut buf = vec![0; dtb.len()];
+	let compacted = bloated.compact_strings(&mut buf).unwrap();
+	assert_eq!(compacted.check_full(), Ok(()));
+	assert_eq!(dts(&compacted).to_string(), dts(&FDT::from_bytes(DTB).unwrap()).to_string());
+	assert_eq!(compacted.layout().free_space(), 0);
+
+	// every name is there once, unless it ends another name
+	let strings = compacted.layout().block(BlockKind::Strings);
+	let size = compacted.total_size() as usize;
+	let names: Vec<&[u8]> = buf[strings.offset..strings.end() - 1].split(|&b| b == 0).collect();
+	for (i, name) in names.iter().enumerate() {
+		assert!(!names.iter().enumerate().any(|(j, other)| i != j && other.ends_with(name)));
+	}
+	assert!(names.contains(&&b"#address-cells"[..]));
+	assert!(!names.contains(&&b"unused"[..]));
+	assert_eq!(strings.size, merged_size(&bloated));
+	assert!(size < DTB.len());
+
+	// NOP tags are kept, unlike by pack
+	let dtb = nopped("pinctrl-names");
+	let nops = FDT::from_bytes(&dtb).unwrap();
+	let mut buf = vec![0; dtb.len()];
+	let compacted = nops.compact_strings(&mut buf).unwrap();
+	let count = |fdt: &FDT| fdt.tags().filter(|tag| tag == &Ok(Tag::Nop)).count();
+	assert_eq!(count(&compacted), count(&nops));
+	let size = compacted.total_size() as usize;
+	let mut packed = vec![0; size];
+	let packed = compacted.pack(&mut packed).unwrap();
+	assert_eq!(packed.total_size() as usize, size - count(&nops) * 4);
+	assert_eq!(nops.compact_strings(&mut vec![0; size - 1]).err(), Some(Error::NoSpace));
+}
+
+#[test]
+fn test_pack_many_names() {
+	let count = 1 << 12;
+	let dtb = many_names(count);
+	let fdt = FDT::from_bytes(&dtb).unwrap();
+	let mut buf = vec![0; dtb.len()];
+	let packed = fdt.pack(&mut buf).unwrap();
+	// only the prop-<i> names are left
+	let strings: usize = (0..count).map(|i| format!("prop-{}", i).len() + 1).sum();
+	assert_eq!(packed.header().size_dt_strings, Some(strings as u32));
+	let names: Vec<_> = packed.nodes().next().unwrap().properties().map(|prop| prop.name()).collect();
+	let expected: Vec<_> = (0..count).map(|i| format!("prop-{}", i))
+		.chain((0..count).map(|i| i.to_string()))
+		.collect();
+	assert_eq!(names, expected);
+}
@@ -0,0 +1,115 @@
+#![cfg(feature = "alloc")]
+
+extern crate fdt;
+extern crate proptest;
+
+use proptest::collection::{btree_map, vec};
+use proptest::prelude::*;
+
+use fdt::{FDT, MemoryReserveMapEntry};
+use fdt::owned::{OwnedNode, OwnedProperty, OwnedTree};
+
+fn property() -> impl Strategy<Value=(String, Vec<u8>)> {
+	("[a-z#][a-z0-9,._+-]{0,20}", vec(any::<u8>(), 0..40))
+}
+
+fn leaf(name: String) -> impl Strategy<Value=OwnedNode> {
+	btree_map(property().prop_map(|(name, _)| name), vec(any::<u8>(), 0..40), 0..6)
+		.prop_map(move |properties| OwnedNode {
+			name: name.clone(),
+			properties: properties.into_iter().map(|(name, value)| OwnedProperty { name, value }).collect(),
+			children: Vec::new(),
+		})
+}
+
+fn node(name: String, depth: u32) -> BoxedStrategy<OwnedNode> {
+	if depth == 0 {
+		return leaf(name).boxed();
+	}
+	let children = btree_map("[a-z][a-z0-9,._+-]{0,12}(@[0-9a-f]{1,8})?", Just(()), 0..4)
+		.prop_flat_map(move |names| names.into_keys().map(|name| node(name, depth - 1)).collect::<Vec<_>>());
+	(leaf(name), children).prop_map(|(mut node, children)| {
+		node.children = children;
+		node
+	}).boxed()
+}
+
+fn tree() -> impl Strategy<Value=OwnedTree> {
+	let entry = (any::<u64>(), 1..u64::MAX).prop_map(|(address, size)| MemoryReserveMapEntry { address, size });
+	(vec(entry, 0..4), any::<u32>(), node(String::new(), 3)).prop_map(|(memory_reserve, boot_cpuid_phys, root)| {
+		OwnedTree { memory_reserve, boot_cpuid_phys, root }
+	})
+}
+
+fn paths(node: &OwnedNode, prefix: &str, out: &mut Vec<(String, usize)>) {
+	for child in &node.children {
+		let path = format!("{}/{}", prefix, child.name);
+		paths(child, &path, out);
+		out.push((path, child.properties.len()));
+	}
+}
+
+/// Returns the size of the strings block of [tree] as pack() writes it,
+/// the names in use once each, but for names ending a longer one.
+fn packed_strings(tree: &OwnedTree) -> usize {
+	fn names<'a>(node: &'a OwnedNode, out: &mut Vec<&'a str>) {
+		out.extend(node.properties.iter().map(|prop| prop.name.as_str()));
+		for child in &node.children {
+			names(child, out);
+		}
+	}
+	let mut all = Vec::new();
+	names(&tree.root, &mut all);
+	all.sort();
+	all.dedup();
+	all.iter()
+		.filter(|name| !all.iter().any(|other| other.len() > name.len() && other.ends_with(*name)))
+		.map(|name| name.len() + 1)
+		.sum()
+}
+
+proptest! {
+	#[test]
+	fn test_roundtrip(tree in tree()) {
+		let dtb = tree.to_dtb();
+		let fdt = FDT::from_bytes(&dtb).unwrap();
+		prop_assert_eq!(fdt.total_size() as usize, dtb.len());
+		prop_assert_eq!(fdt.boot_cpuid_phys(), Some(tree.boot_cpuid_phys));
+		prop_assert_eq!(fdt.memory_reserve_map().collect::<Vec<_>>(), tree.memory_reserve.clone());
+		prop_assert_eq!(&OwnedTree::from_fdt(&fdt), &tree);
+		prop_assert_eq!(OwnedTree::from_fdt(&fdt).to_dtb(), dtb);
+	}
+
+	#[test]
+	fn test_relocate(tree in tree(), extra in 0..64usize) {
+		let dtb = tree.to_dtb();
+		let fdt = FDT::from_bytes(&dtb).unwrap();
+		let mut buf = vec![0; dtb.len() + extra];
+		let opened = fdt.open_into(&mut buf).unwrap();
+		prop_assert_eq!(opened.layout().free_space(), extra);
+		prop_assert_eq!(&OwnedTree::from_fdt(&opened), &tree);
+		let mut packed = vec![0; dtb.len()];
+		let packed = opened.pack(&mut packed).unwrap();
+		let strings = fdt.header().size_dt_strings.unwrap() as usize;
+		prop_assert_eq!(packed.total_size() as usize, dtb.len() - strings + packed_strings(&tree));
+		prop_assert_eq!(packed.layout().free_space(), 0);
+		prop_assert_eq!(&OwnedTree::from_fdt(&packed), &tree);
+	}
+
+	#[test]
+	fn test_lookup(tree in tree()) {
+		let dtb = tree.to_dtb();
+		let fdt = FDT::from_bytes(&dtb).unwrap();
+		let mut all = Vec::new();
+		paths(&tree.root, "", &mut all);
+		for (path, properties) in all {
+			let node = fdt.nodes().with_path(&path).next().unwrap();
+			prop_assert_eq!(node.path().to_string(), path.clone());
+			prop_assert_eq!(node.properties().count(), properties);
+			let owned = tree.node(&path).unwrap();
+			for prop in &owned.properties {
+				prop_assert_eq!(node.property(&prop.name).unwrap().raw(), &prop.value[..]);
+			}
+		}
+	}
+}
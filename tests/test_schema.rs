@@ -0,0 +1,174 @@
+extern crate fdt;
+
+use fdt::{FDT, NodeIterator};
+#[cfg(feature = "alloc")]
+use fdt::error::Error;
+#[cfg(feature = "alloc")]
+use fdt::gpio;
+#[cfg(feature = "alloc")]
+use fdt::owned::OwnedTree;
+use fdt::schema::{self, ChildSchema, PropertySchema, Schema, Type, Violation};
+
+mod common;
+use common::{leak, patched, value_range};
+#[cfg(feature = "alloc")]
+use common::{cells, node};
+
+const DTB: &[u8] = include_bytes!("dt.dtb");
+
+fn init() -> FDT<'static> {
+	unsafe {
+		FDT::from_raw(DTB.as_ptr()).unwrap()
+	}
+}
+
+static SPIDEV: Schema = Schema {
+	compatible: &["spidev"],
+	required: &["reg", "spi-max-frequency"],
+	properties: &[
+		PropertySchema::reg(1, 1),
+		PropertySchema::new("spi-max-frequency", Type::U32),
+		PropertySchema::new("#address-cells", Type::U32),
+		PropertySchema::new("#size-cells", Type::U32),
+	],
+	additional_properties: false,
+	..Schema::EMPTY
+};
+
+static SPI: Schema = Schema {
+	compatible: &["brcm,bcm2835-spi"],
+	required: &["reg", "interrupts", "clocks", "#address-cells", "#size-cells"],
+	properties: &[
+		PropertySchema::reg(1, 1),
+		PropertySchema::new("interrupts", Type::Matrix).items(1, 1),
+		PropertySchema::new("clocks", Type::PhandleArray).items(1, 1),
+		PropertySchema::new("#address-cells", Type::U32),
+		PropertySchema::new("#size-cells", Type::U32),
+		PropertySchema::new("cs-gpios", Type::PhandleArray).items(1, 4),
+		PropertySchema::new("dmas", Type::PhandleArray).items(2, 2),
+		PropertySchema::new("dma-names", Type::StringList),
+	],
+	additional_properties: false,
+	children: &[
+		ChildSchema { pattern: "spidev@*", schema: Some(&SPIDEV), min: 0, max: 2 },
+	],
+	additional_children: false,
+	..Schema::EMPTY
+};
+
+#[test]
+fn test_valid_nodes() {
+	let fdt = init();
+	let mut violations = Vec::new();
+	let count = schema::validate(&fdt, &[&SPI, &SPIDEV], |report| {
+		violations.push((report.node.name(), report.violation));
+	});
+	assert_eq!(violations, []);
+	assert_eq!(count, 0);
+}
+
+#[test]
+fn test_violations() {
+	static STRICT: Schema = Schema {
+		compatible: &["brcm,bcm2835-spi-v2"],
+		required: &["reg", "fifo-depth"],
+		properties: &[
+			PropertySchema::reg(2, 2),
+			PropertySchema::new("interrupts", Type::Flag),
+			PropertySchema::new("status", Type::String(&["okay"])),
+			PropertySchema::new("clocks", Type::PhandleArray),
+		],
+		additional_properties: false,
+		children: &[
+			ChildSchema { pattern: "spidev@*", schema: None, min: 3, max: 3 },
+		],
+		..Schema::EMPTY
+	};
+
+	let fdt = init();
+	let spi = fdt.nodes().with_name("spi@7e204000").next().unwrap();
+	let mut violations = Vec::new();
+	let count = STRICT.validate_node(&spi, |report| violations.push(report.violation));
+	assert_eq!(count, violations.len());
+	assert!(violations.contains(&Violation::BadCompatible("brcm,bcm2835-spi")));
+	assert!(violations.contains(&Violation::MissingProperty("fifo-depth")));
+	assert!(violations.contains(&Violation::BadItemCount { property: "reg", count: 1 }));
+	assert!(violations.contains(&Violation::BadType("interrupts")));
+	assert!(violations.contains(&Violation::BadValue("status")));
+	assert!(violations.contains(&Violation::UnexpectedProperty("dmas")));
+	assert!(!violations.contains(&Violation::UnexpectedProperty("pinctrl-0")));
+	assert!(violations.contains(&Violation::ChildCount { pattern: "spidev@*", count: 2 }));
+	assert_eq!(violations.len(), 11);
+}
+
+#[test]
+fn test_names_mismatch() {
+	static PL011: Schema = Schema {
+		node_name: Some("serial@7e201000"),
+		..Schema::EMPTY
+	};
+
+	let fdt = init();
+	let uart = fdt.nodes().with_name("serial@7e201000").next().unwrap();
+	assert!(PL011.selects(&uart));
+	assert_eq!(PL011.validate_node(&uart, |_| {}), 0);
+
+	// split "apb_pclk" in two, giving three clock names for two clocks
+	let fdt = leak(patched(DTB, |dtb| {
+		let pos = dtb.windows(9).position(|w| w == b"apb_pclk\0").unwrap();
+		dtb[pos + 3] = 0;
+	}));
+	let uart = fdt.nodes().with_name("serial@7e201000").next().unwrap();
+	let mut violations = Vec::new();
+	PL011.validate_node(&uart, |report| violations.push(report.violation));
+	assert_eq!(violations, [Violation::NamesMismatch { property: "clock-names", names: 3, entries: 2 }]);
+}
+
+#[test]
+fn test_bad_utf8() {
+	// corrupt the compatible and dma-names strings of the spi controller
+	let compatible = value_range(DTB, "/soc/spi@7e204000", "compatible");
+	let names = value_range(DTB, "/soc/spi@7e204000", "dma-names");
+	let fdt = leak(patched(DTB, |dtb| {
+		dtb[compatible.start + 4] = 0xff;
+		dtb[names.start + 3] = 0xff;
+	}));
+	let spi = fdt.nodes().with_name("spi@7e204000").next().unwrap();
+	let mut violations = Vec::new();
+	SPI.validate_node(&spi, |report| violations.push(report.violation));
+	assert_eq!(violations, [Violation::BadType("compatible"), Violation::BadType("dma-names")]);
+}
+
+#[cfg(feature = "alloc")]
+#[test]
+fn test_phandle_array_cells() {
+	static CONSUMER: Schema = Schema {
+		node_name: Some("consumer"),
+		properties: &[
+			PropertySchema::new("cs-gpios", Type::PhandleArray).items(3, 3),
+			PropertySchema::new("reset-gpios", Type::PhandleArray),
+		],
+		..Schema::EMPTY
+	};
+
+	let mut tree = OwnedTree::default();
+	tree.root.children = vec![
+		node("gpio", &[("gpio-controller", b""), ("#gpio-cells", &cells(&[2])), ("phandle", &cells(&[1]))], vec![]),
+		node("legacy", &[("gpio-controller", b""), ("phandle", &cells(&[2]))], vec![]),
+		node("consumer", &[
+			("cs-gpios", &cells(&[1, 8, 0, 0, 1, 7, 1])),
+			("reset-gpios", &cells(&[2, 2])),
+		], vec![]),
+	];
+	let dtb = tree.to_dtb();
+	let fdt = FDT::from_bytes(&dtb).unwrap();
+	let consumer = fdt.nodes().with_name("consumer").next().unwrap();
+
+	// holes count as entries, and providers lacking #gpio-cells are errors
+	// for the schema as they are for the bindings
+	let mut violations = Vec::new();
+	CONSUMER.validate_node(&consumer, |report| violations.push(report.violation));
+	assert_eq!(violations, [Violation::BadType("reset-gpios")]);
+	assert_eq!(gpio::gpios(&consumer, "cs").unwrap().count(), 3);
+	assert_eq!(gpio::gpios(&consumer, "reset").unwrap().next().unwrap().err(), Some(Error::BadNCells));
+}
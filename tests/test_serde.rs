@@ -0,0 +1,134 @@
+#![cfg(feature = "serde")]
+
+extern crate fdt;
+#[macro_use]
+extern crate serde_derive;
+extern crate serde_json;
+
+use std::collections::BTreeMap;
+
+use fdt::{FDT, NodeIterator};
+use fdt::de::{self, CellWidth, Config};
+use fdt::owned::{OwnedNode, OwnedTree};
+
+const DTB: &[u8] = include_bytes!("dt.dtb");
+
+fn init() -> FDT<'static> {
+	unsafe {
+		FDT::from_raw(DTB.as_ptr()).unwrap()
+	}
+}
+
+#[derive(Deserialize, Debug, PartialEq)]
+enum Status {
+	#[serde(rename = "okay")]
+	Okay,
+	#[serde(rename = "disabled")]
+	Disabled,
+}
+
+#[derive(Deserialize, Debug)]
+struct Spi<'a> {
+	#[serde(borrow)]
+	compatible: Vec<&'a str>,
+	reg: Vec<(u32, u32)>,
+	status: Status,
+	#[serde(rename = "cs-gpios")]
+	cs_gpios: Vec<[u32; 3]>,
+	#[serde(rename = "pinctrl-names")]
+	pinctrl_names: Vec<String>,
+	#[serde(default, rename = "gpio-controller")]
+	gpio_controller: bool,
+	#[serde(rename = "spidev@0")]
+	spidev: BTreeMap<String, serde_json::Value>,
+	#[serde(rename = "dma-coherent")]
+	dma_coherent: Option<bool>,
+}
+
+#[test]
+fn test_deserialize_struct() {
+	let fdt = init();
+	let node = fdt.nodes().with_name("spi@7e204000").next().unwrap();
+	let spi: Spi = de::from_node(node).unwrap();
+	assert_eq!(spi.compatible, ["brcm,bcm2835-spi"]);
+	assert_eq!(spi.reg, [(0x7e204000, 0x1000)]);
+	assert_eq!(spi.status, Status::Disabled);
+	assert_eq!(spi.cs_gpios, [[10, 8, 1], [10, 7, 1]]);
+	assert_eq!(spi.pinctrl_names, ["default"]);
+	assert!(!spi.gpio_controller);
+	assert_eq!(spi.spidev["spi-max-frequency"], 125000000);
+	assert_eq!(spi.dma_coherent, None);
+}
+
+#[test]
+fn test_deserialize_value() {
+	let fdt = init();
+	let node = fdt.nodes().with_name("serial@7e201000").next().unwrap();
+	let value: serde_json::Value = de::from_node(node).unwrap();
+	assert_eq!(value["compatible"], serde_json::json!(["brcm,bcm2835-pl011", "arm,pl011", "arm,primecell"]));
+	assert_eq!(value["status"], "okay");
+	assert_eq!(value["cts-event-workaround"], true);
+	assert_eq!(value["reg"], serde_json::json!([0x7e201000, 0x1000]));
+	assert_eq!(value["phandle"], 0x1c);
+}
+
+#[test]
+fn test_deserialize_cell_width() {
+	let fdt = init();
+	let node = fdt.nodes().with_name("serial@7e201000").next().unwrap();
+	let config = Config { cell_width: CellWidth::U64 };
+	let value: serde_json::Value = de::from_node_with_config(node, config).unwrap();
+	assert_eq!(value["reg"], 0x7e20100000001000u64);
+}
+
+#[test]
+fn test_deserialize_errors() {
+	let fdt = init();
+	let node = fdt.nodes().with_name("serial@7e201000").next().unwrap();
+	let prop = node.property("reg").unwrap();
+	assert_eq!(de::from_property::<u32>(&prop, Config::default()), Err(de::Error::TrailingBytes(4)));
+	assert_eq!(de::from_property::<[u32; 3]>(&prop, Config::default()),
+		Err(de::Error::Value(fdt::error::Error::Truncated)));
+}
+
+#[test]
+fn test_deserialize_signed() {
+	let mut tree = OwnedTree::default();
+	tree.root.set_property("minus-one", &[0xff; 4]);
+	tree.root.set_property("i16-min", &0xffff8000u32.to_be_bytes());
+	tree.root.set_property("i8-max", &0x7fu32.to_be_bytes());
+	tree.root.set_property("large", &0x8000u32.to_be_bytes());
+	tree.root.set_property("minus-two-64", &(-2i64).to_be_bytes());
+	let dtb = tree.to_dtb();
+	let fdt = FDT::from_bytes(&dtb).unwrap();
+	let root = fdt.nodes().next().unwrap();
+	let read = |name| root.property(name).unwrap();
+	let config = Config::default();
+
+	assert_eq!(de::from_property::<i32>(&read("minus-one"), config), Ok(-1));
+	assert_eq!(de::from_property::<i16>(&read("minus-one"), config), Ok(-1));
+	assert_eq!(de::from_property::<i8>(&read("minus-one"), config), Ok(-1));
+	assert_eq!(de::from_property::<i16>(&read("i16-min"), config), Ok(i16::MIN));
+	assert_eq!(de::from_property::<i32>(&read("i16-min"), config), Ok(-0x8000));
+	assert_eq!(de::from_property::<i8>(&read("i8-max"), config), Ok(127));
+	assert_eq!(de::from_property::<i64>(&read("minus-two-64"), config), Ok(-2));
+	assert_eq!(de::from_property::<Vec<i32>>(&read("minus-two-64"), config), Ok(vec![-1, -2]));
+	// the value must be sign extended from the width of the type
+	assert!(de::from_property::<i8>(&read("i16-min"), config).is_err());
+	assert!(de::from_property::<i16>(&read("large"), config).is_err());
+	assert_eq!(de::from_property::<u16>(&read("large"), config), Ok(0x8000));
+	assert!(de::from_property::<u16>(&read("minus-one"), config).is_err());
+}
+
+#[test]
+fn test_serialize_round_trip() {
+	let fdt = init();
+	let node = fdt.nodes().with_name("cpus").next().unwrap();
+	let borrowed = serde_json::to_value(&node).unwrap();
+	let owned = serde_json::to_value(OwnedNode::from_node(&node)).unwrap();
+	assert_eq!(borrowed, owned);
+	assert_eq!(borrowed["cpu@2"]["device_type"], "cpu");
+	assert_eq!(borrowed["#size-cells"], 0);
+	let deserialized: serde_json::Value = de::from_node(node).unwrap();
+	assert_eq!(deserialized, borrowed);
+}
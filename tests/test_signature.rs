@@ -0,0 +1,435 @@
+#![cfg(feature = "alloc")]
+
+extern crate fdt;
+
+use fdt::digest::{self, Algorithm, Backend, Digest};
+use fdt::fit::Fit;
+use fdt::owned::OwnedTree;
+use fdt::signature::{self, parse_algo, Crypto, EcdsaKey, Keys, RsaKey, Scheme, SignatureError};
+use fdt::FDT;
+
+mod common;
+use common::{get, hex, node, set};
+
+const MODULUS: &str = "bc76be76b96c95001b6bc15e2742817cec707233707ce29b2229a36d7a9e06b4030eda70d038f45eaebc295bc63f528f\
+	d37a475397ed14350d5662e1ac19cba9abb776464b8543c273082dbac1b7fddb018a86d5ec9074c21dffe3683cbcb3080b1dd0bbfc18d83e45cf\
+	0270b5d13ad3629fbeceab9e5e5f7943676bd54b16b3";
+const R_SQUARED: &str = "646e002d9884c0e9594de1ed6f00e79f5283b18782473275220e2ca489c2079a172570c439c6e5f19453fa8b3e418420\
+	0ad23bb19256b9cdd75b05ab91e5c8e726797ada32465f583271c10fde4ade3688979837823e797911177f353734aa4c735667ac88499fb01a6b\
+	c630751c4ef4515a7549235d5f094ca40c5684931f28";
+const N0_INVERSE: u32 = 0x4dd07785;
+
+/// The PKCS #1 v1.5 signature of conf-1.
+const CONF_SIGNATURE: &str = "b1235080fa26b07f9183203c5dbf670117256f7ac9fb00995f1072818f2b1fb85bab55e740d557d35904db3c342f9a87\
+	6f7219b903ad6bb4c0c557885126feeb21a6cbe196a5921f87fca21b8ebe3744cfc72002f04432ef8fb6ff818f4959fd83c2f53478d93fe93fbe\
+	7a04a85482c4c6f03691b8028e5d539c7713a574ee8a";
+/// The PSS signature of conf-1, with a salt as long as the digest.
+const CONF_PSS_SIGNATURE: &str = "a979dc95aacd50aaa101b879fa8b3612b597646ed759198db0b46d975cc5adafe483929e71d41596115d306ad589613c\
+	51a04146de0bba48ffdbd57049dc7ae464060d833929bdedfb9d7fd1698f71bf61dfe7b8018d408e7856cd1809c484558e3d3ee1fde5cafcb5e9\
+	9619f8a0f195da1aaea12553bb6dd60e76934a3bb4d7";
+/// The PKCS #1 v1.5 signature of the kernel data.
+const IMAGE_SIGNATURE: &str = "53442cb2af6d7ec4365a313108fbd3625cefc946c230ff1ef8863b9ba2f409e7d8ea8ede3c100b3a5405ee1a06aa59e6\
+	61db3a4182ac07ff79f0d9c145ab3ab47739bd4f3869a1f09b2529addb2863e9d7b4028af23ce2b31f7d62f2785b6a655210e2d2fd45e961f3a3\
+	b0978c8f1e9a272da103d3a5803d46563db7531a10a1";
+
+/// A control device tree holding the test key as 'key-dev', required for
+/// [required], and the same modulus with exponent 3 as 'key-other'.
+fn control(required: &str, mode: &str, other: bool) -> Vec<u8> {
+	let key = |name: &str, exponent: u64| {
+		let (modulus, r_squared) = (hex(MODULUS), hex(R_SQUARED));
+		let mut key = node(name, &[
+			("algo", b"sha256,rsa1024\0"), ("key-name-hint", &[&name.as_bytes()[4..], b"\0"].concat()),
+			("rsa,num-bits", &1024u32.to_be_bytes()), ("rsa,n0-inverse", &N0_INVERSE.to_be_bytes()),
+			("rsa,exponent", &exponent.to_be_bytes()), ("rsa,modulus", &modulus), ("rsa,r-squared", &r_squared),
+		], vec![]);
+		if !required.is_empty() {
+			key.set_property("required", &[required.as_bytes(), b"\0"].concat());
+		}
+		key
+	};
+	let mut signature = node("signature", &[], vec![key("key-dev", 65537)]);
+	if other {
+		signature.children.push(key("key-other", 3));
+	}
+	if !mode.is_empty() {
+		signature.set_property("required-mode", &[mode.as_bytes(), b"\0"].concat());
+	}
+	let mut tree = OwnedTree::default();
+	tree.root.children = vec![signature];
+	tree.to_dtb()
+}
+
+fn fit_tree() -> OwnedTree {
+	let mut tree = OwnedTree::default();
+	tree.root.set_property("description", b"signed image\0");
+	tree.root.children = vec![
+		node("images", &[], vec![
+			node("kernel-1", &[("data", b"kernel data"), ("type", b"kernel\0"),
+				("load", &0x80080000u32.to_be_bytes())], vec![
+				node("hash-1", &[("algo", b"sha256\0"),
+					("value", &hex("dc2d3aebca53ecefb16a5cf228314ff8fb8cd15ede0392787d46844fd46756a3"))], vec![]),
+			]),
+			node("fdt-1", &[("data", b"fdt data"), ("type", b"flat_dt\0")], vec![]),
+		]),
+		node("configurations", &[("default", b"conf-1\0")], vec![
+			node("conf-1", &[("kernel", b"kernel-1\0"), ("fdt", b"fdt-1\0")], vec![]),
+			node("conf-2", &[("kernel", b"kernel-1\0")], vec![]),
+		]),
+	];
+	tree
+}
+
+/// A FIT whose conf-1 is signed with the test key, with PKCS #1 v1.5 by
+/// 'signature-1' and PSS by 'signature-2', covering the kernel but not the
+/// device tree.
+fn fit() -> Vec<u8> {
+	let mut tree = fit_tree();
+	let signature = |name: &str, padding: &str, value: &str| {
+		let mut signature = node(name, &[
+			("algo", b"sha256,rsa1024\0"), ("key-name-hint", b"dev\0"), ("sign-images", b"kernel\0"),
+			("hashed-nodes", b"/\0/configurations/conf-1\0/images/kernel-1\0/images/kernel-1/hash-1\0"),
+			("hashed-strings", &[0; 8]),
+			("value", &hex(value)),
+		], vec![]);
+		if !padding.is_empty() {
+			signature.set_property("padding", &[padding.as_bytes(), b"\0"].concat());
+		}
+		signature
+	};
+	let conf = &mut tree.root.children[1].children[0];
+	conf.children.push(signature("signature-1", "", CONF_SIGNATURE));
+	conf.children.push(signature("signature-2", "pss", CONF_PSS_SIGNATURE));
+	tree.root.children[0].children[0].children.push(node("signature-1", &[
+		("algo", b"sha256,rsa1024\0"), ("key-name-hint", b"dev\0"), ("value", &hex(IMAGE_SIGNATURE)),
+	], vec![]));
+
+	// mkimage signs the whole strings block
+	let dtb = tree.to_dtb();
+	let strings = [&[0; 4][..], &dtb[32..36]].concat();
+	for signature in &mut tree.root.children[1].children[0].children {
+		signature.set_property("hashed-strings", &strings);
+	}
+	tree.to_dtb()
+}
+
+/// Replaces every occurrence of [from] in [blob] by [to], of the same length.
+fn patch(blob: &[u8], from: &[u8], to: &[u8]) -> Vec<u8> {
+	let mut blob = blob.to_vec();
+	let mut found = false;
+	for i in 0..blob.len() - from.len() {
+		if &blob[i..i + from.len()] == from {
+			blob[i..i + from.len()].copy_from_slice(to);
+			found = true;
+		}
+	}
+	assert!(found, "{:?} not found", from);
+	blob
+}
+
+/// Inserts a decoy `/images@0` in front of `/images` of [image], holding a
+/// `kernel-1` with [data] and its sha256 hash.
+fn with_decoy(image: &[u8], data: &[u8]) -> Vec<u8> {
+	let (off_struct, off_strings) = (get(image, 8) as usize, get(image, 12) as usize);
+	let strings = &image[off_strings..off_strings + get(image, 32) as usize];
+	let name_off = |name: &str| {
+		let name = [name.as_bytes(), b"\0"].concat();
+		strings.windows(name.len()).position(|w| w == &name[..]).unwrap() as u32
+	};
+	let mut decoy = Vec::new();
+	let pad = |decoy: &mut Vec<u8>| decoy.resize((decoy.len() + 3) & !3, 0);
+	let begin = |decoy: &mut Vec<u8>, name: &str| {
+		decoy.extend_from_slice(&1u32.to_be_bytes());
+		decoy.extend_from_slice(name.as_bytes());
+		decoy.push(0);
+		pad(decoy);
+	};
+	let prop = |decoy: &mut Vec<u8>, name: u32, value: &[u8]| {
+		for word in &[3, value.len() as u32, name] {
+			decoy.extend_from_slice(&word.to_be_bytes());
+		}
+		decoy.extend_from_slice(value);
+		pad(decoy);
+	};
+	let hash = digest::Software.digest(Algorithm::Sha256, &mut Some(data).into_iter()).unwrap();
+	begin(&mut decoy, "images@0");
+	begin(&mut decoy, "kernel-1");
+	prop(&mut decoy, name_off("data"), data);
+	begin(&mut decoy, "hash-1");
+	prop(&mut decoy, name_off("algo"), b"sha256\0");
+	prop(&mut decoy, name_off("value"), hash.as_ref());
+	for _ in 0..3 {
+		decoy.extend_from_slice(&2u32.to_be_bytes());
+	}
+
+	let at = off_struct + image[off_struct..].windows(12).position(|w| w == b"\0\0\0\x01images\0\0").unwrap();
+	let mut blob = image[..at].to_vec();
+	blob.extend_from_slice(&decoy);
+	blob.extend_from_slice(&image[at..]);
+	for &offset in &[4, 12, 36] {
+		let value = get(&blob, offset) + decoy.len() as u32;
+		set(&mut blob, offset, value);
+	}
+	blob
+}
+
+fn verify_conf(image: &[u8], control: &[u8]) -> Result<usize, SignatureError> {
+	let fit = Fit::from_bytes(image).unwrap();
+	let control = FDT::from_bytes(control).unwrap();
+	fit.configuration("conf-1").unwrap()
+		.verify_required_signatures(&Keys::new(&control), &mut digest::Software, &mut signature::Software)
+}
+
+#[test]
+fn test_keys() {
+	assert_eq!(parse_algo("sha256,rsa2048"), Some((Algorithm::Sha256, Scheme::Rsa)));
+	assert_eq!(parse_algo("sha1,ecdsa256"), Some((Algorithm::Sha1, Scheme::Ecdsa)));
+	assert_eq!(parse_algo("md5,rsa2048"), None);
+	assert_eq!(parse_algo("sha256"), None);
+
+	let control = control("conf", "", true);
+	let control = FDT::from_bytes(&control).unwrap();
+	let keys = Keys::new(&control);
+	let hints: Vec<_> = keys.iter().map(|key| key.hint()).collect();
+	assert_eq!(hints, ["dev", "other"]);
+	assert!(keys.require_all());
+	let key = keys.get("dev").unwrap();
+	assert_eq!(key.algo(), Some("sha256,rsa1024"));
+	assert_eq!(key.required(), Some("conf"));
+	let rsa = key.rsa().unwrap();
+	assert_eq!((rsa.bits, rsa.n0_inverse, rsa.exponent), (1024, N0_INVERSE, 65537));
+	assert_eq!(rsa.modulus, &hex(MODULUS)[..]);
+	assert_eq!(keys.get("other").unwrap().rsa().unwrap().exponent, 3);
+	assert_eq!(key.ecdsa().err(), Some(SignatureError::Malformed("ecdsa,curve")));
+	assert!(Keys::new(&FDT::from_bytes(&fit()).unwrap()).iter().next().is_none());
+}
+
+#[test]
+fn test_rsa_public() {
+	let (modulus, r_squared) = (hex(MODULUS), hex(R_SQUARED));
+	let mut key = RsaKey { bits: 1024, n0_inverse: N0_INVERSE, exponent: 65537, modulus: &modulus, r_squared: &r_squared };
+	let signature: Vec<u8> = (1..=128).collect();
+	let mut out = [0; 128];
+	assert!(signature::Software.rsa_public(&key, &signature, &mut out));
+	assert_eq!(&out[..], &hex("18f67f145190af4cdfc037424963fee496d53ccbd902603dbff6b435a2b7e3f2f47004615d4277c9e8bb2ccad9c0b9f5\
+		5e30a11d7481d88850690f83f4e51f7926b507aefa4fd7242b47559f271a456d8fcea1d6d0b191277e9a0d2ce8007483310a464a924aa8e5\
+		44b54fa470269ad136f5a57645fe0991da6934945668387f")[..]);
+	key.exponent = 3;
+	assert!(signature::Software.rsa_public(&key, &signature, &mut out));
+	assert_eq!(&out[..], &hex("a0c5096008b77f554d56d5af583bcf30181625e6c0be5f46810328efb1c85901fe848ce4afd066849b9eb5435b1f8c4f\
+		7aefd04a0cc2517c3d097f91d8efbf1787fa2ce463b4ddc679f33047f015182ac3751e19f99b267391cb7422436b0e87314bccd8d90e71c4\
+		a63f889789b20865a8a13023e9963d6678824d48a2974bf4")[..]);
+	assert!(!signature::Software.rsa_public(&key, &signature[1..], &mut out[1..]));
+	assert!(!signature::Software.rsa_public(&key, &modulus, &mut out));
+	assert!(!signature::Software.rsa_public(&key, &[0xff; 128], &mut out));
+}
+
+#[test]
+fn test_verify() {
+	let image = fit();
+	let control = control("conf", "", false);
+	assert_eq!(verify_conf(&image, &control), Ok(1));
+
+	let fit = Fit::from_bytes(&image).unwrap();
+	let control = FDT::from_bytes(&control).unwrap();
+	let keys = Keys::new(&control);
+	let key = keys.get("dev").unwrap();
+	let config = fit.configuration("conf-1").unwrap();
+	let signatures: Vec<_> = config.signatures().map(|signature| (signature.name(), signature.padding())).collect();
+	assert_eq!(signatures, [("signature-1", None), ("signature-2", Some("pss"))]);
+	for signature in config.signatures() {
+		assert_eq!(signature.key_name_hint(), Some("dev"));
+		assert!(signature.signed_images().unwrap().contains("kernel"));
+		assert_eq!(signature.verify(&key, &mut digest::Software, &mut signature::Software), Ok(()));
+	}
+
+	let kernel = fit.image("kernel-1").unwrap();
+	let signature = kernel.signatures().next().unwrap();
+	assert_eq!(signature.digest(&mut digest::Software).unwrap().as_ref(), kernel.hashes().next().unwrap().value().unwrap());
+	assert_eq!(signature.verify(&key, &mut digest::Software, &mut signature::Software), Ok(()));
+	assert_eq!(kernel.verify_required_signatures(&keys, &mut digest::Software, &mut signature::Software), Ok(0));
+	let control = self::control("image", "", false);
+	let control = FDT::from_bytes(&control).unwrap();
+	assert_eq!(kernel.verify_required_signatures(&Keys::new(&control), &mut digest::Software, &mut signature::Software),
+		Ok(1));
+}
+
+#[test]
+fn test_signed_regions() {
+	let image = fit();
+	let control = control("conf", "", false);
+	// image data is covered by the image hashes, unhashed nodes aren't signed
+	assert_eq!(verify_conf(&patch(&image, b"kernel data", b"kernel DATA"), &control), Ok(1));
+	assert_eq!(verify_conf(&patch(&image, b"flat_dt", b"flat_DT"), &control), Ok(1));
+	assert_eq!(verify_conf(&patch(&image, b"fdt data", b"fdt DATA"), &control), Ok(1));
+	// properties of hashed nodes are
+	assert_eq!(verify_conf(&patch(&image, b"signed image", b"signed IMAGE"), &control), Err(SignatureError::Invalid));
+	assert_eq!(verify_conf(&patch(&image, &[0x80, 8, 0, 0], &[0x80, 0, 0, 0]), &control), Err(SignatureError::Invalid));
+	assert_eq!(verify_conf(&patch(&image, b"kernel\0", b"kernal\0"), &control), Err(SignatureError::Invalid));
+}
+
+/// A FIT and control tree signed outside this crate, see signed.py, with
+/// the property order and strings block layout of mkimage.
+#[test]
+fn test_mkimage_layout() {
+	let image = include_bytes!("signed.itb");
+	let dtb = include_bytes!("signed.dtb");
+	assert_eq!(verify_conf(image, dtb), Ok(1));
+	assert_eq!(verify_conf(&patch(image, b"boot\0", b"BOOT\0"), dtb), Err(SignatureError::Invalid));
+
+	let fit = Fit::from_bytes(image).unwrap();
+	assert!(fit.verify_images(&mut digest::Software).all(|(_, verification)| verification.is_verified()));
+	let control = FDT::from_bytes(dtb).unwrap();
+	let keys = Keys::new(&control);
+	let key = keys.get("dev").unwrap();
+	assert_eq!(key.rsa().unwrap().bits, 2048);
+	let kernel = fit.image("kernel-1").unwrap();
+	let signature = kernel.signatures().next().unwrap();
+	assert_eq!(signature.verify(&key, &mut digest::Software, &mut signature::Software), Ok(()));
+	let config = fit.configuration("conf-1").unwrap();
+	let signature = config.signatures().next().unwrap();
+	assert_eq!(signature.signed_images().unwrap().strings().collect::<Vec<_>>(), ["fdt", "kernel"]);
+	assert_eq!(signature.hashed_nodes().unwrap().strings().count(), 6);
+}
+
+#[test]
+fn test_decoy_images() {
+	let image = include_bytes!("signed.itb");
+	let dtb = include_bytes!("signed.dtb");
+	let decoyed = with_decoy(image, b"evil kernel");
+	let fit = Fit::from_bytes(&decoyed).unwrap();
+	let real = Fit::from_bytes(image).unwrap();
+	let config = fit.configuration("conf-1").unwrap();
+	let kernel = config.kernel().unwrap().unwrap();
+	assert_eq!(kernel.data(), real.image("kernel-1").unwrap().data());
+	assert!(kernel.verify(&mut digest::Software).is_verified());
+	assert_eq!(fit.images().count(), 2);
+	// the begin tag of the decoy is among the signed regions of the root
+	assert_eq!(verify_conf(&decoyed, dtb), Err(SignatureError::Invalid));
+
+	// U-Boot refuses to verify nodes with unit addresses since CVE-2021-27138
+	let renamed = patch(image, b"conf-1\0", b"conf@1\0");
+	let fit = Fit::from_bytes(&renamed).unwrap();
+	let control = FDT::from_bytes(dtb).unwrap();
+	assert_eq!(fit.configuration("conf@1").unwrap()
+		.verify_required_signatures(&Keys::new(&control), &mut digest::Software, &mut signature::Software),
+		Err(SignatureError::UnitAddress));
+}
+
+#[test]
+fn test_required_mode() {
+	let image = fit();
+	assert_eq!(verify_conf(&image, &control("", "", false)), Ok(0));
+	assert_eq!(verify_conf(&image, &control("image", "", false)), Ok(0));
+	assert_eq!(verify_conf(&image, &control("conf", "", true)), Err(SignatureError::Invalid));
+	assert_eq!(verify_conf(&image, &control("conf", "all", true)), Err(SignatureError::Invalid));
+	assert_eq!(verify_conf(&image, &control("conf", "any", true)), Ok(1));
+
+	let fit = Fit::from_bytes(&image).unwrap();
+	let control = control("conf", "", false);
+	let control = FDT::from_bytes(&control).unwrap();
+	let config = fit.configuration("conf-2").unwrap();
+	assert_eq!(config.verify_required_signatures(&Keys::new(&control), &mut digest::Software, &mut signature::Software),
+		Err(SignatureError::Missing));
+}
+
+#[test]
+fn test_errors() {
+	let image = fit();
+	let control = control("conf", "", false);
+	assert_eq!(verify_conf(&patch(&image, b"/configurations/conf-1", b"/configurations/conf-2"), &control),
+		Err(SignatureError::NotHashed));
+	assert_eq!(verify_conf(&patch(&image, b"sha256,rsa1024", b"sha256,rsa2048"), &control),
+		Err(SignatureError::KeyMismatch));
+	assert_eq!(verify_conf(&image, &patch(&control, b"sha256,rsa1024", b"sha384,rsa1024")),
+		Err(SignatureError::KeyMismatch));
+	assert_eq!(verify_conf(&patch(&image, b"sha256,rsa1024", b"sha256,dsa1024"), &control),
+		Err(SignatureError::Unsupported));
+	assert_eq!(verify_conf(&image, &patch(&control, b"rsa,r-squared", b"rsa,r-squares")),
+		Err(SignatureError::Malformed("rsa,r-squared")));
+
+	let key = Keys::new(&FDT::from_bytes(&control).unwrap()).get("dev").unwrap();
+	let value = hex(CONF_SIGNATURE);
+	let tampered = patch(&image, &value, &[&value[..127], &[value[127] ^ 1]].concat());
+	let signature = Fit::from_bytes(&tampered).unwrap().configuration("conf-1").unwrap().signatures().next().unwrap();
+	assert_eq!(signature.verify(&key, &mut digest::Software, &mut signature::Software), Err(SignatureError::Invalid));
+	let unknown = patch(&image, b"pss\0", b"pst\0");
+	let pss = Fit::from_bytes(&unknown).unwrap().configuration("conf-1").unwrap().signatures().nth(1).unwrap();
+	assert_eq!(pss.verify(&key, &mut digest::Software, &mut signature::Software), Err(SignatureError::Unsupported));
+	assert_eq!(SignatureError::NotHashed.to_string(), "configuration not in hashed nodes");
+
+	let mut tree = OwnedTree::default();
+	tree.root.children = vec![node("signature", &[], vec![
+		node("key-empty", &[("rsa,num-bits", &[0; 4]), ("rsa,n0-inverse", &[0; 4]), ("rsa,modulus", b""),
+			("rsa,r-squared", b"")], vec![]),
+	])];
+	let control = tree.to_dtb();
+	let control = FDT::from_bytes(&control).unwrap();
+	let key = Keys::new(&control).get("empty").unwrap();
+	assert_eq!(key.rsa().err(), Some(SignatureError::Malformed("rsa,num-bits")));
+	assert_eq!(signature::verify(&key, "sha256,rsa2048", Some("pss"), &Digest::new(&[0; 32]), b"",
+		&mut digest::Software, &mut signature::Software), Err(SignatureError::Malformed("rsa,num-bits")));
+}
+
+/// A backend without sha256, which PSS needs for its mask.
+struct Crc32Only;
+
+impl Backend for Crc32Only {
+	fn digest(&mut self, algorithm: Algorithm, regions: &mut dyn Iterator<Item=&[u8]>) -> Option<Digest> {
+		match algorithm {
+			Algorithm::Crc32 => digest::Software.digest(algorithm, regions),
+			_ => None,
+		}
+	}
+}
+
+#[test]
+fn test_backends() {
+	let image = fit();
+	let fit = Fit::from_bytes(&image).unwrap();
+	let control = control("conf", "", false);
+	let control = FDT::from_bytes(&control).unwrap();
+	let key = Keys::new(&control).get("dev").unwrap();
+	let pss = fit.configuration("conf-1").unwrap().signatures().nth(1).unwrap();
+	let digest = pss.digest(&mut digest::Software).unwrap();
+	assert_eq!(signature::verify(&key, "sha256,rsa1024", Some("pss"), &digest, pss.value().unwrap(),
+		&mut Crc32Only, &mut signature::Software), Err(SignatureError::Unsupported));
+	assert_eq!(pss.verify(&key, &mut Crc32Only, &mut signature::Software), Err(SignatureError::Unsupported));
+}
+
+/// An ECDSA backend accepting the signature "rs" for any digest.
+struct Ecdsa;
+
+impl Crypto for Ecdsa {
+	fn rsa_public(&mut self, _: &RsaKey, _: &[u8], _: &mut [u8]) -> bool {
+		false
+	}
+
+	fn ecdsa_verify(&mut self, key: &EcdsaKey, digest: &[u8], signature: &[u8]) -> Option<bool> {
+		assert_eq!(digest.len(), 32);
+		match key.curve {
+			"prime256v1" => Some(key.x == [1; 32] && signature == b"rs"),
+			_ => None,
+		}
+	}
+}
+
+#[test]
+fn test_ecdsa() {
+	let mut tree = OwnedTree::default();
+	tree.root.children = vec![node("signature", &[], vec![
+		node("key-ec", &[("algo", b"sha256,ecdsa256\0"), ("ecdsa,curve", b"prime256v1\0"),
+			("ecdsa,x-point", &[1; 32]), ("ecdsa,y-point", &[2; 32])], vec![]),
+	])];
+	let control = tree.to_dtb();
+	let control = FDT::from_bytes(&control).unwrap();
+	let key = Keys::new(&control).get("ec").unwrap();
+	let digest = Digest::new(&[0; 32]);
+	let verify = |signature: &[u8], crypto: &mut dyn Crypto| {
+		signature::verify(&key, "sha256,ecdsa256", None, &digest, signature, &mut digest::Software, crypto)
+	};
+	assert_eq!(verify(b"rs", &mut Ecdsa), Ok(()));
+	assert_eq!(verify(b"sr", &mut Ecdsa), Err(SignatureError::Invalid));
+	assert_eq!(verify(b"rs", &mut signature::Software), Err(SignatureError::Unsupported));
+	assert_eq!(key.rsa().err(), Some(SignatureError::Malformed("rsa,num-bits")));
+}
+
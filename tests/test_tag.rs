@@ -0,0 +1,71 @@
+extern crate fdt;
+
+use fdt::FDT;
+use fdt::error::Error;
+use fdt::tag::Tag;
+
+const DTB: &[u8] = include_bytes!("dt.dtb");
+
+fn init() -> FDT<'static> {
+	unsafe {
+		FDT::from_raw(DTB.as_ptr()).unwrap()
+	}
+}
+
+#[test]
+fn test_tags_match_nodes() {
+	let fdt = init();
+	let tags: Vec<Tag> = fdt.tags().map(Result::unwrap).collect();
+	assert_eq!(tags.last(), Some(&Tag::End));
+
+	let begins: Vec<(&str, usize)> = tags.iter().filter_map(|tag| match *tag {
+		Tag::BeginNode { name, offset } => Some((name, offset)),
+		_ => None,
+	}).collect();
+	let nodes: Vec<(&str, usize)> = fdt.nodes().map(|node| (node.name(), node.offset())).collect();
+	assert_eq!(begins, nodes);
+
+	let ends = tags.iter().filter(|&&tag| tag == Tag::EndNode).count();
+	assert_eq!(ends, nodes.len());
+	let props = tags.iter().filter(|tag| matches!(tag, Tag::Prop { .. })).count();
+	assert_eq!(props, fdt.nodes().map(|node| node.properties().count()).sum::<usize>());
+}
+
+#[test]
+fn test_seek_and_strings() {
+	let fdt = init();
+	let cpus = fdt.nodes().find(|node| node.name() == "cpus").unwrap();
+	let mut cursor = fdt.tags();
+	cursor.seek(cpus.offset()).unwrap();
+	assert_eq!(cursor.next(), Some(Ok(Tag::BeginNode { name: "cpus", offset: cpus.offset() })));
+	match cursor.next() {
+		Some(Ok(Tag::Prop { name_off, value, .. })) => {
+			assert_eq!(cursor.string(name_off), Ok("#address-cells"));
+			assert_eq!(value, [0, 0, 0, 1]);
+		},
+		other => panic!("unexpected {:?}", other),
+	}
+	assert_eq!(cursor.seek(cpus.offset() + 1), Err(Error::BadOffset));
+	assert_eq!(cursor.string(u32::MAX), Err(Error::BadOffset));
+}
+
+#[test]
+fn test_truncated_property() {
+	let mut dtb = DTB.to_vec();
+	let off_dt_struct = u32::from_be_bytes([dtb[8], dtb[9], dtb[10], dtb[11]]) as usize;
+	let first_prop = first_prop(&init());
+	// a property length running past the structure block
+	dtb[off_dt_struct + first_prop + 4..][..4].copy_from_slice(&0x7fff_fff0u32.to_be_bytes());
+	let fdt = unsafe { FDT::from_raw(dtb.as_ptr()).unwrap() };
+	let mut cursor = fdt.tags();
+	assert!(matches!(cursor.next(), Some(Ok(Tag::BeginNode { .. }))));
+	assert_eq!(cursor.next(), Some(Err(Error::Truncated)));
+	assert_eq!(cursor.next(), None);
+}
+
+fn first_prop(fdt: &FDT) -> usize {
+	fdt.tags().find_map(|tag| match tag {
+		Ok(Tag::Prop { offset, .. }) => Some(offset),
+		_ => None,
+	}).unwrap()
+}